@@ -1,17 +1,130 @@
+use std::time::Duration;
+
 use leptos::*;
 use crate::components::*;
-use crate::hooks::use_local_storage;
-use crate::types::Toast;
+use crate::hooks::{
+    use_clone_detection, use_fs_events, use_lint, use_local_storage, use_outline, use_tab_session,
+    use_workspace_persistence,
+};
+use crate::types::{EditorTab, PanelView, SidebarView, Toast};
+use crate::utils::diagnostics::{DiagnosticStore, QuickFix};
+use crate::utils::editor::buffer::Position;
+use crate::utils::editor::editing::EditPlan;
+use crate::utils::editor::selection::{Selection, Selections};
+use crate::utils::editor::tabs::TabManager;
+use crate::utils::file_system::archive::ArchiveFormat;
+use crate::utils::file_system::events::{is_within, FsEvent};
+use crate::utils::file_system::metadata::GENERATED_FOLDERS;
+use crate::utils::file_system::search::{FileReplacement, SearchMatch};
+use crate::utils::file_system::VirtualFileSystem;
+use wasm_bindgen::JsCast;
+
+/// How long a download's object URL outlives the click that starts it.
+const DOWNLOAD_URL_LIFETIME: Duration = Duration::from_secs(30);
+
+fn sample_workspace() -> VirtualFileSystem {
+    let mut fs = VirtualFileSystem::new();
+    let _ = fs.write(
+        "src/App.tsx",
+        "import React from 'react'\n\nexport default function App() {\n  return <div>\n    Hello, Bloop!\n  </div>\n}\n",
+    );
+    let _ = fs.write(
+        "src/main.tsx",
+        "import React from 'react'\nimport ReactDOM from 'react-dom/client'\nimport App from './App'\n\nReactDOM.createRoot(document.getElementById('root')!).render(<App />)\n",
+    );
+    fs
+}
+
+fn download_bytes(file_name: &str, bytes: &[u8], mime: &str) -> Result<(), wasm_bindgen::JsValue> {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+    let document = web_sys::window().unwrap().document().unwrap();
+    let anchor: web_sys::HtmlAnchorElement = document.create_element("a")?.unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    // Revoking at once cancels the download in some browsers (Firefox).
+    set_timeout(
+        move || {
+            let _ = web_sys::Url::revoke_object_url(&url);
+        },
+        DOWNLOAD_URL_LIFETIME,
+    );
+    Ok(())
+}
 
 #[component]
 pub fn App() -> impl IntoView {
     let (show_command_palette, set_show_command_palette) = create_signal(false);
     let (sidebar_collapsed, set_sidebar_collapsed) = create_signal(false);
+    let (sidebar_view, set_sidebar_view) = create_signal(SidebarView::default());
     let (assistant_collapsed, set_assistant_collapsed) = create_signal(false);
     let (terminal_visible, set_terminal_visible) = create_signal(false);
+    let (panel_view, set_panel_view) = create_signal(PanelView::default());
     let (toasts, set_toasts) = create_signal(Vec::<Toast>::new());
     let (do_not_disturb, set_do_not_disturb) = create_signal(false);
     let (sound_enabled, set_sound_enabled) = create_signal(true);
+    let (vfs, set_vfs) = create_signal(sample_workspace());
+    let locked: Vec<String> = GENERATED_FOLDERS.iter().map(|folder| folder.to_string()).collect();
+    set_vfs.update_untracked(|fs| {
+        let _ = fs.set_locked_globs(&locked);
+    });
+    let workspace_loaded = use_workspace_persistence(vfs, set_vfs);
+    let (tabs, set_tabs) = create_signal(TabManager::new());
+    if let Ok(content) = vfs.with_untracked(|fs| fs.read("/src/App.tsx").map(str::to_string)) {
+        set_tabs.update_untracked(|tabs| tabs.open(EditorTab::new("/src/App.tsx", &content), false));
+    }
+    use_tab_session(workspace_loaded, vfs, tabs, set_tabs);
+    let active_file = create_memo(move |_| tabs.with(|tabs| tabs.active_id().map(str::to_string)));
+    let outline = use_outline(tabs);
+    let (diagnostics, set_diagnostics) = create_signal(DiagnosticStore::new());
+    use_lint(tabs, vfs, set_diagnostics);
+    use_clone_detection(vfs, set_diagnostics);
+
+    // Keep open tabs in step with renames, deletions and changes made
+    // anywhere in the workspace (sidebar, undo, archive import). Tabs with
+    // unsaved changes keep them; saving recreates a deleted file.
+    let fs_events = use_fs_events(set_vfs, None);
+    create_effect(move |_| {
+        let events = fs_events.get();
+        let mut changed = Vec::new();
+        for event in &events {
+            match event {
+                FsEvent::Renamed { from, to } => {
+                    set_tabs.update(|tabs| tabs.rename(from, to));
+                    set_diagnostics.update(|diagnostics| diagnostics.rename(from, to));
+                }
+                FsEvent::Deleted { path } => {
+                    set_diagnostics.update(|diagnostics| diagnostics.remove(path));
+                    changed.push(path.clone())
+                }
+                FsEvent::Created { path, .. } | FsEvent::Modified { path } => changed.push(path.clone()),
+            }
+        }
+        if changed.is_empty() {
+            return;
+        }
+        let stale: Vec<String> = tabs.with_untracked(|tabs| {
+            tabs.tabs()
+                .iter()
+                .filter(|tab| !tab.modified && changed.iter().any(|path| is_within(&tab.id, path)))
+                .map(|tab| tab.id.clone())
+                .collect()
+        });
+        for id in stale {
+            let current = vfs.with_untracked(|fs| fs.read(&id).map(str::to_string).ok());
+            set_tabs.update(|tabs| match current {
+                Some(content) if tabs.get(&id).is_some_and(|tab| tab.content != *content) => tabs.reload(&id, &content),
+                Some(_) => {}
+                None => {
+                    tabs.close(&id);
+                }
+            });
+        }
+    });
 
     let (sidebar_width, set_sidebar_width) = use_local_storage("bloop-sidebar-width", 320);
     let (assistant_width, set_assistant_width) = use_local_storage("bloop-assistant-width", 480);
@@ -28,6 +141,192 @@ pub fn App() -> impl IntoView {
         set_toasts.update(|toasts| toasts.push(toast));
     };
 
+    let open_tab = move |path: String, preview: bool| {
+        if tabs.with_untracked(|tabs| tabs.get(&path).is_some()) {
+            set_tabs.update(|tabs| {
+                tabs.activate(&path);
+                if !preview {
+                    tabs.keep(&path);
+                }
+            });
+            return;
+        }
+        match vfs.with_untracked(|fs| fs.read(&path).map(str::to_string)) {
+            Ok(content) => set_tabs.update(|tabs| tabs.open(EditorTab::new(&path, &content), preview)),
+            Err(e) => add_toast(crate::types::ToastType::Error, e.to_string()),
+        }
+    };
+    let open_file = Callback::new(move |path: String| open_tab(path, false));
+    let preview_file = Callback::new(move |path: String| open_tab(path, true));
+    // Opens a search result's file with the match selected.
+    let open_match = Callback::new(move |found: SearchMatch| {
+        open_tab(found.path.clone(), false);
+        set_tabs.update(|tabs| {
+            let Some(tab) = tabs.get_mut(&found.path) else {
+                return;
+            };
+            let start = tab.content.position_to_offset(Position::new(found.line - 1, found.column - 1));
+            // A char is at most four bytes.
+            let limit = tab.content.floor_char_boundary(start + found.length * 4);
+            let end = tab
+                .content
+                .slice(start..limit)
+                .char_indices()
+                .nth(found.length)
+                .map_or(limit, |(index, _)| start + index);
+            tab.selections = Selections::single(Selection::new(start, end));
+        });
+    });
+
+    // Selects a symbol's name in the active tab. The outline may lag behind
+    // typing, so the range is kept inside the text.
+    let go_to_symbol = Callback::new(move |range: std::ops::Range<usize>| {
+        set_tabs.update(|tabs| {
+            if let Some(tab) = tabs.active_mut() {
+                let start = tab.content.floor_char_boundary(range.start);
+                let end = tab.content.floor_char_boundary(range.end);
+                tab.selections = Selections::single(Selection::new(start, end));
+            }
+        });
+    });
+
+    // Opens the file of a problem with its range selected.
+    let open_problem = Callback::new(move |(path, range): (String, std::ops::Range<usize>)| {
+        open_tab(path.clone(), false);
+        set_tabs.update(|tabs| {
+            if let Some(tab) = tabs.get_mut(&path) {
+                let start = tab.content.floor_char_boundary(range.start);
+                let end = tab.content.floor_char_boundary(range.end);
+                tab.selections = Selections::single(Selection::new(start, end));
+            }
+        });
+    });
+    // Makes a quick fix's edits in the file's tab as one undo step, unless
+    // the file is protected or its text has changed since the fix was found.
+    let apply_fix = Callback::new(move |(path, fix): (String, QuickFix)| {
+        open_tab(path.clone(), false);
+        let fits = tabs.with_untracked(|tabs| tabs.get(&path).is_some_and(|tab| fix.fits(&tab.content)));
+        if !fits || active_file.get_untracked().as_deref() != Some(path.as_str()) {
+            add_toast(crate::types::ToastType::Warning, format!("Could not apply \"{}\"", fix.title));
+            return;
+        }
+        let mut result = Ok(false);
+        set_tabs.update(|tabs| {
+            result = vfs.with_untracked(|fs| {
+                let plan = EditPlan::new(fix.edits.clone());
+                tabs.edit_selections(fs, |selections, buffer| plan.apply(selections, buffer))
+            });
+        });
+        if let Err(e) = result {
+            add_toast(crate::types::ToastType::Warning, format!("Could not apply \"{}\": {}", fix.title, e));
+        }
+    });
+    let show_problems = move || {
+        set_panel_view.set(PanelView::Problems);
+        set_terminal_visible.set(true);
+    };
+
+    // Writes a search panel replacement to the workspace as one undo step.
+    // Files with unsaved changes in a tab are left alone rather than
+    // overwritten.
+    let replace_in_files = move |plan: Vec<FileReplacement>| {
+        let unsaved: Vec<String> = tabs.with_untracked(|tabs| {
+            plan.iter()
+                .filter(|file| tabs.get(&file.path).is_some_and(|tab| tab.modified))
+                .map(|file| file.path.clone())
+                .collect()
+        });
+        if !unsaved.is_empty() {
+            add_toast(
+                crate::types::ToastType::Warning,
+                format!("Save or revert {} before replacing in it", unsaved.join(", ")),
+            );
+            return;
+        }
+        let count: usize = plan.iter().map(|file| file.count).sum();
+        let mut result = Ok(());
+        set_vfs.update(|fs| result = fs.apply_replacements(&plan));
+        match result {
+            Ok(()) => add_toast(
+                crate::types::ToastType::Success,
+                format!("Replaced {} occurrences in {} files", count, plan.len()),
+            ),
+            Err(e) => add_toast(crate::types::ToastType::Error, e.to_string()),
+        }
+    };
+
+    // Writes a tab's content to its file, recreating the file if it was
+    // deleted meanwhile. Returns whether it was saved.
+    let save_tab = Callback::new(move |id: String| {
+        let Some(content) = tabs.with_untracked(|tabs| tabs.get(&id).map(|tab| tab.content.to_string())) else {
+            return false;
+        };
+        let mut result = Ok(());
+        set_vfs.update(|fs| result = fs.write(&id, content));
+        match result {
+            Ok(()) => {
+                set_tabs.update(|tabs| tabs.mark_saved(&id));
+                true
+            }
+            Err(e) => {
+                add_toast(crate::types::ToastType::Error, e.to_string());
+                false
+            }
+        }
+    });
+
+    let import_archive = move |(name, bytes): (String, Vec<u8>)| {
+        let mut result = Ok(0);
+        set_vfs.update(|fs| result = fs.import_archive("/", &bytes));
+        match result {
+            Ok(count) => add_toast(
+                crate::types::ToastType::Success,
+                format!("Imported {} files from {}", count, name),
+            ),
+            Err(e) => add_toast(crate::types::ToastType::Error, e.to_string()),
+        }
+    };
+
+    let export_archive = move |format: ArchiveFormat| {
+        let result = vfs
+            .with_untracked(|fs| fs.export_archive("/", format))
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                download_bytes(&format!("workspace.{}", format.extension()), &bytes, "application/octet-stream")
+                    .map_err(|_| "Download failed".to_string())
+            });
+        if let Err(message) = result {
+            add_toast(crate::types::ToastType::Error, message);
+        }
+    };
+
+    // Undoes (or redoes) the last change to the workspace itself, as a
+    // deleted folder; open tabs follow through the change events.
+    let undo_workspace = move |redo: bool| {
+        let mut result = Ok(false);
+        set_vfs.update(|fs| result = if redo { fs.redo() } else { fs.undo() });
+        if let Err(e) = result {
+            add_toast(crate::types::ToastType::Error, e.to_string());
+        }
+    };
+
+    let trash_entry = move |path: String| {
+        let mut result = Ok(0);
+        set_vfs.update(|fs| result = fs.move_to_trash(&path));
+        if let Err(e) = result {
+            add_toast(crate::types::ToastType::Error, e.to_string());
+        }
+    };
+
+    let restore_trash = move |id: u64| {
+        let mut result = Ok(String::new());
+        set_vfs.update(|fs| result = fs.restore_from_trash(id));
+        match result {
+            Ok(path) => add_toast(crate::types::ToastType::Success, format!("Restored {}", path)),
+            Err(e) => add_toast(crate::types::ToastType::Error, e.to_string()),
+        }
+    };
+
     let remove_toast = move |id: String| {
         set_toasts.update(|toasts| toasts.retain(|t| t.id != id));
     };
@@ -60,18 +359,69 @@ pub fn App() -> impl IntoView {
             e.prevent_default();
             set_sidebar_collapsed.update(|c| *c = !*c);
         }
+        if (e.ctrl_key() || e.meta_key()) && e.shift_key() && e.key().eq_ignore_ascii_case("f") {
+            e.prevent_default();
+            set_sidebar_collapsed.set(false);
+            set_sidebar_view.set(SidebarView::Search);
+        }
         if (e.ctrl_key() || e.meta_key()) && e.key() == "`" {
             e.prevent_default();
             set_terminal_visible.update(|v| *v = !*v);
         }
+        if (e.ctrl_key() || e.meta_key()) && e.shift_key() && e.key().eq_ignore_ascii_case("m") {
+            e.prevent_default();
+            show_problems();
+        }
         if (e.ctrl_key() || e.meta_key()) && e.key() == "s" {
             e.prevent_default();
-            add_toast(crate::types::ToastType::Success, "File saved successfully".to_string());
+            if let Some(id) = active_file.get_untracked() {
+                if save_tab.call(id) {
+                    add_toast(crate::types::ToastType::Success, "File saved successfully".to_string());
+                }
+            }
+        }
+        // Undo and redo belong to the editor unless another text field has
+        // focus.
+        let in_other_field = e.target().and_then(|target| target.dyn_into::<web_sys::Element>().ok()).is_some_and(|el| {
+            matches!(el.tag_name().as_str(), "INPUT" | "TEXTAREA") && !el.has_attribute("data-editor")
+        });
+        // In the explorer they undo changes to the workspace.
+        let in_explorer = e
+            .target()
+            .and_then(|target| target.dyn_into::<web_sys::Element>().ok())
+            .and_then(|el| el.closest("[data-explorer]").ok().flatten())
+            .is_some();
+        if (e.ctrl_key() || e.meta_key()) && e.key().eq_ignore_ascii_case("z") && in_explorer {
+            e.prevent_default();
+            undo_workspace(e.shift_key());
+        } else if (e.ctrl_key() || e.meta_key()) && e.key().eq_ignore_ascii_case("z") && !in_other_field {
+            e.prevent_default();
+            let redo = e.shift_key();
+            set_tabs.update(|tabs| {
+                if redo {
+                    tabs.redo();
+                } else {
+                    tabs.undo();
+                }
+            });
+        }
+        if e.ctrl_key() && e.key() == "Tab" {
+            e.prevent_default();
+            set_tabs.update(|tabs| tabs.cycle_recent(!e.shift_key()));
         }
     }) as Box<dyn FnMut(_)>);
     document.add_event_listener_with_callback("keydown", closure.as_ref().unchecked_ref()).unwrap();
     closure.forget();
 
+    // Releasing Ctrl ends a Ctrl+Tab cycle on the tab it reached.
+    let closure = wasm_bindgen::closure::Closure::wrap(Box::new(move |e: web_sys::KeyboardEvent| {
+        if e.key() == "Control" {
+            set_tabs.update(|tabs| tabs.finish_cycle());
+        }
+    }) as Box<dyn FnMut(_)>);
+    document.add_event_listener_with_callback("keyup", closure.as_ref().unchecked_ref()).unwrap();
+    closure.forget();
+
     view! {
         <div
             style="width: 100vw; height: 100vh; background: #1e1e1e; color: #cccccc; display: flex; flex-direction: column; overflow: hidden; font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; font-size: 13px;"
@@ -79,6 +429,8 @@ pub fn App() -> impl IntoView {
             <MenuBar
                 on_toggle_terminal=move || set_terminal_visible.update(|v| *v = !*v)
                 on_show_toast=add_toast
+                vfs=vfs
+                on_open_file=open_file
             />
 
             <div style="display: flex; flex: 1; overflow: hidden; flex-direction: column;">
@@ -87,12 +439,35 @@ pub fn App() -> impl IntoView {
                         <LeftSidebar
                             on_collapse=move || set_sidebar_collapsed.set(true)
                             width=sidebar_width.get()
+                            vfs=vfs
+                            view=sidebar_view
+                            set_view=set_sidebar_view
+                            on_open_file=open_file
+                            on_preview_file=preview_file
+                            on_import_archive=import_archive
+                            on_export_archive=export_archive
+                            on_undo_workspace=move |_| undo_workspace(false)
+                            on_redo_workspace=move |_| undo_workspace(true)
+                            on_trash_entry=trash_entry
+                            on_restore_trash=restore_trash
+                            on_empty_trash=move |_| set_vfs.update(|fs| fs.empty_trash())
+                            on_open_match=open_match
+                            on_replace_in_files=replace_in_files
+                            outline=outline
+                            on_go_to_symbol=go_to_symbol
                             on_show_toast=add_toast
                         />
                         <ResizeHandle on_resize=handle_sidebar_resize direction="horizontal" />
                     </Show>
 
-                    <EditorArea on_show_toast=add_toast />
+                    <EditorArea
+                        vfs=vfs
+                        tabs=tabs
+                        set_tabs=set_tabs
+                        outline=outline
+                        diagnostics=diagnostics
+                        on_save_tab=save_tab
+                    />
 
                     <Show when=move || !assistant_collapsed.get()>
                         <ResizeHandle on_resize=handle_assistant_resize direction="horizontal" />
@@ -108,6 +483,13 @@ pub fn App() -> impl IntoView {
                         on_close=move || set_terminal_visible.set(false)
                         height=terminal_height.get()
                         on_resize=handle_terminal_resize
+                        view=panel_view
+                        set_view=set_panel_view
+                        diagnostics=diagnostics
+                        tabs=tabs
+                        vfs=vfs
+                        on_open_problem=open_problem
+                        on_apply_fix=apply_fix
                     />
                 </Show>
             </div>
@@ -115,10 +497,20 @@ pub fn App() -> impl IntoView {
             <StatusBar
                 terminal_visible=terminal_visible.get()
                 on_toggle_terminal=move || set_terminal_visible.update(|v| *v = !*v)
+                vfs=vfs
+                set_vfs=set_vfs
+                active_file=active_file
+                diagnostics=diagnostics
+                on_show_problems=show_problems
+                on_show_toast=add_toast
             />
 
             <Show when=move || show_command_palette.get()>
-                <CommandPalette on_close=move || set_show_command_palette.set(false) />
+                <CommandPalette
+                    vfs=vfs
+                    on_open_file=open_file
+                    on_close=move || set_show_command_palette.set(false)
+                />
             </Show>
 
             <BeginnerGuide />
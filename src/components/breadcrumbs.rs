@@ -0,0 +1,61 @@
+use std::ops::Range;
+
+use leptos::*;
+use crate::utils::code_analyzer::outline::{symbols_at, Symbol, SymbolKind};
+use crate::utils::editor::tabs::TabManager;
+
+/// The active file's folders and name, then the symbols the cursor is in.
+/// Clicking a symbol goes to it.
+#[component]
+pub fn Breadcrumbs(
+    tabs: ReadSignal<TabManager>,
+    outline: ReadSignal<Vec<Symbol>>,
+    #[prop(into)] on_go_to: Callback<Range<usize>>,
+) -> impl IntoView {
+    let path = create_memo(move |_| tabs.with(|tabs| tabs.active().map(|tab| tab.path.clone()).unwrap_or_default()));
+    let head = create_memo(move |_| tabs.with(|tabs| tabs.active().map(|tab| tab.selections.primary().head)));
+    let around = create_memo(move |_| {
+        let Some(head) = head.get() else {
+            return Vec::<(SymbolKind, String, Range<usize>)>::new();
+        };
+        outline.with(|symbols| {
+            symbols_at(symbols, head)
+                .into_iter()
+                .map(|symbol| (symbol.kind, symbol.name.clone(), symbol.name_range.clone()))
+                .collect()
+        })
+    });
+    let separator = || view! { <span style="margin: 0 4px; color: #6e6e6e;">"›"</span> };
+
+    view! {
+        <div style="display: flex; align-items: center; padding: 2px 16px; background: #1e1e1e; border-bottom: 1px solid #2b2b2b; color: #a9a9a9; font-size: 12px; white-space: nowrap; overflow: hidden;">
+            {move || {
+                path.get()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, part)| view! {
+                        {(index > 0).then(separator)}
+                        <span>{part}</span>
+                    })
+                    .collect_view()
+            }}
+            {move || {
+                around
+                    .get()
+                    .into_iter()
+                    .map(|(kind, name, range)| view! {
+                        {separator()}
+                        <span
+                            title=name.clone()
+                            on:click=move |_| on_go_to.call(range.clone())
+                            style="cursor: pointer;"
+                        >
+                            <span style="margin-right: 4px; color: #c586c0;">{kind.icon()}</span>
+                            {name}
+                        </span>
+                    })
+                    .collect_view()
+            }}
+        </div>
+    }
+}
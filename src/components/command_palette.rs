@@ -1,7 +1,27 @@
 use leptos::*;
+use crate::utils::file_system::VirtualFileSystem;
+
+const MAX_PALETTE_ITEMS: usize = 50;
 
 #[component]
-pub fn CommandPalette(on_close: impl Fn() + 'static) -> impl IntoView {
+pub fn CommandPalette(
+    vfs: ReadSignal<VirtualFileSystem>,
+    #[prop(into)] on_open_file: Callback<String>,
+    on_close: impl Fn() + Clone + 'static,
+) -> impl IntoView {
+    let (query, set_query) = create_signal(String::new());
+    let matches = create_memo(move |_| {
+        let needle = query.get().trim().to_lowercase();
+        vfs.with(|fs| {
+            fs.file_paths()
+                .into_iter()
+                .filter(|path| needle.is_empty() || path.to_lowercase().contains(&needle))
+                .take(MAX_PALETTE_ITEMS)
+                .collect::<Vec<_>>()
+        })
+    });
+    let close = on_close.clone();
+
     view! {
         <div
             style="position: fixed; top: 0; left: 0; right: 0; bottom: 0; background: rgba(0, 0, 0, 0.5); display: flex; align-items: center; justify-content: center; z-index: 1000;"
@@ -16,9 +36,39 @@ pub fn CommandPalette(on_close: impl Fn() + 'static) -> impl IntoView {
                     placeholder="Type a command..."
                     style="width: 100%; background: #1e1e1e; border: none; padding: 12px; color: #cccccc; font-size: 14px;"
                     autofocus=true
+                    on:input=move |ev| set_query.set(event_target_value(&ev))
                 />
-                <div style="padding: 8px; color: #858585; font-size: 11px;">
-                    "Commands will appear here"
+                <div style="max-height: 340px; overflow-y: auto;">
+                    <Show
+                        when=move || !matches.get().is_empty()
+                        fallback=|| view! {
+                            <div style="padding: 8px; color: #858585; font-size: 11px;">
+                                "No matching files"
+                            </div>
+                        }
+                    >
+                        {
+                            let close = close.clone();
+                            move || {
+                                let close = close.clone();
+                                matches.get().into_iter().map(move |path| {
+                                    let close = close.clone();
+                                    let label = path.clone();
+                                    view! {
+                                        <div
+                                            on:click=move |_| {
+                                                on_open_file.call(path.clone());
+                                                close();
+                                            }
+                                            style="padding: 6px 12px; color: #cccccc; font-size: 12px; cursor: pointer;"
+                                        >
+                                            {label}
+                                        </div>
+                                    }
+                                }).collect::<Vec<_>>()
+                            }
+                        }
+                    </Show>
                 </div>
             </div>
         </div>
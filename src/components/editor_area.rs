@@ -1,32 +1,1351 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::Duration;
+
+use leptos::leptos_dom::helpers::TimeoutHandle;
 use leptos::*;
-use crate::types::ToastType;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::HtmlTextAreaElement;
+use crate::components::{Breadcrumbs, FindWidget};
+use crate::utils::code_analyzer::outline::Symbol;
+use crate::utils::diagnostics::DiagnosticStore;
+use crate::utils::editor::buffer::{Position, TextBuffer};
+use crate::utils::editor::editing::{
+    delete_pair, jump_to_bracket, matching_bracket, newline, toggle_block_comment, toggle_line_comment, type_char,
+    EditPlan,
+};
+use crate::utils::editor::find::{next_match, FindQuery, FindScope};
+use crate::utils::editor::folding::{fold_ranges, FoldCommand, FoldRange, FoldedView};
+use crate::utils::editor::history::Edit;
+use crate::utils::editor::selection::{Motion, Selection, Selections};
+use crate::utils::editor::tabs::TabManager;
+use crate::utils::file_system::{FsError, VirtualFileSystem};
+use crate::utils::syntax::highlighter::Highlighter;
+use crate::utils::syntax::theme::Theme;
+use crate::utils::syntax::Language;
+use crate::types::EditorTab;
+
+/// Editor text metrics, matching the textarea's font size and line height.
+const LINE_HEIGHT: f64 = 14.0 * 1.6;
+const TAB_SIZE: usize = 4;
+/// Rows drawn past each end of the viewport, so scrolling a little never
+/// shows missing lines.
+const OVERSCAN: usize = 20;
+/// Quiet time after typing before fold ranges are worked out again, when
+/// nothing is folded.
+const FOLD_DELAY: Duration = Duration::from_millis(300);
+
+/// Selection and length of the textarea just before an edit, in UTF-16
+/// units.
+#[derive(Clone, Copy)]
+struct DomState {
+    selection_start: u32,
+    selection_end: u32,
+    backward: bool,
+    len: u32,
+}
+
+impl DomState {
+    fn of(textarea: &HtmlTextAreaElement) -> Self {
+        let selection_start = textarea.selection_start().ok().flatten().unwrap_or(0);
+        Self {
+            selection_start,
+            selection_end: textarea.selection_end().ok().flatten().unwrap_or(selection_start),
+            backward: textarea.selection_direction().ok().flatten().as_deref() == Some("backward"),
+            len: textarea.text_length(),
+        }
+    }
+
+    /// The selection as byte offsets into `buffer`, which `view` must show
+    /// as the textarea's text.
+    fn selection(&self, buffer: &TextBuffer, view: &FoldedView) -> Selection {
+        let start = view.utf16_to_offset(buffer, self.selection_start as usize);
+        let end = view.utf16_to_offset(buffer, self.selection_end as usize);
+        if self.backward {
+            Selection::new(end, start)
+        } else {
+            Selection::new(start, end)
+        }
+    }
+}
+
+/// Applies the edit the browser just made to `textarea` to `buffer`
+/// without copying the whole text out of the DOM, and returns it. An edit
+/// replaces `[start, end)` of the old text with `[start, caret)` of the new
+/// one; `start` and `end` follow from the caret and the change in length,
+/// which covers typing, deleting in either direction, paste and cut.
+/// Anything else (browser undo, inconsistent state) falls back to comparing
+/// the whole text with what `view` showed of `buffer`.
+fn apply_dom_edit(
+    buffer: &mut TextBuffer,
+    view: &FoldedView,
+    textarea: &HtmlTextAreaElement,
+    before: Option<DomState>,
+) -> Edit {
+    let caret = textarea.selection_start().ok().flatten().unwrap_or(0);
+    let len = textarea.text_length();
+    let range = before.and_then(|before| {
+        let start = before.selection_start.min(caret);
+        let end = (before.len + caret).checked_sub(len)?;
+        let consistent =
+            before.len as usize == view.len_utf16(buffer) && end >= before.selection_end && end <= before.len;
+        consistent.then_some((start, end))
+    });
+    let Some((start, end)) = range else {
+        let (shown, value) = (view.text(buffer), textarea.value());
+        let prefix: usize = shown
+            .chars()
+            .zip(value.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(c, _)| c.len_utf8())
+            .sum();
+        let suffix: usize = shown[prefix..]
+            .chars()
+            .rev()
+            .zip(value[prefix..].chars().rev())
+            .take_while(|(a, b)| a == b)
+            .map(|(c, _)| c.len_utf8())
+            .sum();
+        let utf16 = |bytes: usize| shown[..bytes].encode_utf16().count();
+        let start = view.utf16_to_offset(buffer, utf16(prefix));
+        let end = view.utf16_to_offset(buffer, utf16(shown.len() - suffix));
+        let edit = Edit::replace(buffer, start..end, &value[prefix..value.len() - suffix]);
+        edit.apply(buffer);
+        return edit;
+    };
+    let inserted = js_sys::Reflect::get(textarea, &JsValue::from_str("value"))
+        .map(|value| String::from(value.unchecked_into::<js_sys::JsString>().substring(start, caret)))
+        .unwrap_or_default();
+    let start = view.utf16_to_offset(buffer, start as usize);
+    let end = view.utf16_to_offset(buffer, end as usize);
+    let edit = Edit::replace(buffer, start..end, inserted);
+    edit.apply(buffer);
+    edit
+}
+
+/// Screen column of byte `index` in `line`, with tabs expanded.
+fn visual_column(line: &str, index: usize) -> usize {
+    line[..index]
+        .chars()
+        .fold(0, |column, c| if c == '\t' { column + TAB_SIZE - column % TAB_SIZE } else { column + 1 })
+}
+
+/// Boxes covering `range` of `buffer`, one per line, as the line and the
+/// screen columns they span. Past the end of a line the box takes one more
+/// column for the line break.
+fn line_boxes(buffer: &TextBuffer, range: Range<usize>) -> Vec<(usize, usize, usize)> {
+    let (first, last) = (buffer.offset_to_line(range.start), buffer.offset_to_line(range.end));
+    (first..=last)
+        .map(|line| {
+            let line_range = buffer.line_range(line);
+            let text = buffer.line(line);
+            let from = visual_column(&text, range.start.max(line_range.start) - line_range.start);
+            let to = if range.end > line_range.end {
+                visual_column(&text, text.len()) + 1
+            } else {
+                visual_column(&text, range.end - line_range.start)
+            };
+            (line, from, to)
+        })
+        .filter(|&(_, from, to)| to > from)
+        .collect()
+}
+
+/// A CSS background drawing a wavy line in `color`.
+fn squiggle(color: &str) -> String {
+    format!(
+        "url(\"data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000/svg' width='6' height='4'%3E%3Cpath d='M0 3 L1.5 1 L3 3 L4.5 1 L6 3' fill='none' stroke='{}' stroke-width='1'/%3E%3C/svg%3E\") repeat-x left bottom",
+        color.replace('#', "%23"),
+    )
+}
+
+/// The drawn `rows` of a layer as tall as all the rows of `view`, pushed
+/// down to where they belong.
+fn drawn(view: &FoldedView, rows: Range<usize>, line_count: usize, lines: View) -> View {
+    let row_count = view.row_count(line_count);
+    let style = format!(
+        "height: {}px; padding-top: {}px; box-sizing: border-box;",
+        row_count as f64 * LINE_HEIGHT,
+        rows.start.min(row_count) as f64 * LINE_HEIGHT,
+    );
+    view! { <div style=style>{lines}</div> }.into_view()
+}
+
+/// Char column of `line` nearest to screen column `visual`.
+fn char_column(line: &str, visual: usize) -> usize {
+    let mut column = 0;
+    for (index, c) in line.chars().enumerate() {
+        let width = if c == '\t' { TAB_SIZE - column % TAB_SIZE } else { 1 };
+        if column + width / 2 >= visual {
+            return index;
+        }
+        column += width;
+    }
+    line.chars().count()
+}
+
+/// The folding command for Ctrl+Shift+[ and ], with Alt to fold or unfold
+/// everything, or Ctrl+Shift+Alt and a digit to fold a level.
+fn fold_command(ev: &web_sys::KeyboardEvent) -> Option<FoldCommand> {
+    if !(ev.ctrl_key() || ev.meta_key()) || !ev.shift_key() {
+        return None;
+    }
+    Some(match (ev.code().as_str(), ev.alt_key()) {
+        ("BracketLeft", false) => FoldCommand::Fold,
+        ("BracketRight", false) => FoldCommand::Unfold,
+        ("BracketLeft", true) => FoldCommand::FoldAll,
+        ("BracketRight", true) => FoldCommand::UnfoldAll,
+        (code, true) => match code.strip_prefix("Digit")?.parse() {
+            Ok(level @ 1..=7) => FoldCommand::FoldLevel(level),
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+/// The key's cursor motion while editing with several cursors.
+fn motion_for(key: &str) -> Option<Motion> {
+    Some(match key {
+        "ArrowLeft" => Motion::Left,
+        "ArrowRight" => Motion::Right,
+        "ArrowUp" => Motion::Up,
+        "ArrowDown" => Motion::Down,
+        "Home" => Motion::LineStart,
+        "End" => Motion::LineEnd,
+        _ => return None,
+    })
+}
 
 #[component]
-pub fn EditorArea(on_show_toast: impl Fn(ToastType, String) + 'static) -> impl IntoView {
-    view! {
-        <div style="flex: 1; display: flex; flex-direction: column; background: #1e1e1e; overflow: hidden;">
-            <div style="display: flex; background: #2d2d2d; border-bottom: 1px solid #3e3e3e;">
+pub fn EditorArea(
+    vfs: ReadSignal<VirtualFileSystem>,
+    tabs: ReadSignal<TabManager>,
+    set_tabs: WriteSignal<TabManager>,
+    outline: ReadSignal<Vec<Symbol>>,
+    diagnostics: ReadSignal<DiagnosticStore>,
+    #[prop(into)] on_save_tab: Callback<String, bool>,
+) -> impl IntoView {
+    let active_id = create_memo(move |_| tabs.with(|tabs| tabs.active_id().map(str::to_string)));
+    let replacements = create_memo(move |_| tabs.with(TabManager::replacements));
+    let dom_before_edit = store_value(None::<DomState>);
+    let textarea_ref = create_node_ref::<html::Textarea>();
+    let dragged = store_value(None::<String>);
+    // Tab the context menu was opened on, and where.
+    let (menu, set_menu) = create_signal(None::<(String, i32, i32)>);
+    // Tabs waiting to be closed until the user decides about their unsaved
+    // changes.
+    let (pending_close, set_pending_close) = create_signal(None::<Vec<String>>);
+
+    let close_tabs = move |ids: Vec<String>| {
+        let unsaved = tabs.with_untracked(|tabs| ids.iter().any(|id| tabs.get(id).is_some_and(|tab| tab.modified)));
+        if unsaved {
+            set_pending_close.set(Some(ids));
+        } else {
+            set_tabs.update(|tabs| {
+                for id in &ids {
+                    tabs.close(id);
+                }
+            });
+        }
+    };
+    let finish_close = move |save: bool| {
+        let Some(ids) = pending_close.get_untracked() else {
+            return;
+        };
+        set_pending_close.set(None);
+        let unsaved: Vec<String> = tabs.with_untracked(|tabs| {
+            ids.iter().filter(|id| tabs.get(id).is_some_and(|tab| tab.modified)).cloned().collect()
+        });
+        let closing: Vec<String> = if save {
+            // A tab that fails to save stays open.
+            let failed: Vec<String> = unsaved.into_iter().filter(|id| !on_save_tab.call(id.clone())).collect();
+            ids.into_iter().filter(|id| !failed.contains(id)).collect()
+        } else {
+            ids
+        };
+        set_tabs.update(|tabs| {
+            for id in &closing {
+                tabs.close(id);
+            }
+        });
+    };
+
+    // Why the open file cannot be edited, if it cannot.
+    let protection = move || {
+        let path = active_id.get()?;
+        vfs.with(|fs| fs.exists(&path).then(|| fs.check_writable(&path).err()).flatten())
+            .map(|err| match err {
+                FsError::PermissionDenied(protected) if protected == path => "This file is read-only.".to_string(),
+                FsError::PermissionDenied(protected) => format!("{} is locked against edits.", protected),
+                other => other.to_string(),
+            })
+    };
+    // Bumped when the cursors change without the text changing, so they
+    // are shown again.
+    let (cursors_moved, set_cursors_moved) = create_signal(0u64);
+    let (scroll, set_scroll) = create_signal((0.0, 0.0));
+    let (viewport_height, set_viewport_height) = create_signal(0.0);
+    // Only the rows in view and `OVERSCAN` more either side are drawn. The
+    // range moves in steps of `OVERSCAN`, so scrolling redraws them only
+    // now and then.
+    let drawn_rows = create_memo(move |_| {
+        let first = (scroll.get().0 / LINE_HEIGHT) as usize;
+        let last = first + (viewport_height.get() / LINE_HEIGHT).ceil() as usize;
+        (first / OVERSCAN).saturating_sub(1) * OVERSCAN..(last / OVERSCAN + 2) * OVERSCAN
+    });
+    let measure_ref = create_node_ref::<html::Span>();
+    // The textarea's height follows the window and the panels around it.
+    create_effect(move |_| {
+        let Some(textarea) = textarea_ref.get() else {
+            return;
+        };
+        set_viewport_height.set(textarea.client_height() as f64);
+        let observed = textarea.clone();
+        let closure = wasm_bindgen::closure::Closure::wrap(Box::new(move || {
+            set_viewport_height.set(observed.client_height() as f64);
+        }) as Box<dyn FnMut()>);
+        if let Ok(observer) = web_sys::ResizeObserver::new(closure.as_ref().unchecked_ref()) {
+            observer.observe(&textarea);
+            on_cleanup(move || observer.disconnect());
+        }
+        closure.forget();
+    });
+    // Where an Alt+drag box selection started.
+    let column_anchor = store_value(None::<Position>);
+    let multiple = move || tabs.with_untracked(|tabs| tabs.active().is_some_and(|tab| tab.selections.is_multiple()));
+    // Protected files refuse edits; the textarea is read-only for them and
+    // the banner above it says why.
+    let edit_selections = move |edit: &dyn Fn(&mut Selections, &mut TextBuffer) -> Vec<Edit>| {
+        set_tabs.update(|tabs| {
+            let _ = vfs.with_untracked(|fs| tabs.edit_selections(fs, edit));
+        });
+        set_cursors_moved.update(|moved| *moved += 1);
+    };
+    let change_selections = move |change: &dyn Fn(&mut Selections, &TextBuffer)| {
+        set_tabs.update(|tabs| {
+            if let Some(tab) = tabs.active_mut() {
+                change(&mut tab.selections, &tab.content);
+                tab.history.break_coalescing();
+            }
+        });
+        set_cursors_moved.update(|moved| *moved += 1);
+    };
+
+    // Each tab keeps a highlighter, brought up to date with its text before
+    // use, so edits only re-tokenize the lines they affect. Highlighting
+    // and folding both work from its tokens.
+    let highlighters = store_value(HashMap::<String, Highlighter>::new());
+    let sync_highlighter = move |tabs: &TabManager, tab: &EditorTab| {
+        let language = tab.language.as_deref().map_or(Language::PlainText, Language::from_extension);
+        highlighters.update_value(|highlighters| {
+            highlighters.retain(|id, _| tabs.get(id).is_some());
+            let highlighter = highlighters
+                .entry(tab.id.clone())
+                .or_insert_with(|| Highlighter::new(language, &tab.content));
+            if highlighter.language() != language {
+                *highlighter = Highlighter::new(language, &tab.content);
+            }
+            highlighter.update(&tab.content);
+        });
+    };
+
+    // Folding. The textarea only holds the lines left visible, so offsets
+    // into it go through the active tab's folded view.
+    //
+    // Finding fold ranges scans the whole buffer. While typing in a tab
+    // with nothing folded they only place the gutter markers, so they are
+    // worked out once typing pauses; with folds, the text shown depends on
+    // them and they are worked out right away.
+    let (fold_ranges_due, set_fold_ranges_due) = create_signal(0u32);
+    let fold_timer = store_value(None::<TimeoutHandle>);
+    // The tab, text and due count the ranges were last worked out for.
+    let folded_for = store_value(None::<(String, TextBuffer, u32)>);
+    let foldable = create_memo(move |previous: Option<&Vec<FoldRange>>| {
+        let due = fold_ranges_due.get();
+        tabs.with(|tabs| {
+            let Some(tab) = tabs.active() else {
+                return Vec::new();
+            };
+            let last = folded_for.with_value(|last| {
+                last.as_ref().map(|(id, text, last_due)| {
+                    (id == &tab.id, text.is_same(&tab.content), *last_due == due)
+                })
+            });
+            match (previous, last) {
+                (Some(previous), Some((true, true, _))) => return previous.clone(),
+                (Some(previous), Some((true, false, true))) if tab.folds.is_empty() => {
+                    if let Some(timer) = fold_timer.get_value() {
+                        timer.clear();
+                    }
+                    let due = move || {
+                        fold_timer.set_value(None);
+                        set_fold_ranges_due.update(|due| *due += 1);
+                    };
+                    fold_timer.set_value(set_timeout_with_handle(due, FOLD_DELAY).ok());
+                    return previous.clone();
+                }
+                _ => {}
+            }
+            if let Some(timer) = fold_timer.get_value() {
+                timer.clear();
+                fold_timer.set_value(None);
+            }
+            folded_for.set_value(Some((tab.id.clone(), tab.content.clone(), due)));
+            sync_highlighter(tabs, tab);
+            highlighters.with_value(|highlighters| {
+                highlighters
+                    .get(&tab.id)
+                    .map_or_else(Vec::new, |highlighter| fold_ranges(&tab.content, highlighter))
+            })
+        })
+    });
+    let hidden_lines = create_memo(move |_| {
+        foldable.with(|ranges| tabs.with(|tabs| tabs.active().map_or_else(Vec::new, |tab| tab.folds.hidden(ranges))))
+    });
+    let folded_view = move || {
+        let hidden = hidden_lines.get_untracked();
+        tabs.with_untracked(|tabs| tabs.active().map(|tab| FoldedView::new(&tab.content, hidden)))
+            .unwrap_or_default()
+    };
+    // Changes the active tab's folds, then moves cursors the folds hid to
+    // the end of the line they fold under.
+    let change_folds = move |change: &dyn Fn(&mut EditorTab, &[FoldRange])| {
+        // Ranges put off while typing are worked out before folding.
+        if fold_timer.get_value().is_some() {
+            set_fold_ranges_due.update(|due| *due += 1);
+        }
+        let ranges = foldable.get_untracked();
+        set_tabs.update(|tabs| {
+            let Some(tab) = tabs.active_mut() else {
+                return;
+            };
+            tab.folds.retain(&ranges);
+            change(tab, &ranges);
+            let hidden = tab.folds.hidden(&ranges);
+            let line = tab.content.offset_to_line(tab.selections.primary().head);
+            if let Some(lines) = hidden.iter().find(|lines| lines.contains(&line)) {
+                let end = tab.content.line_range(lines.start() - 1).end;
+                tab.selections = Selections::single(Selection::cursor(end));
+            }
+        });
+        set_cursors_moved.update(|moved| *moved += 1);
+    };
+    let run_fold_command = move |command: FoldCommand| {
+        change_folds(&|tab, ranges| {
+            let line = tab.content.offset_to_line(tab.selections.primary().head);
+            tab.folds.run(command, ranges, line);
+        });
+    };
+
+    // Language-aware editing. Edits are planned from the active tab's
+    // text, selections and highlighter, then made as one undoable step;
+    // returns whether there was anything to do.
+    let plan_edit = move |plan: &dyn Fn(&Selections, &TextBuffer, &Highlighter) -> Option<EditPlan>| {
+        let planned = tabs.with_untracked(|tabs| {
+            let tab = tabs.active()?;
+            sync_highlighter(tabs, tab);
+            highlighters.with_value(|highlighters| plan(&tab.selections, &tab.content, highlighters.get(&tab.id)?))
+        });
+        let Some(planned) = planned else {
+            return false;
+        };
+        edit_selections(&|selections, buffer| planned.apply(selections, buffer));
+        true
+    };
+    // The bracket at the cursor and its match.
+    let matched_brackets = create_memo(move |_| {
+        cursors_moved.track();
+        tabs.with(|tabs| {
+            let tab = tabs.active()?;
+            let selection = tab.selections.primary();
+            if !selection.is_empty() {
+                return None;
+            }
+            sync_highlighter(tabs, tab);
+            highlighters.with_value(|highlighters| {
+                let highlighter = highlighters.get(&tab.id)?;
+                matching_bracket(&tab.content, highlighter, highlighter.language().config(), selection.head)
+            })
+        })
+    });
+    let go_to_bracket = move || {
+        let target = tabs.with_untracked(|tabs| {
+            let tab = tabs.active()?;
+            sync_highlighter(tabs, tab);
+            highlighters.with_value(|highlighters| {
+                let highlighter = highlighters.get(&tab.id)?;
+                let head = tab.selections.primary().head;
+                jump_to_bracket(&tab.content, highlighter, highlighter.language().config(), head)
+            })
+        });
+        if let Some(offset) = target {
+            change_selections(&|selections, _| *selections = Selections::single(Selection::cursor(offset)));
+        }
+    };
+    // Selects a symbol's name from the breadcrumbs. The outline may lag
+    // behind typing, so the range is kept inside the text.
+    let go_to_symbol = move |range: Range<usize>| {
+        change_selections(&|selections, buffer| {
+            let start = buffer.floor_char_boundary(range.start);
+            let end = buffer.floor_char_boundary(range.end);
+            *selections = Selections::single(Selection::new(start, end));
+        });
+        if let Some(textarea) = textarea_ref.get_untracked() {
+            let _ = textarea.focus();
+        }
+    };
+
+    // Find and replace. What "find in selection" searches is captured when
+    // it is turned on and follows the edits made after.
+    let (find_open, set_find_open) = create_signal(false);
+    let (show_replace, set_show_replace) = create_signal(false);
+    let (find_query, set_find_query) = create_signal(FindQuery::default());
+    let (find_scope, set_find_scope) = create_signal(None::<FindScope>);
+    let find_input = create_node_ref::<html::Input>();
+    let in_selection = create_memo(move |_| find_query.with(|query| query.in_selection));
+    create_effect(move |_| {
+        active_id.track();
+        let scope = in_selection
+            .get()
+            .then(|| {
+                tabs.with_untracked(|tabs| {
+                    let tab = tabs.active()?;
+                    let mut ranges: Vec<Range<usize>> = tab
+                        .selections
+                        .ranges()
+                        .iter()
+                        .filter(|selection| !selection.is_empty())
+                        .map(Selection::range)
+                        .collect();
+                    if ranges.is_empty() {
+                        let line = tab.content.offset_to_line(tab.selections.primary().head);
+                        ranges.push(tab.content.line_range(line));
+                    }
+                    Some(FindScope::new(&tab.content, ranges))
+                })
+            })
+            .flatten();
+        set_find_scope.set(scope);
+    });
+    let find_results = create_memo(move |_| {
+        if !find_open.get() {
+            return Ok(Vec::new());
+        }
+        let Some(finder) = find_query.with(FindQuery::compile)? else {
+            return Ok(Vec::new());
+        };
+        Ok(tabs.with(|tabs| {
+            tabs.active().map_or_else(Vec::new, |tab| {
+                let scope = find_scope.with(|scope| scope.as_ref().map(|scope| scope.ranges_in(&tab.content)));
+                finder.find_all(&tab.content.to_string(), scope.as_deref())
+            })
+        }))
+    });
+    // The match the primary selection is on.
+    let current_match = create_memo(move |_| {
+        cursors_moved.track();
+        let primary = tabs.with(|tabs| tabs.active().map(|tab| tab.selections.primary().range()))?;
+        find_results.with(|results| results.as_ref().ok()?.iter().position(|found| *found == primary))
+    });
+    // Scrolls the textarea so the line of `offset` is in view.
+    let reveal = move |offset: usize| {
+        let Some(textarea) = textarea_ref.get_untracked() else {
+            return;
+        };
+        let view = folded_view();
+        let line = tabs.with_untracked(|tabs| tabs.active().map(|tab| tab.content.offset_to_line(offset)));
+        let Some(row) = line.and_then(|line| view.row(line)) else {
+            return;
+        };
+        let top = row as f64 * LINE_HEIGHT;
+        let height = textarea.client_height() as f64;
+        let scroll_top = textarea.scroll_top() as f64;
+        if top < scroll_top || top + LINE_HEIGHT > scroll_top + height {
+            textarea.set_scroll_top((top - height / 2.0).max(0.0) as i32);
+        }
+    };
+    let find = move |forward: bool| {
+        let Ok(matches) = find_results.get_untracked() else {
+            return;
+        };
+        let Some(primary) = tabs.with_untracked(|tabs| tabs.active().map(|tab| tab.selections.primary())) else {
+            return;
+        };
+        let from = if forward { primary.end() } else { primary.start() };
+        let Some(found) = next_match(&matches, from, forward).map(|index| matches[index].clone()) else {
+            return;
+        };
+        change_selections(&|selections, _| *selections = Selections::single(Selection::new(found.start, found.end)));
+    };
+    // Replaces `matches` as one undoable step and leaves a cursor where the
+    // primary selection ended. Protected files are left alone; the find
+    // widget shows why.
+    let replace_matches = move |matches: Vec<Range<usize>>, replace: String| {
+        if protection().is_some() {
+            return;
+        }
+        let Some(finder) = find_query.with_untracked(FindQuery::compile).ok().flatten() else {
+            return;
+        };
+        let mut replaced = None;
+        set_tabs.update(|tabs| {
+            let Some(before) = tabs.active().map(|tab| tab.content.clone()) else {
+                return;
+            };
+            let mut edits = Vec::new();
+            let _ = vfs.with_untracked(|fs| {
+                tabs.edit_selections(fs, |selections, buffer| {
+                    edits = finder.replace_all(buffer, &matches, &replace);
+                    let head = edits.iter().fold(selections.primary().end(), |offset, edit| edit.map_offset(offset));
+                    *selections = Selections::single(Selection::cursor(head));
+                    edits.clone()
+                })
+            });
+            replaced = tabs.active().map(|tab| (before, edits, tab.content.clone()));
+        });
+        if let Some((before, edits, after)) = replaced {
+            set_find_scope.update(|scope| {
+                if let Some(scope) = scope {
+                    scope.edited(&before, &edits, &after);
+                }
+            });
+        }
+    };
+    let replace_one = move |replace: String| {
+        let current = current_match
+            .get_untracked()
+            .and_then(|index| find_results.with_untracked(|results| results.as_ref().ok()?.get(index).cloned()));
+        if let Some(found) = current {
+            replace_matches(vec![found], replace);
+        }
+        find(true);
+    };
+    let replace_all = move |replace: String| {
+        if let Ok(matches) = find_results.get_untracked() {
+            replace_matches(matches, replace);
+        }
+    };
+    let open_find = move |replace: bool| {
+        let selected = tabs.with_untracked(|tabs| {
+            let tab = tabs.active()?;
+            let text = tab.content.slice(tab.selections.primary().range());
+            (!text.is_empty() && !text.contains('\n')).then_some(text)
+        });
+        if let Some(text) = selected {
+            set_find_query.update(|query| query.text = text);
+        }
+        set_show_replace.set(replace);
+        set_find_open.set(true);
+        request_animation_frame(move || {
+            if let Some(input) = find_input.get_untracked() {
+                let _ = input.focus();
+                input.select();
+            }
+        });
+    };
+    let close_find = move || {
+        set_find_open.set(false);
+        if let Some(textarea) = textarea_ref.get_untracked() {
+            let _ = textarea.focus();
+        }
+    };
+
+    // Closing pairs, indenting new lines and the like are edited in the
+    // model. So is everything with several cursors, where the browser
+    // cannot edit the text itself; the edit is made at every cursor.
+    let on_before_input = move |ev: web_sys::InputEvent| {
+        let data = ev.data().unwrap_or_default().replace("\r\n", "\n");
+        let mut chars = data.chars();
+        let typed = chars.next().filter(|_| chars.next().is_none());
+        let planned = match ev.input_type().as_str() {
+            "insertText" => typed.is_some_and(|typed| {
+                plan_edit(&|selections, buffer, highlighter| {
+                    type_char(selections, buffer, highlighter, highlighter.language().config(), typed)
+                })
+            }),
+            "insertLineBreak" | "insertParagraph" => plan_edit(&|selections, buffer, highlighter| {
+                Some(newline(selections, buffer, highlighter.language().config()))
+            }),
+            "deleteContentBackward" => plan_edit(&|selections, buffer, highlighter| {
+                delete_pair(selections, buffer, highlighter.language().config())
+            }),
+            _ => false,
+        };
+        if planned {
+            ev.prevent_default();
+            return;
+        }
+        if !multiple() {
+            dom_before_edit.set_value(Some(DomState::of(&event_target(&ev))));
+            return;
+        }
+        ev.prevent_default();
+        match ev.input_type().as_str() {
+            "insertText" | "insertFromPaste" | "insertReplacementText" => {
+                edit_selections(&|selections, buffer| selections.insert(buffer, &data))
+            }
+            "deleteContentBackward" => edit_selections(&|selections, buffer| selections.delete(buffer, false)),
+            "deleteContentForward" => edit_selections(&|selections, buffer| selections.delete(buffer, true)),
+            "deleteByCut" => edit_selections(&|selections, buffer| {
+                selections.apply_edits(buffer, |_, selection| (selection.range(), String::new()))
+            }),
+            _ => {}
+        }
+    };
+    let on_input = move |ev: web_sys::Event| {
+        let textarea: HtmlTextAreaElement = event_target(&ev);
+        let before = dom_before_edit.get_value().filter(|_| {
+            ev.dyn_ref::<web_sys::InputEvent>()
+                .is_some_and(|ev| !ev.input_type().starts_with("history"))
+        });
+        dom_before_edit.set_value(None);
+        let view = folded_view();
+        set_tabs.update(|tabs| {
+            if let Some(tab) = tabs.active_mut() {
+                let selections_before = before
+                    .map_or(tab.selections.clone(), |before| Selections::single(before.selection(&tab.content, &view)));
+                let edit = apply_dom_edit(&mut tab.content, &view, &textarea, before);
+                let view = view.edited(&edit);
+                tab.folds.update(&tab.content);
+                let selections_after = Selections::single(DomState::of(&textarea).selection(&tab.content, &view));
+                tab.history.record(edit, selections_before, selections_after.clone(), js_sys::Date::now() as u64);
+                tab.selections = selections_after;
+                tab.modified = true;
+                tab.preview = false;
+            }
+        });
+    };
+    let on_key_down = move |ev: web_sys::KeyboardEvent| {
+        let key = ev.key();
+        let command = ev.ctrl_key() || ev.meta_key();
+        if command && !ev.shift_key() && !ev.alt_key() && (key.eq_ignore_ascii_case("f") || key.eq_ignore_ascii_case("h")) {
+            ev.prevent_default();
+            open_find(key.eq_ignore_ascii_case("h"));
+        } else if key == "F3" {
+            ev.prevent_default();
+            find(!ev.shift_key());
+        } else if command && !ev.shift_key() && !ev.alt_key() && key.eq_ignore_ascii_case("d") {
+            ev.prevent_default();
+            change_selections(&|selections, buffer| {
+                selections.select_next_occurrence(buffer);
+            });
+        } else if ev.ctrl_key() && ev.alt_key() && (key == "ArrowUp" || key == "ArrowDown") {
+            ev.prevent_default();
+            let above = key == "ArrowUp";
+            change_selections(&|selections, buffer| {
+                selections.add_cursor_vertically(buffer, above);
+            });
+        } else if command && ev.shift_key() && ev.code() == "Backslash" {
+            ev.prevent_default();
+            go_to_bracket();
+        } else if command && !ev.shift_key() && !ev.alt_key() && ev.code() == "Slash" {
+            ev.prevent_default();
+            if protection().is_none() {
+                plan_edit(&|selections, buffer, highlighter| {
+                    let config = highlighter.language().config();
+                    toggle_line_comment(selections, buffer, config)
+                        .or_else(|| toggle_block_comment(selections, buffer, config))
+                });
+            }
+        } else if !command && ev.shift_key() && ev.alt_key() && ev.code() == "KeyA" {
+            ev.prevent_default();
+            if protection().is_none() {
+                plan_edit(&|selections, buffer, highlighter| {
+                    toggle_block_comment(selections, buffer, highlighter.language().config())
+                });
+            }
+        } else if let Some(fold) = fold_command(&ev) {
+            ev.prevent_default();
+            run_fold_command(fold);
+        } else if multiple() && key == "Escape" {
+            ev.prevent_default();
+            change_selections(&|selections, _| selections.collapse_to_primary());
+        } else if find_open.get_untracked() && key == "Escape" {
+            ev.prevent_default();
+            close_find();
+        } else if let Some(motion) = motion_for(&key).filter(|_| multiple() && !command && !ev.alt_key()) {
+            ev.prevent_default();
+            let extend = ev.shift_key();
+            change_selections(&|selections, buffer| selections.move_all(buffer, motion, extend));
+        }
+    };
+    // Remember where the cursor is, so switching back to the tab or undoing
+    // restores it. Moving the cursor with the mouse or keyboard (other than
+    // the keys handled above) leaves a single cursor.
+    let track_selection = move |ev: web_sys::Event| {
+        if column_anchor.get_value().is_some() {
+            return;
+        }
+        let dom = DomState::of(&event_target(&ev));
+        let view = folded_view();
+        let mut moved = false;
+        set_tabs.update_untracked(|tabs| {
+            if let Some(tab) = tabs.active_mut() {
+                let selection = dom.selection(&tab.content, &view);
+                if selection != tab.selections.primary() {
+                    // Bracket matching and the find widget's "N of M"
+                    // follow the selection too.
+                    moved = true;
+                    tab.selections = Selections::single(selection);
+                    tab.history.break_coalescing();
+                }
+            }
+        });
+        if moved {
+            set_cursors_moved.update(|moved| *moved += 1);
+        }
+    };
+
+    // Position in the text under the mouse.
+    let position_at = move |ev: &web_sys::MouseEvent| {
+        let char_width = measure_ref
+            .get_untracked()
+            .map_or(8.4, |measure| measure.get_bounding_client_rect().width() / 100.0);
+        let (top, left) = scroll.get_untracked();
+        let row = ((ev.offset_y() as f64 + top) / LINE_HEIGHT).max(0.0) as usize;
+        let visual = ((ev.offset_x() as f64 + left) / char_width).round().max(0.0) as usize;
+        let line = folded_view().line_at(row);
+        tabs.with_untracked(|tabs| {
+            let buffer = &tabs.active()?.content;
+            let line = line.min(buffer.line_count() - 1);
+            Some(Position::new(line, char_column(&buffer.line(line), visual)))
+        })
+    };
+    let on_mouse_down = move |ev: web_sys::MouseEvent| {
+        if !ev.alt_key() || ev.button() != 0 {
+            return;
+        }
+        ev.prevent_default();
+        if let Some(textarea) = textarea_ref.get_untracked() {
+            let _ = textarea.focus();
+        }
+        let Some(anchor) = position_at(&ev) else {
+            return;
+        };
+        column_anchor.set_value(Some(anchor));
+        change_selections(&|selections, buffer| *selections = Selections::column(buffer, anchor, anchor));
+    };
+    // The messages of the problems under the mouse, shown as a tooltip.
+    let (hovered_problems, set_hovered_problems) = create_signal(None::<String>);
+    let on_mouse_move = move |ev: web_sys::MouseEvent| {
+        let hovered = position_at(&ev).and_then(|position| {
+            tabs.with_untracked(|tabs| {
+                let tab = tabs.active()?;
+                let offset = tab.content.position_to_offset(position);
+                let messages: Vec<String> = diagnostics.with_untracked(|store| {
+                    store
+                        .for_file(&tab.id)
+                        .into_iter()
+                        .filter(|diagnostic| diagnostic.range.start <= offset && offset <= diagnostic.range.end)
+                        .map(|diagnostic| match &diagnostic.code {
+                            Some(code) => format!("{} {}({})", diagnostic.message, diagnostic.source, code),
+                            None => format!("{} {}", diagnostic.message, diagnostic.source),
+                        })
+                        .collect()
+                });
+                (!messages.is_empty()).then(|| messages.join("\n"))
+            })
+        });
+        if hovered_problems.with_untracked(|shown| *shown != hovered) {
+            set_hovered_problems.set(hovered);
+        }
+        let Some(anchor) = column_anchor.get_value() else {
+            return;
+        };
+        if ev.buttons() & 1 == 0 {
+            column_anchor.set_value(None);
+            return;
+        }
+        if let Some(head) = position_at(&ev) {
+            change_selections(&|selections, buffer| *selections = Selections::column(buffer, anchor, head));
+        }
+    };
+
+    // Put the tab's primary selection back into the textarea once it has
+    // been refilled, and scroll to it; the others are drawn over it. The
+    // selection may also be set from outside, as when opening a search
+    // result, and folds hiding its head are opened.
+    let active_selection = create_memo(move |_| tabs.with(|tabs| tabs.active().map(|tab| tab.selections.primary())));
+    create_effect(move |_| {
+        active_id.track();
+        replacements.track();
+        cursors_moved.track();
+        hidden_lines.track();
+        let Some(selection) = active_selection.get() else {
+            return;
+        };
+        let line = tabs.with_untracked(|tabs| tabs.active().map(|tab| tab.content.offset_to_line(selection.head)));
+        if line.is_some_and(|line| folded_view().row(line).is_none()) {
+            change_folds(&|tab, ranges| {
+                tab.folds.reveal(ranges, tab.content.offset_to_line(selection.head));
+            });
+            return;
+        }
+        queue_microtask(move || {
+            let Some(textarea) = textarea_ref.get_untracked() else {
+                return;
+            };
+            let view = folded_view();
+            tabs.with_untracked(|tabs| {
+                let Some(tab) = tabs.active() else {
+                    return;
+                };
+                let selection = tab.selections.primary();
+                let start = view.offset_to_utf16(&tab.content, selection.start()) as u32;
+                let end = view.offset_to_utf16(&tab.content, selection.end()) as u32;
+                let direction = if selection.is_backward() { "backward" } else { "forward" };
+                let _ = textarea.set_selection_range_with_direction(start, end, direction);
+                reveal(selection.head);
+            });
+        });
+    });
+
+    // The text, colored, under the textarea (whose own text is
+    // transparent), with a marker after each folded line.
+    let theme = Theme::default();
+    let highlighted_text = move || {
+        let hidden = hidden_lines.get();
+        let rows = drawn_rows.get();
+        tabs.with(|tabs| {
+            let tab = tabs.active()?;
+            sync_highlighter(tabs, tab);
+            let view = FoldedView::new(&tab.content, hidden);
+            highlighters.with_value(|highlighters| {
+                let highlighter = highlighters.get(&tab.id)?;
+                let lines = view
+                    .lines_on_rows(rows.clone(), tab.content.line_count())
+                    .map(|line| {
+                        let text = tab.content.line(line);
+                        let mut at = 0;
+                        let mut spans = Vec::new();
+                        for token in highlighter.tokens(line) {
+                            if token.start > at {
+                                spans.push(text[at..token.start].to_string().into_view());
+                            }
+                            let token_text = text[token.start..token.end].to_string();
+                            spans.push(match theme.style(token.kind.scope()) {
+                                Some(style) => view! { <span style=style>{token_text}</span> }.into_view(),
+                                None => token_text.into_view(),
+                            });
+                            at = token.end;
+                        }
+                        spans.push(text[at..].to_string().into_view());
+                        if view.is_folded(line) {
+                            spans.push(view! { <span style="color: #858585;">" ⋯"</span> }.into_view());
+                        }
+                        view! { <div style=format!("height: {}px;", LINE_HEIGHT)>{spans}</div> }
+                    })
+                    .collect_view();
+                Some(drawn(&view, rows, tab.content.line_count(), lines))
+            })
+        })
+    };
+
+    // Find matches, matching brackets, and secondary cursors and
+    // selections, as boxes positioned over the text. Only what is in view
+    // is drawn.
+    let cursor_overlay = move || {
+        let (top, left) = scroll.get();
+        let height = viewport_height.get();
+        cursors_moved.track();
+        let brackets = matched_brackets.get();
+        let char_width = measure_ref
+            .get_untracked()
+            .map_or(8.4, |measure| measure.get_bounding_client_rect().width() / 100.0);
+        let hidden = hidden_lines.get();
+        tabs.with(|tabs| {
+            let tab = tabs.active()?;
+            let buffer = &tab.content;
+            let view = FoldedView::new(buffer, hidden);
+            let first = view.line_at((top / LINE_HEIGHT) as usize).min(buffer.line_count() - 1);
+            let last = view.line_at(((top + height) / LINE_HEIGHT) as usize).min(buffer.line_count() - 1);
+            let visible = buffer.line_start(first)..buffer.line_range(last).end;
+            let in_view = |range: Range<usize>| {
+                (range.end >= visible.start && range.start <= visible.end)
+                    .then(|| range.start.max(visible.start)..range.end.min(visible.end + 1))
+            };
+            // Line, screen columns, background and border.
+            let mut boxes: Vec<(usize, usize, usize, &'static str, Option<&'static str>)> = Vec::new();
+            find_results.with(|results| {
+                let current = current_match.get();
+                for (index, found) in results.iter().flatten().enumerate() {
+                    let Some(found) = in_view(found.clone()) else {
+                        continue;
+                    };
+                    let color = if current == Some(index) { theme.find_match } else { theme.find_match_highlight };
+                    boxes.extend(
+                        line_boxes(buffer, found)
+                            .into_iter()
+                            .map(|(line, from, to)| (line, from, to, color, None)),
+                    );
+                }
+            });
+            for at in brackets.into_iter().flat_map(|(at, found)| [at, found]) {
+                boxes.extend(line_boxes(buffer, at..buffer.next_char_boundary(at)).into_iter().map(|(line, from, to)| {
+                    (line, from, to, theme.bracket_match, Some(theme.bracket_match_border))
+                }));
+            }
+            if tab.selections.is_multiple() {
+                let primary = tab.selections.primary_index();
+                for (_, selection) in tab.selections.ranges().iter().enumerate().filter(|&(index, _)| index != primary) {
+                    let Some(range) = in_view(selection.range()) else {
+                        continue;
+                    };
+                    boxes.extend(
+                        line_boxes(buffer, range)
+                            .into_iter()
+                            .map(|(line, from, to)| (line, from, to, theme.selection, None)),
+                    );
+                    let line = buffer.offset_to_line(selection.head);
+                    let column = visual_column(&buffer.line(line), selection.head - buffer.line_start(line));
+                    boxes.push((line, column, column, theme.cursor, None));
+                }
+            }
+            // Line, screen columns and color of each squiggle under a
+            // problem. The problems may be a moment behind the text, so
+            // their ranges are kept inside it.
+            let squiggles: Vec<(usize, usize, usize, &'static str)> = diagnostics.with(|store| {
+                store
+                    .for_file(&tab.id)
+                    .into_iter()
+                    .filter_map(|diagnostic| {
+                        let start = buffer.floor_char_boundary(diagnostic.range.start);
+                        let end = buffer.floor_char_boundary(diagnostic.range.end);
+                        let end = end.max(buffer.next_char_boundary(start));
+                        Some((in_view(start..end)?, theme.severity(diagnostic.severity)))
+                    })
+                    .flat_map(|(range, color)| {
+                        line_boxes(buffer, range).into_iter().map(move |(line, from, to)| (line, from, to, color))
+                    })
+                    .collect()
+            });
+            let squiggles = squiggles
+                .into_iter()
+                .filter_map(|(line, from, to, color)| Some((view.row(line)?, from, to, color)))
+                .map(|(row, from, to, color)| {
+                    let style = format!(
+                        "position: absolute; top: {}px; left: {}px; width: {}px; height: 4px; background: {};",
+                        (row + 1) as f64 * LINE_HEIGHT - 4.0 - top,
+                        from as f64 * char_width - left,
+                        (to - from) as f64 * char_width,
+                        squiggle(color),
+                    );
+                    view! { <div style=style></div> }
+                });
+            Some(
+                boxes
+                    .into_iter()
+                    .filter_map(|(line, from, to, background, border)| {
+                        Some((view.row(line)?, from, to, background, border))
+                    })
+                    .map(|(row, from, to, background, border)| {
+                        // An empty box is a cursor.
+                        let style = format!(
+                            "position: absolute; top: {}px; left: {}px; width: {}px; height: {}px; background: {}; box-sizing: border-box; border: {};",
+                            row as f64 * LINE_HEIGHT - top,
+                            from as f64 * char_width - left,
+                            if to == from { 2.0 } else { (to - from) as f64 * char_width },
+                            LINE_HEIGHT,
+                            background,
+                            border.map_or("none".to_string(), |border| format!("1px solid {}", border)),
+                        );
+                        view! { <div style=style></div> }
+                    })
+                    .chain(squiggles)
+                    .collect_view(),
+            )
+        })
+    };
+
+    // Line numbers, with a toggle on each line a fold starts on. The gutter
+    // is as wide as the longest number.
+    let line_count = create_memo(move |_| tabs.with(|tabs| tabs.active().map_or(1, |tab| tab.content.line_count())));
+    let gutter = move || {
+        let hidden = hidden_lines.get();
+        let rows = drawn_rows.get();
+        tabs.with(|tabs| {
+            let tab = tabs.active()?;
+            let view = FoldedView::new(&tab.content, hidden);
+            foldable.with(|ranges| {
+                let lines = view
+                    .lines_on_rows(rows.clone(), tab.content.line_count())
+                    .map(|line| {
+                        let folds = ranges.binary_search_by_key(&line, |range| range.start).is_ok();
+                        let folded = view.is_folded(line);
+                        view! {
+                            <div style=format!("height: {}px; display: flex; justify-content: flex-end; gap: 4px;", LINE_HEIGHT)>
+                                <span>{line + 1}</span>
+                                <span
+                                    title=if folded { "Unfold" } else { "Fold" }
+                                    on:mousedown=move |ev| {
+                                        ev.prevent_default();
+                                        change_folds(&|tab, _| tab.folds.toggle(line));
+                                    }
+                                    style=format!(
+                                        "width: 12px; text-align: center; cursor: {};",
+                                        if folds { "pointer" } else { "default" },
+                                    )
+                                >
+                                    {folds.then_some(if folded { "▸" } else { "▾" })}
+                                </span>
+                            </div>
+                        }
+                    })
+                    .collect_view();
+                Some(drawn(&view, rows, tab.content.line_count(), lines))
+            })
+        })
+    };
+
+    let tab_strip = move || {
+        tabs.with(|manager| {
+            manager
+                .tabs()
+                .iter()
+                .enumerate()
+                .map(|(index, tab)| {
+                    let id = tab.id.clone();
+                    let active = manager.active_id() == Some(tab.id.as_str());
+                    let style = format!(
+                        "display: flex; align-items: center; gap: 6px; padding: 8px 8px 8px 16px; background: {}; border-right: 1px solid #3e3e3e; color: {}; font-size: 12px; cursor: pointer; white-space: nowrap; font-style: {};",
+                        if active { "#1e1e1e" } else { "#2d2d2d" },
+                        if active { "#ffffff" } else { "#969696" },
+                        if tab.preview { "italic" } else { "normal" },
+                    );
+                    let (activated, kept, menu_id, drag_id, closed) =
+                        (id.clone(), id.clone(), id.clone(), id.clone(), id.clone());
+                    view! {
+                        <div
+                            style=style
+                            title=tab.id.clone()
+                            draggable="true"
+                            on:click=move |_| set_tabs.update(|tabs| {
+                                tabs.activate(&activated);
+                            })
+                            on:dblclick=move |_| set_tabs.update(|tabs| tabs.keep(&kept))
+                            on:contextmenu=move |ev| {
+                                ev.prevent_default();
+                                set_menu.set(Some((menu_id.clone(), ev.client_x(), ev.client_y())));
+                            }
+                            on:dragstart=move |_| dragged.set_value(Some(drag_id.clone()))
+                            on:dragover=|ev| ev.prevent_default()
+                            on:drop=move |ev| {
+                                ev.prevent_default();
+                                if let Some(moved) = dragged.get_value() {
+                                    set_tabs.update(|tabs| tabs.move_tab(&moved, index));
+                                }
+                                dragged.set_value(None);
+                            }
+                        >
+                            {tab.pinned.then_some("📌")}
+                            <span>{tab.name.clone()}</span>
+                            <button
+                                title=if tab.modified { "Unsaved changes - close" } else { "Close" }
+                                on:click=move |ev| {
+                                    ev.stop_propagation();
+                                    close_tabs(vec![closed.clone()]);
+                                }
+                                style="background: transparent; border: none; color: inherit; cursor: pointer; padding: 0 4px; width: 20px;"
+                            >
+                                {if tab.modified { "●" } else { "×" }}
+                            </button>
+                        </div>
+                    }
+                })
+                .collect_view()
+        })
+    };
+
+    let context_menu = move || {
+        menu.get().map(|(id, x, y)| {
+            let pinned = tabs.with_untracked(|tabs| tabs.get(&id).is_some_and(|tab| tab.pinned));
+            let item = move |label: &'static str, action: Box<dyn Fn()>| view! {
+                <div
+                    on:click=move |_| {
+                        set_menu.set(None);
+                        action();
+                    }
+                    style="padding: 4px 16px; cursor: pointer; color: #cccccc; font-size: 12px; white-space: nowrap;"
+                >
+                    {label}
+                </div>
+            };
+            let (closed, others, right, pin) = (id.clone(), id.clone(), id.clone(), id);
+            view! {
                 <div
-                    style="padding: 8px 16px; background: #1e1e1e; border-right: 1px solid #3e3e3e; color: #cccccc; font-size: 12px; cursor: pointer;"
+                    style=format!(
+                        "position: fixed; left: {}px; top: {}px; background: #252526; border: 1px solid #3e3e3e; padding: 4px 0; z-index: 100; box-shadow: 0 4px 12px rgba(0, 0, 0, 0.3);",
+                        x, y
+                    )
                 >
-                    App.tsx
+                    {item("Close", Box::new(move || close_tabs(vec![closed.clone()])))}
+                    {item("Close Others", Box::new(move || close_tabs(tabs.with_untracked(|tabs| tabs.others(&others)))))}
+                    {item("Close to the Right", Box::new(move || close_tabs(tabs.with_untracked(|tabs| tabs.to_the_right(&right)))))}
+                    {item(
+                        if pinned { "Unpin" } else { "Pin" },
+                        Box::new(move || set_tabs.update(|tabs| tabs.set_pinned(&pin, !pinned))),
+                    )}
+                </div>
+            }
+        })
+    };
+
+    let close_dialog = move || {
+        pending_close.get().map(|ids| {
+            let names: Vec<String> = tabs.with_untracked(|tabs| {
+                ids.iter()
+                    .filter_map(|id| tabs.get(id).filter(|tab| tab.modified).map(|tab| tab.name.clone()))
+                    .collect()
+            });
+            let question = match names.as_slice() {
+                [name] => format!("Do you want to save the changes you made to {}?", name),
+                _ => format!("Do you want to save the changes to the following {} files?", names.len()),
+            };
+            let listed = (names.len() > 1).then(|| names.join(", "));
+            view! {
+                <div style="position: fixed; top: 0; left: 0; right: 0; bottom: 0; background: rgba(0, 0, 0, 0.5); display: flex; align-items: center; justify-content: center; z-index: 1000;">
+                    <div style="background: #2d2d2d; border: 1px solid #3e3e3e; border-radius: 4px; width: 420px; padding: 16px; color: #cccccc; font-size: 13px;">
+                        <div style="margin-bottom: 8px;">{question}</div>
+                        {listed.map(|listed| view! { <div style="margin-bottom: 8px; color: #858585;">{listed}</div> })}
+                        <div style="margin-bottom: 16px; color: #858585; font-size: 12px;">"Your changes will be lost if you don't save them."</div>
+                        <div style="display: flex; justify-content: flex-end; gap: 8px;">
+                            <button
+                                on:click=move |_| finish_close(true)
+                                style="background: #0e639c; border: none; color: #ffffff; padding: 4px 12px; cursor: pointer;"
+                            >
+                                "Save"
+                            </button>
+                            <button
+                                on:click=move |_| finish_close(false)
+                                style="background: #3a3d41; border: none; color: #cccccc; padding: 4px 12px; cursor: pointer;"
+                            >
+                                "Don't Save"
+                            </button>
+                            <button
+                                on:click=move |_| set_pending_close.set(None)
+                                style="background: #3a3d41; border: none; color: #cccccc; padding: 4px 12px; cursor: pointer;"
+                            >
+                                "Cancel"
+                            </button>
+                        </div>
+                    </div>
                 </div>
+            }
+        })
+    };
+
+    view! {
+        <div
+            style="flex: 1; display: flex; flex-direction: column; background: #1e1e1e; overflow: hidden;"
+            on:click=move |_| set_menu.set(None)
+        >
+            <div style="display: flex; background: #2d2d2d; border-bottom: 1px solid #3e3e3e; overflow-x: auto;">
+                {tab_strip}
             </div>
+            {context_menu}
+            {close_dialog}
+            <Show when=move || active_id.get().is_some()>
+                <Breadcrumbs tabs=tabs outline=outline on_go_to=go_to_symbol />
+            </Show>
             <div style="flex: 1; overflow: auto; padding: 16px; color: #cccccc; font-family: 'Fira Code', monospace; font-size: 14px; line-height: 1.6;">
-                <pre style="margin: 0; white-space: pre-wrap;">
-                    <span style="color: #569cd6;">import</span> <span style="color: #4ec9b0;">React</span> <span style="color: #569cd6;">from</span> <span style="color: #ce9178;">'react'</span>
-                    <br/>
-                    <br/>
-                    <span style="color: #569cd6;">export default function</span> <span style="color: #dcdcaa;">App</span>() <span style="color: #569cd6;">{</span>
-                    <br/>
-                    <span style="color: #808080;">  </span><span style="color: #569cd6;">return</span> <span style="color: #569cd6;">&lt;</span><span style="color: #4ec9b0;">div</span><span style="color: #569cd6;">&gt;</span>
-                    <br/>
-                    <span style="color: #808080;">    </span><span style="color: #ce9178;">Hello, Bloop!</span>
-                    <br/>
-                    <span style="color: #808080;">  </span><span style="color: #569cd6;">&lt;/</span><span style="color: #4ec9b0;">div</span><span style="color: #569cd6;">&gt;</span>
-                    <br/>
-                    <span style="color: #569cd6;">}</span>
-                </pre>
+                {move || protection().map(|reason| view! {
+                    <div style="margin-bottom: 8px; padding: 6px 10px; background: #3a3d41; border-left: 3px solid #cca700; color: #cccccc; font-family: sans-serif; font-size: 12px;">
+                        "🔒 " {reason}
+                    </div>
+                })}
+                <Show
+                    when=move || active_id.get().is_some()
+                    fallback=|| view! {
+                        <div style="color: #858585; font-size: 12px;">"Open a file from the explorer or press Ctrl+K"</div>
+                    }
+                >
+                    <div style="display: flex; width: 100%; height: 100%;">
+                        <div style=move || format!(
+                            "min-width: {}ch; padding: 0 12px 0 8px; overflow: hidden; color: #858585; user-select: none;",
+                            line_count.get().to_string().len() + 2,
+                        )>
+                            <div style=move || format!("transform: translateY({}px);", -scroll.get().0)>
+                                {gutter}
+                            </div>
+                        </div>
+                        <div style="position: relative; flex: 1; height: 100%;">
+                            <span
+                                node_ref=measure_ref
+                                aria-hidden="true"
+                                style="position: absolute; visibility: hidden; white-space: pre; font: inherit;"
+                            >
+                                {"0".repeat(100)}
+                            </span>
+                            <div
+                                aria-hidden="true"
+                                style=move || {
+                                    let (top, left) = scroll.get();
+                                    format!(
+                                        "position: absolute; top: 0; left: 0; white-space: pre; tab-size: 4; color: {}; transform: translate({}px, {}px);",
+                                        theme.foreground, -left, -top,
+                                    )
+                                }
+                            >
+                                {highlighted_text}
+                            </div>
+                            <textarea
+                                node_ref=textarea_ref
+                                data-editor="true"
+                                wrap="off"
+                                style=format!(
+                                    "width: 100%; height: 100%; margin: 0; padding: 0; border: none; outline: none; resize: none; position: relative; background: transparent; color: transparent; caret-color: {}; font: inherit; line-height: inherit; tab-size: 4; white-space: pre;",
+                                    theme.cursor,
+                                )
+                                spellcheck="false"
+                                title=move || hovered_problems.get()
+                                readonly=move || protection().is_some()
+                                prop:value=move || {
+                                    active_id.track();
+                                    replacements.track();
+                                    hidden_lines.track();
+                                    let view = folded_view();
+                                    tabs.with_untracked(|tabs| tabs.active().map(|tab| view.text(&tab.content)).unwrap_or_default())
+                                }
+                                on:beforeinput=on_before_input
+                                on:input=on_input
+                                on:keydown=on_key_down
+                                on:keyup=move |ev| track_selection(ev.into())
+                                on:mousedown=on_mouse_down
+                                on:mousemove=on_mouse_move
+                                on:mouseup=move |ev| {
+                                    if column_anchor.get_value().is_some() {
+                                        column_anchor.set_value(None);
+                                    } else {
+                                        track_selection(ev.into());
+                                    }
+                                }
+                                on:scroll=move |ev| {
+                                    let textarea: HtmlTextAreaElement = event_target(&ev);
+                                    set_scroll.set((textarea.scroll_top() as f64, textarea.scroll_left() as f64));
+                                }
+                            />
+                            <div style="position: absolute; top: 0; left: 0; right: 0; bottom: 0; overflow: hidden; pointer-events: none;">
+                                {cursor_overlay}
+                            </div>
+                            <Show when=move || find_open.get()>
+                                <FindWidget
+                                    query=find_query
+                                    set_query=set_find_query
+                                    show_replace=show_replace
+                                    set_show_replace=set_show_replace
+                                    results=find_results
+                                    current=current_match
+                                    find_input=find_input
+                                    on_find=find
+                                    on_replace=replace_one
+                                    on_replace_all=replace_all
+                                    on_close=move |_| close_find()
+                                    protection=Signal::derive(protection)
+                                />
+                            </Show>
+                        </div>
+                    </div>
+                </Show>
             </div>
         </div>
     }
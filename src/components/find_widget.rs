@@ -0,0 +1,166 @@
+use std::ops::Range;
+
+use leptos::*;
+use crate::utils::editor::find::FindQuery;
+
+#[component]
+pub fn FindWidget(
+    query: ReadSignal<FindQuery>,
+    set_query: WriteSignal<FindQuery>,
+    show_replace: ReadSignal<bool>,
+    set_show_replace: WriteSignal<bool>,
+    #[prop(into)] results: Signal<Result<Vec<Range<usize>>, String>>,
+    #[prop(into)] current: Signal<Option<usize>>,
+    find_input: NodeRef<html::Input>,
+    #[prop(into)] on_find: Callback<bool>,
+    #[prop(into)] on_replace: Callback<String>,
+    #[prop(into)] on_replace_all: Callback<String>,
+    #[prop(into)] on_close: Callback<()>,
+    /// Why the file cannot be edited, if it cannot; replacing is then off.
+    #[prop(into)] protection: Signal<Option<String>>,
+) -> impl IntoView {
+    let (replacement, set_replacement) = create_signal(String::new());
+
+    let toggle = move |label: &'static str, title: &'static str, field: fn(&mut FindQuery) -> &mut bool| {
+        let active = move || *field(&mut query.get());
+        view! {
+            <button
+                title=title
+                on:click=move |_| set_query.update(|query| {
+                    let value = field(query);
+                    *value = !*value;
+                })
+                style=move || format!(
+                    "background: {}; border: 1px solid {}; color: #cccccc; font-size: 11px; padding: 1px 4px; cursor: pointer; border-radius: 3px;",
+                    if active() { "rgba(0, 122, 204, 0.4)" } else { "transparent" },
+                    if active() { "#007acc" } else { "transparent" },
+                )
+            >
+                {label}
+            </button>
+        }
+    };
+    // Alt+C, Alt+W, Alt+R, Alt+P and Alt+L toggle the options, as in VS Code.
+    let toggle_key = move |ev: &web_sys::KeyboardEvent| -> bool {
+        if !ev.alt_key() {
+            return false;
+        }
+        let field: fn(&mut FindQuery) -> &mut bool = match ev.key().to_ascii_lowercase().as_str() {
+            "c" => |query| &mut query.case_sensitive,
+            "w" => |query| &mut query.whole_word,
+            "r" => |query| &mut query.regex,
+            "p" => |query| &mut query.preserve_case,
+            "l" => |query| &mut query.in_selection,
+            _ => return false,
+        };
+        set_query.update(|query| {
+            let value = field(query);
+            *value = !*value;
+        });
+        true
+    };
+    let status = move || match results.get() {
+        Err(err) => view! { <span style="color: #f48771;" title=err.clone()>"Invalid pattern"</span> }.into_view(),
+        Ok(found) if found.is_empty() => {
+            let empty = query.with(|query| query.text.is_empty());
+            view! { <span style="color: #f48771;">{(!empty).then_some("No results")}</span> }.into_view()
+        }
+        Ok(found) => {
+            let position = current.get().map_or("?".to_string(), |index| (index + 1).to_string());
+            view! { <span>{format!("{} of {}", position, found.len())}</span> }.into_view()
+        }
+    };
+    let input_style = "width: 200px; background: #3c3c3c; border: 1px solid #3c3c3c; color: #cccccc; font-size: 12px; padding: 3px 6px; outline: none;";
+    let button_style = "background: transparent; border: none; color: #cccccc; cursor: pointer; font-size: 12px; padding: 0 4px;";
+
+    view! {
+        <div
+            style="position: absolute; top: 0; right: 16px; z-index: 10; display: flex; gap: 4px; padding: 4px 8px; background: #252526; box-shadow: 0 2px 8px rgba(0, 0, 0, 0.36); color: #cccccc; font-family: sans-serif; font-size: 12px;"
+            on:keydown=move |ev| {
+                if ev.key() == "Escape" {
+                    ev.prevent_default();
+                    on_close.call(());
+                } else if toggle_key(&ev) {
+                    ev.prevent_default();
+                }
+            }
+        >
+            <button
+                title="Toggle Replace"
+                on:click=move |_| set_show_replace.update(|shown| *shown = !*shown)
+                style=button_style
+            >
+                {move || if show_replace.get() { "▾" } else { "▸" }}
+            </button>
+            <div style="display: flex; flex-direction: column; gap: 4px;">
+                <div style="display: flex; align-items: center; gap: 4px;">
+                    <input
+                        node_ref=find_input
+                        type="text"
+                        placeholder="Find"
+                        style=input_style
+                        prop:value=move || query.with(|query| query.text.clone())
+                        on:input=move |ev| {
+                            let text = event_target_value(&ev);
+                            set_query.update(|query| query.text = text);
+                        }
+                        on:keydown=move |ev| {
+                            if ev.key() == "Enter" {
+                                ev.prevent_default();
+                                on_find.call(!ev.shift_key());
+                            }
+                        }
+                    />
+                    {toggle("Aa", "Match Case (Alt+C)", |query| &mut query.case_sensitive)}
+                    {toggle("ab", "Match Whole Word (Alt+W)", |query| &mut query.whole_word)}
+                    {toggle(".*", "Use Regular Expression (Alt+R)", |query| &mut query.regex)}
+                    <span style="min-width: 70px; padding: 0 4px; white-space: nowrap;">{status}</span>
+                    <button title="Previous Match (Shift+Enter)" on:click=move |_| on_find.call(false) style=button_style>"↑"</button>
+                    <button title="Next Match (Enter)" on:click=move |_| on_find.call(true) style=button_style>"↓"</button>
+                    {toggle("≡", "Find in Selection (Alt+L)", |query| &mut query.in_selection)}
+                    <button title="Close (Escape)" on:click=move |_| on_close.call(()) style=button_style>"×"</button>
+                </div>
+                <Show when=move || show_replace.get()>
+                    <div style="display: flex; align-items: center; gap: 4px;">
+                        <input
+                            type="text"
+                            placeholder="Replace"
+                            style=input_style
+                            title=move || protection.get()
+                            disabled=move || protection.with(Option::is_some)
+                            prop:value=replacement
+                            on:input=move |ev| set_replacement.set(event_target_value(&ev))
+                            on:keydown=move |ev| {
+                                if ev.key() == "Enter" {
+                                    ev.prevent_default();
+                                    if ev.ctrl_key() && ev.alt_key() {
+                                        on_replace_all.call(replacement.get_untracked());
+                                    } else {
+                                        on_replace.call(replacement.get_untracked());
+                                    }
+                                }
+                            }
+                        />
+                        {toggle("AB", "Preserve Case (Alt+P)", |query| &mut query.preserve_case)}
+                        <button
+                            title=move || protection.get().unwrap_or_else(|| "Replace (Enter)".to_string())
+                            disabled=move || protection.with(Option::is_some)
+                            on:click=move |_| on_replace.call(replacement.get_untracked())
+                            style=button_style
+                        >
+                            "Replace"
+                        </button>
+                        <button
+                            title=move || protection.get().unwrap_or_else(|| "Replace All (Ctrl+Alt+Enter)".to_string())
+                            disabled=move || protection.with(Option::is_some)
+                            on:click=move |_| on_replace_all.call(replacement.get_untracked())
+                            style=button_style
+                        >
+                            "All"
+                        </button>
+                    </div>
+                </Show>
+            </div>
+        </div>
+    }
+}
@@ -1,12 +1,144 @@
+use std::ops::Range;
+
 use leptos::*;
-use crate::types::ToastType;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use crate::components::SearchPanel;
+use crate::types::{FileNode, FileType, SidebarView, ToastType};
+use crate::utils::code_analyzer::outline::Symbol;
+use crate::utils::file_system::archive::ArchiveFormat;
+use crate::utils::file_system::search::{FileReplacement, SearchMatch};
+use crate::utils::file_system::VirtualFileSystem;
+
+/// Reads the file picked in an `<input type="file">` and hands its name and
+/// bytes to `on_loaded`.
+fn read_picked_file(ev: &web_sys::Event, on_loaded: Callback<(String, Vec<u8>)>) {
+    let input: web_sys::HtmlInputElement = event_target(ev);
+    let Some(file) = input.files().and_then(|files| files.get(0)) else {
+        return;
+    };
+    input.set_value("");
+    let Ok(reader) = web_sys::FileReader::new() else {
+        return;
+    };
+    let name = file.name();
+    let loaded = reader.clone();
+    let onload = Closure::once(move |_: web_sys::ProgressEvent| {
+        if let Ok(buffer) = loaded.result() {
+            on_loaded.call((name, js_sys::Uint8Array::new(&buffer).to_vec()));
+        }
+    });
+    reader.set_onload(Some(onload.as_ref().unchecked_ref()));
+    onload.forget();
+    let _ = reader.read_as_array_buffer(&file);
+}
+
+fn tree_rows(
+    fs: &VirtualFileSystem,
+    node: &FileNode,
+    depth: usize,
+    on_open_file: Callback<String>,
+    on_preview_file: Callback<String>,
+    on_trash_entry: Callback<String>,
+    rows: &mut Vec<View>,
+) {
+    for child in node.children.iter().flatten() {
+        let path = child.path.clone().unwrap_or_default();
+        let is_file = child.file_type == FileType::File;
+        let icon = match child.file_type {
+            FileType::Folder => "📁",
+            FileType::File => "📄",
+        };
+        let lock = if fs.is_writable(&path) { "" } else { " 🔒" };
+        let label = format!("{} {}{}", icon, child.name, lock);
+        let opened = path.clone();
+        let trashed = path.clone();
+        rows.push(view! {
+            <div
+                style=format!("display: flex; color: #cccccc; font-size: 12px; padding: 2px 0; margin-left: {}px; cursor: pointer;", depth * 16)
+                title=path.clone()
+                on:click=move |_| {
+                    if is_file {
+                        on_preview_file.call(path.clone());
+                    }
+                }
+                on:dblclick=move |_| {
+                    if is_file {
+                        on_open_file.call(opened.clone());
+                    }
+                }
+            >
+                <span style="flex: 1; overflow: hidden; text-overflow: ellipsis; white-space: nowrap;">{label}</span>
+                <button
+                    title="Move to Trash"
+                    on:click=move |ev| {
+                        ev.stop_propagation();
+                        on_trash_entry.call(trashed.clone());
+                    }
+                    style="background: transparent; border: none; color: #858585; cursor: pointer; padding: 0 4px; font-size: 11px;"
+                >
+                    "🗑"
+                </button>
+            </div>
+        }.into_view());
+        tree_rows(fs, child, depth + 1, on_open_file, on_preview_file, on_trash_entry, rows);
+    }
+}
+
+fn outline_rows(symbols: &[Symbol], depth: usize, on_go_to_symbol: Callback<Range<usize>>, rows: &mut Vec<View>) {
+    for symbol in symbols {
+        let range = symbol.name_range.clone();
+        rows.push(view! {
+            <div
+                style=format!("display: flex; gap: 6px; color: #cccccc; font-size: 12px; padding: 2px 0; margin-left: {}px; cursor: pointer; white-space: nowrap;", depth * 16)
+                title=symbol.name.clone()
+                on:click=move |_| on_go_to_symbol.call(range.clone())
+            >
+                <span style="color: #c586c0;">{symbol.kind.icon()}</span>
+                <span style="overflow: hidden; text-overflow: ellipsis;">{symbol.name.clone()}</span>
+            </div>
+        }.into_view());
+        outline_rows(&symbol.children, depth + 1, on_go_to_symbol, rows);
+    }
+}
 
 #[component]
 pub fn LeftSidebar(
     on_collapse: impl Fn() + 'static,
     width: u32,
+    vfs: ReadSignal<VirtualFileSystem>,
+    view: ReadSignal<SidebarView>,
+    set_view: WriteSignal<SidebarView>,
+    #[prop(into)] on_open_file: Callback<String>,
+    #[prop(into)] on_preview_file: Callback<String>,
+    #[prop(into)] on_import_archive: Callback<(String, Vec<u8>)>,
+    #[prop(into)] on_export_archive: Callback<ArchiveFormat>,
+    #[prop(into)] on_undo_workspace: Callback<()>,
+    #[prop(into)] on_redo_workspace: Callback<()>,
+    #[prop(into)] on_trash_entry: Callback<String>,
+    #[prop(into)] on_restore_trash: Callback<u64>,
+    #[prop(into)] on_empty_trash: Callback<()>,
+    #[prop(into)] on_open_match: Callback<SearchMatch>,
+    #[prop(into)] on_replace_in_files: Callback<Vec<FileReplacement>>,
+    outline: ReadSignal<Vec<Symbol>>,
+    #[prop(into)] on_go_to_symbol: Callback<Range<usize>>,
     on_show_toast: impl Fn(ToastType, String) + 'static,
 ) -> impl IntoView {
+    let view_tab = move |label: &'static str, target: SidebarView| {
+        view! {
+            <button
+                on:click=move |_| set_view.set(target)
+                style=move || format!(
+                    "background: transparent; border: none; border-bottom: 1px solid {}; color: {}; cursor: pointer; padding: 2px 4px; font-weight: 600; font-size: 11px; text-transform: uppercase;",
+                    if view.get() == target { "#e7e7e7" } else { "transparent" },
+                    if view.get() == target { "#ffffff" } else { "#858585" },
+                )
+            >
+                {label}
+            </button>
+        }
+    };
+
     view! {
         <div
             style=move || format!(
@@ -16,23 +148,126 @@ pub fn LeftSidebar(
         >
             <div style="padding: 8px; border-bottom: 1px solid #3e3e3e;">
                 <div style="display: flex; align-items: center; justify-content: space-between;">
-                    <span style="font-weight: 600; color: #ffffff; font-size: 11px; text-transform: uppercase;">
-                        Explorer
-                    </span>
-                    <button
-                        on:click=move |_| on_collapse()
-                        style="background: transparent; border: none; color: #cccccc; cursor: pointer; padding: 2px 4px;"
-                    >
-                        "×"
-                    </button>
+                    <div style="display: flex; gap: 4px;">
+                        {view_tab("Explorer", SidebarView::Explorer)}
+                        {view_tab("Search", SidebarView::Search)}
+                    </div>
+                    <div style="display: flex; align-items: center; gap: 4px;">
+                        <Show when=move || view.get() == SidebarView::Explorer>
+                            <label
+                                title="Import .zip / .tar / .tar.gz"
+                                style="color: #cccccc; cursor: pointer; padding: 2px 4px; font-size: 11px;"
+                            >
+                                "Import"
+                                <input
+                                    type="file"
+                                    accept=".zip,.tar,.tar.gz,.tgz"
+                                    style="display: none;"
+                                    on:change=move |ev| read_picked_file(&ev, on_import_archive)
+                                />
+                            </label>
+                            <button
+                                title="Download workspace as .zip"
+                                on:click=move |_| on_export_archive.call(ArchiveFormat::Zip)
+                                style="background: transparent; border: none; color: #cccccc; cursor: pointer; padding: 2px 4px; font-size: 11px;"
+                            >
+                                "Export"
+                            </button>
+                            <button
+                                title=move || vfs.with(|fs| fs.undo_label().map_or("Nothing to undo".to_string(), |label| format!("Undo {} (Ctrl+Z)", label)))
+                                disabled=move || !vfs.with(VirtualFileSystem::can_undo)
+                                on:click=move |_| on_undo_workspace.call(())
+                                style="background: transparent; border: none; color: #cccccc; cursor: pointer; padding: 2px 4px; font-size: 11px;"
+                            >
+                                "↶"
+                            </button>
+                            <button
+                                title=move || vfs.with(|fs| fs.redo_label().map_or("Nothing to redo".to_string(), |label| format!("Redo {} (Ctrl+Shift+Z)", label)))
+                                disabled=move || !vfs.with(VirtualFileSystem::can_redo)
+                                on:click=move |_| on_redo_workspace.call(())
+                                style="background: transparent; border: none; color: #cccccc; cursor: pointer; padding: 2px 4px; font-size: 11px;"
+                            >
+                                "↷"
+                            </button>
+                        </Show>
+                        <button
+                            on:click=move |_| on_collapse()
+                            style="background: transparent; border: none; color: #cccccc; cursor: pointer; padding: 2px 4px;"
+                        >
+                            "×"
+                        </button>
+                    </div>
                 </div>
             </div>
-            <div style="flex: 1; overflow-y: auto; padding: 8px;">
+            // Both views stay mounted so switching keeps the search and its
+            // results.
+            <div
+                style="flex: 1; overflow: hidden; padding: 8px;"
+                style:display=move || (view.get() != SidebarView::Search).then_some("none")
+            >
+                <SearchPanel vfs=vfs on_open_match=on_open_match on_replace=on_replace_in_files />
+            </div>
+            // Focusable so that Ctrl+Z after clicking in the explorer undoes
+            // workspace changes rather than edits.
+            <div
+                tabindex="-1"
+                data-explorer=""
+                style="flex: 1; overflow-y: auto; padding: 8px; outline: none;"
+                style:display=move || (view.get() != SidebarView::Explorer).then_some("none")
+            >
                 <div style="color: #858585; font-size: 11px; margin-bottom: 8px;">OPEN EDITORS</div>
                 <div style="color: #858585; font-size: 11px; margin-top: 16px; margin-bottom: 8px;">EXPLORER</div>
-                <div style="color: #cccccc; font-size: 12px; padding: 2px 0;">📁 src</div>
-                <div style="color: #cccccc; font-size: 12px; padding: 2px 0; margin-left: 16px;">📄 App.tsx</div>
-                <div style="color: #cccccc; font-size: 12px; padding: 2px 0; margin-left: 16px;">📄 main.tsx</div>
+                {move || vfs.with(|fs| {
+                    let mut rows = Vec::new();
+                    tree_rows(fs, fs.root(), 0, on_open_file, on_preview_file, on_trash_entry, &mut rows);
+                    rows
+                })}
+                <div style="display: flex; align-items: center; justify-content: space-between; color: #858585; font-size: 11px; margin-top: 16px; margin-bottom: 8px;">
+                    <span>{move || format!("TRASH ({})", vfs.with(|fs| fs.trash_entries().len()))}</span>
+                    <button
+                        title="Permanently delete everything in the trash"
+                        on:click=move |_| on_empty_trash.call(())
+                        style="background: transparent; border: none; color: #858585; cursor: pointer; padding: 0 4px; font-size: 11px;"
+                    >
+                        "Empty"
+                    </button>
+                </div>
+                {move || vfs.with(|fs| {
+                    fs.trash_entries()
+                        .iter()
+                        .rev()
+                        .map(|entry| {
+                            let id = entry.id;
+                            let deleted = js_sys::Date::new(&(entry.deleted_at as f64).into())
+                                .to_locale_string("default", &JsValue::UNDEFINED);
+                            let title = format!("{} (deleted {})", entry.original_path, String::from(deleted));
+                            view! {
+                                <div style="display: flex; color: #858585; font-size: 12px; padding: 2px 0;" title=title>
+                                    <span style="flex: 1; overflow: hidden; text-overflow: ellipsis; white-space: nowrap;">
+                                        {entry.node.name.clone()}
+                                    </span>
+                                    <button
+                                        on:click=move |_| on_restore_trash.call(id)
+                                        style="background: transparent; border: none; color: #cccccc; cursor: pointer; padding: 0 4px; font-size: 11px;"
+                                    >
+                                        "Restore"
+                                    </button>
+                                </div>
+                            }
+                        })
+                        .collect_view()
+                })}
+                <div style="color: #858585; font-size: 11px; margin-top: 16px; margin-bottom: 8px;">OUTLINE</div>
+                {move || outline.with(|symbols| {
+                    if symbols.is_empty() {
+                        return view! {
+                            <div style="color: #858585; font-size: 12px;">"No symbols in the open file"</div>
+                        }.into_view();
+                    }
+                    let mut rows = Vec::new();
+                    outline_rows(symbols, 0, on_go_to_symbol, &mut rows);
+                    rows.into_view()
+                })}
             </div>
         </div>
     }
@@ -1,11 +1,53 @@
 use leptos::*;
 use crate::types::ToastType;
+use crate::utils::file_system::search::SearchQuery;
+use crate::utils::file_system::VirtualFileSystem;
+
+const MAX_SEARCH_RESULTS: usize = 20;
+
+/// Quick-search entries for the menu bar box: file paths first (the query
+/// is used as a glob when it contains glob syntax), then content hits.
+fn quick_search(fs: &VirtualFileSystem, query: &str) -> Vec<(String, String)> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let is_glob = query.contains(|c| matches!(c, '*' | '?' | '[' | '{' | '!'));
+    let paths = if is_glob {
+        fs.glob(&[query.to_string()]).unwrap_or_default()
+    } else {
+        let needle = query.to_lowercase();
+        fs.file_paths()
+            .into_iter()
+            .filter(|path| path.rsplit('/').next().unwrap_or("").to_lowercase().contains(&needle))
+            .collect()
+    };
+    let mut results: Vec<(String, String)> = paths
+        .into_iter()
+        .take(MAX_SEARCH_RESULTS)
+        .map(|path| (path.clone(), path))
+        .collect();
+
+    if !is_glob {
+        let mut content_query = SearchQuery::literal(query);
+        content_query.max_results = Some(MAX_SEARCH_RESULTS - results.len());
+        for hit in fs.search(&content_query).unwrap_or_default() {
+            let label = format!("{}:{}  {}", hit.path, hit.line, hit.preview.trim());
+            results.push((hit.path, label));
+        }
+    }
+    results
+}
 
 #[component]
 pub fn MenuBar(
     on_toggle_terminal: impl Fn() + 'static,
     on_show_toast: impl Fn(ToastType, String) + 'static,
+    vfs: ReadSignal<VirtualFileSystem>,
+    #[prop(into)] on_open_file: Callback<String>,
 ) -> impl IntoView {
+    let (search_text, set_search_text) = create_signal(String::new());
+    let search_results = create_memo(move |_| vfs.with(|fs| quick_search(fs, &search_text.get())));
     let menu_items = vec!["File", "Edit", "View", "Go", "Run", "Terminal", "Help"];
     let (hovered_item, set_hovered_item) = create_signal(None::<String>);
     let (active_menu, set_active_menu) = create_signal(None::<String>);
@@ -52,11 +94,32 @@ pub fn MenuBar(
             <div style="flex: 1;" />
 
             <div style="display: flex; align-items: center; gap: 8px;">
-                <input
-                    type="text"
-                    placeholder="Search..."
-                    style="background: #1e1e1e; border: 1px solid #3e3e3e; border-radius: 4px; padding: 4px 8px; color: #cccccc; font-size: 12px; width: 200px;"
-                />
+                <div style="position: relative;">
+                    <input
+                        type="text"
+                        placeholder="Search..."
+                        prop:value=move || search_text.get()
+                        on:input=move |ev| set_search_text.set(event_target_value(&ev))
+                        style="background: #1e1e1e; border: 1px solid #3e3e3e; border-radius: 4px; padding: 4px 8px; color: #cccccc; font-size: 12px; width: 200px;"
+                    />
+                    <Show when=move || !search_results.get().is_empty()>
+                        <div style="position: absolute; top: 28px; right: 0; width: 360px; max-height: 320px; overflow-y: auto; background: #252526; border: 1px solid #3e3e3e; border-radius: 4px; z-index: 500;">
+                            {move || search_results.get().into_iter().map(|(path, label)| {
+                                view! {
+                                    <div
+                                        on:click=move |_| {
+                                            on_open_file.call(path.clone());
+                                            set_search_text.set(String::new());
+                                        }
+                                        style="padding: 4px 8px; color: #cccccc; font-size: 12px; cursor: pointer; white-space: nowrap; overflow: hidden; text-overflow: ellipsis;"
+                                    >
+                                        {label}
+                                    </div>
+                                }
+                            }).collect::<Vec<_>>()}
+                        </div>
+                    </Show>
+                </div>
                 <button
                     on:click=move |_| on_show_toast(ToastType::Info, "Notifications".to_string())
                     style="background: transparent; border: none; color: #cccccc; cursor: pointer; padding: 4px;"
@@ -1,6 +1,9 @@
 pub mod menu_bar;
 pub mod left_sidebar;
 pub mod editor_area;
+pub mod breadcrumbs;
+pub mod find_widget;
+pub mod search_panel;
 pub mod assistant_panel;
 pub mod status_bar;
 pub mod command_palette;
@@ -8,10 +11,14 @@ pub mod beginner_guide;
 pub mod resize_handle;
 pub mod toast;
 pub mod terminal_panel;
+pub mod problems_panel;
 
 pub use menu_bar::MenuBar;
 pub use left_sidebar::LeftSidebar;
 pub use editor_area::EditorArea;
+pub use breadcrumbs::Breadcrumbs;
+pub use find_widget::FindWidget;
+pub use search_panel::SearchPanel;
 pub use assistant_panel::AssistantPanel;
 pub use status_bar::StatusBar;
 pub use command_palette::CommandPalette;
@@ -19,3 +26,4 @@ pub use beginner_guide::BeginnerGuide;
 pub use resize_handle::ResizeHandle;
 pub use toast::ToastComponent;
 pub use terminal_panel::TerminalPanel;
+pub use problems_panel::ProblemsPanel;
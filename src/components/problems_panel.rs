@@ -0,0 +1,134 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use leptos::*;
+use crate::utils::diagnostics::{DiagnosticStore, QuickFix};
+use crate::utils::editor::buffer::TextBuffer;
+use crate::utils::editor::tabs::TabManager;
+use crate::utils::file_system::VirtualFileSystem;
+use crate::utils::syntax::theme::Theme;
+
+#[component]
+pub fn ProblemsPanel(
+    diagnostics: ReadSignal<DiagnosticStore>,
+    tabs: ReadSignal<TabManager>,
+    vfs: ReadSignal<VirtualFileSystem>,
+    #[prop(into)] on_open: Callback<(String, Range<usize>)>,
+    #[prop(into)] on_apply_fix: Callback<(String, QuickFix)>,
+) -> impl IntoView {
+    let (collapsed, set_collapsed) = create_signal(HashSet::<String>::new());
+    let theme = Theme::default();
+    // Lines and columns are counted in the text the problems were found
+    // in: the open tab's, or else the saved file's.
+    let text_of = move |path: &str| {
+        tabs.with_untracked(|tabs| tabs.get(path).map(|tab| tab.content.clone()))
+            .or_else(|| vfs.with_untracked(|fs| fs.read(path).ok().map(TextBuffer::from)))
+            .unwrap_or_default()
+    };
+    let location = |buffer: &TextBuffer, offset: usize| {
+        let position = buffer.offset_to_position(offset);
+        format!("[Ln {}, Col {}]", position.line + 1, position.column + 1)
+    };
+
+    let rows = move || {
+        diagnostics.with(|store| {
+            store
+                .paths()
+                .map(|path| {
+                    let path = path.to_string();
+                    let buffer = text_of(&path);
+                    let toggled = path.clone();
+                    let is_collapsed = collapsed.with(|collapsed| collapsed.contains(&path));
+                    let (name, folder) = path.rsplit_once('/').map_or((path.as_str(), ""), |(folder, name)| (name, folder));
+                    let (name, folder) = (name.to_string(), folder.to_string());
+                    let found = store.for_file(&path);
+                    let count = found.len();
+                    let problems = (!is_collapsed).then(|| {
+                        found
+                            .into_iter()
+                            .map(|diagnostic| {
+                                let (opened, range) = (path.clone(), diagnostic.range.clone());
+                                let source = match &diagnostic.code {
+                                    Some(code) => format!("{}({})", diagnostic.source, code),
+                                    None => diagnostic.source.clone(),
+                                };
+                                let fixes = diagnostic.fixes.iter().cloned().map(|fix| {
+                                    let (fixed, title) = (path.clone(), format!("💡 {}", fix.title));
+                                    view! {
+                                        <button
+                                            on:click=move |ev| {
+                                                ev.stop_propagation();
+                                                on_apply_fix.call((fixed.clone(), fix.clone()));
+                                            }
+                                            style="background: transparent; border: none; color: #3794ff; cursor: pointer; font-size: 11px; padding: 0 4px;"
+                                        >
+                                            {title}
+                                        </button>
+                                    }
+                                });
+                                let related = diagnostic.related.iter().map(|related| {
+                                    let (opened, range) = (related.path.clone(), related.range.clone());
+                                    let text = format!("{} {}", related.message, related.path);
+                                    view! {
+                                        <div
+                                            on:click=move |_| on_open.call((opened.clone(), range.clone()))
+                                            style="padding: 1px 0 1px 44px; color: #858585; cursor: pointer; white-space: nowrap; overflow: hidden; text-overflow: ellipsis;"
+                                        >
+                                            {format!("↳ {}", text)}
+                                        </div>
+                                    }
+                                });
+                                view! {
+                                    <div
+                                        title=diagnostic.severity.label()
+                                        on:click=move |_| on_open.call((opened.clone(), range.clone()))
+                                        style="display: flex; align-items: center; gap: 6px; padding: 1px 0 1px 22px; cursor: pointer; white-space: nowrap;"
+                                    >
+                                        <span style=format!("color: {};", theme.severity(diagnostic.severity))>
+                                            {diagnostic.severity.icon()}
+                                        </span>
+                                        <span style="overflow: hidden; text-overflow: ellipsis;">{diagnostic.message.clone()}</span>
+                                        <span style="color: #858585;">{source}</span>
+                                        <span style="color: #858585;">{location(&buffer, diagnostic.range.start)}</span>
+                                        {fixes.collect_view()}
+                                    </div>
+                                    {related.collect_view()}
+                                }
+                            })
+                            .collect_view()
+                    });
+                    view! {
+                        <div>
+                            <div
+                                title=path.clone()
+                                on:click=move |_| set_collapsed.update(|collapsed| {
+                                    if !collapsed.remove(&toggled) {
+                                        collapsed.insert(toggled.clone());
+                                    }
+                                })
+                                style="display: flex; align-items: center; gap: 4px; padding: 2px 0; cursor: pointer;"
+                            >
+                                <span style="width: 12px;">{if is_collapsed { "▸" } else { "▾" }}</span>
+                                <span>{name}</span>
+                                <span style="color: #858585; font-size: 11px;">{folder}</span>
+                                <span style="background: #4d4d4d; border-radius: 8px; padding: 0 6px; font-size: 11px;">{count}</span>
+                            </div>
+                            {problems}
+                        </div>
+                    }
+                })
+                .collect_view()
+        })
+    };
+
+    view! {
+        <div style="color: #cccccc; font-size: 12px;">
+            <Show
+                when=move || !diagnostics.with(DiagnosticStore::is_empty)
+                fallback=|| view! { <div style="color: #858585;">"No problems have been detected in the workspace."</div> }
+            >
+                {rows}
+            </Show>
+        </div>
+    }
+}
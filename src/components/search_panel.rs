@@ -0,0 +1,424 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use leptos::leptos_dom::helpers::TimeoutHandle;
+use leptos::*;
+use crate::utils::file_system::search::{FileMatches, FileReplacement, SearchMatch, SearchQuery, SearchSession};
+use crate::utils::file_system::VirtualFileSystem;
+
+/// Bytes of file content searched between yields to the browser.
+const SEARCH_SLICE: usize = 256 * 1024;
+/// Quiet time after typing before a search starts.
+const SEARCH_DELAY: Duration = Duration::from_millis(250);
+const MAX_RESULTS: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Progress {
+    Idle,
+    /// Files searched so far, and in all.
+    Searching(usize, usize),
+    Done,
+    Cancelled,
+    Failed(String),
+}
+
+/// The globs in a comma-separated list, negated for the exclude field.
+fn parse_globs(list: &str, exclude: bool) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|glob| !glob.is_empty())
+        .map(|glob| if exclude { format!("!{}", glob) } else { glob.to_string() })
+        .collect()
+}
+
+/// Searches the next slice of the workspace and, unless that finishes the
+/// search, schedules the one after it, so the page stays responsive.
+fn search_slice(
+    session: StoredValue<Option<SearchSession>>,
+    timer: StoredValue<Option<TimeoutHandle>>,
+    vfs: ReadSignal<VirtualFileSystem>,
+    set_results: WriteSignal<Vec<FileMatches>>,
+    set_progress: WriteSignal<Progress>,
+) {
+    let step = session
+        .try_update_value(|session| {
+            let session = session.as_mut()?;
+            let found = vfs.with_untracked(|fs| session.step(fs, SEARCH_SLICE));
+            Some((found, session.progress(), session.is_done()))
+        })
+        .flatten();
+    let Some((found, (searched, total), done)) = step else {
+        return;
+    };
+    if !found.is_empty() {
+        set_results.update(|results| results.extend(found));
+    }
+    if done {
+        session.set_value(None);
+        timer.set_value(None);
+        set_progress.set(Progress::Done);
+    } else {
+        set_progress.set(Progress::Searching(searched, total));
+        let next = move || search_slice(session, timer, vfs, set_results, set_progress);
+        timer.set_value(set_timeout_with_handle(next, Duration::ZERO).ok());
+    }
+}
+
+#[component]
+pub fn SearchPanel(
+    vfs: ReadSignal<VirtualFileSystem>,
+    #[prop(into)] on_open_match: Callback<SearchMatch>,
+    #[prop(into)] on_replace: Callback<Vec<FileReplacement>>,
+) -> impl IntoView {
+    let (text, set_text) = create_signal(String::new());
+    let (replacement, set_replacement) = create_signal(String::new());
+    let (case_sensitive, set_case_sensitive) = create_signal(false);
+    let (whole_word, set_whole_word) = create_signal(false);
+    let (regex, set_regex) = create_signal(false);
+    let (include, set_include) = create_signal(String::new());
+    let (exclude, set_exclude) = create_signal(String::new());
+    let (show_replace, set_show_replace) = create_signal(false);
+    let (results, set_results) = create_signal(Vec::<FileMatches>::new());
+    let (progress, set_progress) = create_signal(Progress::Idle);
+    let (collapsed, set_collapsed) = create_signal(HashSet::<String>::new());
+    // Files taken out of the results, and so out of "Replace All".
+    let (dismissed, set_dismissed) = create_signal(HashSet::<String>::new());
+    let (previewing, set_previewing) = create_signal(false);
+    let session = store_value(None::<SearchSession>);
+    let timer = store_value(None::<TimeoutHandle>);
+
+    let query = create_memo(move |_| {
+        let text = text.get();
+        if text.is_empty() {
+            return None;
+        }
+        let mut query = if regex.get() {
+            SearchQuery::regex(&text)
+        } else {
+            SearchQuery::literal(&text)
+        };
+        query.case_sensitive = case_sensitive.get();
+        query.whole_word = whole_word.get();
+        query.globs = parse_globs(&include.get(), false);
+        query.globs.extend(parse_globs(&exclude.get(), true));
+        query.max_results = Some(MAX_RESULTS);
+        Some(query)
+    });
+    let stop = move || {
+        if let Some(timer) = timer.get_value() {
+            timer.clear();
+        }
+        timer.set_value(None);
+        session.set_value(None);
+    };
+    // Search again whenever the query or the workspace changes.
+    create_effect(move |_| {
+        let query = query.get();
+        vfs.track();
+        stop();
+        let Some(query) = query else {
+            set_results.set(Vec::new());
+            set_progress.set(Progress::Idle);
+            return;
+        };
+        let start = move || match vfs.with_untracked(|fs| SearchSession::new(fs, &query)) {
+            Ok(started) => {
+                session.set_value(Some(started));
+                set_results.set(Vec::new());
+                search_slice(session, timer, vfs, set_results, set_progress);
+            }
+            Err(err) => {
+                set_results.set(Vec::new());
+                set_progress.set(Progress::Failed(err.to_string()));
+            }
+        };
+        timer.set_value(set_timeout_with_handle(start, SEARCH_DELAY).ok());
+    });
+    create_effect(move |_| {
+        query.track();
+        set_dismissed.set(HashSet::new());
+        set_previewing.set(false);
+    });
+    on_cleanup(stop);
+
+    let shown = move || {
+        results.with(|results| {
+            dismissed.with(|dismissed| {
+                results.iter().filter(|file| !dismissed.contains(&file.path)).cloned().collect::<Vec<_>>()
+            })
+        })
+    };
+    let plan = create_memo(move |_| {
+        if !previewing.get() {
+            return Ok(Vec::new());
+        }
+        let Some(query) = query.get() else {
+            return Ok(Vec::new());
+        };
+        let paths: Vec<String> = shown().into_iter().map(|file| file.path).collect();
+        let replacement = replacement.get();
+        vfs.with(|fs| fs.plan_replace(&query, &paths, &replacement)).map_err(|err| err.to_string())
+    });
+
+    let summary = move || {
+        let (files, matches) = results.with(|results| {
+            dismissed.with(|dismissed| {
+                let shown = results.iter().filter(|file| !dismissed.contains(&file.path));
+                shown.fold((0, 0), |(files, matches), file| (files + 1, matches + file.matches.len()))
+            })
+        });
+        let counts = format!(
+            "{} result{} in {} file{}",
+            matches,
+            if matches == 1 { "" } else { "s" },
+            files,
+            if files == 1 { "" } else { "s" },
+        );
+        match progress.get() {
+            Progress::Idle => String::new(),
+            Progress::Searching(searched, total) => format!("{} (searched {} of {} files)", counts, searched, total),
+            Progress::Done if matches >= MAX_RESULTS => format!("{}; only the first {} are shown", counts, MAX_RESULTS),
+            Progress::Done if files == 0 => "No results found. Review your settings for configured exclusions.".to_string(),
+            Progress::Done => counts,
+            Progress::Cancelled => format!("{} (search cancelled)", counts),
+            Progress::Failed(err) => err,
+        }
+    };
+
+    let toggle = move |label: &'static str, title: &'static str, value: ReadSignal<bool>, set_value: WriteSignal<bool>| {
+        view! {
+            <button
+                title=title
+                on:click=move |_| set_value.update(|value| *value = !*value)
+                style=move || format!(
+                    "background: {}; border: 1px solid {}; color: #cccccc; font-size: 11px; padding: 1px 4px; cursor: pointer; border-radius: 3px;",
+                    if value.get() { "rgba(0, 122, 204, 0.4)" } else { "transparent" },
+                    if value.get() { "#007acc" } else { "transparent" },
+                )
+            >
+                {label}
+            </button>
+        }
+    };
+    let input_style = "flex: 1; min-width: 0; background: #3c3c3c; border: 1px solid #3c3c3c; color: #cccccc; font-size: 12px; padding: 3px 6px; outline: none;";
+    let button_style = "background: transparent; border: none; color: #cccccc; cursor: pointer; font-size: 11px; padding: 0 4px;";
+
+    let result_rows = move || {
+        shown()
+            .into_iter()
+            .map(|file| {
+                let path = file.path.clone();
+                let (toggled, closed) = (path.clone(), path.clone());
+                let is_collapsed = collapsed.with(|collapsed| collapsed.contains(&path));
+                let (name, folder) = path.rsplit_once('/').map_or((path.as_str(), ""), |(folder, name)| (name, folder));
+                let (name, folder) = (name.to_string(), folder.to_string());
+                let count = file.matches.len();
+                let lines = (!is_collapsed).then(|| {
+                    file.matches
+                        .into_iter()
+                        .map(|found| {
+                            let (start, end) = found.preview_range;
+                            let before = found.preview[..start].to_string();
+                            let matched = found.preview[start..end].to_string();
+                            let after = found.preview[end..].to_string();
+                            let title = format!("Line {}, column {}", found.line, found.column);
+                            view! {
+                                <div
+                                    title=title
+                                    on:click=move |_| on_open_match.call(found.clone())
+                                    style="padding: 1px 0 1px 22px; color: #cccccc; font-size: 12px; white-space: nowrap; overflow: hidden; text-overflow: ellipsis; cursor: pointer;"
+                                >
+                                    {before}
+                                    <span style="background: rgba(234, 92, 0, 0.33);">{matched}</span>
+                                    {after}
+                                </div>
+                            }
+                        })
+                        .collect_view()
+                });
+                view! {
+                    <div>
+                        <div
+                            title=path.clone()
+                            on:click=move |_| set_collapsed.update(|collapsed| {
+                                if !collapsed.remove(&toggled) {
+                                    collapsed.insert(toggled.clone());
+                                }
+                            })
+                            style="display: flex; align-items: center; gap: 4px; padding: 2px 0; color: #cccccc; font-size: 12px; cursor: pointer;"
+                        >
+                            <span style="width: 12px;">{if is_collapsed { "▸" } else { "▾" }}</span>
+                            <span>{name}</span>
+                            <span style="flex: 1; color: #858585; font-size: 11px; overflow: hidden; text-overflow: ellipsis; white-space: nowrap;">
+                                {folder}
+                            </span>
+                            <span style="background: #4d4d4d; border-radius: 8px; padding: 0 6px; font-size: 11px;">{count}</span>
+                            <button
+                                title="Dismiss"
+                                on:click=move |ev| {
+                                    ev.stop_propagation();
+                                    set_dismissed.update(|dismissed| {
+                                        dismissed.insert(closed.clone());
+                                    });
+                                }
+                                style=button_style
+                            >
+                                "×"
+                            </button>
+                        </div>
+                        {lines}
+                    </div>
+                }
+            })
+            .collect_view()
+    };
+
+    let preview_rows = move || match plan.get() {
+        Err(err) => view! { <div style="color: #f48771; font-size: 12px;">{err}</div> }.into_view(),
+        Ok(plan) => plan
+            .into_iter()
+            .map(|file| {
+                let hunks = file
+                    .hunks
+                    .into_iter()
+                    .map(|hunk| {
+                        let removed = hunk.removed.into_iter().map(|line| view! {
+                            <div style="background: rgba(255, 0, 0, 0.2); white-space: pre;">{format!("- {}", line)}</div>
+                        });
+                        let added = hunk.added.into_iter().map(|line| view! {
+                            <div style="background: rgba(155, 185, 85, 0.2); white-space: pre;">{format!("+ {}", line)}</div>
+                        });
+                        view! {
+                            <div style="margin: 2px 0 6px;">
+                                <div style="color: #858585;">{format!("Line {}", hunk.line)}</div>
+                                {removed.collect_view()}
+                                {added.collect_view()}
+                            </div>
+                        }
+                    })
+                    .collect_view();
+                view! {
+                    <div style="margin-bottom: 8px;">
+                        <div style="color: #cccccc; font-size: 12px; padding: 2px 0;">
+                            {format!("{} ({})", file.path, file.count)}
+                        </div>
+                        <div style="font-family: 'Fira Code', monospace; font-size: 11px; color: #cccccc; overflow-x: auto;">
+                            {hunks}
+                        </div>
+                    </div>
+                }
+            })
+            .collect_view(),
+    };
+    let apply = move |_| {
+        if let Ok(plan) = plan.get_untracked() {
+            if !plan.is_empty() {
+                on_replace.call(plan);
+            }
+        }
+        set_previewing.set(false);
+    };
+    let apply_label = move || {
+        plan.with(|plan| {
+            let (files, count) = plan
+                .as_ref()
+                .map_or((0, 0), |plan| (plan.len(), plan.iter().map(|file| file.count).sum::<usize>()));
+            format!("Replace {} occurrence{} in {} file{}", count, if count == 1 { "" } else { "s" }, files, if files == 1 { "" } else { "s" })
+        })
+    };
+
+    view! {
+        <div style="display: flex; flex-direction: column; gap: 4px; height: 100%;">
+            <div style="display: flex; gap: 4px;">
+                <button
+                    title="Toggle Replace"
+                    on:click=move |_| set_show_replace.update(|shown| *shown = !*shown)
+                    style=button_style
+                >
+                    {move || if show_replace.get() { "▾" } else { "▸" }}
+                </button>
+                <div style="flex: 1; display: flex; flex-direction: column; gap: 4px; min-width: 0;">
+                    <div style="display: flex; align-items: center; gap: 2px;">
+                        <input
+                            type="text"
+                            placeholder="Search"
+                            style=input_style
+                            prop:value=text
+                            on:input=move |ev| set_text.set(event_target_value(&ev))
+                        />
+                        {toggle("Aa", "Match Case", case_sensitive, set_case_sensitive)}
+                        {toggle("ab", "Match Whole Word", whole_word, set_whole_word)}
+                        {toggle(".*", "Use Regular Expression", regex, set_regex)}
+                    </div>
+                    <Show when=move || show_replace.get()>
+                        <div style="display: flex; align-items: center; gap: 2px;">
+                            <input
+                                type="text"
+                                placeholder="Replace"
+                                style=input_style
+                                prop:value=replacement
+                                on:input=move |ev| set_replacement.set(event_target_value(&ev))
+                            />
+                            <button
+                                title="Replace All (preview first)"
+                                disabled=move || results.with(Vec::is_empty)
+                                on:click=move |_| set_previewing.set(true)
+                                style=button_style
+                            >
+                                "All"
+                            </button>
+                        </div>
+                    </Show>
+                </div>
+            </div>
+            <input
+                type="text"
+                placeholder="files to include (e.g. src/**, *.rs)"
+                style=input_style
+                prop:value=include
+                on:input=move |ev| set_include.set(event_target_value(&ev))
+            />
+            <input
+                type="text"
+                placeholder="files to exclude (e.g. node_modules, *.min.js)"
+                style=input_style
+                prop:value=exclude
+                on:input=move |ev| set_exclude.set(event_target_value(&ev))
+            />
+            <div style="display: flex; align-items: center; gap: 4px; color: #858585; font-size: 11px; min-height: 16px;">
+                <span style="flex: 1;">{summary}</span>
+                <Show when=move || matches!(progress.get(), Progress::Searching(..))>
+                    <button
+                        title="Cancel Search"
+                        on:click=move |_| {
+                            stop();
+                            set_progress.set(Progress::Cancelled);
+                        }
+                        style=button_style
+                    >
+                        "Cancel"
+                    </button>
+                </Show>
+            </div>
+            <div style="flex: 1; overflow-y: auto;">
+                <Show when=move || previewing.get() fallback=result_rows>
+                    <div style="display: flex; gap: 4px; margin-bottom: 8px;">
+                        <button
+                            on:click=apply
+                            style="background: #0e639c; border: none; color: #ffffff; cursor: pointer; font-size: 12px; padding: 3px 8px;"
+                        >
+                            {apply_label}
+                        </button>
+                        <button
+                            on:click=move |_| set_previewing.set(false)
+                            style="background: #3a3d41; border: none; color: #cccccc; cursor: pointer; font-size: 12px; padding: 3px 8px;"
+                        >
+                            "Cancel"
+                        </button>
+                    </div>
+                    {preview_rows}
+                </Show>
+            </div>
+        </div>
+    }
+}
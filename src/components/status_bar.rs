@@ -1,18 +1,106 @@
 use leptos::*;
+use crate::types::{LineEnding, TextEncoding, ToastType};
+use crate::utils::diagnostics::{DiagnosticStore, Severity};
+use crate::utils::file_system::VirtualFileSystem;
 
 #[component]
 pub fn StatusBar(
     terminal_visible: bool,
     on_toggle_terminal: impl Fn() + 'static,
+    vfs: ReadSignal<VirtualFileSystem>,
+    set_vfs: WriteSignal<VirtualFileSystem>,
+    #[prop(into)] active_file: Signal<Option<String>>,
+    diagnostics: ReadSignal<DiagnosticStore>,
+    on_show_problems: impl Fn() + 'static,
+    on_show_toast: impl Fn(ToastType, String) + 'static,
 ) -> impl IntoView {
+    let (show_encodings, set_show_encodings) = create_signal(false);
+    let on_show_toast = Callback::new(move |(toast_type, message)| on_show_toast(toast_type, message));
+
+    // Encoding and line endings of the open text file; binary files have
+    // neither.
+    let text_format = move || {
+        let path = active_file.get()?;
+        vfs.with(|fs| {
+            let node = fs.get(&path)?;
+            node.content.as_ref()?;
+            Some((node.metadata.encoding, node.metadata.line_ending))
+        })
+    };
+    let convert = move |encoding: TextEncoding, line_ending: LineEnding| {
+        let Some(path) = active_file.get_untracked() else {
+            return;
+        };
+        let mut result = Ok(());
+        set_vfs.update(|fs| result = fs.set_text_format(&path, encoding, line_ending));
+        if let Err(err) = result {
+            on_show_toast.call((ToastType::Error, err.to_string()));
+        }
+    };
+
     view! {
         <div
             style="height: 22px; background: #007acc; color: #ffffff; display: flex; align-items: center; padding: 0 8px; font-size: 11px;"
         >
             <div style="margin-right: 16px;">"Ready"</div>
+            <button
+                on:click=move |_| on_show_problems()
+                title="Problems"
+                style="background: transparent; border: none; color: #ffffff; cursor: pointer; padding: 2px 4px; margin-right: 16px;"
+            >
+                {move || diagnostics.with(|store| format!(
+                    "{} {}  {} {}",
+                    Severity::Error.icon(),
+                    store.count(Severity::Error),
+                    Severity::Warning.icon(),
+                    store.count(Severity::Warning),
+                ))}
+            </button>
             <div style="margin-right: 16px;">"Ln 1, Col 1"</div>
             <div style="margin-right: 16px;">"Spaces: 2"</div>
             <div style="flex: 1;" />
+            {move || text_format().map(|(encoding, line_ending)| view! {
+                <div style="position: relative; margin-right: 8px;">
+                    <button
+                        on:click=move |_| set_show_encodings.update(|v| *v = !*v)
+                        title="Change encoding"
+                        style="background: transparent; border: none; color: #ffffff; cursor: pointer; padding: 2px 4px;"
+                    >
+                        {encoding.label()}
+                    </button>
+                    <Show when=move || show_encodings.get()>
+                        <div style="position: absolute; bottom: 22px; right: 0; background: #252526; border: 1px solid #3e3e3e; min-width: 140px; z-index: 10;">
+                            {TextEncoding::ALL.into_iter().map(|option| view! {
+                                <div
+                                    on:click=move |_| {
+                                        set_show_encodings.set(false);
+                                        convert(option, line_ending);
+                                    }
+                                    style=format!(
+                                        "padding: 4px 8px; cursor: pointer; color: {};",
+                                        if option == encoding { "#ffffff" } else { "#cccccc" }
+                                    )
+                                >
+                                    {option.label()}
+                                </div>
+                            }).collect_view()}
+                        </div>
+                    </Show>
+                </div>
+                <button
+                    on:click=move |_| {
+                        let toggled = match line_ending {
+                            LineEnding::Lf => LineEnding::CrLf,
+                            LineEnding::CrLf => LineEnding::Lf,
+                        };
+                        convert(encoding, toggled);
+                    }
+                    title="Change line endings"
+                    style="background: transparent; border: none; color: #ffffff; cursor: pointer; padding: 2px 4px; margin-right: 8px;"
+                >
+                    {line_ending.label()}
+                </button>
+            })}
             <button
                 on:click=move |_| on_toggle_terminal()
                 style="background: transparent; border: none; color: #ffffff; cursor: pointer; padding: 2px 4px;"
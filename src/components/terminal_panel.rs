@@ -1,11 +1,46 @@
+use std::ops::Range;
+
 use leptos::*;
+use crate::components::ProblemsPanel;
+use crate::types::PanelView;
+use crate::utils::diagnostics::{DiagnosticStore, QuickFix};
+use crate::utils::editor::tabs::TabManager;
+use crate::utils::file_system::VirtualFileSystem;
 
 #[component]
 pub fn TerminalPanel(
     on_close: impl Fn() + 'static,
     height: u32,
     on_resize: impl Fn(i32) + 'static,
+    view: ReadSignal<PanelView>,
+    set_view: WriteSignal<PanelView>,
+    diagnostics: ReadSignal<DiagnosticStore>,
+    tabs: ReadSignal<TabManager>,
+    vfs: ReadSignal<VirtualFileSystem>,
+    #[prop(into)] on_open_problem: Callback<(String, Range<usize>)>,
+    #[prop(into)] on_apply_fix: Callback<(String, QuickFix)>,
 ) -> impl IntoView {
+    let view_tab = move |label: &'static str, target: PanelView| {
+        view! {
+            <button
+                on:click=move |_| set_view.set(target)
+                style=move || format!(
+                    "background: transparent; border: none; border-bottom: 1px solid {}; color: {}; cursor: pointer; padding: 2px 4px; font-size: 11px; text-transform: uppercase;",
+                    if view.get() == target { "#e7e7e7" } else { "transparent" },
+                    if view.get() == target { "#ffffff" } else { "#858585" },
+                )
+            >
+                {label}
+                {(target == PanelView::Problems).then(|| move || {
+                    let count = diagnostics.with(DiagnosticStore::len);
+                    (count > 0).then(|| view! {
+                        <span style="margin-left: 4px; background: #4d4d4d; border-radius: 8px; padding: 0 6px;">{count}</span>
+                    })
+                })}
+            </button>
+        }
+    };
+
     view! {
         <div
             style=format!(
@@ -14,7 +49,10 @@ pub fn TerminalPanel(
             )
         >
             <div style="padding: 4px 8px; background: #2d2d2d; border-bottom: 1px solid #3e3e3e; display: flex; align-items: center; justify-content: space-between;">
-                <span style="font-size: 11px; color: #cccccc; text-transform: uppercase;">Terminal</span>
+                <div style="display: flex; gap: 8px;">
+                    {view_tab("Terminal", PanelView::Terminal)}
+                    {view_tab("Problems", PanelView::Problems)}
+                </div>
                 <button
                     on:click=move |_| on_close()
                     style="background: transparent; border: none; color: #cccccc; cursor: pointer; padding: 2px 4px;"
@@ -22,10 +60,25 @@ pub fn TerminalPanel(
                     "×"
                 </button>
             </div>
-            <div style="flex: 1; overflow-y: auto; padding: 8px; color: #cccccc; font-family: 'Fira Code', monospace; font-size: 12px;">
-                <div style="color: #4ec9b0;">$</div>
-                <div style="margin-top: 4px;">Ready for input...</div>
-            </div>
+            <Show
+                when=move || view.get() == PanelView::Problems
+                fallback=|| view! {
+                    <div style="flex: 1; overflow-y: auto; padding: 8px; color: #cccccc; font-family: 'Fira Code', monospace; font-size: 12px;">
+                        <div style="color: #4ec9b0;">$</div>
+                        <div style="margin-top: 4px;">Ready for input...</div>
+                    </div>
+                }
+            >
+                <div style="flex: 1; overflow-y: auto; padding: 8px;">
+                    <ProblemsPanel
+                        diagnostics=diagnostics
+                        tabs=tabs
+                        vfs=vfs
+                        on_open=on_open_problem
+                        on_apply_fix=on_apply_fix
+                    />
+                </div>
+            </Show>
         </div>
     }
 }
@@ -1,7 +1,35 @@
+use std::rc::Rc;
+use std::time::Duration;
+
 use gloo_storage::{LocalStorage, Storage};
 use leptos::*;
+use leptos::leptos_dom::helpers::TimeoutHandle;
 use serde::{Deserialize, Serialize};
 
+use crate::utils::code_analyzer::clones::{self, clone_diagnostics, find_workspace_clones, CloneOptions};
+use crate::utils::code_analyzer::lint::{self, lint, LintConfig};
+use crate::utils::code_analyzer::outline::{outline, Symbol};
+use crate::utils::diagnostics::DiagnosticStore;
+use crate::utils::editor::tabs::{TabManager, TabSession};
+use crate::utils::file_system::events::FsEvent;
+use crate::utils::file_system::indexed_db::IndexedDbStorage;
+use crate::utils::file_system::metadata::GENERATED_FOLDERS;
+use crate::utils::file_system::storage::{MemoryStorage, StorageBackend};
+use crate::utils::file_system::VirtualFileSystem;
+
+const WORKSPACE_DB: &str = "bloop-workspace";
+const TAB_SESSION_KEY: &str = "bloop-editor-tabs";
+const TAB_SESSION_SAVE_DELAY: Duration = Duration::from_secs(1);
+/// Quiet time after typing before the outline is read again.
+const OUTLINE_DELAY: Duration = Duration::from_millis(300);
+/// Where the workspace configures the lint rules.
+const LINT_CONFIG_PATH: &str = "/.lintrc.json";
+/// Quiet time after typing before the active file is linted again.
+const LINT_DELAY: Duration = Duration::from_millis(500);
+/// Quiet time after workspace changes before duplicates are looked for
+/// again; the whole workspace is read each time.
+const CLONE_DELAY: Duration = Duration::from_secs(2);
+
 pub fn use_local_storage<T>(key: &str, initial: T) -> (ReadSignal<T>, WriteSignal<T>)
 where
     T: Clone + Serialize + for<'de> Deserialize<'de> + 'static,
@@ -18,3 +46,218 @@ where
 
     (value, set_value)
 }
+
+/// Restores the workspace from IndexedDB on startup and then writes every
+/// change back incrementally. Falls back to in-memory storage when
+/// IndexedDB is unavailable. Until the stored tree has loaded, the current
+/// (sample) workspace is left untouched and nothing is written. Returns
+/// whether loading has finished.
+pub fn use_workspace_persistence(
+    vfs: ReadSignal<VirtualFileSystem>,
+    set_vfs: WriteSignal<VirtualFileSystem>,
+) -> ReadSignal<bool> {
+    let storage = store_value(None::<Rc<dyn StorageBackend>>);
+    let (ready, set_ready) = create_signal(false);
+
+    IndexedDbStorage::open(WORKSPACE_DB, move |result| {
+        let backend: Rc<dyn StorageBackend> = match result {
+            Ok(db) => Rc::new(db),
+            Err(e) => {
+                web_sys::console::warn_1(&format!("Workspace will not persist: {}", e).into());
+                Rc::new(MemoryStorage::new())
+            }
+        };
+        let loader = backend.clone();
+        loader.load(Box::new(move |stored| {
+            match stored {
+                Ok(stored) if stored.is_empty() => set_vfs.update(|fs| fs.mark_all_unsaved()),
+                Ok(stored) => match VirtualFileSystem::from_stored(stored) {
+                    Ok(fs) => set_vfs.update(|current| current.replace_with(fs)),
+                    Err(e) => web_sys::console::warn_1(&format!("Error loading workspace: {}", e).into()),
+                },
+                Err(e) => web_sys::console::warn_1(&format!("Error loading workspace: {}", e).into()),
+            }
+            storage.set_value(Some(backend));
+            set_ready.set(true);
+        }));
+    });
+
+    create_effect(move |_| {
+        vfs.track();
+        if !ready.get() {
+            return;
+        }
+        storage.with_value(|backend| {
+            if let Some(backend) = backend {
+                set_vfs.update_untracked(|fs| fs.save_to(backend.as_ref()));
+            }
+        });
+    });
+    ready
+}
+
+/// Reopens the editor tabs of the last session once the workspace has
+/// loaded, then saves them whenever they change, at most once a second.
+pub fn use_tab_session(
+    workspace_loaded: ReadSignal<bool>,
+    vfs: ReadSignal<VirtualFileSystem>,
+    tabs: ReadSignal<TabManager>,
+    set_tabs: WriteSignal<TabManager>,
+) {
+    let (restored, set_restored) = create_signal(false);
+    let save_timer = store_value(None::<TimeoutHandle>);
+
+    create_effect(move |_| {
+        if !workspace_loaded.get() || restored.get_untracked() {
+            return;
+        }
+        if let Ok(session) = LocalStorage::get::<TabSession>(TAB_SESSION_KEY) {
+            let manager = vfs.with_untracked(|fs| TabManager::restore(session, |path| fs.read(path).ok().map(str::to_string)));
+            set_tabs.set(manager);
+        }
+        set_restored.set(true);
+    });
+
+    create_effect(move |_| {
+        tabs.track();
+        if !restored.get() {
+            return;
+        }
+        if let Some(timer) = save_timer.get_value() {
+            timer.clear();
+        }
+        let save = move || {
+            let session = tabs.with_untracked(TabManager::session);
+            if let Err(e) = LocalStorage::set(TAB_SESSION_KEY, &session) {
+                web_sys::console::warn_1(&format!("Error saving editor tabs: {:?}", e).into());
+            }
+        };
+        save_timer.set_value(set_timeout_with_handle(save, TAB_SESSION_SAVE_DELAY).ok());
+    });
+}
+
+/// The symbol outline of the active tab. It is read again a moment after
+/// typing stops, and at once when another tab becomes active.
+pub fn use_outline(tabs: ReadSignal<TabManager>) -> ReadSignal<Vec<Symbol>> {
+    let (symbols, set_symbols) = create_signal(Vec::<Symbol>::new());
+    let timer = store_value(None::<TimeoutHandle>);
+    // Moving the cursor keeps the buffer, so it compares cheaply.
+    let source = create_memo(move |_| {
+        tabs.with(|tabs| tabs.active().map(|tab| (tab.id.clone(), tab.language.clone(), tab.content.clone())))
+    });
+
+    create_effect(move |previous: Option<Option<String>>| {
+        if let Some(timer) = timer.get_value() {
+            timer.clear();
+        }
+        timer.set_value(None);
+        let Some((id, language, content)) = source.get() else {
+            set_symbols.set(Vec::new());
+            return None;
+        };
+        let read = move || set_symbols.set(outline(&content.to_string(), language.as_deref().unwrap_or_default()));
+        if previous.flatten().as_deref() == Some(id.as_str()) {
+            timer.set_value(set_timeout_with_handle(read, OUTLINE_DELAY).ok());
+        } else {
+            read();
+        }
+        Some(id)
+    });
+    symbols
+}
+
+/// Lints the active tab and publishes what it finds into the diagnostics.
+/// Like the outline, it runs a moment after typing stops and at once when
+/// another tab becomes active. Rules are configured in `/.lintrc.json`.
+pub fn use_lint(
+    tabs: ReadSignal<TabManager>,
+    vfs: ReadSignal<VirtualFileSystem>,
+    set_diagnostics: WriteSignal<DiagnosticStore>,
+) {
+    let timer = store_value(None::<TimeoutHandle>);
+    let config = create_memo(move |_| {
+        vfs.with(|fs| match fs.read(LINT_CONFIG_PATH).map(serde_json::from_str::<LintConfig>) {
+            Ok(Ok(config)) => config,
+            Ok(Err(e)) => {
+                web_sys::console::warn_1(&format!("Error reading {}: {}", LINT_CONFIG_PATH, e).into());
+                LintConfig::default()
+            }
+            Err(_) => LintConfig::default(),
+        })
+    });
+    let source = create_memo(move |_| {
+        tabs.with(|tabs| tabs.active().map(|tab| (tab.id.clone(), tab.language.clone(), tab.content.clone())))
+    });
+
+    create_effect(move |previous: Option<Option<String>>| {
+        if let Some(timer) = timer.get_value() {
+            timer.clear();
+        }
+        timer.set_value(None);
+        let config = config.get();
+        let (id, language, content) = source.get()?;
+        let path = id.clone();
+        let run = move || {
+            let found = lint(&content.to_string(), language.as_deref().unwrap_or_default(), &config);
+            set_diagnostics.update(|diagnostics| diagnostics.publish(&path, lint::SOURCE, found));
+        };
+        if previous.flatten().as_deref() == Some(id.as_str()) {
+            timer.set_value(set_timeout_with_handle(run, LINT_DELAY).ok());
+        } else {
+            run();
+        }
+        Some(id)
+    });
+}
+
+/// Looks for code duplicated across the saved workspace, outside
+/// generated folders, a moment after it stops changing, and publishes
+/// each copy into the diagnostics.
+pub fn use_clone_detection(vfs: ReadSignal<VirtualFileSystem>, set_diagnostics: WriteSignal<DiagnosticStore>) {
+    let timer = store_value(None::<TimeoutHandle>);
+    let globs: Vec<String> = GENERATED_FOLDERS.iter().map(|folder| format!("!{}", folder)).collect();
+
+    create_effect(move |_| {
+        vfs.track();
+        if let Some(timer) = timer.get_value() {
+            timer.clear();
+        }
+        let globs = globs.clone();
+        let detect = move || {
+            let found = vfs.with_untracked(|fs| find_workspace_clones(fs, &globs, &CloneOptions::default()));
+            let pairs = match found {
+                Ok(pairs) => pairs,
+                Err(e) => {
+                    web_sys::console::warn_1(&format!("Error looking for duplicates: {}", e).into());
+                    return;
+                }
+            };
+            set_diagnostics.update(|diagnostics| {
+                diagnostics.clear_source(clones::SOURCE);
+                for (path, found) in clone_diagnostics(&pairs) {
+                    diagnostics.publish(&path, clones::SOURCE, found);
+                }
+            });
+        };
+        timer.set_value(set_timeout_with_handle(detect, CLONE_DELAY).ok());
+    });
+}
+
+/// Bridges workspace change events into a signal holding the most recent
+/// batch, optionally limited to events affecting `prefix`. Updates are
+/// deferred to a microtask because listeners fire while the workspace
+/// signal is still borrowed for the mutation.
+pub fn use_fs_events(set_vfs: WriteSignal<VirtualFileSystem>, prefix: Option<String>) -> ReadSignal<Vec<FsEvent>> {
+    let (events, set_events) = create_signal(Vec::<FsEvent>::new());
+    let mut subscription = None;
+    set_vfs.update_untracked(|fs| {
+        subscription = Some(fs.subscribe(prefix.as_deref(), move |batch| {
+            let batch = batch.to_vec();
+            queue_microtask(move || set_events.set(batch));
+        }));
+    });
+    if let Some(id) = subscription {
+        on_cleanup(move || set_vfs.update_untracked(|fs| fs.unsubscribe(id)));
+    }
+    events
+}
@@ -1,15 +1,150 @@
+use std::fmt;
+use std::rc::Rc;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::utils::editor::buffer::TextBuffer;
+use crate::utils::editor::folding::Folds;
+use crate::utils::editor::history::History;
+use crate::utils::editor::selection::Selections;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FileNode {
     pub name: String,
     pub file_type: FileType,
     pub path: Option<String>,
     pub children: Option<Vec<FileNode>>,
     pub modified: bool,
+    /// Shared with every other file, journal entry or snapshot holding the
+    /// same content.
+    #[serde(default)]
+    pub content: Option<Rc<str>>,
+    /// Raw bytes for files that are not valid text; `content` is `None`
+    /// for these.
+    #[serde(default)]
+    pub data: Option<Rc<[u8]>>,
+    #[serde(default)]
+    pub metadata: FileMetadata,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Timestamps are milliseconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct FileMetadata {
+    pub size: u64,
+    pub created: u64,
+    pub modified: u64,
+    pub mime_type: String,
+    pub permissions: FilePermissions,
+    #[serde(default)]
+    pub encoding: TextEncoding,
+    #[serde(default)]
+    pub line_ending: LineEnding,
+    /// Content hash of files; `None` for folders.
+    #[serde(default)]
+    pub hash: Option<BlobId>,
+}
+
+/// SHA-1 object id of a file's content, in git's format (see
+/// [`blob_id`](crate::utils::file_system::blob::blob_id)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BlobId(pub [u8; 20]);
+
+impl fmt::Display for BlobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// On-disk encoding of a text file. Content is always held as a Rust
+/// string; this records how to write it back out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    // UTF-16 used to be written with a BOM always, so the BOM variants keep
+    // the names stored before there were BOM-less ones.
+    #[serde(rename = "Utf16LeNoBom")]
+    Utf16Le,
+    #[serde(rename = "Utf16BeNoBom")]
+    Utf16Be,
+    #[serde(rename = "Utf16Le")]
+    Utf16LeBom,
+    #[serde(rename = "Utf16Be")]
+    Utf16BeBom,
+    Latin1,
+}
+
+impl TextEncoding {
+    pub const ALL: [TextEncoding; 7] = [
+        TextEncoding::Utf8,
+        TextEncoding::Utf8Bom,
+        TextEncoding::Utf16Le,
+        TextEncoding::Utf16LeBom,
+        TextEncoding::Utf16Be,
+        TextEncoding::Utf16BeBom,
+        TextEncoding::Latin1,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf8Bom => "UTF-8 with BOM",
+            TextEncoding::Utf16Le => "UTF-16 LE",
+            TextEncoding::Utf16Be => "UTF-16 BE",
+            TextEncoding::Utf16LeBom => "UTF-16 LE with BOM",
+            TextEncoding::Utf16BeBom => "UTF-16 BE with BOM",
+            TextEncoding::Latin1 => "ISO 8859-1",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn label(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilePermissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Default for FilePermissions {
+    fn default() -> Self {
+        Self {
+            read: true,
+            write: true,
+            execute: false,
+        }
+    }
+}
+
+impl FilePermissions {
+    pub fn read_only() -> Self {
+        Self {
+            write: false,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FileType {
     File,
     Folder,
@@ -20,9 +155,52 @@ pub struct EditorTab {
     pub id: String,
     pub name: String,
     pub path: Vec<String>,
-    pub content: String,
+    pub content: TextBuffer,
     pub modified: bool,
     pub language: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    /// Shown in italics and replaced by the next file opened in preview,
+    /// until edited or opened for good.
+    #[serde(default)]
+    pub preview: bool,
+    #[serde(default)]
+    pub selections: Selections,
+    #[serde(skip)]
+    pub history: History,
+    #[serde(skip)]
+    pub folds: Folds,
+}
+
+impl EditorTab {
+    /// A tab for the file at `path` (normalized, e.g. `/src/main.rs`),
+    /// identified by that path.
+    pub fn new(path: &str, content: &str) -> Self {
+        let content = TextBuffer::from(content);
+        let mut tab = Self {
+            id: String::new(),
+            name: String::new(),
+            path: Vec::new(),
+            content: content.clone(),
+            modified: false,
+            language: None,
+            pinned: false,
+            preview: false,
+            selections: Selections::default(),
+            history: History::new(),
+            folds: Folds::new(&content, []),
+        };
+        tab.set_path(path);
+        tab
+    }
+
+    /// Points the tab at another file, e.g. after a rename.
+    pub fn set_path(&mut self, path: &str) {
+        self.id = path.to_string();
+        self.name = path.rsplit('/').next().unwrap_or(path).to_string();
+        self.path = path.split('/').filter(|part| !part.is_empty()).map(str::to_string).collect();
+        self.language = self.name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase());
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,3 +233,19 @@ pub struct Toast {
     pub message: String,
     pub duration: Option<u32>,
 }
+
+/// What the left sidebar shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SidebarView {
+    #[default]
+    Explorer,
+    Search,
+}
+
+/// What the bottom panel shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PanelView {
+    #[default]
+    Terminal,
+    Problems,
+}
@@ -1,3 +0,0 @@
-pub fn analyze_code(_code: &str, _language: &str) -> String {
-    "Code analysis placeholder".to_string()
-}
@@ -0,0 +1,359 @@
+//! Copy-pasted code across the workspace. Files are reduced to their
+//! tokens with names and literals abstracted, so renamed copies still
+//! match; windows of tokens are fingerprinted by winnowing, and pairs of
+//! equal fingerprints are grown into the longest equal run around them.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+use super::{lex, Tok};
+use crate::utils::diagnostics::{Diagnostic, RelatedLocation, Severity};
+use crate::utils::file_system::{FsResult, VirtualFileSystem};
+use crate::utils::syntax::{Language, TokenKind};
+
+/// The `source` of duplicate-code diagnostics.
+pub const SOURCE: &str = "duplicates";
+
+/// Fingerprints found more often than this are boilerplate, like import
+/// lists, and are not compared.
+const MAX_OCCURRENCES: usize = 64;
+const HASH_BASE: u64 = 1_000_003;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CloneOptions {
+    /// Fewest tokens a duplicated block has to span.
+    pub min_tokens: usize,
+}
+
+impl Default for CloneOptions {
+    fn default() -> Self {
+        Self { min_tokens: 50 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloneLocation {
+    pub path: String,
+    /// Byte range in the file.
+    pub range: Range<usize>,
+    /// First and last line, 0-based.
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// Two copies of the same block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClonePair {
+    pub first: CloneLocation,
+    pub second: CloneLocation,
+    /// How many tokens the block spans.
+    pub tokens: usize,
+}
+
+/// A file's code tokens, normalized.
+struct Fingerprinted<'a> {
+    path: &'a str,
+    hashes: Vec<u64>,
+    /// Byte range and line of each token.
+    spans: Vec<(Range<usize>, usize)>,
+}
+
+impl Fingerprinted<'_> {
+    fn location(&self, tokens: Range<usize>) -> CloneLocation {
+        let (first, last) = (&self.spans[tokens.start], &self.spans[tokens.end - 1]);
+        CloneLocation {
+            path: self.path.to_string(),
+            range: first.0.start..last.0.end,
+            start_line: first.1,
+            end_line: last.1,
+        }
+    }
+}
+
+/// Where clones are looked for: code, but not markup or data.
+fn is_code(language: Language) -> bool {
+    matches!(
+        language,
+        Language::Rust | Language::TypeScript | Language::Tsx | Language::Python | Language::Shell
+    )
+}
+
+/// A token's hash, the same for every name and for every literal.
+fn normalized(token: &Tok) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    if token.is_name() || token.kind == TokenKind::Variable {
+        "$name".hash(&mut hasher);
+    } else if matches!(token.kind, TokenKind::String | TokenKind::Number) {
+        "$literal".hash(&mut hasher);
+    } else {
+        token.text.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Rolling hashes of every `k` tokens in a row.
+fn k_grams(hashes: &[u64], k: usize) -> Vec<u64> {
+    if hashes.len() < k {
+        return Vec::new();
+    }
+    let top = (1..k).fold(1u64, |power, _| power.wrapping_mul(HASH_BASE));
+    let mut hash = hashes[..k].iter().fold(0u64, |hash, &h| hash.wrapping_mul(HASH_BASE).wrapping_add(h));
+    let mut grams = vec![hash];
+    for index in k..hashes.len() {
+        hash = hash
+            .wrapping_sub(hashes[index - k].wrapping_mul(top))
+            .wrapping_mul(HASH_BASE)
+            .wrapping_add(hashes[index]);
+        grams.push(hash);
+    }
+    grams
+}
+
+/// The smallest k-gram hash of every `window` k-grams in a row (the last
+/// one on ties), with its position, each taken once.
+fn winnow(grams: &[u64], window: usize) -> Vec<(u64, usize)> {
+    let window = window.min(grams.len());
+    let mut fingerprints: Vec<(u64, usize)> = Vec::new();
+    // Positions whose hashes increase, the window's minimum first.
+    let mut candidates = VecDeque::new();
+    for (position, &hash) in grams.iter().enumerate() {
+        while candidates.back().is_some_and(|&last| grams[last] >= hash) {
+            candidates.pop_back();
+        }
+        candidates.push_back(position);
+        if candidates.front().is_some_and(|&first| first + window <= position) {
+            candidates.pop_front();
+        }
+        if position + 1 >= window {
+            let minimum = candidates[0];
+            if fingerprints.last().is_none_or(|&(_, last)| last != minimum) {
+                fingerprints.push((grams[minimum], minimum));
+            }
+        }
+    }
+    fingerprints
+}
+
+/// Blocks of at least `options.min_tokens` tokens that occur twice among
+/// `files`, given as paths and contents, largest first. Files that are
+/// not code are skipped.
+pub fn find_clones(files: &[(&str, &str)], options: &CloneOptions) -> Vec<ClonePair> {
+    let min_tokens = options.min_tokens.max(1);
+    // Every block of `min_tokens` tokens holds a whole window of k-grams,
+    // so it has at least one fingerprint.
+    let k = (min_tokens / 3).clamp(1, 16);
+    let window = min_tokens - k + 1;
+
+    let fingerprinted: Vec<Fingerprinted> = files
+        .iter()
+        .filter(|(path, _)| is_code(Language::from_path(path)))
+        .map(|&(path, text)| {
+            let (_, _, tokens) = lex(text, Language::from_path(path));
+            Fingerprinted {
+                path,
+                hashes: tokens.iter().map(normalized).collect(),
+                spans: tokens.iter().map(|token| (token.offset..token.end(), token.line)).collect(),
+            }
+        })
+        .collect();
+
+    let mut pairs = Vec::new();
+    let mut seen: HashMap<u64, Vec<(usize, usize)>> = HashMap::new();
+    // Equal runs already grown, as token ranges of the earlier copy, by
+    // both files and the distance between the copies. Runs are grown as
+    // far as they go, so a fingerprint inside one leads to the same run.
+    let mut grown: HashMap<(usize, usize, isize), Vec<Range<usize>>> = HashMap::new();
+    for (file, fingerprinted_file) in fingerprinted.iter().enumerate() {
+        for (hash, position) in winnow(&k_grams(&fingerprinted_file.hashes, k), window) {
+            let earlier = seen.entry(hash).or_default();
+            if earlier.len() >= MAX_OCCURRENCES {
+                continue;
+            }
+            for &(other, other_position) in earlier.iter() {
+                let key = (other, file, position as isize - other_position as isize);
+                let runs = grown.entry(key).or_default();
+                if runs.iter().any(|run| run.contains(&other_position)) {
+                    continue;
+                }
+                let (first, second) = grow(&fingerprinted[other], other_position, fingerprinted_file, position);
+                runs.push(first.clone());
+                let copies = if other == file && first.end > second.start {
+                    // Code repeated back to back matches itself shifted by
+                    // one copy: split the run into the copies.
+                    let length = second.start - first.start;
+                    let starts: Vec<usize> = (first.start..second.end - length + 1).step_by(length).collect();
+                    starts.windows(2).map(|pair| (pair[0]..pair[0] + length, pair[1]..pair[1] + length)).collect()
+                } else {
+                    vec![(first, second)]
+                };
+                for (first, second) in copies.into_iter().filter(|(first, _)| first.len() >= min_tokens) {
+                    pairs.push(ClonePair {
+                        first: fingerprinted[other].location(first.clone()),
+                        second: fingerprinted_file.location(second),
+                        tokens: first.len(),
+                    });
+                }
+            }
+            earlier.push((file, position));
+        }
+    }
+    without_overlaps(pairs)
+}
+
+/// The pairs, largest first, less those with both copies overlapping the
+/// copies of a larger one: repeated code matches itself at every shift,
+/// and each shift would otherwise be reported.
+fn without_overlaps(mut pairs: Vec<ClonePair>) -> Vec<ClonePair> {
+    pairs.sort_by_key(|pair| std::cmp::Reverse(pair.tokens));
+    let overlaps = |location: &CloneLocation, pair: &ClonePair| {
+        [&pair.first, &pair.second].into_iter().any(|other| {
+            other.path == location.path
+                && other.range.start < location.range.end
+                && location.range.start < other.range.end
+        })
+    };
+    let mut kept: Vec<ClonePair> = Vec::new();
+    for pair in pairs {
+        if !kept.iter().any(|larger| overlaps(&pair.first, larger) && overlaps(&pair.second, larger)) {
+            kept.push(pair);
+        }
+    }
+    kept
+}
+
+/// The longest equal token runs around `a` in `first` and `b` in `second`.
+/// Runs in the same file may overlap.
+fn grow(first: &Fingerprinted, a: usize, second: &Fingerprinted, b: usize) -> (Range<usize>, Range<usize>) {
+    // How far the runs reach before and after `a` and `b`.
+    let (mut start, mut end) = (0usize, 0usize);
+    while a + end < first.hashes.len()
+        && b + end < second.hashes.len()
+        && first.hashes[a + end] == second.hashes[b + end]
+    {
+        end += 1;
+    }
+    while start < a && start < b && first.hashes[a - start - 1] == second.hashes[b - start - 1] {
+        start += 1;
+    }
+    (a - start..a + end, b - start..b + end)
+}
+
+/// Clones among the files of the workspace matching `globs` (see
+/// [`SearchQuery::globs`](crate::utils::file_system::search::SearchQuery::globs)).
+pub fn find_workspace_clones(
+    fs: &VirtualFileSystem,
+    globs: &[String],
+    options: &CloneOptions,
+) -> FsResult<Vec<ClonePair>> {
+    let paths = fs.glob(globs)?;
+    let files: Vec<(&str, &str)> = paths
+        .iter()
+        .filter_map(|path| fs.read(path).ok().map(|text| (path.as_str(), text)))
+        .collect();
+    Ok(find_clones(&files, options))
+}
+
+/// A diagnostic at each copy of every pair, pointing to the other copy,
+/// by path.
+pub fn clone_diagnostics(pairs: &[ClonePair]) -> BTreeMap<String, Vec<Diagnostic>> {
+    let mut diagnostics: BTreeMap<String, Vec<Diagnostic>> = BTreeMap::new();
+    for pair in pairs {
+        for (here, there) in [(&pair.first, &pair.second), (&pair.second, &pair.first)] {
+            let mut diagnostic = Diagnostic::new(
+                Severity::Info,
+                here.range.clone(),
+                format!("Duplicated block of {} tokens", pair.tokens),
+                SOURCE,
+            );
+            diagnostic.related.push(RelatedLocation {
+                path: there.path.clone(),
+                range: there.range.clone(),
+                message: format!("Also at lines {}–{} of", there.start_line + 1, there.end_line + 1),
+            });
+            diagnostics.entry(here.path.clone()).or_default().push(diagnostic);
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str) -> String {
+        format!(
+            concat!(
+                "function {name}(a, b) {{\n",
+                "  const x = a + b * 2;\n",
+                "  if (x > 10) {{ console.log(x); return x - 1; }}\n",
+                "  for (let i = 0; i < x; i++) {{ a += i; b -= i; }}\n",
+                "  return a * b + x;\n",
+                "}}\n",
+            ),
+            name = name
+        )
+    }
+
+    fn ranges(pairs: &[ClonePair]) -> Vec<(&str, Range<usize>, &str, Range<usize>)> {
+        let mut ranges: Vec<_> = pairs
+            .iter()
+            .map(|pair| {
+                let (first, second) = (&pair.first, &pair.second);
+                (first.path.as_str(), first.range.clone(), second.path.as_str(), second.range.clone())
+            })
+            .collect();
+        ranges.sort_by_key(|(first, range, second, other)| (*first, range.start, *second, other.start));
+        ranges
+    }
+
+    #[test]
+    fn finds_renamed_copies_across_files() {
+        let a = format!("{}let z = 1;\n", function("one"));
+        let b = format!("const q = 2;\n{}", function("two"));
+        let pairs = find_clones(&[("/a.ts", &a), ("/b.ts", &b), ("/notes.md", &a)], &CloneOptions::default());
+        assert_eq!(ranges(&pairs), [("/a.ts", 0..a.len() - 12, "/b.ts", 13..b.len() - 1)]);
+        assert_eq!(pairs[0].first.start_line, 0);
+        assert_eq!(pairs[0].first.end_line, 5);
+
+        let short = CloneOptions { min_tokens: 100 };
+        assert!(find_clones(&[("/a.ts", &a), ("/b.ts", &b)], &short).is_empty());
+    }
+
+    #[test]
+    fn splits_adjacent_copies_in_one_file() {
+        let code = format!("{}{}", function("p"), function("q"));
+        let length = function("p").len();
+        let pairs = find_clones(&[("/c.ts", &code)], &CloneOptions::default());
+        assert_eq!(ranges(&pairs), [("/c.ts", 0..length - 1, "/c.ts", length..2 * length - 1)]);
+    }
+
+    #[test]
+    fn reports_every_copy_of_a_block() {
+        let code = format!("let k = 0;\n{}{}{}", function("p"), function("q"), function("r"));
+        let length = function("p").len();
+        let copy = |index: usize| 11 + index * length..11 + (index + 1) * length - 1;
+        let pairs = find_clones(&[("/c.ts", &code)], &CloneOptions::default());
+        assert_eq!(
+            ranges(&pairs),
+            [
+                ("/c.ts", copy(0), "/c.ts", copy(1)),
+                ("/c.ts", copy(0), "/c.ts", copy(2)),
+                ("/c.ts", copy(1), "/c.ts", copy(2)),
+            ]
+        );
+        let diagnostics = clone_diagnostics(&pairs);
+        for index in 0..3 {
+            assert!(diagnostics["/c.ts"].iter().any(|diagnostic| diagnostic.range == copy(index)));
+        }
+    }
+
+    #[test]
+    fn repeated_statements_are_not_reported_at_every_shift() {
+        let body: String = (0..50).map(|_| "    total = total + compute(value, 1);\n").collect();
+        let code = format!("fn f() {{\n{}}}\n", body);
+        let pairs = find_clones(&[("/a.rs", &code)], &CloneOptions::default());
+        assert!(!pairs.is_empty() && pairs.len() <= 3, "{} pairs", pairs.len());
+    }
+}
@@ -0,0 +1,670 @@
+//! Lint rules: small checks of a file's tokens and function metrics, each
+//! with an ID and a severity the workspace may change or turn off.
+//!
+//! A comment silences rules: `lint-ignore` on its own line,
+//! `lint-ignore-next-line` on the line after it and `lint-ignore-file`
+//! everywhere. The rule IDs follow, separated by commas; with none, every
+//! rule is silenced.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use super::{language_named, lex, measure, CodeMetrics, Tok};
+use crate::utils::diagnostics::{Diagnostic, QuickFix, Severity};
+use crate::utils::syntax::{Language, Token, TokenKind};
+
+/// The `source` of lint diagnostics.
+pub const SOURCE: &str = "lint";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LintConfig {
+    /// Severities replacing the rules' own, by rule ID; `null` turns a
+    /// rule off.
+    pub rules: BTreeMap<String, Option<Severity>>,
+    /// Longest function `max-function-lines` allows.
+    pub max_function_lines: usize,
+    /// Deepest nesting of control blocks `max-nesting` allows.
+    pub max_nesting: usize,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            rules: BTreeMap::new(),
+            max_function_lines: 80,
+            max_nesting: 4,
+        }
+    }
+}
+
+impl LintConfig {
+    /// How `rule` reports, or `None` if it is turned off.
+    pub fn severity(&self, rule: &Rule) -> Option<Severity> {
+        self.rules.get(rule.id).copied().unwrap_or(Some(rule.severity))
+    }
+}
+
+pub struct Rule {
+    pub id: &'static str,
+    pub description: &'static str,
+    /// How the rule reports unless configured otherwise.
+    pub severity: Severity,
+    /// The languages checked; all of them if empty.
+    pub languages: &'static [Language],
+    check: fn(&Source, &LintConfig, &mut Vec<Finding>),
+}
+
+impl Rule {
+    pub fn applies_to(&self, language: Language) -> bool {
+        self.languages.is_empty() || self.languages.contains(&language)
+    }
+}
+
+const SCRIPTS: &[Language] = &[Language::TypeScript, Language::Tsx];
+const BLOCK_LANGUAGES: &[Language] = &[Language::Rust, Language::TypeScript, Language::Tsx, Language::Python];
+
+/// Every rule, in the order they run.
+pub const RULES: &[Rule] = &[
+    Rule {
+        id: "no-console",
+        description: "Debug output through `console` left in code",
+        severity: Severity::Warning,
+        languages: SCRIPTS,
+        check: check_console,
+    },
+    Rule {
+        id: "no-debugger",
+        description: "`debugger` statements left in code",
+        severity: Severity::Warning,
+        languages: SCRIPTS,
+        check: check_debugger,
+    },
+    Rule {
+        id: "no-dbg",
+        description: "`dbg!` left in code",
+        severity: Severity::Warning,
+        languages: &[Language::Rust],
+        check: check_dbg,
+    },
+    Rule {
+        id: "no-unwrap",
+        description: "`unwrap()` outside tests",
+        severity: Severity::Warning,
+        languages: &[Language::Rust],
+        check: check_unwrap,
+    },
+    Rule {
+        id: "no-breakpoint",
+        description: "`breakpoint()` or `pdb.set_trace()` left in code",
+        severity: Severity::Warning,
+        languages: &[Language::Python],
+        check: check_breakpoint,
+    },
+    Rule {
+        id: "todo-owner",
+        description: "`TODO` or `FIXME` comments without an owner, as `TODO(name)`",
+        severity: Severity::Info,
+        languages: &[],
+        check: check_todo_owner,
+    },
+    Rule {
+        id: "max-function-lines",
+        description: "Functions longer than the configured number of lines",
+        severity: Severity::Warning,
+        languages: BLOCK_LANGUAGES,
+        check: check_function_lines,
+    },
+    Rule {
+        id: "max-nesting",
+        description: "Functions nesting control blocks deeper than configured",
+        severity: Severity::Warning,
+        languages: BLOCK_LANGUAGES,
+        check: check_nesting,
+    },
+];
+
+/// The rule with ID `id`.
+pub fn rule(id: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|rule| rule.id == id)
+}
+
+/// What one rule found, before its severity is known.
+struct Finding {
+    range: Range<usize>,
+    message: String,
+    fixes: Vec<QuickFix>,
+}
+
+impl Finding {
+    fn new(range: Range<usize>, message: impl Into<String>) -> Self {
+        Self {
+            range,
+            message: message.into(),
+            fixes: Vec::new(),
+        }
+    }
+
+    fn with_fix(mut self, fix: Option<QuickFix>) -> Self {
+        self.fixes.extend(fix);
+        self
+    }
+}
+
+/// A lexed file, as the rules see it.
+struct Source<'a> {
+    text: &'a str,
+    lines: Vec<&'a str>,
+    /// The byte offset each line starts at.
+    line_starts: Vec<usize>,
+    /// The tokens of each line, comments included.
+    tokens: Vec<Vec<Token>>,
+    /// All tokens but comments.
+    code: Vec<Tok<'a>>,
+    metrics: CodeMetrics,
+}
+
+impl<'a> Source<'a> {
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset).saturating_sub(1)
+    }
+
+    /// The text of `line` without its indentation, as a range.
+    fn line_range(&self, line: usize) -> Range<usize> {
+        let text = self.lines[line];
+        let start = self.line_starts[line] + text.len() - text.trim_start().len();
+        start..(self.line_starts[line] + text.trim_end().len()).max(start)
+    }
+
+    /// The comments of each line, with their offsets in the file.
+    fn comments(&self) -> impl Iterator<Item = (usize, usize, &'a str)> + '_ {
+        self.lines.iter().zip(&self.tokens).enumerate().flat_map(move |(line, (&text, tokens))| {
+            tokens
+                .iter()
+                .filter(|token| token.kind == TokenKind::Comment)
+                .map(move |token| (line, self.line_starts[line] + token.start, &text[token.start..token.end]))
+        })
+    }
+
+    /// The index of the bracket closing the one at `open`.
+    fn closing(&self, open: usize) -> Option<usize> {
+        let mut depth = 0usize;
+        for (index, token) in self.code.iter().enumerate().skip(open) {
+            if token.is("(") || token.is("[") || token.is("{") {
+                depth += 1;
+            } else if token.is(")") || token.is("]") || token.is("}") {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+        }
+        None
+    }
+
+    /// A fix deleting the statement from token `first` to token `last`,
+    /// with a `;` after it, if the statement fills its lines alone.
+    fn removal(&self, title: &str, first: usize, last: usize) -> Option<QuickFix> {
+        let last = match self.code.get(last + 1) {
+            Some(token) if token.is(";") => last + 1,
+            _ => last,
+        };
+        let (start, end) = (self.code[first].offset, self.code[last].end());
+        let (first_line, last_line) = (self.line_of(start), self.line_of(end));
+        let alone = self.text[self.line_starts[first_line]..start].trim().is_empty()
+            && self.lines[last_line][end - self.line_starts[last_line]..].trim().is_empty();
+        if !alone {
+            return None;
+        }
+        let remove_end = self.line_starts.get(last_line + 1).copied().unwrap_or(self.text.len());
+        Some(QuickFix::new(
+            title,
+            self.text,
+            vec![(self.line_starts[first_line]..remove_end, String::new())],
+        ))
+    }
+}
+
+/// Runs the rules for `language` (a name or extension) over `code`.
+pub fn lint(code: &str, language: &str, config: &LintConfig) -> Vec<Diagnostic> {
+    let language = language_named(language);
+    let (lines, tokens, code_tokens) = lex(code, language);
+    let metrics = measure(language, &lines, &tokens, &code_tokens);
+    let mut line_starts = Vec::with_capacity(lines.len());
+    let mut offset = 0;
+    for line in &lines {
+        line_starts.push(offset);
+        offset += line.len() + 1;
+    }
+    let source = Source {
+        text: code,
+        lines,
+        line_starts,
+        tokens,
+        code: code_tokens,
+        metrics,
+    };
+    let suppressions = Suppressions::read(&source);
+
+    let mut diagnostics = Vec::new();
+    for rule in RULES.iter().filter(|rule| rule.applies_to(language)) {
+        let Some(severity) = config.severity(rule) else {
+            continue;
+        };
+        let mut findings = Vec::new();
+        (rule.check)(&source, config, &mut findings);
+        for finding in findings {
+            if suppressions.silences(rule.id, source.line_of(finding.range.start)) {
+                continue;
+            }
+            let mut diagnostic = Diagnostic::new(severity, finding.range, finding.message, SOURCE);
+            diagnostic.code = Some(rule.id.to_string());
+            diagnostic.fixes = finding.fixes;
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.range.start, diagnostic.severity));
+    diagnostics
+}
+
+/// The `lint-ignore` comments of a file.
+struct Suppressions<'a> {
+    /// The line silenced, or `None` for the whole file, and the rule IDs
+    /// silenced there; all rules if there are none.
+    entries: Vec<(Option<usize>, Vec<&'a str>)>,
+}
+
+impl<'a> Suppressions<'a> {
+    fn read(source: &Source<'a>) -> Self {
+        let mut entries = Vec::new();
+        for (line, _, comment) in source.comments() {
+            let Some(at) = comment.find("lint-ignore") else {
+                continue;
+            };
+            let directive = &comment[at + "lint-ignore".len()..];
+            let (scope, rest) = if let Some(rest) = directive.strip_prefix("-next-line") {
+                (Some(line + 1), rest)
+            } else if let Some(rest) = directive.strip_prefix("-file") {
+                (None, rest)
+            } else {
+                (Some(line), directive)
+            };
+            if rest.starts_with(|c: char| c.is_alphanumeric() || c == '-') {
+                continue;
+            }
+            let ids = rest
+                .trim_start_matches(':')
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|word| !word.is_empty())
+                .take_while(|word| {
+                    word.starts_with(|c: char| c.is_ascii_lowercase())
+                        && word.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+                })
+                .collect();
+            entries.push((scope, ids));
+        }
+        Self { entries }
+    }
+
+    fn silences(&self, id: &str, line: usize) -> bool {
+        self.entries
+            .iter()
+            .any(|(scope, ids)| scope.is_none_or(|scope| scope == line) && (ids.is_empty() || ids.contains(&id)))
+    }
+}
+
+fn check_console(source: &Source, _: &LintConfig, findings: &mut Vec<Finding>) {
+    const METHODS: &[&str] = &["log", "debug", "trace", "dir", "table"];
+    let code = &source.code;
+    for index in 0..code.len().saturating_sub(2) {
+        let (object, dot, method) = (code[index], code[index + 1], code[index + 2]);
+        if object.text != "console" || !object.is_name() || !dot.is(".") || !METHODS.contains(&method.text) {
+            continue;
+        }
+        if index > 0 && code[index - 1].is(".") {
+            continue;
+        }
+        let call = format!("console.{}", method.text);
+        let fix = code
+            .get(index + 3)
+            .filter(|token| token.is("("))
+            .and_then(|_| source.closing(index + 3))
+            .and_then(|close| source.removal(&format!("Remove the `{}` call", call), index, close));
+        findings.push(Finding::new(object.offset..method.end(), format!("Unexpected `{}` call", call)).with_fix(fix));
+    }
+}
+
+fn check_debugger(source: &Source, _: &LintConfig, findings: &mut Vec<Finding>) {
+    for (index, token) in source.code.iter().enumerate() {
+        if token.is("debugger") {
+            let fix = source.removal("Remove the `debugger` statement", index, index);
+            findings.push(Finding::new(token.offset..token.end(), "Unexpected `debugger` statement").with_fix(fix));
+        }
+    }
+}
+
+fn check_dbg(source: &Source, _: &LintConfig, findings: &mut Vec<Finding>) {
+    let code = &source.code;
+    for (index, token) in code.iter().enumerate() {
+        if token.kind != TokenKind::Macro || token.text != "dbg!" {
+            continue;
+        }
+        let close = code.get(index + 1).filter(|open| open.is("(")).and_then(|_| source.closing(index + 1));
+        let fix = close.and_then(|close| {
+            // `dbg!(a, b)` returns a tuple and `dbg!()` nothing, so only a
+            // single argument can stand alone.
+            let mut depth = 0usize;
+            for argument in &code[index + 2..close] {
+                if argument.is("(") || argument.is("[") || argument.is("{") {
+                    depth += 1;
+                } else if argument.is(")") || argument.is("]") || argument.is("}") {
+                    depth -= 1;
+                } else if depth == 0 && argument.is(",") {
+                    return None;
+                }
+            }
+            (close > index + 2).then(|| {
+                QuickFix::new(
+                    "Remove `dbg!`, keeping its argument",
+                    source.text,
+                    vec![
+                        (token.offset..code[index + 1].end(), String::new()),
+                        (code[close].offset..code[close].end(), String::new()),
+                    ],
+                )
+            })
+        });
+        let range = token.offset..close.map_or(token.end(), |close| code[close].end());
+        findings.push(Finding::new(range, "`dbg!` left in code").with_fix(fix));
+    }
+}
+
+/// Whether a Rust attribute marks test code: `#[test]`, `#[tokio::test]`
+/// or `#[cfg(test)]`.
+fn is_test_attribute(attribute: &str) -> bool {
+    let inner = attribute.trim_start_matches("#[").trim_end_matches(']').trim();
+    inner == "test"
+        || inner.ends_with("::test")
+        || (inner.starts_with("cfg(")
+            && !inner.contains("not(")
+            && inner.split(|c: char| !c.is_alphanumeric() && c != '_').any(|word| word == "test"))
+}
+
+fn check_unwrap(source: &Source, _: &LintConfig, findings: &mut Vec<Finding>) {
+    let code = &source.code;
+    let mut depth = 0usize;
+    // The depth a test attribute was read at, until the item's `{`.
+    let mut test_item = None;
+    // The depth inside the body of a test item.
+    let mut test_body = None;
+    for (index, token) in code.iter().enumerate() {
+        if token.kind == TokenKind::Attribute && test_body.is_none() && is_test_attribute(token.text) {
+            test_item = test_item.or(Some(depth));
+        } else if token.is("{") {
+            depth += 1;
+            if test_item.take().is_some() {
+                test_body = Some(depth);
+            }
+        } else if token.is("}") {
+            if test_body == Some(depth) {
+                test_body = None;
+            }
+            depth = depth.saturating_sub(1);
+        } else if token.is(";") && test_item == Some(depth) {
+            test_item = None;
+        } else if token.text == "unwrap"
+            && test_body.is_none()
+            && index > 0
+            && code[index - 1].is(".")
+            && code.get(index + 1).is_some_and(|open| open.is("("))
+            && code.get(index + 2).is_some_and(|close| close.is(")"))
+        {
+            findings.push(Finding::new(
+                token.offset..code[index + 2].end(),
+                "`unwrap()` panics on `None` or `Err`; handle the error or use `expect` to say why it cannot fail",
+            ));
+        }
+    }
+}
+
+fn check_breakpoint(source: &Source, _: &LintConfig, findings: &mut Vec<Finding>) {
+    let code = &source.code;
+    for (index, token) in code.iter().enumerate() {
+        let first = if token.text == "breakpoint" && (index == 0 || !code[index - 1].is(".")) {
+            index
+        } else if token.text == "set_trace" && index >= 2 && code[index - 1].is(".") && code[index - 2].text == "pdb" {
+            index - 2
+        } else {
+            continue;
+        };
+        let Some(close) = code.get(index + 1).filter(|open| open.is("(")).and_then(|_| source.closing(index + 1))
+        else {
+            continue;
+        };
+        let call = source.text[code[first].offset..token.end()].to_string();
+        let fix = source.removal(&format!("Remove the `{}()` call", call), first, close);
+        let range = code[first].offset..code[close].end();
+        findings.push(Finding::new(range, format!("`{}()` left in code", call)).with_fix(fix));
+    }
+}
+
+fn check_todo_owner(source: &Source, _: &LintConfig, findings: &mut Vec<Finding>) {
+    for (_, offset, comment) in source.comments() {
+        for marker in ["TODO", "FIXME"] {
+            for (at, _) in comment.match_indices(marker) {
+                let end = at + marker.len();
+                let is_word = |c: char| c.is_alphanumeric() || c == '_';
+                if comment[..at].ends_with(is_word) || comment[end..].starts_with(is_word) {
+                    continue;
+                }
+                let owner = comment[end..]
+                    .strip_prefix('(')
+                    .and_then(|rest| rest.split_once(')'))
+                    .is_some_and(|(owner, _)| !owner.trim().is_empty());
+                if !owner {
+                    findings.push(Finding::new(
+                        offset + at..offset + end,
+                        format!("`{}` has no owner; write it as `{}(name)`", marker, marker),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn check_function_lines(source: &Source, config: &LintConfig, findings: &mut Vec<Finding>) {
+    for function in &source.metrics.function_metrics {
+        let length = function.end_line - function.start_line + 1;
+        if length > config.max_function_lines {
+            findings.push(Finding::new(
+                source.line_range(function.start_line),
+                format!(
+                    "`{}` is {} lines long (at most {} allowed)",
+                    function.name, length, config.max_function_lines
+                ),
+            ));
+        }
+    }
+}
+
+fn check_nesting(source: &Source, config: &LintConfig, findings: &mut Vec<Finding>) {
+    for function in &source.metrics.function_metrics {
+        if function.nesting > config.max_nesting {
+            findings.push(Finding::new(
+                source.line_range(function.start_line),
+                format!(
+                    "`{}` nests control blocks {} deep (at most {} allowed)",
+                    function.name, function.nesting, config.max_nesting
+                ),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The rule ID and the text flagged of each diagnostic.
+    fn found<'a>(code: &'a str, diagnostics: &'a [Diagnostic]) -> Vec<(&'a str, &'a str)> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.code.as_deref().unwrap_or_default(), &code[diagnostic.range.clone()]))
+            .collect()
+    }
+
+    fn ids(code: &str, language: &str) -> Vec<String> {
+        lint(code, language, &LintConfig::default()).into_iter().filter_map(|diagnostic| diagnostic.code).collect()
+    }
+
+    #[test]
+    fn script_rules() {
+        let code = concat!(
+            "function f(x) {\n",
+            "  console.log(x, g(x));\n",
+            "  debugger;\n",
+            "  console.error(x);\n",
+            "  window.console.log(x);\n",
+            "  return x; console.debug(x);\n",
+            "}\n",
+        );
+        let diagnostics = lint(code, "ts", &LintConfig::default());
+        assert_eq!(
+            found(code, &diagnostics),
+            [
+                ("no-console", "console.log"),
+                ("no-debugger", "debugger"),
+                ("no-console", "console.debug"),
+            ]
+        );
+        assert!(diagnostics.iter().all(|diagnostic| diagnostic.severity == Severity::Warning));
+        let line = |number: usize| {
+            let start: usize = code.split_inclusive('\n').take(number).map(str::len).sum();
+            start..start + code.split_inclusive('\n').nth(number).unwrap().len()
+        };
+        assert_eq!(diagnostics[0].fixes[0].edits, [(line(1), String::new())]);
+        assert_eq!(diagnostics[0].fixes[0].replaced, ["  console.log(x, g(x));\n"]);
+        assert_eq!(diagnostics[1].fixes[0].edits, [(line(2), String::new())]);
+        // Not alone on its line.
+        assert!(diagnostics[2].fixes.is_empty());
+        assert!(ids(code, "rs").is_empty());
+    }
+
+    #[test]
+    fn dbg_keeps_a_single_argument() {
+        let code = "fn f(x: u8) -> u8 {\n    let y = dbg!(x + 1);\n    dbg!(x, y);\n    y\n}\n";
+        let diagnostics = lint(code, "rust", &LintConfig::default());
+        assert_eq!(
+            found(code, &diagnostics),
+            [("no-dbg", "dbg!(x + 1)"), ("no-dbg", "dbg!(x, y)")]
+        );
+        let start = code.find("dbg!(x + 1)").unwrap();
+        assert_eq!(
+            diagnostics[0].fixes[0].edits,
+            [(start..start + 5, String::new()), (start + 10..start + 11, String::new())]
+        );
+        assert!(diagnostics[1].fixes.is_empty());
+    }
+
+    #[test]
+    fn unwrap_outside_tests() {
+        let code = concat!(
+            "fn main() { let n = parse().unwrap(); let m = parse().unwrap_or(0); }\n",
+            "#[test]\n",
+            "fn parses() { parse().unwrap(); }\n",
+            "#[cfg(test)]\n",
+            "mod tests { fn helper() { parse().unwrap(); } }\n",
+            "#[cfg(not(test))]\n",
+            "fn live() { parse().unwrap(); }\n",
+        );
+        let diagnostics = lint(code, "rust", &LintConfig::default());
+        assert_eq!(found(code, &diagnostics), [("no-unwrap", "unwrap()"); 2]);
+        assert_eq!(code[..diagnostics[1].range.start].matches('\n').count(), 6);
+    }
+
+    #[test]
+    fn python_breakpoints() {
+        let code = "def f(x):\n    breakpoint()\n    import pdb; pdb.set_trace()\n    x.breakpoint()\n    return x\n";
+        let diagnostics = lint(code, "py", &LintConfig::default());
+        assert_eq!(
+            found(code, &diagnostics),
+            [("no-breakpoint", "breakpoint()"), ("no-breakpoint", "pdb.set_trace()")]
+        );
+        let start = code.find("    breakpoint").unwrap();
+        assert_eq!(diagnostics[0].fixes[0].edits, [(start..start + "    breakpoint()\n".len(), String::new())]);
+        assert!(diagnostics[1].fixes.is_empty());
+    }
+
+    #[test]
+    fn todos_need_an_owner() {
+        let code = "// TODO: fix\n// TODO(sam) later\n// TODOS and XFIXME\n/* FIXME() */ let todo = \"TODO\";\n";
+        let diagnostics = lint(code, "rs", &LintConfig::default());
+        assert_eq!(found(code, &diagnostics), [("todo-owner", "TODO"), ("todo-owner", "FIXME")]);
+        assert_eq!(diagnostics[0].severity, Severity::Info);
+        assert_eq!(ids("# TODO\n", "py"), ["todo-owner"]);
+    }
+
+    #[test]
+    fn function_length_and_nesting() {
+        let code = concat!(
+            "function deep(a) {\n",
+            "  if (a) { for (;;) { while (a) { if (a) { try { a(); } catch (e) {} } } } }\n",
+            "}\n",
+            "function short() { return 1; }\n",
+        );
+        assert_eq!(ids(code, "ts"), ["max-nesting"]);
+        let config = LintConfig {
+            max_function_lines: 2,
+            max_nesting: 5,
+            ..LintConfig::default()
+        };
+        let diagnostics = lint(code, "ts", &config);
+        assert_eq!(found(code, &diagnostics), [("max-function-lines", "function deep(a) {")]);
+        assert_eq!(diagnostics[0].message, "`deep` is 3 lines long (at most 2 allowed)");
+    }
+
+    #[test]
+    fn suppressions() {
+        let code = concat!(
+            "debugger; // lint-ignore\n",
+            "// lint-ignore-next-line no-console, no-debugger\n",
+            "console.log(1); debugger;\n",
+            "// lint-ignore-next-line: no-debugger\n",
+            "console.log(2);\n",
+            "console.log(3); // lint-ignored\n",
+            "debugger; // lint-ignore no-console\n",
+        );
+        assert_eq!(ids(code, "ts"), ["no-console", "no-console", "no-debugger"]);
+        let diagnostics = lint(code, "ts", &LintConfig::default());
+        assert_eq!(
+            diagnostics.iter().map(|diagnostic| diagnostic.range.start).collect::<Vec<_>>(),
+            [
+                code.find("console.log(2)").unwrap(),
+                code.find("console.log(3)").unwrap(),
+                code.rfind("debugger").unwrap(),
+            ]
+        );
+
+        let file = format!("{}/* lint-ignore-file no-debugger */\n", code);
+        assert_eq!(ids(&file, "ts"), ["no-console", "no-console"]);
+        let file = format!("// lint-ignore-file\n{}", code);
+        assert!(ids(&file, "ts").is_empty());
+    }
+
+    #[test]
+    fn configured_severities() {
+        let config: LintConfig =
+            serde_json::from_str(r#"{ "rules": { "no-console": null, "no-debugger": "error" } }"#).unwrap();
+        assert_eq!((config.max_function_lines, config.max_nesting), (80, 4));
+        assert_eq!(config.severity(rule("no-console").unwrap()), None);
+        assert_eq!(config.severity(rule("no-debugger").unwrap()), Some(Severity::Error));
+        assert_eq!(config.severity(rule("todo-owner").unwrap()), Some(Severity::Info));
+
+        let diagnostics = lint("console.log(1);\ndebugger;\n", "js", &config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].severity, diagnostics[0].source.as_str()), (Severity::Error, SOURCE));
+        assert!(rule("no-such-rule").is_none());
+    }
+}
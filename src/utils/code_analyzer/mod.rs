@@ -0,0 +1,592 @@
+//! Code metrics: line counts, cyclomatic complexity, and the functions,
+//! classes and imports of a file. They are taken from the syntax
+//! highlighter's tokens, so strings and comments never count as code.
+
+pub mod clones;
+pub mod lint;
+pub mod outline;
+
+use crate::utils::syntax::{tokenize, Language, Token, TokenKind};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodeMetrics {
+    pub lines: usize,
+    pub non_empty_lines: usize,
+    /// Lines holding only comments.
+    pub comment_lines: usize,
+    pub code_lines: usize,
+    /// Cyclomatic complexity of the whole file: one, plus one per branch.
+    pub complexity: usize,
+    pub functions: usize,
+    /// Classes, or in Rust structs, enums, unions and traits.
+    pub classes: usize,
+    pub imports: usize,
+    /// Each function's own metrics, for Rust, TypeScript and Python.
+    pub function_metrics: Vec<FunctionMetrics>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionMetrics {
+    /// `<anonymous>` for unnamed functions.
+    pub name: String,
+    /// First and last line, 0-based.
+    pub start_line: usize,
+    pub end_line: usize,
+    /// One, plus one per branch in the function but not in functions
+    /// nested in it.
+    pub complexity: usize,
+    /// How deep control-flow blocks (`if`, loops, `match`, `try`...) nest
+    /// in the function; 0 if it has none.
+    pub nesting: usize,
+}
+
+/// The language named `language`, either by name (`"rust"`,
+/// `"javascript"`) or by file extension.
+fn language_named(language: &str) -> Language {
+    match language.to_ascii_lowercase().as_str() {
+        "rust" => Language::Rust,
+        "typescript" | "javascript" => Language::TypeScript,
+        "typescriptreact" | "javascriptreact" => Language::Tsx,
+        "python" => Language::Python,
+        "markdown" => Language::Markdown,
+        "shell" | "shellscript" => Language::Shell,
+        other => Language::from_extension(other),
+    }
+}
+
+/// Keywords and operators that each add a path through the code. Rust
+/// counts each `match` arm.
+fn branches(language: Language) -> &'static [&'static str] {
+    match language {
+        Language::Rust => &["if", "while", "for", "=>", "&&", "||"],
+        Language::TypeScript | Language::Tsx => &["if", "for", "while", "case", "catch", "&&", "||", "??", "?"],
+        Language::Python => &["if", "elif", "for", "while", "except", "and", "or"],
+        Language::Shell => &["if", "elif", "for", "while", "until", "&&", "||"],
+        _ => &[],
+    }
+}
+
+/// Keywords opening the blocks counted as nesting.
+fn control_keywords(language: Language) -> &'static [&'static str] {
+    match language {
+        Language::Rust => &["if", "else", "for", "while", "loop", "match"],
+        Language::TypeScript | Language::Tsx => {
+            &["if", "else", "for", "while", "do", "switch", "try", "catch", "finally"]
+        }
+        Language::Python => &["if", "elif", "else", "for", "while", "try", "except", "finally", "with", "match"],
+        _ => &[],
+    }
+}
+
+/// A code token with its text, line and byte offset in the file.
+#[derive(Debug, Clone, Copy)]
+struct Tok<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    line: usize,
+    offset: usize,
+}
+
+impl Tok<'_> {
+    fn end(&self) -> usize {
+        self.offset + self.text.len()
+    }
+
+    fn is(&self, text: &str) -> bool {
+        self.text == text && matches!(self.kind, TokenKind::Keyword | TokenKind::Operator | TokenKind::Punctuation)
+    }
+
+    /// Whether this is one of the keywords or operators `words`.
+    fn is_one_of(&self, words: &[&str]) -> bool {
+        matches!(self.kind, TokenKind::Keyword | TokenKind::Operator) && words.contains(&self.text)
+    }
+
+    fn is_name(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Identifier | TokenKind::Function | TokenKind::Type | TokenKind::Property | TokenKind::Constant
+        )
+    }
+}
+
+/// Analyzes `code` written in `language`, given by name or extension.
+pub fn analyze_code(code: &str, language: &str) -> CodeMetrics {
+    let language = language_named(language);
+    let (lines, tokens, code_tokens) = lex(code, language);
+    measure(language, &lines, &tokens, &code_tokens)
+}
+
+fn measure(language: Language, lines: &[&str], tokens: &[Vec<Token>], code_tokens: &[Tok]) -> CodeMetrics {
+    let mut metrics = CodeMetrics {
+        lines: lines.len(),
+        complexity: 1,
+        ..CodeMetrics::default()
+    };
+    for (line, line_tokens) in lines.iter().zip(tokens) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        metrics.non_empty_lines += 1;
+        if is_comment_line(line, line_tokens) {
+            metrics.comment_lines += 1;
+        } else {
+            metrics.code_lines += 1;
+        }
+    }
+    let branches = branches(language);
+    metrics.complexity += code_tokens
+        .iter()
+        .filter(|token| token.is_one_of(branches))
+        .count();
+    match language {
+        Language::Rust | Language::TypeScript | Language::Tsx => {
+            analyze_braces(language, code_tokens, lines.len(), &mut metrics)
+        }
+        Language::Python => analyze_python(lines, code_tokens, &mut metrics),
+        _ => {}
+    }
+    metrics.functions = metrics.function_metrics.len();
+    metrics
+}
+
+/// `code` split into lines, the tokens of each line, and all tokens but
+/// comments in order.
+fn lex(code: &str, language: Language) -> (Vec<&str>, Vec<Vec<Token>>, Vec<Tok<'_>>) {
+    let lines: Vec<&str> = code.split('\n').collect();
+    let tokens = tokenize(language, code);
+    let mut code_tokens = Vec::new();
+    let mut offset = 0;
+    for (number, (line, line_tokens)) in lines.iter().zip(&tokens).enumerate() {
+        code_tokens.extend(
+            line_tokens
+                .iter()
+                .filter(|token| token.kind != TokenKind::Comment)
+                .map(|token| Tok {
+                    kind: token.kind,
+                    text: &line[token.start..token.end],
+                    line: number,
+                    offset: offset + token.start,
+                }),
+        );
+        offset += line.len() + 1;
+    }
+    (lines, tokens, code_tokens)
+}
+
+/// Python's statements: the tokens of a line, and of the lines after it
+/// while brackets are open.
+fn statements<'t, 'a>(tokens: &'t [Tok<'a>]) -> Vec<&'t [Tok<'a>]> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    while start < tokens.len() {
+        let mut parens = 0usize;
+        let mut end = start;
+        while end < tokens.len() && (end == start || parens > 0 || tokens[end].line == tokens[end - 1].line) {
+            let token = tokens[end];
+            if token.is("(") || token.is("[") || token.is("{") {
+                parens += 1;
+            } else if token.is(")") || token.is("]") || token.is("}") {
+                parens = parens.saturating_sub(1);
+            }
+            end += 1;
+        }
+        statements.push(&tokens[start..end]);
+        start = end;
+    }
+    statements
+}
+
+/// Whether every non-blank char of `line` is in a comment.
+fn is_comment_line(line: &str, tokens: &[Token]) -> bool {
+    let comments: Vec<&Token> = tokens.iter().filter(|token| token.kind == TokenKind::Comment).collect();
+    !comments.is_empty()
+        && line
+            .char_indices()
+            .filter(|(_, c)| !c.is_whitespace())
+            .all(|(index, _)| comments.iter().any(|token| token.start <= index && index < token.end))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    /// A function body; the index of its metrics.
+    Function(usize),
+    Class,
+    Control,
+    Other,
+}
+
+/// What the next `{` at paren depth `depth` opens.
+#[derive(Debug, Clone)]
+struct Pending {
+    block: PendingBlock,
+    depth: usize,
+}
+
+#[derive(Debug, Clone)]
+enum PendingBlock {
+    /// A function named `name`, declared on `line`.
+    Function { name: String, line: usize },
+    Class,
+    Control,
+}
+
+/// Functions, classes, imports and nesting in Rust and TypeScript, whose
+/// blocks are braces.
+fn analyze_braces(language: Language, tokens: &[Tok], line_count: usize, metrics: &mut CodeMetrics) {
+    let rust = language == Language::Rust;
+    let class_keywords: &[&str] = if rust { &["struct", "enum", "union", "trait"] } else { &["class"] };
+    let branches = branches(language);
+    let control = control_keywords(language);
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut pending: Option<Pending> = None;
+    let mut parens = 0usize;
+    let function_of = |blocks: &[Block]| {
+        blocks.iter().rev().find_map(|block| match block {
+            Block::Function(index) => Some(*index),
+            _ => None,
+        })
+    };
+    for (index, token) in tokens.iter().enumerate() {
+        let next = tokens.get(index + 1);
+        let previous = index.checked_sub(1).map(|index| tokens[index]);
+        let pending_function = |name: String| PendingBlock::Function { name, line: token.line };
+        if token.is_one_of(class_keywords) {
+            metrics.classes += 1;
+            pending = Some(Pending { block: PendingBlock::Class, depth: parens });
+        } else if token.kind == TokenKind::Keyword && (token.text == "use" || token.text == "import") {
+            // Not TypeScript's dynamic `import(...)`.
+            if !next.is_some_and(|next| next.is("(")) {
+                metrics.imports += 1;
+            }
+        } else if token.is("function") || (token.is("fn") && next.is_some_and(|next| next.is_name())) {
+            // Not Rust's `fn(u8) -> u8` pointer types.
+            let name = next
+                .filter(|next| next.is_name())
+                .map_or("<anonymous>".to_string(), |next| next.text.to_string());
+            pending = Some(Pending { block: pending_function(name), depth: parens });
+        } else if token.is("=>") && !rust {
+            let name = arrow_name(tokens, index);
+            if next.is_some_and(|next| next.is("{")) {
+                pending = Some(Pending { block: pending_function(name), depth: parens });
+            } else {
+                // An expression body, taken to end on this line.
+                metrics.function_metrics.push(FunctionMetrics {
+                    name,
+                    start_line: token.line,
+                    end_line: token.line,
+                    complexity: 1,
+                    nesting: 0,
+                });
+            }
+        } else if !rust
+            && blocks.last() == Some(&Block::Class)
+            && parens == 0
+            && token.is_name()
+            && next.is_some_and(|next| next.is("("))
+            && !previous.is_some_and(|previous| previous.kind == TokenKind::Operator || previous.is("."))
+        {
+            pending = Some(Pending { block: pending_function(token.text.to_string()), depth: parens });
+        } else if token.is_one_of(control) {
+            // Not one in a function's signature, as in a default argument.
+            if !matches!(&pending, Some(Pending { block: PendingBlock::Function { .. }, .. })) {
+                pending = Some(Pending { block: PendingBlock::Control, depth: parens });
+            }
+        } else if token.is("(") || token.is("[") {
+            parens += 1;
+        } else if token.is(")") || token.is("]") {
+            parens = parens.saturating_sub(1);
+        } else if token.is(";") && pending.as_ref().is_some_and(|pending| pending.depth == parens) {
+            // A declaration without a body.
+            pending = None;
+        } else if token.is("{") {
+            let block = match pending.take().filter(|pending| pending.depth == parens).map(|pending| pending.block) {
+                Some(PendingBlock::Function { name, line }) => {
+                    metrics.function_metrics.push(FunctionMetrics {
+                        name,
+                        start_line: line,
+                        end_line: line,
+                        complexity: 1,
+                        nesting: 0,
+                    });
+                    Block::Function(metrics.function_metrics.len() - 1)
+                }
+                Some(PendingBlock::Class) => Block::Class,
+                Some(PendingBlock::Control) => {
+                    let depth = blocks
+                        .iter()
+                        .rev()
+                        .take_while(|block| !matches!(block, Block::Function(_)))
+                        .filter(|block| **block == Block::Control)
+                        .count()
+                        + 1;
+                    if let Some(function) = function_of(&blocks) {
+                        let function = &mut metrics.function_metrics[function];
+                        function.nesting = function.nesting.max(depth);
+                    }
+                    Block::Control
+                }
+                None => Block::Other,
+            };
+            blocks.push(block);
+        } else if token.is("}") {
+            // Whatever was pending had no body.
+            if pending.as_ref().is_some_and(|pending| pending.depth == parens) {
+                pending = None;
+            }
+            if let Some(Block::Function(function)) = blocks.pop() {
+                metrics.function_metrics[function].end_line = token.line;
+            }
+        }
+        if token.is_one_of(branches) {
+            if let Some(function) = function_of(&blocks) {
+                metrics.function_metrics[function].complexity += 1;
+            }
+        }
+    }
+    // Functions left open at the end run to it.
+    for block in blocks {
+        if let Block::Function(function) = block {
+            metrics.function_metrics[function].end_line = line_count.saturating_sub(1);
+        }
+    }
+}
+
+/// The name an arrow function at `tokens[arrow]` is assigned to, as in
+/// `const name = (...) =>` or `name: (...) =>`.
+fn arrow_name(tokens: &[Tok], arrow: usize) -> String {
+    // Back over the parameters, a lone name or a parenthesized list.
+    let mut index = arrow.checked_sub(1);
+    if index.is_some_and(|index| tokens[index].is(")")) {
+        let mut depth = 0usize;
+        while let Some(at) = index {
+            let token = tokens[at];
+            index = at.checked_sub(1);
+            if token.is(")") {
+                depth += 1;
+            } else if token.is("(") {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+    } else {
+        index = index.and_then(|index| index.checked_sub(1));
+    }
+    if index.is_some_and(|index| tokens[index].is("async")) {
+        index = index.and_then(|index| index.checked_sub(1));
+    }
+    index
+        .filter(|&index| index > 0 && (tokens[index].is("=") || tokens[index].is(":")))
+        .map(|index| tokens[index - 1])
+        .filter(Tok::is_name)
+        .map_or("<anonymous>".to_string(), |token| token.text.to_string())
+}
+
+/// Functions, classes, imports and nesting in Python, whose blocks are
+/// indented.
+fn analyze_python(lines: &[&str], tokens: &[Tok], metrics: &mut CodeMetrics) {
+    let branches = branches(Language::Python);
+    let control = control_keywords(Language::Python);
+    // Open blocks with the indentation of the line that opened them.
+    let mut blocks: Vec<(Block, usize)> = Vec::new();
+    let mut last_code_line = 0;
+    let function_of = |blocks: &[(Block, usize)]| {
+        blocks.iter().rev().find_map(|(block, _)| match block {
+            Block::Function(index) => Some(*index),
+            _ => None,
+        })
+    };
+    for statement in statements(tokens) {
+        let line = statement[0].line;
+        let indent = lines[line].len() - lines[line].trim_start().len();
+        while blocks.last().is_some_and(|(_, opened)| *opened >= indent) {
+            if let Some((Block::Function(function), _)) = blocks.pop() {
+                metrics.function_metrics[function].end_line = last_code_line;
+            }
+        }
+        last_code_line = statement[statement.len() - 1].line;
+        let first = statement.iter().position(|token| !token.is("async")).map(|index| statement[index]);
+        let Some(first) = first else {
+            continue;
+        };
+        if first.is("import") || first.is("from") {
+            metrics.imports += 1;
+        }
+        for token in statement {
+            if token.is_one_of(branches) {
+                if let Some(function) = function_of(&blocks) {
+                    metrics.function_metrics[function].complexity += 1;
+                }
+            }
+        }
+        let opens_block = statement.last().is_some_and(|token| token.is(":"));
+        if first.is("def") {
+            let name = statement
+                .iter()
+                .skip_while(|token| !token.is("def"))
+                .nth(1)
+                .filter(|token| token.is_name())
+                .map_or("<anonymous>".to_string(), |token| token.text.to_string());
+            metrics.function_metrics.push(FunctionMetrics {
+                name,
+                start_line: line,
+                end_line: line,
+                complexity: 1,
+                nesting: 0,
+            });
+            blocks.push((Block::Function(metrics.function_metrics.len() - 1), indent));
+        } else if first.is("class") {
+            metrics.classes += 1;
+            blocks.push((Block::Class, indent));
+        } else if opens_block && first.is_one_of(control) {
+            let depth = blocks
+                .iter()
+                .rev()
+                .take_while(|(block, _)| !matches!(block, Block::Function(_)))
+                .filter(|(block, _)| *block == Block::Control)
+                .count()
+                + 1;
+            if let Some(function) = function_of(&blocks) {
+                let function = &mut metrics.function_metrics[function];
+                function.nesting = function.nesting.max(depth);
+            }
+            blocks.push((Block::Control, indent));
+        } else if opens_block {
+            blocks.push((Block::Other, indent));
+        }
+    }
+    for (block, _) in blocks {
+        if let Block::Function(function) = block {
+            metrics.function_metrics[function].end_line = last_code_line;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn functions(metrics: &CodeMetrics) -> Vec<(&str, usize, usize, usize, usize)> {
+        metrics
+            .function_metrics
+            .iter()
+            .map(|function| {
+                (function.name.as_str(), function.start_line, function.end_line, function.complexity, function.nesting)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rust_functions_and_items() {
+        let code = concat!(
+            "use std::fmt;\n",
+            "\n",
+            "// A point.\n",
+            "struct Point { x: i32, y: i32 }\n",
+            "\n",
+            "impl Point {\n",
+            "    fn sign(&self) -> i32 {\n",
+            "        if self.x > 0 && self.y > 0 {\n",
+            "            for _ in 0..2 { if self.x == 1 { return 2; } }\n",
+            "            1\n",
+            "        } else {\n",
+            "            0\n",
+            "        }\n",
+            "    }\n",
+            "}\n",
+            "\n",
+            "fn declared();\n",
+        );
+        let metrics = analyze_code(code, "rust");
+        let lines = (metrics.lines, metrics.non_empty_lines, metrics.comment_lines, metrics.code_lines);
+        assert_eq!(lines, (18, 14, 1, 13));
+        assert_eq!((metrics.classes, metrics.imports, metrics.functions), (1, 1, 1));
+        assert_eq!(metrics.complexity, 5);
+        assert_eq!(functions(&metrics), [("sign", 6, 13, 5, 3)]);
+    }
+
+    #[test]
+    fn fn_pointer_types_are_not_functions() {
+        let code = concat!(
+            "struct S { f: fn(u8) -> u8 }\n",
+            "impl S {\n",
+            "    fn call(&self, g: fn(u8) -> u8) -> u8 { (self.f)(g(1)) }\n",
+            "}\n",
+            "type Callback = fn();\n",
+            "mod m { }\n",
+        );
+        let metrics = analyze_code(code, "rs");
+        assert_eq!(functions(&metrics), [("call", 2, 2, 1, 0)]);
+    }
+
+    #[test]
+    fn typescript_functions() {
+        let code = concat!(
+            "import { a } from './a';\n",
+            "function outer(x) {\n",
+            "  const inner = (y) => {\n",
+            "    return y ?? x;\n",
+            "  };\n",
+            "  return x > 0 ? inner(x) : 0;\n",
+            "}\n",
+            "const double = (n) => n * 2;\n",
+            "class Counter {\n",
+            "  count(items) {\n",
+            "    try { for (const item of items) { if (item) { this.n++; } } } catch (e) {}\n",
+            "  }\n",
+            "}\n",
+            "const later = import('./later');\n",
+        );
+        let metrics = analyze_code(code, "typescript");
+        assert_eq!((metrics.classes, metrics.imports), (1, 1));
+        assert_eq!(
+            functions(&metrics),
+            [("outer", 1, 6, 2, 0), ("inner", 2, 4, 2, 0), ("double", 7, 7, 1, 0), ("count", 9, 11, 4, 3)]
+        );
+    }
+
+    #[test]
+    fn python_functions() {
+        let code = concat!(
+            "import os\n",
+            "from sys import argv\n",
+            "\n",
+            "class Tool:\n",
+            "    def run(self, args):\n",
+            "        if args and args[0]:\n",
+            "            for arg in args:\n",
+            "                print(arg)\n",
+            "        return len(\n",
+            "            args)\n",
+            "\n",
+            "def main():\n",
+            "    pass\n",
+        );
+        let metrics = analyze_code(code, "py");
+        assert_eq!((metrics.classes, metrics.imports, metrics.functions), (1, 2, 2));
+        assert_eq!(functions(&metrics), [("run", 4, 9, 4, 2), ("main", 11, 12, 1, 0)]);
+    }
+
+    #[test]
+    fn strings_and_comments_do_not_count() {
+        let rust = concat!(
+            "fn f() -> &'static str {\n",
+            "    // if a && b { fn g() {} }\n",
+            "    /* while x || y */\n",
+            "    \"if x { fn h() {} } && y\"\n",
+            "}\n",
+        );
+        let metrics = analyze_code(rust, "rust");
+        assert_eq!((metrics.complexity, metrics.comment_lines), (1, 2));
+        assert_eq!(functions(&metrics), [("f", 0, 4, 1, 0)]);
+
+        let typescript = "const s = 'if (a) { function g() {} }'; // class C {}\n";
+        let metrics = analyze_code(typescript, "ts");
+        assert_eq!((metrics.complexity, metrics.classes, metrics.functions, metrics.comment_lines), (1, 0, 0, 0));
+
+        let python = "x = \"if a and b\"  # def f(): pass\n";
+        let metrics = analyze_code(python, "python");
+        assert_eq!((metrics.complexity, metrics.functions), (1, 0));
+    }
+}
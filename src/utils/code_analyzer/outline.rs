@@ -0,0 +1,484 @@
+//! The symbols a file declares, nested as in the source, for the outline
+//! and the editor's breadcrumbs.
+
+use std::ops::Range;
+
+use super::{language_named, lex, statements, Tok};
+use crate::utils::syntax::{Language, TokenKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Module,
+    Struct,
+    Enum,
+    Trait,
+    Impl,
+    Class,
+    Interface,
+    Function,
+    Method,
+    Constant,
+}
+
+impl SymbolKind {
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Module => "⧉",
+            Self::Struct | Self::Class => "◆",
+            Self::Enum => "◈",
+            Self::Trait | Self::Interface => "◇",
+            Self::Impl => "▣",
+            Self::Function | Self::Method => "ƒ",
+            Self::Constant => "π",
+        }
+    }
+
+    /// Whether methods and constants declared directly inside are listed.
+    fn has_members(self) -> bool {
+        matches!(self, Self::Module | Self::Impl | Self::Trait | Self::Class | Self::Interface)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The whole declaration, in bytes.
+    pub range: Range<usize>,
+    /// The name, selected when going to the symbol.
+    pub name_range: Range<usize>,
+    pub children: Vec<Symbol>,
+}
+
+impl Symbol {
+    fn new(name: &Tok, kind: SymbolKind, start: usize) -> Self {
+        Self {
+            name: name.text.to_string(),
+            kind,
+            range: start..name.end(),
+            name_range: name.offset..name.end(),
+            children: Vec::new(),
+        }
+    }
+}
+
+/// The symbols declared in `code`, written in `language` (by name or
+/// extension). Rust, TypeScript and Python are understood.
+pub fn outline(code: &str, language: &str) -> Vec<Symbol> {
+    let language = language_named(language);
+    let (lines, _, tokens) = lex(code, language);
+    match language {
+        Language::Rust | Language::TypeScript | Language::Tsx => brace_outline(language, code, &tokens),
+        Language::Python => python_outline(&lines, &tokens),
+        _ => Vec::new(),
+    }
+}
+
+/// The symbols whose declaration holds `offset`, outermost first.
+pub fn symbols_at(symbols: &[Symbol], offset: usize) -> Vec<&Symbol> {
+    let mut path = Vec::new();
+    let mut level = symbols;
+    while let Some(symbol) = level.iter().find(|symbol| symbol.range.contains(&offset) || symbol.range.end == offset) {
+        path.push(symbol);
+        level = &symbol.children;
+    }
+    path
+}
+
+/// A block being read: the symbol it declares, if any, and the symbols
+/// found in it so far.
+#[derive(Default)]
+struct Frame {
+    symbol: Option<Symbol>,
+    children: Vec<Symbol>,
+}
+
+impl Frame {
+    fn kind(&self) -> Option<SymbolKind> {
+        self.symbol.as_ref().map(|symbol| symbol.kind)
+    }
+
+    /// Closes the block at `end`, adding what it declares to `parent`.
+    fn close(self, end: usize, parent: &mut Frame) {
+        match self.symbol {
+            Some(mut symbol) => {
+                symbol.range.end = end;
+                symbol.children = self.children;
+                parent.children.push(symbol);
+            }
+            None => parent.children.extend(self.children),
+        }
+    }
+}
+
+/// A declaration waiting for its body, or for the `;` or line end that
+/// shows it has none. It was read `frames` blocks and `parens` brackets
+/// deep.
+struct Pending {
+    symbol: Symbol,
+    frames: usize,
+    parens: usize,
+}
+
+impl Pending {
+    fn is_at(&self, frames: usize, parens: usize) -> bool {
+        self.frames == frames && self.parens == parens
+    }
+}
+
+/// The outline of Rust or TypeScript, whose blocks are braces.
+fn brace_outline(language: Language, code: &str, tokens: &[Tok]) -> Vec<Symbol> {
+    let rust = language == Language::Rust;
+    let mut frames = vec![Frame::default()];
+    let mut pending: Option<Pending> = None;
+    let mut parens = 0usize;
+    for (index, token) in tokens.iter().enumerate() {
+        let next = tokens.get(index + 1);
+        let previous = index.checked_sub(1).map(|index| tokens[index]);
+        let parent = frames.last().and_then(Frame::kind);
+        // TypeScript may leave out the `;` ending a constant.
+        if let (Some(waiting), Some(previous)) = (&pending, previous) {
+            let ended = waiting.symbol.kind == SymbolKind::Constant
+                && waiting.is_at(frames.len(), parens)
+                && token.line > previous.line
+                && !(previous.kind == TokenKind::Operator || previous.is(",") || previous.is("."))
+                && !(token.kind == TokenKind::Operator || token.is("."));
+            if ended {
+                declare(&mut frames, pending.take(), previous.end());
+            }
+        }
+        let named = |kind: SymbolKind| {
+            next.filter(|next| next.is_name() || (kind == SymbolKind::Module && next.kind == TokenKind::String))
+                .map(|name| Symbol::new(name, kind, token.offset))
+        };
+        let declared = if token.kind == TokenKind::Keyword {
+            match token.text {
+                "mod" if rust => named(SymbolKind::Module),
+                "namespace" if !rust => named(SymbolKind::Module),
+                "struct" | "union" if rust => named(SymbolKind::Struct),
+                "enum" => named(SymbolKind::Enum),
+                "trait" if rust => named(SymbolKind::Trait),
+                "interface" if !rust => named(SymbolKind::Interface),
+                "class" if !rust => named(SymbolKind::Class),
+                // `impl` in a type, as in `-> impl Iterator`, is no item.
+                "impl" if rust && parens == 0 && pending.is_none() && starts_item(previous.as_ref()) => {
+                    impl_name(code, tokens, index + 1).map(|(name, name_range)| Symbol {
+                        name,
+                        kind: SymbolKind::Impl,
+                        range: token.offset..name_range.end,
+                        name_range,
+                        children: Vec::new(),
+                    })
+                }
+                "fn" | "function" => {
+                    let kind = if parent.is_some_and(|kind| matches!(kind, SymbolKind::Impl | SymbolKind::Trait)) {
+                        SymbolKind::Method
+                    } else {
+                        SymbolKind::Function
+                    };
+                    match (named(kind), pending.as_mut()) {
+                        (Some(symbol), _) => Some(symbol),
+                        // `const name = function () {...}`
+                        (None, Some(waiting))
+                            if waiting.symbol.kind == SymbolKind::Constant && waiting.is_at(frames.len(), parens) =>
+                        {
+                            waiting.symbol.kind = SymbolKind::Function;
+                            None
+                        }
+                        (None, _) => None,
+                    }
+                }
+                "const" | "static" if parent.is_none_or(SymbolKind::has_members) => {
+                    let name = if next.is_some_and(|next| next.is("mut")) { tokens.get(index + 2) } else { next };
+                    name.filter(|name| name.is_name())
+                        .map(|name| Symbol::new(name, SymbolKind::Constant, token.offset))
+                }
+                _ => None,
+            }
+        } else if !rust
+            && matches!(parent, Some(SymbolKind::Class | SymbolKind::Interface))
+            && parens == 0
+            && token.is_name()
+            && next.is_some_and(|next| next.is("("))
+            && !previous.is_some_and(|previous| previous.kind == TokenKind::Operator || previous.is("."))
+        {
+            let start = previous.filter(|previous| previous.is("get") || previous.is("set")).unwrap_or(*token).offset;
+            Some(Symbol::new(token, SymbolKind::Method, start))
+        } else {
+            None
+        };
+        if let Some(symbol) = declared {
+            pending = Some(Pending {
+                symbol,
+                frames: frames.len(),
+                parens,
+            });
+            continue;
+        }
+        if token.is("(") || token.is("[") {
+            parens += 1;
+        } else if token.is(")") || token.is("]") {
+            parens = parens.saturating_sub(1);
+        } else if token.is("=>") && next.is_some_and(|next| next.is("{")) {
+            // `const name = (...) => {...}`
+            if let Some(waiting) = pending.as_mut().filter(|waiting| waiting.is_at(frames.len(), parens)) {
+                if waiting.symbol.kind == SymbolKind::Constant {
+                    waiting.symbol.kind = SymbolKind::Function;
+                }
+            }
+        } else if token.is(";") && pending.as_ref().is_some_and(|waiting| waiting.is_at(frames.len(), parens)) {
+            declare(&mut frames, pending.take(), token.end());
+        } else if token.is("{") {
+            // A constant's value may hold braces; other declarations open
+            // their body.
+            let opens = pending.as_ref().is_some_and(|waiting| {
+                waiting.is_at(frames.len(), parens) && waiting.symbol.kind != SymbolKind::Constant
+            });
+            let symbol = if opens { pending.take().map(|waiting| waiting.symbol) } else { None };
+            frames.push(Frame {
+                symbol,
+                children: Vec::new(),
+            });
+        } else if token.is("}") && frames.len() > 1 {
+            // The last member may have no `;`, as in `{ area(): number }`.
+            if pending.as_ref().is_some_and(|waiting| waiting.frames == frames.len()) {
+                declare(&mut frames, pending.take(), previous.map_or(token.offset, |previous| previous.end()));
+            }
+            if let Some(frame) = frames.pop() {
+                frame.close(token.end(), frames.last_mut().expect("the file's frame is never closed"));
+            }
+        }
+    }
+    let end = tokens.last().map_or(0, Tok::end);
+    if pending.as_ref().is_some_and(|waiting| waiting.frames == frames.len()) {
+        declare(&mut frames, pending.take(), end);
+    }
+    // Blocks left open run to the end of the file.
+    while frames.len() > 1 {
+        if let Some(frame) = frames.pop() {
+            frame.close(code.len(), frames.last_mut().expect("the file's frame is never closed"));
+        }
+    }
+    frames.pop().map(|frame| frame.children).unwrap_or_default()
+}
+
+/// Whether a Rust item may start after `previous`: at the start of the
+/// file or of a block, after another item or an attribute, or after
+/// `unsafe`.
+fn starts_item(previous: Option<&Tok>) -> bool {
+    previous.is_none_or(|previous| {
+        previous.kind == TokenKind::Attribute || ["{", "}", ";", "]", "unsafe"].iter().any(|text| previous.is(text))
+    })
+}
+
+/// Adds a declaration without a body, ending at `end`, to the innermost
+/// block.
+fn declare(frames: &mut [Frame], pending: Option<Pending>, end: usize) {
+    if let (Some(pending), Some(frame)) = (pending, frames.last_mut()) {
+        let mut symbol = pending.symbol;
+        symbol.range.end = end;
+        frame.children.push(symbol);
+    }
+}
+
+/// The name of the Rust `impl` whose generics or type start at
+/// `tokens[start]`: what it is for, as in `Display for Point`, and where
+/// that is.
+fn impl_name(code: &str, tokens: &[Tok], start: usize) -> Option<(String, Range<usize>)> {
+    let mut index = start;
+    // Skip the impl's own generics, as in `impl<T: Into<u8>>`.
+    if tokens.get(index).is_some_and(|token| token.is("<")) {
+        let mut depth = 0isize;
+        while let Some(token) = tokens.get(index) {
+            index += 1;
+            depth += match token.text {
+                "<" => 1,
+                ">" => -1,
+                ">>" => -2,
+                _ => 0,
+            };
+            if depth <= 0 {
+                break;
+            }
+        }
+    }
+    let first = *tokens.get(index)?;
+    let last = tokens[index..]
+        .iter()
+        .take_while(|token| !(token.is("{") || token.is(";") || token.is("where")))
+        .last()?;
+    let name = code[first.offset..last.end()].split_whitespace().collect::<Vec<_>>().join(" ");
+    Some((name, first.offset..last.end()))
+}
+
+/// The outline of Python, whose blocks are indented.
+fn python_outline(lines: &[&str], tokens: &[Tok]) -> Vec<Symbol> {
+    // Open blocks, with the indentation of the line that opened them.
+    let mut frames = vec![(None, Frame::default())];
+    let mut end = 0;
+    for statement in statements(tokens) {
+        let line = lines[statement[0].line];
+        let indent = line.len() - line.trim_start().len();
+        while frames.len() > 1 && frames.last().is_some_and(|(opened, _)| *opened >= Some(indent)) {
+            if let Some((_, frame)) = frames.pop() {
+                frame.close(end, &mut frames.last_mut().expect("the file's frame is never closed").1);
+            }
+        }
+        let last = statement[statement.len() - 1];
+        end = last.end();
+        let opens_block = last.is(":");
+        let parent = frames.last().and_then(|(_, frame)| frame.kind());
+        let Some(start) = statement.iter().position(|token| !token.is("async")) else {
+            continue;
+        };
+        let first = &statement[start];
+        let name = statement.get(start + 1);
+        let symbol = if first.is("def") {
+            let kind = if parent == Some(SymbolKind::Class) { SymbolKind::Method } else { SymbolKind::Function };
+            name.filter(|name| name.is_name()).map(|name| Symbol::new(name, kind, statement[0].offset))
+        } else if first.is("class") {
+            name.filter(|name| name.is_name())
+                .map(|name| Symbol::new(name, SymbolKind::Class, statement[0].offset))
+        } else if parent.is_none_or(|kind| kind == SymbolKind::Class)
+            && is_constant_name(first)
+            && name.is_some_and(|name| name.is("=") || name.is(":"))
+        {
+            let mut symbol = Symbol::new(first, SymbolKind::Constant, first.offset);
+            symbol.range.end = end;
+            frames.last_mut().expect("the file's frame is never closed").1.children.push(symbol);
+            continue;
+        } else {
+            None
+        };
+        match symbol {
+            Some(symbol) if opens_block => frames.push((
+                Some(indent),
+                Frame {
+                    symbol: Some(symbol),
+                    children: Vec::new(),
+                },
+            )),
+            // A one-line definition, as in `def f(): return 1`.
+            Some(mut symbol) => {
+                symbol.range.end = end;
+                frames.last_mut().expect("the file's frame is never closed").1.children.push(symbol);
+            }
+            None if opens_block => frames.push((Some(indent), Frame::default())),
+            None => {}
+        }
+    }
+    while frames.len() > 1 {
+        if let Some((_, frame)) = frames.pop() {
+            frame.close(end, &mut frames.last_mut().expect("the file's frame is never closed").1);
+        }
+    }
+    frames.pop().map(|(_, frame)| frame.children).unwrap_or_default()
+}
+
+/// Whether `token` names a constant by Python's convention, in capitals.
+fn is_constant_name(token: &Tok) -> bool {
+    token.is_name()
+        && token.text.chars().any(|c| c.is_ascii_uppercase())
+        && token.text.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each symbol as `kind name`, children indented under it.
+    fn names(code: &str, language: &str) -> Vec<String> {
+        fn walk(symbols: &[Symbol], depth: usize, out: &mut Vec<String>) {
+            for symbol in symbols {
+                out.push(format!("{}{:?} {}", "  ".repeat(depth), symbol.kind, symbol.name));
+                walk(&symbol.children, depth + 1, out);
+            }
+        }
+        let mut out = Vec::new();
+        walk(&outline(code, language), 0, &mut out);
+        out
+    }
+
+    #[test]
+    fn rust_items_nest() {
+        let code = concat!(
+            "mod shapes {\n",
+            "    pub struct Point { x: f64 }\n",
+            "    impl Point {\n",
+            "        pub const ORIGIN: f64 = 0.0;\n",
+            "        fn norm(&self) -> f64 { self.x }\n",
+            "    }\n",
+            "}\n",
+            "impl<T: Into<u8>> Display for Wrapper<T> {\n",
+            "    fn fmt(&self) {}\n",
+            "}\n",
+            "enum Kind { A, B }\n",
+            "trait Shape { fn area(&self) -> f64; }\n",
+        );
+        assert_eq!(
+            names(code, "rs"),
+            [
+                "Module shapes",
+                "  Struct Point",
+                "  Impl Point",
+                "    Constant ORIGIN",
+                "    Method norm",
+                "Impl Display for Wrapper<T>",
+                "  Method fmt",
+                "Enum Kind",
+                "Trait Shape",
+                "  Method area",
+            ]
+        );
+    }
+
+    #[test]
+    fn impl_in_a_type_is_not_an_item() {
+        let code = concat!(
+            "fn evens() -> impl Iterator<Item = u32> {\n",
+            "    (0..).step_by(2)\n",
+            "}\n",
+            "fn greet(name: impl Into<String>) {\n",
+            "    let _ = name;\n",
+            "}\n",
+            "#[cfg(test)]\n",
+            "impl Point {}\n",
+            "unsafe impl Send for Point {}\n",
+        );
+        assert_eq!(
+            names(code, "rs"),
+            ["Function evens", "Function greet", "Impl Point", "Impl Send for Point"]
+        );
+        let symbols = outline(code, "rs");
+        let evens = &code[symbols[0].range.clone()];
+        assert_eq!(evens, "fn evens() -> impl Iterator<Item = u32> {\n    (0..).step_by(2)\n}");
+    }
+
+    #[test]
+    fn typescript_and_python() {
+        let code = concat!(
+            "export class Circle {\n",
+            "  get area(): number { return 1; }\n",
+            "  scale(by: number) {}\n",
+            "}\n",
+            "const handler = () => {};\n",
+            "const LIMIT = 10\n",
+            "function main() {}\n",
+        );
+        assert_eq!(
+            names(code, "ts"),
+            ["Class Circle", "  Method area", "  Method scale", "Function handler", "Constant LIMIT", "Function main"]
+        );
+        let code = "MAX = 3\nclass Stack:\n    def push(self, item):\n        pass\n\nasync def main():\n    pass\n";
+        assert_eq!(names(code, "py"), ["Constant MAX", "Class Stack", "  Method push", "Function main"]);
+    }
+
+    #[test]
+    fn symbols_at_finds_the_enclosing_path() {
+        let code = "impl Point {\n    fn norm(&self) {}\n}\n";
+        let symbols = outline(code, "rs");
+        let offset = code.find("{}").unwrap();
+        let path: Vec<&str> = symbols_at(&symbols, offset).iter().map(|symbol| symbol.name.as_str()).collect();
+        assert_eq!(path, ["Point", "norm"]);
+    }
+}
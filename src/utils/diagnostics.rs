@@ -0,0 +1,197 @@
+//! Problems found in files. Anything that checks code (the lint engine,
+//! the security scanner, a backend linter) publishes what it found for a
+//! file under its own source name, replacing what it published before.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::editor::buffer::TextBuffer;
+use crate::utils::file_system::events::{is_within, renamed_path};
+
+/// How bad a problem is, worst first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+impl Severity {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Error => "Error",
+            Self::Warning => "Warning",
+            Self::Info => "Info",
+            Self::Hint => "Hint",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Error => "⊗",
+            Self::Warning => "⚠",
+            Self::Info => "ℹ",
+            Self::Hint => "…",
+        }
+    }
+}
+
+/// Another place a problem involves, as the first copy of duplicated code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelatedLocation {
+    pub path: String,
+    pub range: Range<usize>,
+    pub message: String,
+}
+
+/// Replacements that fix a problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuickFix {
+    pub title: String,
+    /// Byte ranges of the file and their replacements, not overlapping.
+    pub edits: Vec<(Range<usize>, String)>,
+    /// What each edit's range held in the text the fix was made for.
+    pub replaced: Vec<String>,
+}
+
+impl QuickFix {
+    /// A fix making `edits` to `text`.
+    pub fn new(title: impl Into<String>, text: &str, edits: Vec<(Range<usize>, String)>) -> Self {
+        let replaced = edits
+            .iter()
+            .map(|(range, _)| text.get(range.clone()).unwrap_or_default().to_string())
+            .collect();
+        Self {
+            title: title.into(),
+            edits,
+            replaced,
+        }
+    }
+
+    /// Whether `text` still holds what the fix replaces, where it replaces
+    /// it. Fixes are found a while after the text they are for, which may
+    /// have been edited since.
+    pub fn fits(&self, text: &TextBuffer) -> bool {
+        let on_boundary = |offset: usize| text.floor_char_boundary(offset) == offset;
+        self.edits.len() == self.replaced.len()
+            && self.edits.iter().zip(&self.replaced).all(|((range, _), replaced)| {
+                on_boundary(range.start) && on_boundary(range.end) && text.slice(range.clone()) == *replaced
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Byte range in the file's text.
+    pub range: Range<usize>,
+    pub message: String,
+    /// What found the problem, as `lint`.
+    pub source: String,
+    /// The source's name for the kind of problem, as a rule ID.
+    pub code: Option<String>,
+    pub related: Vec<RelatedLocation>,
+    pub fixes: Vec<QuickFix>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, range: Range<usize>, message: impl Into<String>, source: impl Into<String>) -> Self {
+        Self {
+            severity,
+            range,
+            message: message.into(),
+            source: source.into(),
+            code: None,
+            related: Vec::new(),
+            fixes: Vec::new(),
+        }
+    }
+}
+
+/// The diagnostics of every file, by path and then by source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiagnosticStore {
+    files: BTreeMap<String, BTreeMap<String, Vec<Diagnostic>>>,
+}
+
+impl DiagnosticStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces what `source` reported for the file at `path`.
+    pub fn publish(&mut self, path: &str, source: &str, mut diagnostics: Vec<Diagnostic>) {
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.range.start, diagnostic.severity));
+        if diagnostics.is_empty() {
+            if let Some(sources) = self.files.get_mut(path) {
+                sources.remove(source);
+                if sources.is_empty() {
+                    self.files.remove(path);
+                }
+            }
+        } else {
+            self.files.entry(path.to_string()).or_default().insert(source.to_string(), diagnostics);
+        }
+    }
+
+    /// Drops everything `source` reported, in all files.
+    pub fn clear_source(&mut self, source: &str) {
+        self.files.retain(|_, sources| {
+            sources.remove(source);
+            !sources.is_empty()
+        });
+    }
+
+    /// Drops the diagnostics of `path` and, for a folder, the files in it.
+    pub fn remove(&mut self, path: &str) {
+        self.files.retain(|file, _| !is_within(file, path));
+    }
+
+    /// Follows a rename of `from` to `to` in the workspace.
+    pub fn rename(&mut self, from: &str, to: &str) {
+        let files = std::mem::take(&mut self.files);
+        for (path, mut sources) in files {
+            for related in sources.values_mut().flatten().flat_map(|diagnostic| &mut diagnostic.related) {
+                if let Some(renamed) = renamed_path(&related.path, from, to) {
+                    related.path = renamed;
+                }
+            }
+            self.files.insert(renamed_path(&path, from, to).unwrap_or(path), sources);
+        }
+    }
+
+    /// The diagnostics of `path` from all sources, in order of position.
+    pub fn for_file(&self, path: &str) -> Vec<&Diagnostic> {
+        let mut diagnostics: Vec<&Diagnostic> =
+            self.files.get(path).into_iter().flat_map(|sources| sources.values().flatten()).collect();
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.range.start, diagnostic.severity));
+        diagnostics
+    }
+
+    /// The files with diagnostics, in path order.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    /// How many diagnostics of `severity` there are in all files.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.files
+            .values()
+            .flat_map(|sources| sources.values().flatten())
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+
+    /// How many diagnostics there are in all files.
+    pub fn len(&self) -> usize {
+        self.files.values().flat_map(BTreeMap::values).map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
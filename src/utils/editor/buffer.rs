@@ -0,0 +1,744 @@
+//! Rope text buffer behind editor tabs.
+//!
+//! Text lives in the leaves of an AVL-balanced tree; every node caches the
+//! byte, UTF-16 and newline counts of its subtree, so edits and line or
+//! offset lookups take O(log n). Edits copy only the path from the root to
+//! the changed leaf, which makes cloning a buffer O(1).
+
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Leaves are split once they grow past this many bytes.
+const MAX_LEAF: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Summary {
+    bytes: usize,
+    utf16: usize,
+    newlines: usize,
+}
+
+impl Summary {
+    fn of(text: &str) -> Self {
+        Self {
+            bytes: text.len(),
+            utf16: text.chars().map(char::len_utf16).sum(),
+            newlines: text.bytes().filter(|&b| b == b'\n').count(),
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            bytes: self.bytes + other.bytes,
+            utf16: self.utf16 + other.utf16,
+            newlines: self.newlines + other.newlines,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Leaf {
+        text: String,
+        summary: Summary,
+    },
+    Branch {
+        left: Rc<Node>,
+        right: Rc<Node>,
+        summary: Summary,
+        height: u8,
+    },
+}
+
+impl Node {
+    fn leaf(text: String) -> Rc<Node> {
+        let summary = Summary::of(&text);
+        Rc::new(Node::Leaf { text, summary })
+    }
+
+    fn branch(left: Rc<Node>, right: Rc<Node>) -> Rc<Node> {
+        Rc::new(Node::Branch {
+            summary: left.summary().add(right.summary()),
+            height: left.height().max(right.height()) + 1,
+            left,
+            right,
+        })
+    }
+
+    fn summary(&self) -> Summary {
+        match self {
+            Node::Leaf { summary, .. } | Node::Branch { summary, .. } => *summary,
+        }
+    }
+
+    fn height(&self) -> u8 {
+        match self {
+            Node::Leaf { .. } => 0,
+            Node::Branch { height, .. } => *height,
+        }
+    }
+
+    /// Children of a node known to be a branch, i.e. of height 1 or more.
+    fn children(&self) -> (&Rc<Node>, &Rc<Node>) {
+        match self {
+            Node::Branch { left, right, .. } => (left, right),
+            Node::Leaf { .. } => unreachable!("leaves have height 0"),
+        }
+    }
+}
+
+/// Splits `text` into roughly equal pieces of at most [`MAX_LEAF`] bytes.
+fn pieces(text: &str) -> Vec<&str> {
+    let count = text.len().div_ceil(MAX_LEAF).max(1);
+    let target = text.len().div_ceil(count);
+    let mut pieces = Vec::with_capacity(count);
+    let mut rest = text;
+    while rest.len() > target {
+        let mut at = target;
+        while !rest.is_char_boundary(at) {
+            at += 1;
+        }
+        if at == rest.len() {
+            break;
+        }
+        let (piece, tail) = rest.split_at(at);
+        pieces.push(piece);
+        rest = tail;
+    }
+    pieces.push(rest);
+    pieces
+}
+
+fn build(pieces: &[&str]) -> Rc<Node> {
+    match pieces {
+        [] => Node::leaf(String::new()),
+        [piece] => Node::leaf(piece.to_string()),
+        _ => {
+            let (left, right) = pieces.split_at(pieces.len() / 2);
+            Node::branch(build(left), build(right))
+        }
+    }
+}
+
+/// Concatenates two balanced trees into one, whatever their heights.
+fn join(left: Rc<Node>, right: Rc<Node>) -> Rc<Node> {
+    if left.summary().bytes == 0 {
+        return right;
+    }
+    if right.summary().bytes == 0 {
+        return left;
+    }
+    if let (Node::Leaf { text: a, .. }, Node::Leaf { text: b, .. }) = (&*left, &*right) {
+        if a.len() + b.len() <= MAX_LEAF {
+            return Node::leaf([a.as_str(), b].concat());
+        }
+    }
+    if left.height() > right.height() + 1 {
+        let (outer, inner) = left.children();
+        balance(outer.clone(), join(inner.clone(), right))
+    } else if right.height() > left.height() + 1 {
+        let (inner, outer) = right.children();
+        balance(join(left, inner.clone()), outer.clone())
+    } else {
+        Node::branch(left, right)
+    }
+}
+
+/// Joins two subtrees whose heights differ by at most two, rotating to
+/// restore the AVL invariant.
+fn balance(left: Rc<Node>, right: Rc<Node>) -> Rc<Node> {
+    if left.height() > right.height() + 1 {
+        let (ll, lr) = left.children();
+        if ll.height() >= lr.height() {
+            Node::branch(ll.clone(), Node::branch(lr.clone(), right))
+        } else {
+            let (lrl, lrr) = lr.children();
+            Node::branch(Node::branch(ll.clone(), lrl.clone()), Node::branch(lrr.clone(), right))
+        }
+    } else if right.height() > left.height() + 1 {
+        let (rl, rr) = right.children();
+        if rr.height() >= rl.height() {
+            Node::branch(Node::branch(left, rl.clone()), rr.clone())
+        } else {
+            let (rll, rlr) = rl.children();
+            Node::branch(Node::branch(left, rll.clone()), Node::branch(rlr.clone(), rr.clone()))
+        }
+    } else {
+        Node::branch(left, right)
+    }
+}
+
+fn insert(node: &Rc<Node>, offset: usize, text: &str) -> Rc<Node> {
+    match &**node {
+        Node::Leaf { text: leaf, .. } => {
+            let combined = [&leaf[..offset], text, &leaf[offset..]].concat();
+            if combined.len() <= MAX_LEAF {
+                Node::leaf(combined)
+            } else {
+                build(&pieces(&combined))
+            }
+        }
+        Node::Branch { left, right, .. } => {
+            let split = left.summary().bytes;
+            if offset <= split {
+                join(insert(left, offset, text), right.clone())
+            } else {
+                join(left.clone(), insert(right, offset - split, text))
+            }
+        }
+    }
+}
+
+fn delete(node: &Rc<Node>, range: Range<usize>) -> Rc<Node> {
+    if range.is_empty() {
+        return node.clone();
+    }
+    match &**node {
+        Node::Leaf { text, .. } => Node::leaf([&text[..range.start], &text[range.end..]].concat()),
+        Node::Branch { left, right, .. } => {
+            let split = left.summary().bytes;
+            let left = if range.start < split {
+                delete(left, range.start..range.end.min(split))
+            } else {
+                left.clone()
+            };
+            let right = if range.end > split {
+                delete(right, range.start.max(split) - split..range.end - split)
+            } else {
+                right.clone()
+            };
+            join(left, right)
+        }
+    }
+}
+
+fn collect(node: &Node, range: Range<usize>, out: &mut String) {
+    match node {
+        Node::Leaf { text, .. } => out.push_str(&text[range]),
+        Node::Branch { left, right, .. } => {
+            let split = left.summary().bytes;
+            if range.start < split {
+                collect(left, range.start..range.end.min(split), out);
+            }
+            if range.end > split {
+                collect(right, range.start.max(split) - split..range.end - split, out);
+            }
+        }
+    }
+}
+
+/// How many bytes two texts, given as chunks, have in common at their
+/// start, or with `from_end` at their end (chunks then come last first).
+/// Equal stretches are compared a slice at a time rather than byte by byte.
+fn common_len<'a>(
+    mut ours: impl Iterator<Item = &'a str>,
+    mut theirs: impl Iterator<Item = &'a str>,
+    from_end: bool,
+) -> usize {
+    let (mut a, mut b): (&[u8], &[u8]) = (&[], &[]);
+    let mut len = 0;
+    loop {
+        if a.is_empty() {
+            match ours.next() {
+                Some(chunk) => a = chunk.as_bytes(),
+                None => return len,
+            }
+        }
+        if b.is_empty() {
+            match theirs.next() {
+                Some(chunk) => b = chunk.as_bytes(),
+                None => return len,
+            }
+        }
+        let n = a.len().min(b.len());
+        let (ours_part, theirs_part, a_rest, b_rest) = if from_end {
+            let (a_rest, a_part) = a.split_at(a.len() - n);
+            let (b_rest, b_part) = b.split_at(b.len() - n);
+            (a_part, b_part, a_rest, b_rest)
+        } else {
+            let (a_part, a_rest) = a.split_at(n);
+            let (b_part, b_rest) = b.split_at(n);
+            (a_part, b_part, a_rest, b_rest)
+        };
+        if ours_part != theirs_part {
+            let pairs = ours_part.iter().zip(theirs_part);
+            let equal = if from_end {
+                pairs.rev().take_while(|(x, y)| x == y).count()
+            } else {
+                pairs.take_while(|(x, y)| x == y).count()
+            };
+            return len + equal;
+        }
+        len += n;
+        (a, b) = (a_rest, b_rest);
+    }
+}
+
+/// A zero-based line and column. Columns count chars.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+/// Editable text addressed by byte offsets, like `String`. Offsets passed
+/// to edits must lie on char boundaries; lookups clamp out-of-range input.
+#[derive(Clone)]
+pub struct TextBuffer {
+    root: Rc<Node>,
+}
+
+impl TextBuffer {
+    pub fn new() -> Self {
+        Self {
+            root: Node::leaf(String::new()),
+        }
+    }
+
+    /// Length in bytes.
+    pub fn len(&self) -> usize {
+        self.root.summary().bytes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Length in UTF-16 code units, as the DOM counts it.
+    pub fn len_utf16(&self) -> usize {
+        self.root.summary().utf16
+    }
+
+    /// Number of lines; text ending in a newline has an empty last line.
+    pub fn line_count(&self) -> usize {
+        self.root.summary().newlines + 1
+    }
+
+    pub fn insert(&mut self, offset: usize, text: &str) {
+        self.assert_char_boundary(offset);
+        if !text.is_empty() {
+            self.root = insert(&self.root, offset, text);
+        }
+    }
+
+    pub fn delete(&mut self, range: Range<usize>) {
+        assert!(range.start <= range.end, "range start {} is after its end {}", range.start, range.end);
+        self.assert_char_boundary(range.start);
+        self.assert_char_boundary(range.end);
+        self.root = delete(&self.root, range);
+    }
+
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        let start = range.start;
+        self.delete(range);
+        self.insert(start, text);
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> String {
+        let end = range.end.min(self.len());
+        let start = range.start.min(end);
+        let mut out = String::with_capacity(end - start);
+        collect(&self.root, start..end, &mut out);
+        out
+    }
+
+    /// The text in order, as stored.
+    pub fn chunks(&self) -> Chunks<'_> {
+        Chunks { stack: vec![&self.root] }
+    }
+
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.chunks().flat_map(str::chars)
+    }
+
+    /// Whether `other` is this buffer itself, rather than one with equal
+    /// text: a clone that has not been edited since.
+    pub fn is_same(&self, other: &TextBuffer) -> bool {
+        Rc::ptr_eq(&self.root, &other.root)
+    }
+
+    /// Length in bytes of the longest common prefix of `self` and `other`.
+    pub fn common_prefix_len(&self, other: &TextBuffer) -> usize {
+        if self.is_same(other) {
+            return self.len();
+        }
+        common_len(self.chunks(), other.chunks(), false)
+    }
+
+    /// Length in bytes of the longest common suffix of `self` and `other`.
+    pub fn common_suffix_len(&self, other: &TextBuffer) -> usize {
+        if self.is_same(other) {
+            return self.len();
+        }
+        let ours: Vec<&str> = self.chunks().collect();
+        let theirs: Vec<&str> = other.chunks().collect();
+        common_len(ours.into_iter().rev(), theirs.into_iter().rev(), true)
+    }
+
+    /// Offset of the first character of `line`; past the last line, the
+    /// end of the text.
+    pub fn line_start(&self, line: usize) -> usize {
+        if line == 0 {
+            return 0;
+        }
+        if line >= self.line_count() {
+            return self.len();
+        }
+        let (leaf, newlines, before) = self.seek(|summary| summary.newlines, line - 1);
+        let index = leaf.match_indices('\n').nth(newlines).map_or(leaf.len(), |(i, _)| i + 1);
+        before.bytes + index
+    }
+
+    /// The byte range of `line`, without its newline.
+    pub fn line_range(&self, line: usize) -> Range<usize> {
+        let start = self.line_start(line);
+        let end = if line + 1 < self.line_count() {
+            self.line_start(line + 1) - 1
+        } else {
+            self.len()
+        };
+        start..end
+    }
+
+    pub fn line(&self, line: usize) -> String {
+        self.slice(self.line_range(line))
+    }
+
+    pub fn offset_to_line(&self, offset: usize) -> usize {
+        let (leaf, index, before) = self.seek_offset(offset);
+        before.newlines + leaf[..index].bytes().filter(|&b| b == b'\n').count()
+    }
+
+    pub fn offset_to_position(&self, offset: usize) -> Position {
+        let offset = self.floor_char_boundary(offset);
+        let line = self.offset_to_line(offset);
+        let column = self.slice(self.line_start(line)..offset).chars().count();
+        Position { line, column }
+    }
+
+    /// Offset of `position`, clamped to the end of its line and to the last
+    /// line.
+    pub fn position_to_offset(&self, position: Position) -> usize {
+        let line = position.line.min(self.line_count() - 1);
+        let range = self.line_range(line);
+        let text = self.slice(range.clone());
+        let column = text.char_indices().nth(position.column).map_or(text.len(), |(i, _)| i);
+        range.start + column
+    }
+
+    pub fn offset_to_utf16(&self, offset: usize) -> usize {
+        let offset = self.floor_char_boundary(offset);
+        let (leaf, index, before) = self.seek_offset(offset);
+        before.utf16 + leaf[..index].encode_utf16().count()
+    }
+
+    /// Byte offset of a DOM (UTF-16) index. An index inside a surrogate
+    /// pair maps to the start of its character.
+    pub fn utf16_to_offset(&self, units: usize) -> usize {
+        let units = units.min(self.len_utf16());
+        let (leaf, mut remaining, before) = self.seek(|summary| summary.utf16, units);
+        let mut index = 0;
+        for c in leaf.chars() {
+            if remaining < c.len_utf16() {
+                break;
+            }
+            remaining -= c.len_utf16();
+            index += c.len_utf8();
+        }
+        before.bytes + index
+    }
+
+    /// The largest char boundary not after `offset`.
+    pub fn floor_char_boundary(&self, offset: usize) -> usize {
+        let offset = offset.min(self.len());
+        let (leaf, mut index, before) = self.seek_offset(offset);
+        while !leaf.is_char_boundary(index) {
+            index -= 1;
+        }
+        before.bytes + index
+    }
+
+    /// Start of the char before `offset`, or 0.
+    pub fn prev_char_boundary(&self, offset: usize) -> usize {
+        match offset.min(self.len()) {
+            0 => 0,
+            offset => self.floor_char_boundary(offset - 1),
+        }
+    }
+
+    /// End of the char at `offset`, or the end of the text.
+    pub fn next_char_boundary(&self, offset: usize) -> usize {
+        let offset = self.floor_char_boundary(offset);
+        let char_len = self
+            .slice(offset..self.floor_char_boundary(offset + 4))
+            .chars()
+            .next()
+            .map_or(0, char::len_utf8);
+        offset + char_len
+    }
+
+    fn assert_char_boundary(&self, offset: usize) {
+        assert!(offset <= self.len(), "offset {} is past the end of the text ({})", offset, self.len());
+        let (leaf, index, _) = self.seek_offset(offset);
+        assert!(leaf.is_char_boundary(index), "offset {} is not a char boundary", offset);
+    }
+
+    fn seek_offset(&self, offset: usize) -> (&str, usize, Summary) {
+        self.seek(|summary| summary.bytes, offset.min(self.len()))
+    }
+
+    /// Finds the leaf holding the `target`-th unit of the measure (or the
+    /// last leaf, for a target at the very end). Returns the leaf, the units
+    /// left within it and the summary of everything before it.
+    fn seek(&self, measure: impl Fn(Summary) -> usize, mut target: usize) -> (&str, usize, Summary) {
+        let mut node = &*self.root;
+        let mut before = Summary::default();
+        loop {
+            match node {
+                Node::Leaf { text, .. } => return (text, target, before),
+                Node::Branch { left, right, .. } => {
+                    let size = measure(left.summary());
+                    if target < size {
+                        node = left;
+                    } else {
+                        target -= size;
+                        before = before.add(left.summary());
+                        node = right;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for TextBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&str> for TextBuffer {
+    fn from(text: &str) -> Self {
+        Self {
+            root: build(&pieces(text)),
+        }
+    }
+}
+
+impl From<String> for TextBuffer {
+    fn from(text: String) -> Self {
+        Self::from(text.as_str())
+    }
+}
+
+impl fmt::Display for TextBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chunks().try_for_each(|chunk| f.write_str(chunk))
+    }
+}
+
+impl fmt::Debug for TextBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+impl PartialEq for TextBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.root, &other.root)
+            || (self.len() == other.len() && self.chunks().flat_map(str::bytes).eq(other.chunks().flat_map(str::bytes)))
+    }
+}
+
+impl Eq for TextBuffer {}
+
+impl PartialEq<str> for TextBuffer {
+    fn eq(&self, other: &str) -> bool {
+        self.len() == other.len() && self.chunks().flat_map(str::bytes).eq(other.bytes())
+    }
+}
+
+impl PartialEq<&str> for TextBuffer {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl Serialize for TextBuffer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TextBuffer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+pub struct Chunks<'a> {
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        while let Some(node) = self.stack.pop() {
+            match node {
+                Node::Leaf { text, .. } if !text.is_empty() => return Some(text),
+                Node::Leaf { .. } => {}
+                Node::Branch { left, right, .. } => {
+                    self.stack.push(right);
+                    self.stack.push(left);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small xorshift generator, so the edits are the same every run.
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n.max(1) as u64) as usize
+        }
+
+        /// A char boundary of `text`.
+        fn boundary(&mut self, text: &str) -> usize {
+            let boundaries: Vec<usize> = (0..=text.len()).filter(|&i| text.is_char_boundary(i)).collect();
+            boundaries[self.below(boundaries.len())]
+        }
+
+        fn text(&mut self) -> String {
+            const PIECES: &[&str] = &["a", "bc", "é", "日本", "😀", "\n", "line\n", "\r\n"];
+            let count = if self.below(20) == 0 { 400 } else { self.below(8) };
+            (0..count).map(|_| PIECES[self.below(PIECES.len())]).collect()
+        }
+    }
+
+    /// Checks every branch's cached summary and balance.
+    fn check_tree(node: &Node) -> (Summary, u8) {
+        match node {
+            Node::Leaf { text, summary } => {
+                assert_eq!(*summary, Summary::of(text));
+                (*summary, 0)
+            }
+            Node::Branch { left, right, summary, height } => {
+                let (left, left_height) = check_tree(left);
+                let (right, right_height) = check_tree(right);
+                assert_eq!(*summary, left.add(right));
+                assert!(left_height.abs_diff(right_height) <= 1, "unbalanced: {} and {}", left_height, right_height);
+                assert_eq!(*height, left_height.max(right_height) + 1);
+                (*summary, *height)
+            }
+        }
+    }
+
+    fn check(buffer: &TextBuffer, model: &str) {
+        check_tree(&buffer.root);
+        assert_eq!(buffer.to_string(), model);
+        assert_eq!(buffer.len(), model.len());
+        assert_eq!(buffer.len_utf16(), model.encode_utf16().count());
+        let lines: Vec<&str> = model.split('\n').collect();
+        assert_eq!(buffer.line_count(), lines.len());
+        let mut start = 0;
+        for (number, line) in lines.iter().enumerate() {
+            assert_eq!(buffer.line_range(number), start..start + line.len());
+            assert_eq!(buffer.offset_to_line(start + line.len()), number);
+            start += line.len() + 1;
+        }
+        assert_eq!(buffer.line_start(lines.len()), model.len());
+    }
+
+    #[test]
+    fn edits_match_a_string() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        let mut model = String::new();
+        let mut buffer = TextBuffer::new();
+        for _ in 0..2000 {
+            let before = buffer.clone();
+            let snapshot = model.clone();
+            let start = random.boundary(&model);
+            match random.below(3) {
+                0 => {
+                    let text = random.text();
+                    model.insert_str(start, &text);
+                    buffer.insert(start, &text);
+                }
+                1 => {
+                    let end = start + random.boundary(&model[start..]);
+                    model.replace_range(start..end, "");
+                    buffer.delete(start..end);
+                }
+                _ => {
+                    let end = start + random.boundary(&model[start..]);
+                    let text = random.text();
+                    model.replace_range(start..end, &text);
+                    buffer.replace(start..end, &text);
+                }
+            }
+            check(&buffer, &model);
+            // Clones share structure but never see later edits.
+            assert_eq!(before, snapshot.as_str());
+        }
+        assert_eq!(TextBuffer::from(model.as_str()), buffer);
+    }
+
+    #[test]
+    fn offsets_and_positions() {
+        let text = "ab\né😀x\n\n日本";
+        let buffer = TextBuffer::from(text);
+        for (offset, _) in text.char_indices().chain([(text.len(), ' ')]) {
+            let position = buffer.offset_to_position(offset);
+            assert_eq!(buffer.position_to_offset(position), offset);
+            let units = text[..offset].encode_utf16().count();
+            assert_eq!(buffer.offset_to_utf16(offset), units);
+            assert_eq!(buffer.utf16_to_offset(units), offset);
+        }
+        assert_eq!(buffer.offset_to_position(6), Position::new(1, 1));
+        // Inside `😀`: its start, or clamped to the line.
+        assert_eq!(buffer.floor_char_boundary(7), 5);
+        assert_eq!(buffer.utf16_to_offset(5), 5);
+        assert_eq!(buffer.position_to_offset(Position::new(1, 99)), 10);
+        assert_eq!(buffer.position_to_offset(Position::new(9, 0)), buffer.line_start(3));
+        assert_eq!((buffer.prev_char_boundary(9), buffer.next_char_boundary(5)), (5, 9));
+        assert_eq!(buffer.slice(3..99), &text[3..]);
+    }
+
+    #[test]
+    fn common_prefix_and_suffix() {
+        let long = "x".repeat(3000);
+        let a = TextBuffer::from(format!("{}é{}", long, long).as_str());
+        let mut b = a.clone();
+        assert!(a.is_same(&b));
+        b.replace(3000..3002, "e");
+        assert!(!a.is_same(&b));
+        assert_eq!((a.common_prefix_len(&b), a.common_suffix_len(&b)), (3000, 3000));
+        assert_eq!(a.common_prefix_len(&TextBuffer::from("xx")), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a char boundary")]
+    fn edits_inside_a_char_panic() {
+        TextBuffer::from("é").insert(1, "x");
+    }
+}
@@ -0,0 +1,465 @@
+//! Language-aware editing: matching brackets, pairs typed together,
+//! indentation on Enter and comment toggling, driven by the language's
+//! [`LanguageConfig`]. Edits are worked out from the current text and
+//! selections as an [`EditPlan`] and applied separately.
+
+use std::ops::Range;
+
+use super::buffer::TextBuffer;
+use super::history::Edit;
+use super::selection::{Selection, Selections};
+use crate::utils::syntax::config::LanguageConfig;
+use crate::utils::syntax::highlighter::Highlighter;
+use crate::utils::syntax::{LineState, TokenKind};
+
+/// Replacements to make at once, and where the selections go after them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditPlan {
+    /// In document order, not overlapping.
+    edits: Vec<(Range<usize>, String)>,
+    /// The selections in the edited text, or `None` to keep the current
+    /// ones, moved by the edits.
+    selections: Option<Selections>,
+}
+
+impl EditPlan {
+    /// Makes `edits`, which must not overlap, keeping the selections.
+    pub fn new(mut edits: Vec<(Range<usize>, String)>) -> Self {
+        edits.sort_by_key(|(range, _)| range.start);
+        Self { edits, selections: None }
+    }
+
+    /// One replacement per selection, each with the new selection's anchor
+    /// and head counted from the start of its replacement.
+    fn at_selections(items: Vec<(Range<usize>, String, usize, usize)>, primary: usize) -> Self {
+        let mut shift = 0isize;
+        let mut selections = Vec::with_capacity(items.len());
+        let mut edits = Vec::with_capacity(items.len());
+        for (range, text, anchor, head) in items {
+            let start = (range.start as isize + shift) as usize;
+            selections.push(Selection::new(start + anchor, start + head));
+            shift += text.len() as isize - range.len() as isize;
+            edits.push((range, text));
+        }
+        Self {
+            edits,
+            selections: Some(Selections::new(selections, primary)),
+        }
+    }
+
+    /// Makes the edits, back to front, and returns them as applied.
+    pub fn apply(&self, selections: &mut Selections, buffer: &mut TextBuffer) -> Vec<Edit> {
+        let edits: Vec<Edit> = self
+            .edits
+            .iter()
+            .rev()
+            .filter(|(range, text)| !range.is_empty() || !text.is_empty())
+            .map(|(range, text)| {
+                let edit = Edit::replace(buffer, range.clone(), text.clone());
+                edit.apply(buffer);
+                edit
+            })
+            .collect();
+        *selections = match &self.selections {
+            Some(after) => after.clone(),
+            None => {
+                let map = |offset: usize| edits.iter().fold(offset, |offset, edit| edit.map_offset(offset));
+                let moved = selections
+                    .ranges()
+                    .iter()
+                    .map(|selection| Selection::new(map(selection.anchor), map(selection.head)))
+                    .collect();
+                Selections::new(moved, selections.primary_index())
+            }
+        };
+        edits
+    }
+}
+
+fn char_at(buffer: &TextBuffer, offset: usize) -> Option<char> {
+    buffer.slice(offset..buffer.next_char_boundary(offset)).chars().next()
+}
+
+fn char_before(buffer: &TextBuffer, offset: usize) -> Option<char> {
+    buffer.slice(buffer.prev_char_boundary(offset)..offset).chars().next()
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Byte ranges of the strings and comments on `line`.
+fn strings_and_comments(highlighter: &Highlighter, line: usize) -> impl Iterator<Item = Range<usize>> + '_ {
+    highlighter
+        .tokens(line)
+        .iter()
+        .filter(|token| matches!(token.kind, TokenKind::String | TokenKind::Comment))
+        .map(|token| token.start..token.end)
+}
+
+/// Whether the char at `offset` is code rather than part of a string or
+/// comment.
+pub fn is_code(buffer: &TextBuffer, highlighter: &Highlighter, offset: usize) -> bool {
+    let line = buffer.offset_to_line(offset);
+    let column = offset - buffer.line_start(line);
+    !strings_and_comments(highlighter, line).any(|range| range.contains(&column))
+}
+
+/// Whether a cursor at `offset` is inside a string or comment, so what is
+/// typed there becomes part of it.
+pub fn in_string_or_comment(
+    buffer: &TextBuffer,
+    highlighter: &Highlighter,
+    config: &LanguageConfig,
+    offset: usize,
+) -> bool {
+    let line = buffer.offset_to_line(offset);
+    let range = buffer.line_range(line);
+    let column = offset - range.start;
+    // At the end of the line only what runs on past it (a line comment,
+    // an unclosed string or block comment) still surrounds the cursor.
+    let open_at_end = |token: Range<usize>| {
+        let text = buffer.slice(range.start + token.start..range.start + token.end);
+        let closed = config.block_comment.is_some_and(|(open, close)| {
+            text.len() >= open.len() + close.len() && text.starts_with(open) && text.ends_with(close)
+        });
+        let unfinished = !matches!(highlighter.state_at(line + 1), LineState::Normal);
+        unfinished || (config.line_comment.is_some_and(|prefix| text.starts_with(prefix)) && !closed)
+    };
+    strings_and_comments(highlighter, line).any(|token| {
+        token.start < column && (column < token.end || column == range.len() && open_at_end(token))
+    })
+}
+
+/// How far bracket matching looks, so an unmatched bracket in a huge file
+/// does not scan all of it on every cursor move.
+const MAX_SCAN_LINES: usize = 5000;
+
+/// Visits the code chars from `offset` to the end of the buffer, or going
+/// backwards from just before it to the start, until `visit` returns
+/// something or `MAX_SCAN_LINES` lines have been looked at.
+fn scan(
+    buffer: &TextBuffer,
+    highlighter: &Highlighter,
+    offset: usize,
+    forward: bool,
+    mut visit: impl FnMut(usize, char) -> Option<usize>,
+) -> Option<usize> {
+    let first = buffer.offset_to_line(offset);
+    let lines: Box<dyn Iterator<Item = usize>> =
+        if forward { Box::new(first..buffer.line_count()) } else { Box::new((0..=first).rev()) };
+    for line in lines.take(MAX_SCAN_LINES) {
+        let start = buffer.line_start(line);
+        let text = buffer.line(line);
+        let skipped: Vec<Range<usize>> = strings_and_comments(highlighter, line).collect();
+        let mut chars: Vec<(usize, char)> = text
+            .char_indices()
+            .filter(|(index, _)| !skipped.iter().any(|range| range.contains(index)))
+            .map(|(index, c)| (start + index, c))
+            .filter(|&(at, _)| if forward { at >= offset } else { at < offset })
+            .collect();
+        if !forward {
+            chars.reverse();
+        }
+        for (at, c) in chars {
+            if let Some(found) = visit(at, c) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+/// The offset of the bracket matching the `pair` half at `at`.
+fn find_match(
+    buffer: &TextBuffer,
+    highlighter: &Highlighter,
+    at: usize,
+    pair: (char, char),
+    opening: bool,
+) -> Option<usize> {
+    let (nested, target) = if opening { pair } else { (pair.1, pair.0) };
+    let from = if opening { at + nested.len_utf8() } else { at };
+    let mut depth = 0usize;
+    scan(buffer, highlighter, from, opening, |at, c| {
+        if c == nested {
+            depth += 1;
+        } else if c == target {
+            if depth == 0 {
+                return Some(at);
+            }
+            depth -= 1;
+        }
+        None
+    })
+}
+
+/// The bracket next to a cursor at `offset`, the one before it first, and
+/// the bracket matching it, as their offsets.
+pub fn matching_bracket(
+    buffer: &TextBuffer,
+    highlighter: &Highlighter,
+    config: &LanguageConfig,
+    offset: usize,
+) -> Option<(usize, usize)> {
+    let before = (offset > 0).then(|| buffer.prev_char_boundary(offset));
+    before.into_iter().chain(Some(offset)).find_map(|at| {
+        let (pair, opening) = config.bracket(char_at(buffer, at)?)?;
+        if !is_code(buffer, highlighter, at) {
+            return None;
+        }
+        find_match(buffer, highlighter, at, pair, opening).map(|found| (at, found))
+    })
+}
+
+/// Where jumping to the bracket matching the one at the cursor lands:
+/// after it going forwards, before it going backwards.
+pub fn jump_to_bracket(
+    buffer: &TextBuffer,
+    highlighter: &Highlighter,
+    config: &LanguageConfig,
+    offset: usize,
+) -> Option<usize> {
+    let (at, found) = matching_bracket(buffer, highlighter, config, offset)?;
+    Some(if found > at { buffer.next_char_boundary(found) } else { found })
+}
+
+/// Types `typed` at every selection, where the language calls for more
+/// than inserting it: selections are wrapped in the pair it opens, an
+/// opening typed before whitespace or a closing char gets its closing half
+/// too, a closing char already at the cursor is typed over, and a closing
+/// bracket typed on a blank line takes the indentation of the line it
+/// closes. `None` if none of this applies at any selection.
+pub fn type_char(
+    selections: &Selections,
+    buffer: &TextBuffer,
+    highlighter: &Highlighter,
+    config: &LanguageConfig,
+    typed: char,
+) -> Option<EditPlan> {
+    let len = typed.len_utf8();
+    let opening = config.auto_closing_pairs.iter().find(|pair| pair.open == typed);
+    let closing = config.auto_closing_pairs.iter().any(|pair| pair.close == typed);
+    let mut special = false;
+    let items = selections
+        .ranges()
+        .iter()
+        .map(|selection| {
+            let range = selection.range();
+            let next = char_at(buffer, range.end);
+            if selection.is_empty() && closing && next == Some(typed) {
+                special = true;
+                return (range.start..range.start, String::new(), len, len);
+            }
+            if let Some(pair) = opening {
+                if !selection.is_empty() {
+                    special = true;
+                    let text = buffer.slice(range.clone());
+                    let (start, end) = (len, len + text.len());
+                    let (anchor, head) = if selection.is_backward() { (end, start) } else { (start, end) };
+                    return (range, format!("{}{}{}", pair.open, text, pair.close), anchor, head);
+                }
+                let before_space = next.is_none_or(|c| {
+                    c.is_whitespace()
+                        || config.close_before.contains(c)
+                        || config.auto_closing_pairs.iter().any(|pair| pair.close == c)
+                });
+                let after_word = pair.open == pair.close
+                    && char_before(buffer, range.start)
+                        .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == pair.open);
+                let allowed = !pair.code_only || !in_string_or_comment(buffer, highlighter, config, range.start);
+                if before_space && !after_word && allowed {
+                    special = true;
+                    return (range, format!("{}{}", pair.open, pair.close), len, len);
+                }
+            }
+            if let Some((pair, false)) = config.bracket(typed).filter(|_| selection.is_empty()) {
+                let line_start = buffer.line_start(buffer.offset_to_line(range.start));
+                let before = buffer.slice(line_start..range.start);
+                if before.trim().is_empty() {
+                    let indent = find_match(buffer, highlighter, range.start, pair, false)
+                        .map(|at| indentation(&buffer.line(buffer.offset_to_line(at))).to_string());
+                    if let Some(indent) = indent.filter(|indent| *indent != before) {
+                        special = true;
+                        let text = format!("{}{}", indent, typed);
+                        let end = text.len();
+                        return (line_start..range.start, text, end, end);
+                    }
+                }
+            }
+            (range, typed.to_string(), len, len)
+        })
+        .collect();
+    special.then(|| EditPlan::at_selections(items, selections.primary_index()))
+}
+
+/// Backspace at every selection, deleting both halves of an empty pair
+/// like `(|)`. `None` if no cursor is inside one.
+pub fn delete_pair(selections: &Selections, buffer: &TextBuffer, config: &LanguageConfig) -> Option<EditPlan> {
+    let mut special = false;
+    let items = selections
+        .ranges()
+        .iter()
+        .map(|selection| {
+            let head = selection.head;
+            if !selection.is_empty() {
+                return (selection.range(), String::new(), 0, 0);
+            }
+            let (before, after) = (char_before(buffer, head), char_at(buffer, head));
+            let paired = config
+                .auto_closing_pairs
+                .iter()
+                .any(|pair| before == Some(pair.open) && after == Some(pair.close));
+            if paired {
+                special = true;
+                (buffer.prev_char_boundary(head)..buffer.next_char_boundary(head), String::new(), 0, 0)
+            } else {
+                (buffer.prev_char_boundary(head)..head, String::new(), 0, 0)
+            }
+        })
+        .collect();
+    special.then(|| EditPlan::at_selections(items, selections.primary_index()))
+}
+
+/// Whether `text` ends with `token`, as a whole word if it is one.
+fn ends_with_token(text: &str, token: &str) -> bool {
+    let Some(rest) = text.strip_suffix(token) else {
+        return false;
+    };
+    let word = |c: char| c.is_alphanumeric() || c == '_';
+    !token.starts_with(word) || !rest.ends_with(word)
+}
+
+/// Enter at every selection. The new line is indented like the cursor's
+/// line, one level deeper after an `indent_after` ending or one level
+/// shallower after an `outdent_after` word; between brackets, the closing
+/// one moves to a line of its own below.
+pub fn newline(selections: &Selections, buffer: &TextBuffer, config: &LanguageConfig) -> EditPlan {
+    let items = selections
+        .ranges()
+        .iter()
+        .map(|selection| {
+            let mut range = selection.range();
+            let line_start = buffer.line_start(buffer.offset_to_line(range.start));
+            let line_end = buffer.line_range(buffer.offset_to_line(range.end)).end;
+            let before = buffer.slice(line_start..range.start);
+            let after = buffer.slice(range.end..line_end);
+            let indent = indentation(&before).to_string();
+            let code = before.trim_end();
+            let deeper = config.indent_after.iter().any(|token| ends_with_token(code, token));
+            let first_word = code.trim_start().split(|c: char| !c.is_alphanumeric() && c != '_').next();
+            let shallower = first_word.is_some_and(|word| config.outdent_after.contains(&word));
+            let mut next = indent.clone();
+            if deeper {
+                next.push_str(config.indent_unit);
+            } else if shallower {
+                if next.ends_with(config.indent_unit) {
+                    next.truncate(next.len() - config.indent_unit.len());
+                } else if next.ends_with('\t') {
+                    next.pop();
+                }
+            }
+            // The text after the cursor keeps the new indentation only.
+            let rest = after.trim_start();
+            range.end += after.len() - rest.len();
+            let closes = code.chars().last().and_then(|c| config.bracket(c));
+            let split = deeper
+                && closes.is_some_and(|((_, close), opening)| opening && rest.starts_with(close));
+            let cursor = 1 + next.len();
+            let text = if split { format!("\n{}\n{}", next, indent) } else { format!("\n{}", next) };
+            (range, text, cursor, cursor)
+        })
+        .collect();
+    EditPlan::at_selections(items, selections.primary_index())
+}
+
+/// The lines the selections cover, in order. A selection ending at the
+/// start of a line does not cover it.
+fn selected_lines(selections: &Selections, buffer: &TextBuffer) -> Vec<usize> {
+    let mut lines: Vec<usize> = selections
+        .ranges()
+        .iter()
+        .flat_map(|selection| {
+            let first = buffer.offset_to_line(selection.start());
+            let mut last = buffer.offset_to_line(selection.end());
+            if last > first && buffer.line_start(last) == selection.end() {
+                last -= 1;
+            }
+            first..=last
+        })
+        .collect();
+    lines.dedup();
+    lines
+}
+
+/// Comments out the lines the selections cover with the language's line
+/// comment token, or uncomments them if every non-blank one is commented.
+/// `None` if the language has no line comments.
+pub fn toggle_line_comment(selections: &Selections, buffer: &TextBuffer, config: &LanguageConfig) -> Option<EditPlan> {
+    let token = config.line_comment?;
+    let lines: Vec<(usize, String)> = selected_lines(selections, buffer)
+        .into_iter()
+        .map(|line| (line, buffer.line(line)))
+        .filter(|(_, text)| !text.trim().is_empty())
+        .collect();
+    let commented = !lines.is_empty() && lines.iter().all(|(_, text)| text.trim_start().starts_with(token));
+    let column = lines.iter().map(|(_, text)| indentation(text).len()).min().unwrap_or(0);
+    let edits = lines
+        .iter()
+        .map(|(line, text)| {
+            let start = buffer.line_start(*line);
+            if commented {
+                let at = indentation(text).len();
+                let len = if text[at + token.len()..].starts_with(' ') { token.len() + 1 } else { token.len() };
+                (start + at..start + at + len, String::new())
+            } else {
+                (start + column..start + column, format!("{} ", token))
+            }
+        })
+        .collect();
+    Some(EditPlan { edits, selections: None })
+}
+
+/// Wraps each selection in the language's block comment tokens, or
+/// unwraps it if they are already at its ends or just around it. Languages
+/// without block comments toggle line comments instead.
+pub fn toggle_block_comment(selections: &Selections, buffer: &TextBuffer, config: &LanguageConfig) -> Option<EditPlan> {
+    let Some((open, close)) = config.block_comment else {
+        return toggle_line_comment(selections, buffer, config);
+    };
+    let items = selections
+        .ranges()
+        .iter()
+        .map(|selection| {
+            let range = selection.range();
+            let text = buffer.slice(range.clone());
+            let select = |start: usize, end: usize| {
+                if selection.is_backward() { (end, start) } else { (start, end) }
+            };
+            let trimmed = text.trim();
+            if trimmed.len() >= open.len() + close.len() && trimmed.starts_with(open) && trimmed.ends_with(close) {
+                let inner = &trimmed[open.len()..trimmed.len() - close.len()];
+                let inner = inner.strip_prefix(' ').unwrap_or(inner);
+                let inner = inner.strip_suffix(' ').unwrap_or(inner);
+                let lead = text.len() - text.trim_start().len();
+                let unwrapped = format!("{}{}{}", &text[..lead], inner, &text[lead + trimmed.len()..]);
+                let (anchor, head) = select(0, unwrapped.len());
+                return (range, unwrapped, anchor, head);
+            }
+            let line_start = buffer.line_start(buffer.offset_to_line(range.start));
+            let line_end = buffer.line_range(buffer.offset_to_line(range.end)).end;
+            let before = buffer.slice(line_start..range.start);
+            let after = buffer.slice(range.end..line_end);
+            let before = before.strip_suffix(' ').map_or((before.as_str(), 0), |before| (before, 1));
+            let after = after.strip_prefix(' ').map_or((after.as_str(), 0), |after| (after, 1));
+            if before.0.ends_with(open) && after.0.starts_with(close) {
+                let around = range.start - before.1 - open.len()..range.end + after.1 + close.len();
+                let (anchor, head) = select(0, text.len());
+                return (around, text, anchor, head);
+            }
+            let (anchor, head) = select(open.len() + 1, open.len() + 1 + text.len());
+            (range, format!("{} {} {}", open, text, close), anchor, head)
+        })
+        .collect();
+    Some(EditPlan::at_selections(items, selections.primary_index()))
+}
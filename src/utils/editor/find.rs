@@ -0,0 +1,270 @@
+//! Find and replace within one buffer.
+
+use std::ops::Range;
+
+use regex::Regex;
+
+use super::buffer::TextBuffer;
+use super::history::Edit;
+use crate::utils::file_system::search::{brace_group_numbers, SearchQuery};
+
+/// What the find widget is looking for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FindQuery {
+    pub text: String,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// `text` is a regular expression and replacements may refer to its
+    /// groups as `$1` or `${name}`.
+    pub regex: bool,
+    /// Replacements take the case of the text they replace.
+    pub preserve_case: bool,
+    /// Only search within the selection made when this was turned on.
+    pub in_selection: bool,
+}
+
+impl FindQuery {
+    /// The compiled query, or `None` if there is nothing to look for.
+    pub fn compile(&self) -> Result<Option<Finder>, String> {
+        if self.text.is_empty() {
+            return Ok(None);
+        }
+        let mut query = if self.regex {
+            SearchQuery::regex(&self.text)
+        } else {
+            SearchQuery::literal(&self.text)
+        };
+        query.case_sensitive = self.case_sensitive;
+        query.whole_word = self.whole_word;
+        let regex = query.compile().map_err(|err| err.to_string())?;
+        Ok(Some(Finder {
+            regex,
+            expand_groups: self.regex,
+            preserve_case: self.preserve_case,
+        }))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Finder {
+    regex: Regex,
+    expand_groups: bool,
+    preserve_case: bool,
+}
+
+impl Finder {
+    /// Byte ranges of the non-empty matches in `text`, in order. With a
+    /// `scope`, only matches lying wholly within one of its ranges count.
+    pub fn find_all(&self, text: &str, scope: Option<&[Range<usize>]>) -> Vec<Range<usize>> {
+        self.regex
+            .find_iter(text)
+            .filter(|found| !found.is_empty())
+            .map(|found| found.range())
+            .filter(|found| {
+                scope.is_none_or(|scope| {
+                    scope
+                        .iter()
+                        .any(|range| range.start <= found.start && found.end <= range.end)
+                })
+            })
+            .collect()
+    }
+
+    /// What replacing the match at `found` in `text` with `replace` inserts.
+    pub fn replacement(&self, text: &str, found: Range<usize>, replace: &str) -> String {
+        let mut replacement = String::new();
+        match self.regex.captures_at(text, found.start).filter(|captures| captures[0].len() == found.len()) {
+            Some(captures) if self.expand_groups => {
+                captures.expand(&brace_group_numbers(replace), &mut replacement)
+            }
+            _ => replacement.push_str(replace),
+        }
+        if self.preserve_case {
+            replacement = preserve_case(&text[found], &replacement);
+        }
+        replacement
+    }
+
+    /// Replaces every match in `matches` (as found in `buffer`'s current
+    /// text) and returns the edits, last match first, as applied.
+    pub fn replace_all(&self, buffer: &mut TextBuffer, matches: &[Range<usize>], replace: &str) -> Vec<Edit> {
+        let text = buffer.to_string();
+        matches
+            .iter()
+            .rev()
+            .map(|found| {
+                let edit = Edit::replace(buffer, found.clone(), self.replacement(&text, found.clone(), replace));
+                edit.apply(buffer);
+                edit
+            })
+            .collect()
+    }
+}
+
+/// The match to go to from `offset`: the first one starting at or after
+/// it, or going backwards the last one starting before it, wrapping
+/// around.
+pub fn next_match(matches: &[Range<usize>], offset: usize, forward: bool) -> Option<usize> {
+    if matches.is_empty() {
+        return None;
+    }
+    if forward {
+        Some(matches.iter().position(|found| found.start >= offset).unwrap_or(0))
+    } else {
+        Some(matches.iter().rposition(|found| found.start < offset).unwrap_or(matches.len() - 1))
+    }
+}
+
+/// `replacement` in the case of `matched`: all upper case, all lower case
+/// or capitalized.
+pub fn preserve_case(matched: &str, replacement: &str) -> String {
+    let letters: Vec<char> = matched.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.len() > 1 && letters.iter().all(|c| c.is_uppercase()) {
+        replacement.to_uppercase()
+    } else if !letters.is_empty() && letters.iter().all(|c| c.is_lowercase()) {
+        replacement.to_lowercase()
+    } else if letters.first().is_some_and(|c| c.is_uppercase()) {
+        let mut chars = replacement.chars();
+        chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+    } else {
+        replacement.to_string()
+    }
+}
+
+/// The ranges searched by "find in selection", remembered with the text
+/// they were selected in so they can follow later edits.
+#[derive(Debug, Clone)]
+pub struct FindScope {
+    text: TextBuffer,
+    ranges: Vec<Range<usize>>,
+}
+
+impl FindScope {
+    pub fn new(text: &TextBuffer, ranges: Vec<Range<usize>>) -> Self {
+        Self {
+            text: text.clone(),
+            ranges,
+        }
+    }
+
+    /// Follows `edits`, which turned `before` into `after`.
+    pub fn edited(&mut self, before: &TextBuffer, edits: &[Edit], after: &TextBuffer) {
+        self.ranges = self
+            .ranges_in(before)
+            .into_iter()
+            .map(|range| {
+                edits.iter().fold(range, |range, edit| edit.map_offset(range.start)..edit.map_offset(range.end))
+            })
+            .collect();
+        self.text = after.clone();
+    }
+
+    /// The ranges in `text`, an edited version of the scope's text. Edits
+    /// are taken to be one replacement between the common prefix and
+    /// suffix of the two; ranges after it shift and ranges touching it grow
+    /// or shrink with it.
+    pub fn ranges_in(&self, text: &TextBuffer) -> Vec<Range<usize>> {
+        let prefix = self.text.common_prefix_len(text);
+        if prefix == self.text.len() && prefix == text.len() {
+            return self.ranges.clone();
+        }
+        let suffix = self
+            .text
+            .common_suffix_len(text)
+            .min(self.text.len() - prefix)
+            .min(text.len() - prefix);
+        let (old_end, new_end) = (self.text.len() - suffix, text.len() - suffix);
+        let map = |offset: usize| match offset {
+            offset if offset <= prefix => offset,
+            offset if offset >= old_end => offset - old_end + new_end,
+            _ => new_end,
+        };
+        self.ranges.iter().map(|range| map(range.start)..map(range.end)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finder(text: &str, configure: impl FnOnce(&mut FindQuery)) -> Finder {
+        let mut query = FindQuery {
+            text: text.to_string(),
+            ..FindQuery::default()
+        };
+        configure(&mut query);
+        query.compile().unwrap().unwrap()
+    }
+
+    fn replaced(finder: &Finder, text: &str, replace: &str) -> String {
+        let mut buffer = TextBuffer::from(text);
+        let matches = finder.find_all(text, None);
+        finder.replace_all(&mut buffer, &matches, replace);
+        buffer.to_string()
+    }
+
+    #[test]
+    fn matches_by_case_word_and_regex() {
+        let text = "Foo food foo FOO";
+        assert_eq!(finder("foo", |_| {}).find_all(text, None).len(), 4);
+        assert_eq!(finder("foo", |q| q.case_sensitive = true).find_all(text, None), vec![4..7, 9..12]);
+        assert_eq!(finder("foo", |q| q.whole_word = true).find_all(text, None), vec![0..3, 9..12, 13..16]);
+        assert_eq!(finder("fo+d", |q| q.regex = true).find_all(text, None), vec![4..8]);
+        // Without `regex`, the pattern is literal.
+        assert!(finder("fo+d", |_| {}).find_all(text, None).is_empty());
+        assert_eq!(finder("foo", |_| {}).find_all(text, Some(&[0..2, 3..12])), vec![4..7, 9..12]);
+        assert!(FindQuery::default().compile().unwrap().is_none());
+        assert_eq!(finder("(", |_| {}).find_all("f(x)", None), vec![1..2]);
+        let invalid = FindQuery {
+            text: "(".to_string(),
+            regex: true,
+            ..FindQuery::default()
+        };
+        assert!(invalid.compile().is_err());
+    }
+
+    #[test]
+    fn expands_groups() {
+        let named = finder(r"(?P<key>\w+)=(\d+)", |q| q.regex = true);
+        assert_eq!(replaced(&named, "a=1, b=2", "$2:${key}"), "1:a, 2:b");
+        let numbered = finder("(f)oo", |q| q.regex = true);
+        assert_eq!(replaced(&numbered, "foo", "$1x"), "fx");
+        assert_eq!(replaced(&numbered, "foo", "$1 $$1"), "f $1");
+        // Groups are only expanded for regex queries.
+        let literal = finder("foo", |_| {});
+        assert_eq!(replaced(&literal, "foo", "$1"), "$1");
+    }
+
+    #[test]
+    fn preserves_case() {
+        let cased = finder("foo", |q| q.preserve_case = true);
+        assert_eq!(replaced(&cased, "foo Foo FOO", "bar"), "bar Bar BAR");
+        assert_eq!(preserve_case("fOo", "bar"), "bar");
+    }
+
+    #[test]
+    fn scope_follows_edits() {
+        let before = TextBuffer::from("one two three");
+        let mut scope = FindScope::new(&before, vec![4..7, 8..13]);
+        let mut after = before.clone();
+        let edit = Edit::replace(&after, 0..0, "XX ".to_string());
+        edit.apply(&mut after);
+        scope.edited(&before, &[edit], &after);
+        assert_eq!(scope.ranges_in(&after), vec![7..10, 11..16]);
+
+        // Typing inside the scope grows it, even before `edited` is told.
+        let mut typed = after.clone();
+        Edit::replace(&typed, 8..8, "w".to_string()).apply(&mut typed);
+        assert_eq!(scope.ranges_in(&typed), vec![7..11, 12..17]);
+    }
+
+    #[test]
+    fn next_match_wraps_around() {
+        let matches = vec![2..4, 6..8, 10..12];
+        assert_eq!(next_match(&matches, 5, true), Some(1));
+        assert_eq!(next_match(&matches, 11, true), Some(0));
+        assert_eq!(next_match(&matches, 6, false), Some(0));
+        assert_eq!(next_match(&matches, 2, false), Some(2));
+        assert_eq!(next_match(&[], 0, true), None);
+    }
+}
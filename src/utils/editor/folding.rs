@@ -0,0 +1,564 @@
+//! Code folding: the ranges of lines that can fold, the folds made in a
+//! buffer, and the buffer as shown with folded lines left out.
+
+use std::collections::BTreeSet;
+use std::ops::{Range, RangeInclusive};
+
+use super::buffer::TextBuffer;
+use super::history::Edit;
+use crate::utils::syntax::highlighter::Highlighter;
+use crate::utils::syntax::{Language, LineState, TokenKind};
+
+/// Where a fold range comes from, in order of precedence when several
+/// start on the same line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FoldKind {
+    /// Between `#region` and `#endregion` comments.
+    Region,
+    /// A Markdown heading's section.
+    Heading,
+    Brackets,
+    Indentation,
+}
+
+/// Lines `start + 1..=end` can be folded away under line `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FoldRange {
+    pub start: usize,
+    pub end: usize,
+    pub kind: FoldKind,
+}
+
+/// The fold ranges of `text`, which `highlighter` must be up to date with,
+/// ordered by start line. At most one range starts on any line.
+pub fn fold_ranges(text: &TextBuffer, highlighter: &Highlighter) -> Vec<FoldRange> {
+    let language = highlighter.language();
+    let lines: Vec<String> = (0..text.line_count()).map(|line| text.line(line)).collect();
+    let mut ranges = region_ranges(&lines);
+    match language {
+        Language::Markdown => ranges.extend(heading_ranges(&lines, highlighter)),
+        Language::Python | Language::Toml | Language::Shell | Language::PlainText => {
+            ranges.extend(indentation_ranges(&lines))
+        }
+        _ => {}
+    }
+    if !matches!(language, Language::Markdown | Language::PlainText) {
+        ranges.extend(bracket_ranges(&lines, highlighter));
+    }
+    ranges.sort_by_key(|range| (range.start, range.kind, std::cmp::Reverse(range.end)));
+    ranges.dedup_by_key(|range| range.start);
+    ranges
+}
+
+/// Whether `line` opens (`Some(true)`) or closes (`Some(false)`) a region:
+/// `#region` or `#endregion` at its start, possibly in a comment.
+fn region_marker(line: &str) -> Option<bool> {
+    let text = line.trim_start();
+    let text = ["//", "/*", "<!--", "--"]
+        .iter()
+        .find_map(|opener| text.strip_prefix(opener))
+        .map_or(text, str::trim_start);
+    let text = text.strip_prefix('#')?;
+    let ends_word = |rest: &str| rest.chars().next().is_none_or(|c| !c.is_alphanumeric());
+    if text.strip_prefix("endregion").is_some_and(ends_word) {
+        Some(false)
+    } else if text.strip_prefix("region").is_some_and(ends_word) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+fn region_ranges(lines: &[String]) -> Vec<FoldRange> {
+    let mut open = Vec::new();
+    let mut ranges = Vec::new();
+    for (number, line) in lines.iter().enumerate() {
+        match region_marker(line) {
+            Some(true) => open.push(number),
+            Some(false) => {
+                if let Some(start) = open.pop() {
+                    ranges.push(FoldRange {
+                        start,
+                        end: number,
+                        kind: FoldKind::Region,
+                    });
+                }
+            }
+            None => {}
+        }
+    }
+    ranges
+}
+
+/// Ranges between matching brackets on different lines, ignoring brackets
+/// in strings and comments. A closing bracket that starts its line stays
+/// visible.
+fn bracket_ranges(lines: &[String], highlighter: &Highlighter) -> Vec<FoldRange> {
+    let mut open: Vec<(char, usize)> = Vec::new();
+    let mut ranges = Vec::new();
+    for (number, line) in lines.iter().enumerate() {
+        let skipped: Vec<Range<usize>> = highlighter
+            .tokens(number)
+            .iter()
+            .filter(|token| matches!(token.kind, TokenKind::String | TokenKind::Comment))
+            .map(|token| token.start..token.end)
+            .collect();
+        let indent = line.len() - line.trim_start().len();
+        for (index, c) in line.char_indices() {
+            if skipped.iter().any(|range| range.contains(&index)) {
+                continue;
+            }
+            let opening = match c {
+                '{' | '[' | '(' => {
+                    open.push((c, number));
+                    continue;
+                }
+                '}' => '{',
+                ']' => '[',
+                ')' => '(',
+                _ => continue,
+            };
+            let Some(position) = open.iter().rposition(|&(bracket, _)| bracket == opening) else {
+                continue;
+            };
+            let start = open[position].1;
+            open.truncate(position);
+            let end = if index == indent { number.saturating_sub(1) } else { number };
+            if end > start {
+                ranges.push(FoldRange {
+                    start,
+                    end,
+                    kind: FoldKind::Brackets,
+                });
+            }
+        }
+    }
+    ranges
+}
+
+/// Width of `line`'s indentation with tabs counted as four columns, or
+/// `None` for a blank line.
+fn indentation(line: &str) -> Option<usize> {
+    let text = line.trim_start();
+    (!text.is_empty()).then(|| {
+        line[..line.len() - text.len()]
+            .chars()
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum()
+    })
+}
+
+/// Each line with the more indented lines after it, up to the last
+/// non-blank one.
+fn indentation_ranges(lines: &[String]) -> Vec<FoldRange> {
+    let mut ranges = Vec::new();
+    let mut close = |start: usize, end: usize| {
+        if end > start {
+            ranges.push(FoldRange {
+                start,
+                end,
+                kind: FoldKind::Indentation,
+            });
+        }
+    };
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut last = 0;
+    for (number, line) in lines.iter().enumerate() {
+        let Some(level) = indentation(line) else {
+            continue;
+        };
+        while let Some(&(outer, start)) = open.last() {
+            if outer < level {
+                break;
+            }
+            open.pop();
+            close(start, last);
+        }
+        open.push((level, number));
+        last = number;
+    }
+    for (_, start) in open {
+        close(start, last);
+    }
+    ranges
+}
+
+/// Level of a Markdown ATX heading (`## Title`).
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.bytes().take_while(|&b| b == b'#').count();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with([' ', '\t']))).then_some(level)
+}
+
+/// Each heading's section: the lines up to the next heading of the same or
+/// a higher level, without trailing blank lines.
+fn heading_ranges(lines: &[String], highlighter: &Highlighter) -> Vec<FoldRange> {
+    let mut ranges = Vec::new();
+    let mut close = |start: usize, end: usize| {
+        if end > start {
+            ranges.push(FoldRange {
+                start,
+                end,
+                kind: FoldKind::Heading,
+            });
+        }
+    };
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut last = 0;
+    for (number, line) in lines.iter().enumerate() {
+        let in_code = matches!(highlighter.state_at(number), LineState::Fence { .. });
+        if let Some(level) = heading_level(line).filter(|_| !in_code) {
+            while let Some(&(outer, start)) = open.last() {
+                if outer < level {
+                    break;
+                }
+                open.pop();
+                close(start, last);
+            }
+            open.push((level, number));
+        }
+        if !line.trim().is_empty() {
+            last = number;
+        }
+    }
+    for (_, start) in open {
+        close(start, last);
+    }
+    ranges
+}
+
+/// How deep each range is nested in the others, 1 being the outermost.
+fn depths(ranges: &[FoldRange]) -> Vec<usize> {
+    let mut ends: Vec<usize> = Vec::new();
+    ranges
+        .iter()
+        .map(|range| {
+            while ends.last().is_some_and(|&end| end < range.start) {
+                ends.pop();
+            }
+            ends.push(range.end);
+            ends.len()
+        })
+        .collect()
+}
+
+/// What the folding keyboard shortcuts do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FoldCommand {
+    /// Fold the innermost range at the cursor.
+    Fold,
+    /// Unfold the fold on the cursor's line, or else the innermost one
+    /// around it.
+    Unfold,
+    FoldAll,
+    UnfoldAll,
+    /// Fold every range nested this deep, 1 being the outermost.
+    FoldLevel(usize),
+}
+
+/// The folds made in a buffer, by the line each starts on. Once given the
+/// text they were made in, [`update`](Self::update) keeps them on the same
+/// lines as edits add and remove lines above them.
+#[derive(Debug, Clone, Default)]
+pub struct Folds {
+    lines: BTreeSet<usize>,
+    text: Option<TextBuffer>,
+}
+
+impl Folds {
+    pub fn new(text: &TextBuffer, lines: impl IntoIterator<Item = usize>) -> Self {
+        Self {
+            lines: lines.into_iter().collect(),
+            text: Some(text.clone()),
+        }
+    }
+
+    /// Start lines of the folds, in order.
+    pub fn lines(&self) -> impl Iterator<Item = usize> + '_ {
+        self.lines.iter().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn is_folded(&self, line: usize) -> bool {
+        self.lines.contains(&line)
+    }
+
+    pub fn fold(&mut self, line: usize) -> bool {
+        self.lines.insert(line)
+    }
+
+    pub fn unfold(&mut self, line: usize) -> bool {
+        self.lines.remove(&line)
+    }
+
+    pub fn toggle(&mut self, line: usize) {
+        if !self.unfold(line) {
+            self.fold(line);
+        }
+    }
+
+    pub fn fold_all(&mut self, ranges: &[FoldRange]) {
+        self.lines.extend(ranges.iter().map(|range| range.start));
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.lines.clear();
+    }
+
+    /// Folds every range nested `level` deep, 1 being the outermost.
+    pub fn fold_level(&mut self, ranges: &[FoldRange], level: usize) {
+        let depths = depths(ranges);
+        self.lines.extend(
+            ranges
+                .iter()
+                .zip(depths)
+                .filter(|&(_, depth)| depth == level)
+                .map(|(range, _)| range.start),
+        );
+    }
+
+    /// Runs `command` with the cursor on `line`.
+    pub fn run(&mut self, command: FoldCommand, ranges: &[FoldRange], line: usize) {
+        match command {
+            FoldCommand::Fold => {
+                if let Some(range) = Self::range_at(ranges, line) {
+                    self.fold(range.start);
+                }
+            }
+            FoldCommand::Unfold => {
+                if !self.unfold(line) {
+                    let folded = ranges
+                        .iter()
+                        .filter(|range| range.start <= line && line <= range.end && self.is_folded(range.start))
+                        .max_by_key(|range| range.start);
+                    if let Some(range) = folded.copied() {
+                        self.unfold(range.start);
+                    }
+                }
+            }
+            FoldCommand::FoldAll => self.fold_all(ranges),
+            FoldCommand::UnfoldAll => self.unfold_all(),
+            FoldCommand::FoldLevel(level) => self.fold_level(ranges, level),
+        }
+    }
+
+    /// The innermost range around `line`, starting on it or containing it.
+    pub fn range_at(ranges: &[FoldRange], line: usize) -> Option<FoldRange> {
+        ranges
+            .iter()
+            .filter(|range| range.start <= line && line <= range.end)
+            .max_by_key(|range| range.start)
+            .copied()
+    }
+
+    /// Unfolds whatever hides `line`. Returns whether anything was.
+    pub fn reveal(&mut self, ranges: &[FoldRange], line: usize) -> bool {
+        let hiding: Vec<usize> = ranges
+            .iter()
+            .filter(|range| range.start < line && line <= range.end && self.lines.contains(&range.start))
+            .map(|range| range.start)
+            .collect();
+        for start in &hiding {
+            self.lines.remove(start);
+        }
+        !hiding.is_empty()
+    }
+
+    /// Follows the edits that turned the text the folds were last given
+    /// into `text`, taken as one replacement between their common prefix
+    /// and suffix. Folds on lines starting after it move with the lines it
+    /// added or removed; folds on lines starting inside it are dropped.
+    pub fn update(&mut self, text: &TextBuffer) {
+        let Some(old) = self.text.replace(text.clone()) else {
+            return;
+        };
+        let prefix = old.common_prefix_len(text);
+        if prefix == old.len() && prefix == text.len() {
+            return;
+        }
+        let suffix = old.common_suffix_len(text).min(old.len() - prefix).min(text.len() - prefix);
+        let (old_end, new_end) = (old.len() - suffix, text.len() - suffix);
+        self.lines = self
+            .lines
+            .iter()
+            .filter_map(|&line| match old.line_start(line) {
+                start if start >= old_end => Some(text.offset_to_line(start - old_end + new_end)),
+                start if start <= prefix => Some(line),
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// Drops folds on lines where nothing can fold any more.
+    pub fn retain(&mut self, ranges: &[FoldRange]) {
+        self.lines
+            .retain(|line| ranges.binary_search_by_key(line, |range| range.start).is_ok());
+    }
+
+    /// The lines hidden by the folds, as ordered, disjoint ranges.
+    pub fn hidden(&self, ranges: &[FoldRange]) -> Vec<RangeInclusive<usize>> {
+        let mut hidden: Vec<RangeInclusive<usize>> = Vec::new();
+        for range in ranges.iter().filter(|range| self.lines.contains(&range.start)) {
+            match hidden.last_mut() {
+                Some(last) if range.start <= *last.end() => {
+                    if range.end > *last.end() {
+                        *last = *last.start()..=range.end;
+                    }
+                }
+                _ => hidden.push(range.start + 1..=range.end),
+            }
+        }
+        hidden
+    }
+}
+
+/// A buffer as shown with folded lines left out, and the mapping between
+/// offsets into the shown text, counted in UTF-16 units like the DOM does,
+/// and offsets into the buffer. Where hidden text was left out maps to the
+/// start of that text, so typing there goes before the fold.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FoldedView {
+    lines: Vec<RangeInclusive<usize>>,
+    /// The bytes the hidden lines take up, from the line break before
+    /// them, and their length in UTF-16 units.
+    spans: Vec<(Range<usize>, usize)>,
+}
+
+impl FoldedView {
+    /// The view of `text` with `hidden` lines (as from [`Folds::hidden`])
+    /// left out.
+    pub fn new(text: &TextBuffer, hidden: Vec<RangeInclusive<usize>>) -> Self {
+        let spans = hidden
+            .iter()
+            .map(|lines| {
+                let start = text.line_range(lines.start() - 1).end;
+                let end = text.line_range(*lines.end()).end;
+                (start..end, text.offset_to_utf16(end) - text.offset_to_utf16(start))
+            })
+            .collect();
+        Self { lines: hidden, spans }
+    }
+
+    pub fn hidden_lines(&self) -> &[RangeInclusive<usize>] {
+        &self.lines
+    }
+
+    /// The view after `edit` was made through it: hidden text after the
+    /// edit moves with it, and hidden text the edit removed is gone.
+    pub fn edited(&self, edit: &Edit) -> Self {
+        let end = edit.offset + edit.deleted.len();
+        let shift = |offset: usize| offset - edit.deleted.len() + edit.inserted.len();
+        let lines = edit.inserted.matches('\n').count() as isize - edit.deleted.matches('\n').count() as isize;
+        let (lines, spans) = self
+            .lines
+            .iter()
+            .zip(&self.spans)
+            .filter_map(|(hidden, (span, len))| {
+                if span.end <= edit.offset {
+                    Some((hidden.clone(), (span.clone(), *len)))
+                } else if span.start >= end {
+                    let hidden = hidden.start().wrapping_add_signed(lines)..=hidden.end().wrapping_add_signed(lines);
+                    Some((hidden, (shift(span.start)..shift(span.end), *len)))
+                } else {
+                    None
+                }
+            })
+            .unzip();
+        Self { lines, spans }
+    }
+
+    /// The shown text.
+    pub fn text(&self, text: &TextBuffer) -> String {
+        let mut shown = String::with_capacity(text.len());
+        let mut at = 0;
+        for (span, _) in &self.spans {
+            shown.push_str(&text.slice(at..span.start));
+            at = span.end;
+        }
+        shown.push_str(&text.slice(at..text.len()));
+        shown
+    }
+
+    pub fn len_utf16(&self, text: &TextBuffer) -> usize {
+        text.len_utf16() - self.spans.iter().map(|(_, len)| len).sum::<usize>()
+    }
+
+    /// Buffer offset of UTF-16 index `index` into the shown text.
+    pub fn utf16_to_offset(&self, text: &TextBuffer, index: usize) -> usize {
+        let mut hidden = 0;
+        for (span, len) in &self.spans {
+            if text.offset_to_utf16(span.start) - hidden >= index {
+                break;
+            }
+            hidden += len;
+        }
+        text.utf16_to_offset(index + hidden)
+    }
+
+    /// UTF-16 index into the shown text of buffer offset `offset`.
+    pub fn offset_to_utf16(&self, text: &TextBuffer, offset: usize) -> usize {
+        let mut hidden = 0;
+        for (span, len) in &self.spans {
+            if offset <= span.start {
+                break;
+            }
+            if offset < span.end {
+                return text.offset_to_utf16(span.start) - hidden;
+            }
+            hidden += len;
+        }
+        text.offset_to_utf16(offset) - hidden
+    }
+
+    /// The row `line` is shown on, or `None` if it is hidden.
+    pub fn row(&self, line: usize) -> Option<usize> {
+        let mut row = line;
+        for lines in &self.lines {
+            if line < *lines.start() {
+                break;
+            }
+            if lines.contains(&line) {
+                return None;
+            }
+            row -= lines.end() - lines.start() + 1;
+        }
+        Some(row)
+    }
+
+    /// The line shown on `row`.
+    pub fn line_at(&self, row: usize) -> usize {
+        let mut line = row;
+        for lines in &self.lines {
+            if *lines.start() > line {
+                break;
+            }
+            line += lines.end() - lines.start() + 1;
+        }
+        line
+    }
+
+    /// Whether the line after `line` is hidden, i.e. `line` is a folded
+    /// header.
+    pub fn is_folded(&self, line: usize) -> bool {
+        self.lines.iter().any(|lines| *lines.start() == line + 1)
+    }
+
+    /// How many rows `line_count` lines take up.
+    pub fn row_count(&self, line_count: usize) -> usize {
+        line_count - self.lines.iter().map(|lines| lines.end() - lines.start() + 1).sum::<usize>()
+    }
+
+    /// The lines shown on `rows`, in order, out of `line_count`.
+    pub fn lines_on_rows(&self, rows: Range<usize>, line_count: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut hidden = self.lines.iter().peekable();
+        (self.line_at(rows.start).min(line_count)..line_count)
+            .filter(move |line| {
+                while hidden.next_if(|lines| lines.end() < line).is_some() {}
+                hidden.peek().is_none_or(|lines| !lines.contains(line))
+            })
+            .take(rows.len())
+    }
+}
@@ -0,0 +1,232 @@
+//! Undo/redo history of one editor tab.
+//!
+//! Consecutive typing or deleting in one place coalesces into a single
+//! step. A step ends when the cursor jumps, typing pauses, or a word ends
+//! (whitespace typed after other text). Each step remembers the selections
+//! before and after it, so undo and redo put the cursors back.
+
+use super::buffer::TextBuffer;
+use super::selection::Selections;
+
+/// Typing after a pause this long starts a new undo step.
+const COALESCE_PAUSE_MS: u64 = 1000;
+
+/// Steps beyond this many are forgotten, oldest first.
+const MAX_STEPS: usize = 1000;
+
+/// Replacement of `deleted` at `offset` by `inserted`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Edit {
+    pub offset: usize,
+    pub deleted: String,
+    pub inserted: String,
+}
+
+impl Edit {
+    pub fn insert(offset: usize, text: impl Into<String>) -> Self {
+        Self {
+            offset,
+            deleted: String::new(),
+            inserted: text.into(),
+        }
+    }
+
+    /// The edit replacing `range` of `buffer` with `text`.
+    pub fn replace(buffer: &TextBuffer, range: std::ops::Range<usize>, text: impl Into<String>) -> Self {
+        Self {
+            offset: range.start,
+            deleted: buffer.slice(range),
+            inserted: text.into(),
+        }
+    }
+
+    pub fn apply(&self, buffer: &mut TextBuffer) {
+        buffer.replace(self.offset..self.offset + self.deleted.len(), &self.inserted);
+    }
+
+    pub fn revert(&self, buffer: &mut TextBuffer) {
+        buffer.replace(self.offset..self.offset + self.inserted.len(), &self.deleted);
+    }
+
+    /// Where `offset`, in the text before this edit, ends up after it.
+    /// Offsets inside the replaced text move to the end of the replacement.
+    pub fn map_offset(&self, offset: usize) -> usize {
+        if offset <= self.offset {
+            offset
+        } else if offset >= self.offset + self.deleted.len() {
+            offset - self.deleted.len() + self.inserted.len()
+        } else {
+            self.offset + self.inserted.len()
+        }
+    }
+
+    fn is_insertion(&self) -> bool {
+        self.deleted.is_empty() && !self.inserted.is_empty()
+    }
+
+    fn is_deletion(&self) -> bool {
+        self.inserted.is_empty() && !self.deleted.is_empty()
+    }
+
+    /// Folds `next`, made right after this edit, into it if both belong to
+    /// one burst of typing or deleting.
+    fn absorb(&mut self, next: &Edit) -> bool {
+        if next.is_insertion() && !self.inserted.is_empty() && next.offset == self.offset + self.inserted.len() {
+            let ends_word = next.inserted.starts_with(char::is_whitespace)
+                && !self.inserted.ends_with(char::is_whitespace);
+            if ends_word {
+                return false;
+            }
+            self.inserted.push_str(&next.inserted);
+            true
+        } else if next.is_deletion() && self.is_deletion() && next.offset + next.deleted.len() == self.offset {
+            // Backspace
+            self.deleted.insert_str(0, &next.deleted);
+            self.offset = next.offset;
+            true
+        } else if next.is_deletion() && self.is_deletion() && next.offset == self.offset {
+            // Delete
+            self.deleted.push_str(&next.deleted);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Step {
+    edits: Vec<Edit>,
+    before: Selections,
+    after: Selections,
+    /// When the last edit was made; `None` for steps that never coalesce.
+    edited_at: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    /// Number of undo steps at the last save, if that state is still
+    /// reachable.
+    saved: Option<usize>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            saved: Some(0),
+        }
+    }
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an edit already applied to the buffer, merging it into the
+    /// previous step when it continues the same burst of typing. `now` is
+    /// in milliseconds.
+    pub fn record(&mut self, edit: Edit, before: Selections, after: Selections, now: u64) {
+        if let Some(last) = self.undo.last_mut() {
+            let continues = last.edited_at.is_some_and(|at| now.saturating_sub(at) <= COALESCE_PAUSE_MS)
+                && last.after == before
+                && last.edits.len() == 1;
+            if continues && last.edits[0].absorb(&edit) {
+                last.after = after;
+                last.edited_at = Some(now);
+                return;
+            }
+        }
+        self.push(Step {
+            edits: vec![edit],
+            before,
+            after,
+            edited_at: Some(now),
+        });
+    }
+
+    /// Records edits applied together (replace all, typing at several
+    /// cursors) as one step that never coalesces with typing.
+    pub fn record_group(&mut self, edits: Vec<Edit>, before: Selections, after: Selections) {
+        if !edits.is_empty() {
+            self.push(Step {
+                edits,
+                before,
+                after,
+                edited_at: None,
+            });
+        }
+    }
+
+    /// Makes the next edit start a new step.
+    pub fn break_coalescing(&mut self) {
+        if let Some(last) = self.undo.last_mut() {
+            last.edited_at = None;
+        }
+    }
+
+    /// Reverts the last step and returns the selections from before it.
+    pub fn undo(&mut self, buffer: &mut TextBuffer) -> Option<Selections> {
+        let step = self.undo.pop()?;
+        for edit in step.edits.iter().rev() {
+            edit.revert(buffer);
+        }
+        let selections = step.before.clone();
+        self.redo.push(step);
+        self.break_coalescing();
+        Some(selections)
+    }
+
+    /// Reapplies the last undone step and returns the selections from
+    /// after it.
+    pub fn redo(&mut self, buffer: &mut TextBuffer) -> Option<Selections> {
+        let mut step = self.redo.pop()?;
+        for edit in &step.edits {
+            edit.apply(buffer);
+        }
+        step.edited_at = None;
+        let selections = step.after.clone();
+        self.undo.push(step);
+        Some(selections)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.undo.len());
+        self.break_coalescing();
+    }
+
+    /// Whether the buffer is back to how it was last saved.
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.undo.len())
+    }
+
+    /// Forgets the saved state, for a buffer that already differs from its
+    /// file when the history starts.
+    pub fn forget_saved(&mut self) {
+        self.saved = None;
+    }
+
+    fn push(&mut self, step: Step) {
+        if self.saved.is_some_and(|saved| saved > self.undo.len()) {
+            self.saved = None;
+        }
+        self.redo.clear();
+        self.undo.push(step);
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+            self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+        }
+    }
+}
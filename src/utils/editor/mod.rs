@@ -0,0 +1,7 @@
+pub mod buffer;
+pub mod editing;
+pub mod find;
+pub mod folding;
+pub mod history;
+pub mod selection;
+pub mod tabs;
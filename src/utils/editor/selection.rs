@@ -0,0 +1,432 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use super::buffer::{Position, TextBuffer};
+use super::history::Edit;
+
+/// A selection as byte offsets into a buffer. `anchor` stays put while
+/// `head`, the cursor, moves; they are equal for a plain cursor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Selection {
+    pub anchor: usize,
+    pub head: usize,
+}
+
+impl Selection {
+    pub fn new(anchor: usize, head: usize) -> Self {
+        Self { anchor, head }
+    }
+
+    pub fn cursor(offset: usize) -> Self {
+        Self::new(offset, offset)
+    }
+
+    pub fn start(&self) -> usize {
+        self.anchor.min(self.head)
+    }
+
+    pub fn end(&self) -> usize {
+        self.anchor.max(self.head)
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start()..self.end()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    /// Whether the head comes before the anchor.
+    pub fn is_backward(&self) -> bool {
+        self.head < self.anchor
+    }
+}
+
+/// Cursor movements applied to every selection at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    LineStart,
+    LineEnd,
+}
+
+/// Every cursor and selected range in a buffer, sorted and never
+/// overlapping. One of them is primary: the one the view scrolls to, and
+/// the one Ctrl+D and adding cursors continue from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Selections {
+    ranges: Vec<Selection>,
+    primary: usize,
+}
+
+impl Default for Selections {
+    fn default() -> Self {
+        Self::single(Selection::default())
+    }
+}
+
+impl Selections {
+    pub fn single(selection: Selection) -> Self {
+        Self {
+            ranges: vec![selection],
+            primary: 0,
+        }
+    }
+
+    /// Selections from `ranges` in any order; the one at `primary` stays
+    /// primary.
+    pub fn new(ranges: Vec<Selection>, primary: usize) -> Self {
+        if ranges.is_empty() {
+            return Self::default();
+        }
+        let mut selections = Self {
+            primary: primary.min(ranges.len() - 1),
+            ranges,
+        };
+        selections.normalize();
+        selections
+    }
+
+    /// A box selection spanning `anchor` to `head`: one range per line,
+    /// between the two columns. Lines too short to reach the box are
+    /// skipped, except the head's line, whose range is primary.
+    pub fn column(buffer: &TextBuffer, anchor: Position, head: Position) -> Self {
+        let last_line = buffer.line_count() - 1;
+        let (anchor_line, head_line) = (anchor.line.min(last_line), head.line.min(last_line));
+        let lines = anchor_line.min(head_line)..=anchor_line.max(head_line);
+        let left = anchor.column.min(head.column);
+        let mut ranges = Vec::new();
+        let mut primary = 0;
+        for line in lines {
+            let length = buffer.line(line).chars().count();
+            if length < left && line != head_line {
+                continue;
+            }
+            if line == head_line {
+                primary = ranges.len();
+            }
+            ranges.push(Selection::new(
+                buffer.position_to_offset(Position::new(line, anchor.column)),
+                buffer.position_to_offset(Position::new(line, head.column)),
+            ));
+        }
+        Self::new(ranges, primary)
+    }
+
+    /// In document order.
+    pub fn ranges(&self) -> &[Selection] {
+        &self.ranges
+    }
+
+    pub fn primary(&self) -> Selection {
+        self.ranges[self.primary]
+    }
+
+    pub fn primary_index(&self) -> usize {
+        self.primary
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn is_multiple(&self) -> bool {
+        self.ranges.len() > 1
+    }
+
+    /// Adds a selection and makes it primary.
+    pub fn add(&mut self, selection: Selection) {
+        self.ranges.push(selection);
+        self.primary = self.ranges.len() - 1;
+        self.normalize();
+    }
+
+    pub fn collapse_to_primary(&mut self) {
+        *self = Self::single(self.primary());
+    }
+
+    /// Keeps every selection within `buffer`, on char boundaries.
+    pub fn clamp(&mut self, buffer: &TextBuffer) {
+        for selection in &mut self.ranges {
+            selection.anchor = buffer.floor_char_boundary(selection.anchor);
+            selection.head = buffer.floor_char_boundary(selection.head);
+        }
+        self.normalize();
+    }
+
+    /// Adds a cursor one line above the topmost selection's cursor, or
+    /// below the bottommost one, keeping its column.
+    pub fn add_cursor_vertically(&mut self, buffer: &TextBuffer, above: bool) -> bool {
+        let edge = if above { self.ranges[0] } else { self.ranges[self.ranges.len() - 1] };
+        let position = buffer.offset_to_position(edge.head);
+        let line = if above {
+            match position.line.checked_sub(1) {
+                Some(line) => line,
+                None => return false,
+            }
+        } else if position.line + 1 < buffer.line_count() {
+            position.line + 1
+        } else {
+            return false;
+        };
+        let offset = buffer.position_to_offset(Position::new(line, position.column));
+        self.add(Selection::cursor(offset));
+        true
+    }
+
+    /// Ctrl+D: selects the word at an empty primary cursor, or else adds
+    /// the next occurrence of the primary selection's text (wrapping
+    /// around) as the new primary. Returns whether anything changed.
+    pub fn select_next_occurrence(&mut self, buffer: &TextBuffer) -> bool {
+        let primary = self.primary();
+        if primary.is_empty() {
+            let Some(word) = word_at(buffer, primary.head) else {
+                return false;
+            };
+            self.ranges[self.primary] = Selection::new(word.start, word.end);
+            self.normalize();
+            return true;
+        }
+        let needle = buffer.slice(primary.range());
+        let text = buffer.to_string();
+        let selected = |start: usize| self.ranges.iter().any(|s| s.start() == start && s.end() == start + needle.len());
+        let after = text[primary.end()..]
+            .match_indices(&needle)
+            .map(|(index, _)| primary.end() + index);
+        let before = text[..primary.start()].match_indices(&needle).map(|(index, _)| index);
+        let Some(start) = after.chain(before).find(|&start| !selected(start)) else {
+            return false;
+        };
+        self.add(Selection::new(start, start + needle.len()));
+        true
+    }
+
+    /// Moves every cursor; with `extend`, selections grow from their
+    /// anchors instead of collapsing.
+    pub fn move_all(&mut self, buffer: &TextBuffer, motion: Motion, extend: bool) {
+        for selection in &mut self.ranges {
+            let head = match motion {
+                Motion::Left if !extend && !selection.is_empty() => selection.start(),
+                Motion::Right if !extend && !selection.is_empty() => selection.end(),
+                Motion::Left => buffer.prev_char_boundary(selection.head),
+                Motion::Right => buffer.next_char_boundary(selection.head),
+                Motion::Up | Motion::Down => {
+                    let position = buffer.offset_to_position(selection.head);
+                    match (motion, position.line) {
+                        (Motion::Up, 0) => 0,
+                        (Motion::Up, line) => buffer.position_to_offset(Position::new(line - 1, position.column)),
+                        (_, line) if line + 1 >= buffer.line_count() => buffer.len(),
+                        (_, line) => buffer.position_to_offset(Position::new(line + 1, position.column)),
+                    }
+                }
+                Motion::LineStart => buffer.line_start(buffer.offset_to_line(selection.head)),
+                Motion::LineEnd => buffer.line_range(buffer.offset_to_line(selection.head)).end,
+            };
+            *selection = if extend {
+                Selection::new(selection.anchor, head)
+            } else {
+                Selection::cursor(head)
+            };
+        }
+        self.normalize();
+    }
+
+    /// Replaces text at every selection at once. `edit_for` gives the
+    /// range to replace and the replacement for each selection, in
+    /// document order; ranges reaching into an earlier one are trimmed.
+    /// Applies the edits to `buffer` back to front, so each offset is still
+    /// valid when applied, leaves a cursor after each replacement and
+    /// returns the edits in the order applied.
+    pub fn apply_edits(
+        &mut self,
+        buffer: &mut TextBuffer,
+        mut edit_for: impl FnMut(usize, Selection) -> (Range<usize>, String),
+    ) -> Vec<Edit> {
+        let mut planned: Vec<(Range<usize>, String)> = Vec::with_capacity(self.ranges.len());
+        for (index, selection) in self.ranges.iter().enumerate() {
+            let (mut range, text) = edit_for(index, *selection);
+            if let Some((previous, _)) = planned.last() {
+                range.start = range.start.max(previous.end);
+                range.end = range.end.max(range.start);
+            }
+            planned.push((range, text));
+        }
+        let mut shift: isize = 0;
+        for (selection, (range, text)) in self.ranges.iter_mut().zip(&planned) {
+            let cursor = (range.start as isize + shift) as usize + text.len();
+            *selection = Selection::cursor(cursor);
+            shift += text.len() as isize - range.len() as isize;
+        }
+        let edits: Vec<Edit> = planned
+            .into_iter()
+            .rev()
+            .map(|(range, text)| {
+                let edit = Edit::replace(buffer, range, text);
+                edit.apply(buffer);
+                edit
+            })
+            .collect();
+        self.normalize();
+        edits
+    }
+
+    /// Types `text` at every selection. Text with one line per selection
+    /// (e.g. pasted from a multi-cursor copy) is split between them.
+    pub fn insert(&mut self, buffer: &mut TextBuffer, text: &str) -> Vec<Edit> {
+        let lines: Vec<&str> = text.split('\n').collect();
+        let distribute = self.is_multiple() && lines.len() == self.len();
+        self.apply_edits(buffer, |index, selection| {
+            let text = if distribute { lines[index] } else { text };
+            (selection.range(), text.to_string())
+        })
+    }
+
+    /// Backspace (or Delete, going `forward`) at every selection: removes
+    /// selected text, or the char next to an empty cursor.
+    pub fn delete(&mut self, buffer: &mut TextBuffer, forward: bool) -> Vec<Edit> {
+        let ranges: Vec<Range<usize>> = self
+            .ranges
+            .iter()
+            .map(|selection| match (selection.is_empty(), forward) {
+                (false, _) => selection.range(),
+                (true, false) => buffer.prev_char_boundary(selection.head)..selection.head,
+                (true, true) => selection.head..buffer.next_char_boundary(selection.head),
+            })
+            .collect();
+        self.apply_edits(buffer, |index, _| (ranges[index].clone(), String::new()))
+    }
+
+    /// Sorts the selections and merges overlapping ones, keeping track of
+    /// the primary.
+    fn normalize(&mut self) {
+        let primary = self.ranges[self.primary];
+        self.ranges.sort_by_key(|selection| (selection.start(), selection.end()));
+        let mut merged: Vec<Selection> = Vec::with_capacity(self.ranges.len());
+        let mut primary_index = 0;
+        for selection in self.ranges.drain(..) {
+            match merged.last_mut() {
+                Some(last) if selection.start() < last.end() || selection.start() == last.start() => {
+                    let (start, end) = (last.start(), last.end().max(selection.end()));
+                    *last = if last.is_backward() { Selection::new(end, start) } else { Selection::new(start, end) };
+                }
+                _ => merged.push(selection),
+            }
+            if selection == primary {
+                primary_index = merged.len() - 1;
+            }
+        }
+        self.ranges = merged;
+        self.primary = primary_index;
+    }
+}
+
+/// The word (letters, digits and `_`) at or just before `offset`.
+pub fn word_at(buffer: &TextBuffer, offset: usize) -> Option<Range<usize>> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let line = buffer.line_range(buffer.offset_to_line(offset));
+    let text = buffer.slice(line.clone());
+    let at = offset - line.start;
+    let start = text[..at]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_word(c))
+        .last()
+        .map_or(at, |(index, _)| index);
+    let end = text[at..]
+        .char_indices()
+        .find(|&(_, c)| !is_word(c))
+        .map_or(text.len(), |(index, _)| at + index);
+    (start < end).then(|| line.start + start..line.start + end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(selections: &Selections) -> Vec<(usize, usize)> {
+        selections.ranges().iter().map(|s| (s.anchor, s.head)).collect()
+    }
+
+    #[test]
+    fn adds_cursors_above_and_below() {
+        let buffer = TextBuffer::from("abc\nde\nfghij");
+        let mut selections = Selections::single(Selection::cursor(6));
+        assert!(selections.add_cursor_vertically(&buffer, false));
+        assert_eq!(offsets(&selections), vec![(6, 6), (9, 9)]);
+        assert!(selections.add_cursor_vertically(&buffer, true));
+        assert_eq!(offsets(&selections), vec![(2, 2), (6, 6), (9, 9)]);
+        assert_eq!(selections.primary(), Selection::cursor(2));
+        assert!(!selections.add_cursor_vertically(&buffer, true));
+        assert!(!selections.add_cursor_vertically(&buffer, false));
+    }
+
+    #[test]
+    fn selects_next_occurrences_and_wraps() {
+        let buffer = TextBuffer::from("foo bar foo baz foo");
+        let mut selections = Selections::single(Selection::cursor(1));
+        assert!(selections.select_next_occurrence(&buffer));
+        assert_eq!(offsets(&selections), vec![(0, 3)]);
+        assert!(selections.select_next_occurrence(&buffer));
+        assert!(selections.select_next_occurrence(&buffer));
+        assert_eq!(offsets(&selections), vec![(0, 3), (8, 11), (16, 19)]);
+        assert_eq!(selections.primary(), Selection::new(16, 19));
+        assert!(!selections.select_next_occurrence(&buffer));
+
+        let mut selections = Selections::single(Selection::new(8, 11));
+        selections.select_next_occurrence(&buffer);
+        selections.select_next_occurrence(&buffer);
+        assert_eq!(selections.primary(), Selection::new(0, 3));
+    }
+
+    #[test]
+    fn column_selection_skips_short_lines() {
+        let buffer = TextBuffer::from("abcdef\nx\nabcdef");
+        let selections = Selections::column(&buffer, Position::new(0, 3), Position::new(2, 5));
+        assert_eq!(offsets(&selections), vec![(3, 5), (12, 14)]);
+        assert_eq!(selections.primary_index(), 1);
+
+        // The head's line is kept even when it is short.
+        let selections = Selections::column(&buffer, Position::new(0, 3), Position::new(1, 5));
+        assert_eq!(offsets(&selections), vec![(3, 5), (8, 8)]);
+        assert_eq!(selections.primary(), Selection::cursor(8));
+    }
+
+    #[test]
+    fn insert_and_delete_shift_later_cursors() {
+        let mut buffer = TextBuffer::from("a b c");
+        let mut selections = Selections::new(vec![1, 3, 5].into_iter().map(Selection::cursor).collect(), 0);
+        selections.insert(&mut buffer, "XY");
+        assert_eq!(buffer.to_string(), "aXY bXY cXY");
+        assert_eq!(offsets(&selections), vec![(3, 3), (7, 7), (11, 11)]);
+        selections.delete(&mut buffer, false);
+        assert_eq!(buffer.to_string(), "aX bX cX");
+        assert_eq!(offsets(&selections), vec![(2, 2), (5, 5), (8, 8)]);
+
+        // One line per cursor is split between them.
+        selections.insert(&mut buffer, "1\n2\n3");
+        assert_eq!(buffer.to_string(), "aX1 bX2 cX3");
+    }
+
+    #[test]
+    fn overlapping_selections_merge() {
+        let selections = Selections::new(vec![Selection::new(0, 3), Selection::new(2, 5)], 1);
+        assert_eq!(offsets(&selections), vec![(0, 5)]);
+        assert_eq!(selections.primary_index(), 0);
+
+        // Deleting towards each other leaves one cursor.
+        let mut buffer = TextBuffer::from("abc");
+        let mut selections = Selections::new(vec![Selection::cursor(1), Selection::cursor(2)], 0);
+        selections.delete(&mut buffer, false);
+        assert_eq!(buffer.to_string(), "c");
+        assert_eq!(offsets(&selections), vec![(0, 0)]);
+    }
+}
@@ -0,0 +1,376 @@
+//! The set of open editor tabs.
+//!
+//! Pinned tabs always come first in the strip. At most one tab is a preview
+//! tab: opening another file in preview replaces it, and editing it (or
+//! opening it for real) makes it permanent. Besides strip order, tabs are
+//! kept in most-recently-used order for Ctrl+Tab.
+
+use serde::{Deserialize, Serialize};
+
+use super::buffer::TextBuffer;
+use super::folding::Folds;
+use super::history::{Edit, History};
+use super::selection::{Selection, Selections};
+use crate::types::EditorTab;
+use crate::utils::file_system::events::renamed_path;
+use crate::utils::file_system::{FsResult, VirtualFileSystem};
+
+#[derive(Debug, Clone, Default)]
+pub struct TabManager {
+    tabs: Vec<EditorTab>,
+    active: Option<String>,
+    /// Tab ids, most recently used first.
+    recent: Vec<String>,
+    /// Position in `recent` while Ctrl+Tab is held.
+    cycling: Option<usize>,
+    replacements: u64,
+}
+
+impl TabManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tabs in strip order.
+    pub fn tabs(&self) -> &[EditorTab] {
+        &self.tabs
+    }
+
+    pub fn get(&self, id: &str) -> Option<&EditorTab> {
+        self.tabs.iter().find(|tab| tab.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut EditorTab> {
+        self.tabs.iter_mut().find(|tab| tab.id == id)
+    }
+
+    pub fn active_id(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    pub fn active(&self) -> Option<&EditorTab> {
+        self.get(self.active.as_deref()?)
+    }
+
+    pub fn active_mut(&mut self) -> Option<&mut EditorTab> {
+        let id = self.active.clone()?;
+        self.get_mut(&id)
+    }
+
+    /// Tab ids, most recently used first.
+    pub fn recent(&self) -> &[String] {
+        &self.recent
+    }
+
+    /// Counts the times a tab's content was replaced wholesale rather than
+    /// edited in the view (reloads, undo), so the view knows to redraw.
+    pub fn replacements(&self) -> u64 {
+        self.replacements
+    }
+
+    pub fn has_unsaved(&self) -> bool {
+        self.tabs.iter().any(|tab| tab.modified)
+    }
+
+    /// Opens `tab` and makes it active. If a tab with its id is already
+    /// open, that one is activated instead (and made permanent unless
+    /// `preview`). A new preview tab takes the place of the current preview
+    /// tab unless that one has unsaved changes.
+    pub fn open(&mut self, mut tab: EditorTab, preview: bool) {
+        let id = tab.id.clone();
+        if let Some(existing) = self.get_mut(&id) {
+            existing.preview &= preview;
+        } else {
+            tab.preview = preview && !tab.pinned;
+            let replaced = self
+                .tabs
+                .iter()
+                .position(|open| open.preview && !open.modified)
+                .filter(|_| tab.preview);
+            match replaced {
+                Some(index) => {
+                    let old = std::mem::replace(&mut self.tabs[index], tab);
+                    self.recent.retain(|recent| *recent != old.id);
+                }
+                None => self.tabs.push(tab),
+            }
+        }
+        self.activate(&id);
+    }
+
+    pub fn activate(&mut self, id: &str) -> bool {
+        if self.get(id).is_none() {
+            return false;
+        }
+        self.cycling = None;
+        self.active = Some(id.to_string());
+        self.recent.retain(|recent| recent != id);
+        self.recent.insert(0, id.to_string());
+        true
+    }
+
+    /// Closes a tab, discarding unsaved changes. Closing the active tab
+    /// activates the one used before it.
+    pub fn close(&mut self, id: &str) -> Option<EditorTab> {
+        let index = self.tabs.iter().position(|tab| tab.id == id)?;
+        let tab = self.tabs.remove(index);
+        self.recent.retain(|recent| recent != id);
+        self.cycling = None;
+        if self.active.as_deref() == Some(id) {
+            self.active = self.recent.first().cloned();
+        }
+        Some(tab)
+    }
+
+    /// Ids "Close Others" would close: every unpinned tab but `id`.
+    pub fn others(&self, id: &str) -> Vec<String> {
+        self.tabs
+            .iter()
+            .filter(|tab| tab.id != id && !tab.pinned)
+            .map(|tab| tab.id.clone())
+            .collect()
+    }
+
+    /// Ids "Close to the Right" would close: the unpinned tabs after `id`.
+    pub fn to_the_right(&self, id: &str) -> Vec<String> {
+        self.tabs
+            .iter()
+            .skip_while(|tab| tab.id != id)
+            .skip(1)
+            .filter(|tab| !tab.pinned)
+            .map(|tab| tab.id.clone())
+            .collect()
+    }
+
+    /// Makes a preview tab permanent.
+    pub fn keep(&mut self, id: &str) {
+        if let Some(tab) = self.get_mut(id) {
+            tab.preview = false;
+        }
+    }
+
+    /// Pins or unpins a tab, moving it to the end of the pinned tabs or the
+    /// start of the unpinned ones.
+    pub fn set_pinned(&mut self, id: &str, pinned: bool) {
+        let Some(index) = self.tabs.iter().position(|tab| tab.id == id) else {
+            return;
+        };
+        let mut tab = self.tabs.remove(index);
+        tab.pinned = pinned;
+        tab.preview &= !pinned;
+        let at = self.pinned_count();
+        self.tabs.insert(at, tab);
+    }
+
+    /// Moves a tab to `index` in the strip, within the pinned or unpinned
+    /// group it belongs to.
+    pub fn move_tab(&mut self, id: &str, index: usize) {
+        let Some(from) = self.tabs.iter().position(|tab| tab.id == id) else {
+            return;
+        };
+        let tab = self.tabs.remove(from);
+        let pinned = self.pinned_count();
+        let index = if tab.pinned {
+            index.min(pinned)
+        } else {
+            index.clamp(pinned, self.tabs.len())
+        };
+        self.tabs.insert(index, tab);
+    }
+
+    /// Steps through tabs in most-recently-used order (Ctrl+Tab, or
+    /// Ctrl+Shift+Tab backwards) without reordering them until
+    /// [`finish_cycle`](Self::finish_cycle).
+    pub fn cycle_recent(&mut self, forward: bool) {
+        let len = self.recent.len();
+        if len < 2 {
+            return;
+        }
+        let current = self.cycling.unwrap_or(0);
+        let next = if forward { (current + 1) % len } else { (current + len - 1) % len };
+        self.cycling = Some(next);
+        self.active = Some(self.recent[next].clone());
+    }
+
+    /// Ends a Ctrl+Tab cycle, making the tab it landed on the most recent.
+    pub fn finish_cycle(&mut self) {
+        if self.cycling.take().is_some() {
+            if let Some(id) = self.active.clone() {
+                self.activate(&id);
+            }
+        }
+    }
+
+    /// Replaces a tab's content with the file's current content in the
+    /// workspace and marks it saved. Its undo history no longer applies
+    /// and is dropped.
+    pub fn reload(&mut self, id: &str, content: &str) {
+        if let Some(tab) = self.get_mut(id) {
+            tab.content = TextBuffer::from(content);
+            tab.modified = false;
+            tab.history = History::new();
+            let primary = tab.selections.primary();
+            tab.selections = Selections::single(Selection::cursor(primary.head));
+            tab.selections.clamp(&tab.content);
+            tab.folds.update(&tab.content);
+            self.replacements += 1;
+        }
+    }
+
+    pub fn mark_saved(&mut self, id: &str) {
+        if let Some(tab) = self.get_mut(id) {
+            tab.modified = false;
+            tab.history.mark_saved();
+        }
+    }
+
+    /// Undoes the last step in the active tab. Returns whether there was
+    /// one.
+    pub fn undo(&mut self) -> bool {
+        self.step_history(History::undo)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.step_history(History::redo)
+    }
+
+    /// Edits the active tab at all of its selections at once (typing with
+    /// several cursors) as one undo step. Returns whether anything changed,
+    /// or fails if `fs` protects the tab's file against edits.
+    pub fn edit_selections(
+        &mut self,
+        fs: &VirtualFileSystem,
+        edit: impl FnOnce(&mut Selections, &mut TextBuffer) -> Vec<Edit>,
+    ) -> FsResult<bool> {
+        let Some(tab) = self.active_mut() else {
+            return Ok(false);
+        };
+        if fs.exists(&tab.id) {
+            fs.check_writable(&tab.id)?;
+        }
+        let before = tab.selections.clone();
+        let edits = edit(&mut tab.selections, &mut tab.content);
+        let edits: Vec<Edit> = edits.into_iter().filter(|edit| edit.deleted != edit.inserted).collect();
+        if edits.is_empty() {
+            return Ok(false);
+        }
+        tab.history.record_group(edits, before, tab.selections.clone());
+        tab.folds.update(&tab.content);
+        tab.modified = true;
+        tab.preview = false;
+        self.replacements += 1;
+        Ok(true)
+    }
+
+    fn step_history(&mut self, step: fn(&mut History, &mut TextBuffer) -> Option<Selections>) -> bool {
+        let Some(tab) = self.active_mut() else {
+            return false;
+        };
+        let Some(selections) = step(&mut tab.history, &mut tab.content) else {
+            return false;
+        };
+        tab.selections = selections;
+        tab.folds.update(&tab.content);
+        tab.modified = !tab.history.is_saved();
+        tab.preview = false;
+        self.replacements += 1;
+        true
+    }
+
+    /// Follows a rename of `from` to `to` in the workspace.
+    pub fn rename(&mut self, from: &str, to: &str) {
+        let renamed = |id: &mut String| {
+            if let Some(path) = renamed_path(id, from, to) {
+                *id = path;
+            }
+        };
+        for tab in &mut self.tabs {
+            if let Some(path) = renamed_path(&tab.id, from, to) {
+                tab.set_path(&path);
+            }
+        }
+        self.active.iter_mut().for_each(renamed);
+        self.recent.iter_mut().for_each(renamed);
+    }
+
+    fn pinned_count(&self) -> usize {
+        self.tabs.iter().take_while(|tab| tab.pinned).count()
+    }
+
+    /// What is saved across reloads: the tabs, their order and unsaved
+    /// content.
+    pub fn session(&self) -> TabSession {
+        TabSession {
+            tabs: self
+                .tabs
+                .iter()
+                .map(|tab| SessionTab {
+                    path: tab.id.clone(),
+                    pinned: tab.pinned,
+                    preview: tab.preview,
+                    unsaved: tab.modified.then(|| tab.content.to_string()),
+                    folded: tab.folds.lines().collect(),
+                })
+                .collect(),
+            active: self.active.clone(),
+            recent: self.recent.clone(),
+        }
+    }
+
+    /// Reopens a saved session. `read` supplies the current content of
+    /// files without unsaved changes; tabs whose file is gone are dropped.
+    pub fn restore(session: TabSession, read: impl Fn(&str) -> Option<String>) -> Self {
+        let mut manager = Self::new();
+        for saved in session.tabs {
+            let (content, modified) = match saved.unsaved {
+                Some(unsaved) => (unsaved, true),
+                None => match read(&saved.path) {
+                    Some(content) => (content, false),
+                    None => continue,
+                },
+            };
+            let mut tab = EditorTab::new(&saved.path, &content);
+            if modified {
+                tab.modified = true;
+                tab.history.forget_saved();
+            }
+            tab.pinned = saved.pinned;
+            tab.preview = saved.preview && !saved.pinned;
+            tab.folds = Folds::new(&tab.content, saved.folded);
+            manager.tabs.push(tab);
+        }
+        manager.tabs.sort_by_key(|tab| !tab.pinned);
+        for id in session.recent.iter().rev() {
+            manager.activate(id);
+        }
+        let unused: Vec<String> = manager
+            .tabs
+            .iter()
+            .filter(|tab| !manager.recent.contains(&tab.id))
+            .map(|tab| tab.id.clone())
+            .collect();
+        manager.recent.extend(unused);
+        if let Some(active) = session.active {
+            manager.activate(&active);
+        }
+        manager
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TabSession {
+    pub tabs: Vec<SessionTab>,
+    pub active: Option<String>,
+    pub recent: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionTab {
+    pub path: String,
+    pub pinned: bool,
+    pub preview: bool,
+    pub unsaved: Option<String>,
+    /// Lines with folds on them.
+    #[serde(default)]
+    pub folded: Vec<usize>,
+}
@@ -1,7 +0,0 @@
-pub struct VirtualFileSystem;
-
-impl VirtualFileSystem {
-    pub fn new() -> Self {
-        Self
-    }
-}
@@ -0,0 +1,823 @@
+//! Workspace import/export as `.zip`, `.tar` and `.tar.gz` archives.
+//!
+//! The readers and writers are implemented here on top of `miniz_oxide` so
+//! they run unchanged in wasm32. Zip64, encrypted zip entries and tar
+//! links are not supported.
+
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec_with_limit;
+
+use super::{encoding, join, normalize_path, split_parent, FsError, FsResult, VirtualFileSystem};
+use crate::types::{FileNode, FileType};
+
+/// Upper bound on the total size of extracted content, to stop
+/// decompression bombs from exhausting memory.
+const MAX_EXTRACTED_BYTES: usize = 512 * 1024 * 1024;
+const DEFLATE_LEVEL: u8 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Sniffs the format from the leading bytes of an archive.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"PK\x03\x04") || bytes.starts_with(b"PK\x05\x06") {
+            Some(ArchiveFormat::Zip)
+        } else if bytes.starts_with(&[0x1f, 0x8b]) {
+            Some(ArchiveFormat::TarGz)
+        } else if bytes.len() >= 263 && &bytes[257..262] == b"ustar" {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+        }
+    }
+}
+
+/// A single archive member. `path` is relative and `/`-separated;
+/// directories carry `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub path: String,
+    pub data: Option<Vec<u8>>,
+}
+
+impl VirtualFileSystem {
+    /// Extracts an archive below `dest` as a single undo step and returns
+    /// the number of files written. Every entry is validated before the
+    /// tree is touched, so a bad archive leaves the workspace unchanged.
+    pub fn import_archive(&mut self, dest: &str, bytes: &[u8]) -> FsResult<usize> {
+        let format = ArchiveFormat::detect(bytes)
+            .ok_or_else(|| FsError::InvalidArchive("unrecognized archive format".to_string()))?;
+        let entries = read_archive(bytes, format)?;
+        let dest = normalize_path(dest)?;
+
+        let mut planned = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let relative = sanitize_entry_path(&entry.path)?;
+            if relative.is_empty() {
+                continue;
+            }
+            planned.push((join(&dest, &relative), entry.data));
+        }
+
+        // Archives may carry locked folders such as `node_modules`, so only
+        // the destination and entries being overwritten are checked.
+        self.check_writable(&dest)?;
+        for (path, _) in &planned {
+            if self.exists(path) {
+                self.check_tree_writable(path)?;
+            }
+        }
+
+        let label = format!("Import archive into {}", dest);
+        self.without_permission_checks(|fs| {
+            fs.transaction(&label, |fs| {
+                fs.create_dir_all(&dest)?;
+                let mut files = 0;
+                for (path, data) in planned {
+                    match data {
+                        Some(data) => {
+                            fs.write_bytes(&path, data)?;
+                            files += 1;
+                        }
+                        None => fs.create_dir_all(&path)?,
+                    }
+                }
+                Ok(files)
+            })
+        })
+    }
+
+    /// Packs the subtree at `path` (the whole workspace for `/`). Entry
+    /// names are relative to `path`; empty folders are preserved.
+    pub fn export_archive(&self, path: &str, format: ArchiveFormat) -> FsResult<Vec<u8>> {
+        let normalized = normalize_path(path)?;
+        let node = self
+            .get(&normalized)
+            .ok_or_else(|| FsError::NotFound(normalized.clone()))?;
+        let mut entries = Vec::new();
+        match node.file_type {
+            FileType::Folder => collect_entries(node, "", &mut entries),
+            FileType::File => {
+                let (_, name) = split_parent(&normalized)?;
+                entries.push(ArchiveEntry {
+                    path: name,
+                    data: Some(self.read_bytes(&normalized)?.into_owned()),
+                });
+            }
+        }
+        write_archive(&entries, format)
+    }
+}
+
+fn collect_entries(node: &FileNode, prefix: &str, out: &mut Vec<ArchiveEntry>) {
+    for child in node.children.iter().flatten() {
+        let path = if prefix.is_empty() {
+            child.name.clone()
+        } else {
+            format!("{}/{}", prefix, child.name)
+        };
+        match child.file_type {
+            FileType::Folder => {
+                out.push(ArchiveEntry {
+                    path: path.clone(),
+                    data: None,
+                });
+                collect_entries(child, &path, out);
+            }
+            FileType::File => {
+                out.push(ArchiveEntry {
+                    path,
+                    data: Some(encoding::node_bytes(child).into_owned()),
+                });
+            }
+        }
+    }
+}
+
+/// Normalizes an archive member name to a relative path, rejecting
+/// absolute paths and anything that climbs out of the extraction root.
+pub fn sanitize_entry_path(name: &str) -> FsResult<String> {
+    let unified = name.replace('\\', "/");
+    let escapes = || FsError::InvalidArchive(format!("entry escapes the archive root: {}", name));
+    let bytes = unified.as_bytes();
+    if unified.starts_with('/') || (bytes.len() >= 2 && bytes[1] == b':' && bytes[0].is_ascii_alphabetic()) {
+        return Err(escapes());
+    }
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in unified.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop().ok_or_else(escapes)?;
+            }
+            other => segments.push(other),
+        }
+    }
+    Ok(segments.join("/"))
+}
+
+pub fn read_archive(bytes: &[u8], format: ArchiveFormat) -> FsResult<Vec<ArchiveEntry>> {
+    match format {
+        ArchiveFormat::Zip => read_zip(bytes),
+        ArchiveFormat::Tar => read_tar(bytes),
+        ArchiveFormat::TarGz => read_tar(&gunzip(bytes)?),
+    }
+}
+
+pub fn write_archive(entries: &[ArchiveEntry], format: ArchiveFormat) -> FsResult<Vec<u8>> {
+    match format {
+        ArchiveFormat::Zip => write_zip(entries),
+        ArchiveFormat::Tar => Ok(write_tar(entries)),
+        ArchiveFormat::TarGz => Ok(gzip(&write_tar(entries))),
+    }
+}
+
+fn invalid(reason: &str) -> FsError {
+    FsError::InvalidArchive(reason.to_string())
+}
+
+// ---------------------------------------------------------------------------
+// zip
+
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4b50;
+const ZIP_CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP_END_OF_CENTRAL_DIR: u32 = 0x0605_4b50;
+const ZIP_FLAG_ENCRYPTED: u16 = 1;
+const ZIP_FLAG_UTF8: u16 = 1 << 11;
+const ZIP_METHOD_STORED: u16 = 0;
+const ZIP_METHOD_DEFLATE: u16 = 8;
+/// 1980-01-01 00:00, the earliest DOS timestamp.
+const ZIP_DOS_DATE: u16 = (1 << 5) | 1;
+
+fn read_zip(bytes: &[u8]) -> FsResult<Vec<ArchiveEntry>> {
+    let search_start = bytes.len().saturating_sub(22 + u16::MAX as usize);
+    let eocd = (search_start..bytes.len().saturating_sub(21))
+        .rev()
+        .find(|&i| read_u32(bytes, i) == Some(ZIP_END_OF_CENTRAL_DIR))
+        .ok_or_else(|| invalid("zip end of central directory not found"))?;
+    let truncated = || invalid("truncated zip");
+    let count = read_u16(bytes, eocd + 10).ok_or_else(truncated)? as usize;
+    let cd_offset = read_u32(bytes, eocd + 16).ok_or_else(truncated)?;
+    if cd_offset == u32::MAX {
+        return Err(invalid("zip64 archives are not supported"));
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    let mut total = 0usize;
+    let mut cursor = cd_offset as usize;
+    for _ in 0..count {
+        let field = |offset: usize| {
+            cursor.checked_add(offset).and_then(|at| read_u16(bytes, at)).ok_or_else(truncated)
+        };
+        let field32 = |offset: usize| {
+            cursor.checked_add(offset).and_then(|at| read_u32(bytes, at)).ok_or_else(truncated)
+        };
+        if field32(0)? != ZIP_CENTRAL_HEADER {
+            return Err(invalid("corrupt zip central directory"));
+        }
+        let flags = field(8)?;
+        let method = field(10)?;
+        let crc = field32(16)?;
+        let compressed_size = field32(20)?;
+        let size = field32(24)?;
+        let name_len = field(28)? as usize;
+        let extra_len = field(30)? as usize;
+        let comment_len = field(32)? as usize;
+        let local_offset = field32(42)?;
+        // Offsets come from the archive, so sums of them may overflow.
+        let name_start = cursor.checked_add(46).ok_or_else(truncated)?;
+        let name_end = name_start.checked_add(name_len).ok_or_else(truncated)?;
+        let name_bytes = bytes.get(name_start..name_end).ok_or_else(truncated)?;
+        cursor = name_end.checked_add(extra_len + comment_len).ok_or_else(truncated)?;
+
+        let name = if flags & ZIP_FLAG_UTF8 != 0 {
+            String::from_utf8(name_bytes.to_vec()).map_err(|_| invalid("invalid UTF-8 entry name"))?
+        } else {
+            String::from_utf8_lossy(name_bytes).into_owned()
+        };
+        if flags & ZIP_FLAG_ENCRYPTED != 0 {
+            return Err(FsError::InvalidArchive(format!("encrypted entry: {}", name)));
+        }
+        if [compressed_size, size, local_offset].contains(&u32::MAX) {
+            return Err(invalid("zip64 archives are not supported"));
+        }
+        if name.ends_with('/') {
+            entries.push(ArchiveEntry { path: name, data: None });
+            continue;
+        }
+
+        let local = local_offset as usize;
+        if read_u32(bytes, local) != Some(ZIP_LOCAL_HEADER) {
+            return Err(FsError::InvalidArchive(format!("missing local header for {}", name)));
+        }
+        let local_field = |offset: usize| {
+            local.checked_add(offset).and_then(|at| read_u16(bytes, at)).ok_or_else(truncated)
+        };
+        let local_name_len = local_field(26)? as usize;
+        let local_extra_len = local_field(28)? as usize;
+        let start = local.checked_add(30 + local_name_len + local_extra_len).ok_or_else(truncated)?;
+        let end = start
+            .checked_add(compressed_size as usize)
+            .ok_or_else(|| invalid("corrupt zip entry size"))?;
+        let raw = bytes.get(start..end).ok_or_else(truncated)?;
+
+        total = total.saturating_add(size as usize);
+        if total > MAX_EXTRACTED_BYTES {
+            return Err(invalid("archive expands beyond the size limit"));
+        }
+        let data = match method {
+            ZIP_METHOD_STORED => raw.to_vec(),
+            ZIP_METHOD_DEFLATE => decompress_to_vec_with_limit(raw, size as usize)
+                .map_err(|_| FsError::InvalidArchive(format!("corrupt deflate data in {}", name)))?,
+            other => {
+                return Err(FsError::InvalidArchive(format!(
+                    "unsupported compression method {} for {}",
+                    other, name
+                )))
+            }
+        };
+        if data.len() != size as usize || crc32(&data) != crc {
+            return Err(FsError::InvalidArchive(format!("checksum mismatch for {}", name)));
+        }
+        entries.push(ArchiveEntry { path: name, data: Some(data) });
+    }
+    Ok(entries)
+}
+
+/// `value` as a 16-bit zip field. The field's maximum marks a zip64
+/// record, which is not written, so it does not fit either.
+fn zip_u16(value: usize, what: &str) -> FsResult<u16> {
+    u16::try_from(value)
+        .ok()
+        .filter(|&value| value != u16::MAX)
+        .ok_or_else(|| FsError::InvalidArchive(format!("{} too large for a zip archive", what)))
+}
+
+/// `value` as a 32-bit zip field, as [`zip_u16`].
+fn zip_u32(value: usize, what: &str) -> FsResult<u32> {
+    u32::try_from(value)
+        .ok()
+        .filter(|&value| value != u32::MAX)
+        .ok_or_else(|| FsError::InvalidArchive(format!("{} too large for a zip archive", what)))
+}
+
+fn write_zip(entries: &[ArchiveEntry]) -> FsResult<Vec<u8>> {
+    let count = zip_u16(entries.len(), "entry count")?;
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for entry in entries {
+        let offset = zip_u32(out.len(), "archive size")?;
+        let (name, payload, method, crc, size) = match &entry.data {
+            None => (format!("{}/", entry.path.trim_end_matches('/')), Vec::new(), ZIP_METHOD_STORED, 0, 0),
+            Some(data) => {
+                let deflated = compress_to_vec(data, DEFLATE_LEVEL);
+                let (payload, method) = if deflated.len() < data.len() {
+                    (deflated, ZIP_METHOD_DEFLATE)
+                } else {
+                    (data.clone(), ZIP_METHOD_STORED)
+                };
+                let size = zip_u32(data.len(), &format!("size of {}", entry.path))?;
+                (entry.path.clone(), payload, method, crc32(data), size)
+            }
+        };
+
+        let mut header = Vec::new();
+        push_u16(&mut header, 20);
+        push_u16(&mut header, ZIP_FLAG_UTF8);
+        push_u16(&mut header, method);
+        push_u16(&mut header, 0);
+        push_u16(&mut header, ZIP_DOS_DATE);
+        push_u32(&mut header, crc);
+        push_u32(&mut header, zip_u32(payload.len(), &format!("size of {}", name))?);
+        push_u32(&mut header, size);
+        push_u16(&mut header, zip_u16(name.len(), &format!("path {}", name))?);
+        push_u16(&mut header, 0);
+
+        push_u32(&mut out, ZIP_LOCAL_HEADER);
+        out.extend_from_slice(&header);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&payload);
+
+        push_u32(&mut central, ZIP_CENTRAL_HEADER);
+        push_u16(&mut central, 20);
+        central.extend_from_slice(&header);
+        push_u16(&mut central, 0);
+        push_u16(&mut central, 0);
+        push_u16(&mut central, 0);
+        push_u32(&mut central, if entry.data.is_none() { 0x10 } else { 0 });
+        push_u32(&mut central, offset);
+        central.extend_from_slice(name.as_bytes());
+    }
+
+    let cd_offset = zip_u32(out.len(), "archive size")?;
+    let cd_size = zip_u32(central.len(), "central directory")?;
+    out.extend_from_slice(&central);
+    push_u32(&mut out, ZIP_END_OF_CENTRAL_DIR);
+    push_u16(&mut out, 0);
+    push_u16(&mut out, 0);
+    push_u16(&mut out, count);
+    push_u16(&mut out, count);
+    push_u32(&mut out, cd_size);
+    push_u32(&mut out, cd_offset);
+    push_u16(&mut out, 0);
+    Ok(out)
+}
+
+// ---------------------------------------------------------------------------
+// tar
+
+const TAR_BLOCK: usize = 512;
+
+fn read_tar(bytes: &[u8]) -> FsResult<Vec<ArchiveEntry>> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    let mut long_name: Option<String> = None;
+    let mut total = 0usize;
+
+    while offset + TAR_BLOCK <= bytes.len() {
+        let header = &bytes[offset..offset + TAR_BLOCK];
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        if tar_checksum(header) != parse_octal(&header[148..156]) {
+            return Err(invalid("corrupt tar header checksum"));
+        }
+        let size = parse_size(&header[124..136]).ok_or_else(|| invalid("corrupt tar entry size"))?;
+        let data_start = offset + TAR_BLOCK;
+        let data_end = data_start.checked_add(size).ok_or_else(|| invalid("corrupt tar entry size"))?;
+        let data = bytes.get(data_start..data_end).ok_or_else(|| invalid("truncated tar entry"))?;
+        offset = size
+            .div_ceil(TAR_BLOCK)
+            .checked_mul(TAR_BLOCK)
+            .and_then(|padded| data_start.checked_add(padded))
+            .ok_or_else(|| invalid("corrupt tar entry size"))?;
+
+        let type_flag = header[156];
+        match type_flag {
+            b'L' => {
+                long_name = Some(c_string(data));
+                continue;
+            }
+            b'x' => {
+                if let Some(path) = pax_path(data) {
+                    long_name = Some(path);
+                }
+                continue;
+            }
+            b'g' => continue,
+            _ => {}
+        }
+
+        let name = long_name.take().unwrap_or_else(|| {
+            let name = c_string(&header[0..100]);
+            let prefix = if &header[257..262] == b"ustar" { c_string(&header[345..500]) } else { String::new() };
+            if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) }
+        });
+        match type_flag {
+            b'5' => entries.push(ArchiveEntry { path: name, data: None }),
+            b'0' | 0 | b'7' => {
+                total += size;
+                if total > MAX_EXTRACTED_BYTES {
+                    return Err(invalid("archive expands beyond the size limit"));
+                }
+                entries.push(ArchiveEntry { path: name, data: Some(data.to_vec()) });
+            }
+            // Links, devices and FIFOs have no meaning in the workspace.
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+fn write_tar(entries: &[ArchiveEntry]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for entry in entries {
+        let is_dir = entry.data.is_none();
+        let path = if is_dir {
+            format!("{}/", entry.path.trim_end_matches('/'))
+        } else {
+            entry.path.clone()
+        };
+        let data: &[u8] = entry.data.as_deref().unwrap_or(&[]);
+
+        let (prefix, name) = split_ustar_name(&path).unwrap_or_else(|| {
+            let record = pax_record("path", &path);
+            push_tar_entry(&mut out, "././@PaxHeader", "", b'x', record.as_bytes());
+            (String::new(), truncate_utf8(&path, 100).to_string())
+        });
+        push_tar_entry(&mut out, &name, &prefix, if is_dir { b'5' } else { b'0' }, data);
+    }
+    out.resize(out.len() + 2 * TAR_BLOCK, 0);
+    out
+}
+
+fn push_tar_entry(out: &mut Vec<u8>, name: &str, prefix: &str, type_flag: u8, data: &[u8]) {
+    let mut header = [0u8; TAR_BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    let mode = if type_flag == b'5' { "0000755" } else { "0000644" };
+    write_octal_field(&mut header[100..108], mode);
+    write_octal_field(&mut header[108..116], "0000000");
+    write_octal_field(&mut header[116..124], "0000000");
+    write_octal_field(&mut header[124..136], &format!("{:011o}", data.len()));
+    write_octal_field(&mut header[136..148], &format!("{:011o}", 0));
+    header[156] = type_flag;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+    header[148..156].copy_from_slice(b"        ");
+    let checksum = tar_checksum(&header);
+    write_octal_field(&mut header[148..155], &format!("{:06o}", checksum));
+    header[155] = b' ';
+
+    out.extend_from_slice(&header);
+    out.extend_from_slice(data);
+    let padding = (TAR_BLOCK - data.len() % TAR_BLOCK) % TAR_BLOCK;
+    out.resize(out.len() + padding, 0);
+}
+
+/// Splits a path into the ustar `prefix` (155 bytes) and `name` (100
+/// bytes) fields, or `None` if it needs a PAX header.
+fn split_ustar_name(path: &str) -> Option<(String, String)> {
+    if path.len() <= 100 {
+        return Some((String::new(), path.to_string()));
+    }
+    let trimmed = path.trim_end_matches('/');
+    path.char_indices()
+        .filter(|&(i, c)| c == '/' && i < trimmed.len())
+        .map(|(i, _)| i)
+        .find(|&i| i <= 155 && path.len() - i - 1 <= 100)
+        .map(|i| (path[..i].to_string(), path[i + 1..].to_string()))
+}
+
+fn pax_record(key: &str, value: &str) -> String {
+    // The length prefix counts itself, so grow it until it is stable.
+    let body = format!(" {}={}\n", key, value);
+    let mut len = body.len() + 1;
+    while len != body.len() + len.to_string().len() {
+        len = body.len() + len.to_string().len();
+    }
+    format!("{}{}", len, body)
+}
+
+fn pax_path(data: &[u8]) -> Option<String> {
+    let mut rest = data;
+    while !rest.is_empty() {
+        let space = rest.iter().position(|&b| b == b' ')?;
+        let len: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        let record = rest.get(space + 1..len)?;
+        if let Some(value) = record.strip_prefix(b"path=") {
+            return String::from_utf8(value.strip_suffix(b"\n").unwrap_or(value).to_vec()).ok();
+        }
+        rest = &rest[len..];
+    }
+    None
+}
+
+fn tar_checksum(header: &[u8]) -> u64 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u64 } else { b as u64 })
+        .sum()
+}
+
+fn parse_octal(field: &[u8]) -> u64 {
+    field
+        .iter()
+        .skip_while(|&&b| b == b' ')
+        .take_while(|&&b| (b'0'..=b'7').contains(&b))
+        .fold(0, |acc, &b| acc * 8 + (b - b'0') as u64)
+}
+
+/// Parses a size field in octal or GNU base-256 form.
+fn parse_size(field: &[u8]) -> Option<usize> {
+    if field[0] & 0x80 != 0 {
+        let mut value: u64 = (field[0] & 0x7f) as u64;
+        for &b in &field[1..] {
+            value = value.checked_mul(256)?.checked_add(b as u64)?;
+        }
+        return usize::try_from(value).ok();
+    }
+    usize::try_from(parse_octal(field)).ok()
+}
+
+fn write_octal_field(field: &mut [u8], value: &str) {
+    field[..value.len()].copy_from_slice(value.as_bytes());
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn truncate_utf8(s: &str, max: usize) -> &str {
+    let mut end = s.len().min(max);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+// ---------------------------------------------------------------------------
+// gzip
+
+const GZIP_FLAG_HCRC: u8 = 1 << 1;
+const GZIP_FLAG_EXTRA: u8 = 1 << 2;
+const GZIP_FLAG_NAME: u8 = 1 << 3;
+const GZIP_FLAG_COMMENT: u8 = 1 << 4;
+
+fn gunzip(bytes: &[u8]) -> FsResult<Vec<u8>> {
+    if bytes.len() < 18 || bytes[0..2] != [0x1f, 0x8b] || bytes[2] != 8 {
+        return Err(invalid("not a gzip stream"));
+    }
+    let flags = bytes[3];
+    let mut offset = 10;
+    if flags & GZIP_FLAG_EXTRA != 0 {
+        let len = read_u16(bytes, offset).ok_or_else(|| invalid("truncated gzip header"))? as usize;
+        offset += 2 + len;
+    }
+    for flag in [GZIP_FLAG_NAME, GZIP_FLAG_COMMENT] {
+        if flags & flag != 0 {
+            let end = bytes
+                .get(offset..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or_else(|| invalid("truncated gzip header"))?;
+            offset += end + 1;
+        }
+    }
+    if flags & GZIP_FLAG_HCRC != 0 {
+        offset += 2;
+    }
+    let body = bytes
+        .get(offset..bytes.len() - 8)
+        .ok_or_else(|| invalid("truncated gzip stream"))?;
+    let data = decompress_to_vec_with_limit(body, MAX_EXTRACTED_BYTES)
+        .map_err(|_| invalid("corrupt or oversized gzip data"))?;
+    let trailer = bytes.len() - 8;
+    if read_u32(bytes, trailer) != Some(crc32(&data)) || read_u32(bytes, trailer + 4) != Some(data.len() as u32) {
+        return Err(invalid("gzip checksum mismatch"));
+    }
+    Ok(data)
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    out.extend_from_slice(&compress_to_vec(data, DEFLATE_LEVEL));
+    push_u32(&mut out, crc32(data));
+    push_u32(&mut out, data.len() as u32);
+    out
+}
+
+// ---------------------------------------------------------------------------
+// helpers
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &b| {
+        CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes.get(offset..offset.checked_add(2)?).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes
+        .get(offset..offset.checked_add(4)?)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [ArchiveFormat; 3] = [ArchiveFormat::Zip, ArchiveFormat::Tar, ArchiveFormat::TarGz];
+
+    fn entries() -> Vec<ArchiveEntry> {
+        let file = |path: &str, data: &[u8]| ArchiveEntry {
+            path: path.to_string(),
+            data: Some(data.to_vec()),
+        };
+        let long = format!("{}/{}.txt", "nested".repeat(20), "name".repeat(30));
+        vec![
+            ArchiveEntry {
+                path: "src".to_string(),
+                data: None,
+            },
+            file("src/main.rs", "fn main() {}\n".repeat(100).as_bytes()),
+            file("empty.txt", b""),
+            file("logo.png", &[0x89, b'P', b'N', b'G', 0, 0xff, 1, 2]),
+            file("caf\u{e9}/notes.md", "# Caf\u{e9}".as_bytes()),
+            file(&long, b"deep"),
+        ]
+    }
+
+    /// Directories read back with the trailing `/` they are written with.
+    fn read_back(entries: &[ArchiveEntry]) -> Vec<ArchiveEntry> {
+        entries
+            .iter()
+            .map(|entry| ArchiveEntry {
+                path: if entry.data.is_none() { format!("{}/", entry.path) } else { entry.path.clone() },
+                data: entry.data.clone(),
+            })
+            .collect()
+    }
+
+    /// `bytes` with the little-endian `value` written at `offset`.
+    fn patched(bytes: &[u8], offset: usize, value: &[u8]) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        bytes[offset..offset + value.len()].copy_from_slice(value);
+        bytes
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        for format in FORMATS {
+            let bytes = write_archive(&entries(), format).unwrap();
+            assert_eq!(ArchiveFormat::detect(&bytes), Some(format));
+            assert_eq!(read_archive(&bytes, format).unwrap(), read_back(&entries()), "{:?}", format);
+            let name = format!("workspace.{}", format.extension());
+            assert_eq!(ArchiveFormat::from_file_name(&name.to_uppercase()), Some(format));
+        }
+        assert_eq!(ArchiveFormat::from_file_name("a.tgz"), Some(ArchiveFormat::TarGz));
+        assert_eq!(ArchiveFormat::detect(b"plain text"), None);
+    }
+
+    #[test]
+    fn workspaces_round_trip() {
+        let mut fs = VirtualFileSystem::new();
+        fs.create_dir_all("/app/src/empty").unwrap();
+        fs.create_file("/app/src/main.rs", "fn main() {}").unwrap();
+        fs.write_bytes("/app/logo.png", vec![0x89, b'P', b'N', b'G', 0]).unwrap();
+        for format in FORMATS {
+            let bytes = fs.export_archive("/app", format).unwrap();
+            let mut copy = VirtualFileSystem::new();
+            assert_eq!(copy.import_archive("/copy", &bytes).unwrap(), 2);
+            assert_eq!(copy.read("/copy/src/main.rs").unwrap(), "fn main() {}");
+            assert_eq!(&*copy.read_bytes("/copy/logo.png").unwrap(), [0x89, b'P', b'N', b'G', 0]);
+            assert!(copy.is_dir("/copy/src/empty"));
+            assert_eq!(copy.undo_label(), Some("Import archive into /copy"));
+        }
+        let single = fs.export_archive("/app/src/main.rs", ArchiveFormat::Zip).unwrap();
+        let entries = read_archive(&single, ArchiveFormat::Zip).unwrap();
+        assert_eq!(entries, [ArchiveEntry { path: "main.rs".to_string(), data: Some(b"fn main() {}".to_vec()) }]);
+    }
+
+    #[test]
+    fn entries_stay_inside_the_root() {
+        assert_eq!(sanitize_entry_path("./a//b/../c\\d").unwrap(), "a/c/d");
+        for name in ["../evil", "a/../../evil", "/etc/passwd", "C:\\evil", "\\evil"] {
+            assert!(sanitize_entry_path(name).is_err(), "{}", name);
+        }
+        let mut fs = VirtualFileSystem::new();
+        fs.create_file("/keep.txt", "kept").unwrap();
+        let hostile = [
+            ArchiveEntry { path: "fine.txt".to_string(), data: Some(b"ok".to_vec()) },
+            ArchiveEntry { path: "../escape.txt".to_string(), data: Some(b"no".to_vec()) },
+        ];
+        for format in FORMATS {
+            let bytes = write_archive(&hostile, format).unwrap();
+            assert!(matches!(fs.import_archive("/dest", &bytes), Err(FsError::InvalidArchive(_))));
+            assert_eq!(fs.file_paths(), ["/keep.txt"]);
+        }
+    }
+
+    #[test]
+    fn truncated_archives_fail() {
+        for format in FORMATS {
+            let bytes = write_archive(&entries(), format).unwrap();
+            for len in 0..bytes.len() {
+                let result = read_archive(&bytes[..len], format);
+                // A tar cut between entries reads as a shorter archive.
+                if format != ArchiveFormat::Tar {
+                    assert!(result.is_err(), "{:?} cut to {} bytes", format, len);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hostile_zip_headers_fail() {
+        let bytes = write_archive(&entries()[1..2], ArchiveFormat::Zip).unwrap();
+        let eocd = bytes.len() - 22;
+        let central = u32::from_le_bytes(bytes[eocd + 16..eocd + 20].try_into().unwrap()) as usize;
+        let expect = |bytes: Vec<u8>, reason: &str| {
+            assert_eq!(read_archive(&bytes, ArchiveFormat::Zip), Err(invalid(reason)));
+        };
+        expect(patched(&bytes, central + 42, &0xffff_fff0u32.to_le_bytes()), "missing local header for src/main.rs");
+        expect(patched(&bytes, 26, &u16::MAX.to_le_bytes()), "truncated zip");
+        expect(patched(&bytes, 28, &u16::MAX.to_le_bytes()), "truncated zip");
+        expect(patched(&bytes, central + 28, &u16::MAX.to_le_bytes()), "truncated zip");
+        expect(patched(&bytes, eocd + 10, &2u16.to_le_bytes()), "corrupt zip central directory");
+        expect(patched(&bytes, eocd + 16, &0xffff_fff0u32.to_le_bytes()), "truncated zip");
+        expect(patched(&bytes, central + 16, &0u32.to_le_bytes()), "checksum mismatch for src/main.rs");
+        expect(patched(&bytes, central + 24, &1u32.to_le_bytes()), "corrupt deflate data in src/main.rs");
+        expect(patched(&bytes, central + 8, &1u16.to_le_bytes()), "encrypted entry: src/main.rs");
+    }
+
+    #[test]
+    fn hostile_tar_headers_fail() {
+        let bytes = write_archive(&entries()[1..2], ArchiveFormat::Tar).unwrap();
+        let result = |bytes: Vec<u8>| read_archive(&bytes, ArchiveFormat::Tar);
+        assert_eq!(result(patched(&bytes, 0, b"x")), Err(invalid("corrupt tar header checksum")));
+        // A huge base-256 size, with the checksum fixed up to match.
+        let mut huge = patched(&bytes, 124, &[0x80, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        huge[148..156].copy_from_slice(b"        ");
+        let checksum = format!("{:06o}\0 ", tar_checksum(&huge[..TAR_BLOCK]));
+        huge[148..156].copy_from_slice(checksum.as_bytes());
+        assert!(result(huge).is_err());
+        let gz = write_archive(&entries()[1..2], ArchiveFormat::TarGz).unwrap();
+        let corrupt = patched(&gz, gz.len() - 1, &[0xee]);
+        assert_eq!(read_archive(&corrupt, ArchiveFormat::TarGz), Err(invalid("gzip checksum mismatch")));
+    }
+}
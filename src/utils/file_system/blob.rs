@@ -0,0 +1,339 @@
+//! Content-addressed storage for file contents.
+//!
+//! File nodes share their content through reference-counted handles, and
+//! every handle for a given content comes from the workspace's
+//! [`BlobStore`], so identical files (and copies kept by the undo journal,
+//! the trash and snapshots) share one allocation. Blobs are keyed by a
+//! git-style object id of their content as held (see [`blob_id`]), which
+//! makes "has this changed" a hash comparison.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+
+use super::metadata::now_millis;
+use super::{normalize_path, VirtualFileSystem};
+use crate::types::{BlobId, FileNode, FileType};
+
+/// The store shrinks to its live blobs whenever it grows past this many
+/// entries, or twice the count left by the previous collection.
+const MIN_COLLECT_THRESHOLD: usize = 64;
+
+/// Shared file content: text for files held as strings, bytes for binary
+/// files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Blob {
+    Text(Rc<str>),
+    Binary(Rc<[u8]>),
+}
+
+impl Blob {
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Blob::Text(text) => text.as_bytes(),
+            Blob::Binary(bytes) => bytes,
+        }
+    }
+
+    /// Number of handles to this content held outside the store.
+    fn users(&self) -> usize {
+        match self {
+            Blob::Text(text) => Rc::strong_count(text) - 1,
+            Blob::Binary(bytes) => Rc::strong_count(bytes) - 1,
+        }
+    }
+}
+
+/// Object id of a blob with this content, computed the way `git
+/// hash-object` does. Text files are hashed as held, decoded and with LF
+/// line endings, so the id only matches git's for UTF-8 files saved with
+/// LF; it says whether the content changed, not how it is encoded.
+pub fn blob_id(content: &[u8]) -> BlobId {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", content.len()).as_bytes());
+    hasher.update(content);
+    BlobId(hasher.finish())
+}
+
+/// Interning table from content hash to the shared handle. The reference
+/// count of a blob is the number of handles held outside the store; blobs
+/// nobody uses any more are dropped by [`collect`](Self::collect).
+#[derive(Debug, Clone)]
+pub(super) struct BlobStore {
+    blobs: HashMap<BlobId, Blob>,
+    collect_at: usize,
+}
+
+impl Default for BlobStore {
+    fn default() -> Self {
+        Self {
+            blobs: HashMap::new(),
+            collect_at: MIN_COLLECT_THRESHOLD,
+        }
+    }
+}
+
+impl BlobStore {
+    /// Returns the shared handle for `text`, adding it if it is new.
+    pub(super) fn intern_text(&mut self, text: Rc<str>) -> (BlobId, Rc<str>) {
+        let id = blob_id(text.as_bytes());
+        if let Some(Blob::Text(existing)) = self.blobs.get(&id) {
+            return (id, existing.clone());
+        }
+        self.add(id, Blob::Text(text.clone()));
+        (id, text)
+    }
+
+    pub(super) fn intern_bytes(&mut self, bytes: Rc<[u8]>) -> (BlobId, Rc<[u8]>) {
+        let id = blob_id(&bytes);
+        if let Some(Blob::Binary(existing)) = self.blobs.get(&id) {
+            return (id, existing.clone());
+        }
+        self.add(id, Blob::Binary(bytes.clone()));
+        (id, bytes)
+    }
+
+    /// Re-interns every file below `node`, for subtrees that were built
+    /// outside this store (e.g. deserialized trash entries).
+    pub(super) fn intern_tree(&mut self, node: &mut FileNode) {
+        if let Some(data) = node.data.take() {
+            let (id, data) = self.intern_bytes(data);
+            node.data = Some(data);
+            node.metadata.hash = Some(id);
+        } else if let Some(content) = node.content.take() {
+            let (id, content) = self.intern_text(content);
+            node.content = Some(content);
+            node.metadata.hash = Some(id);
+        }
+        for child in node.children.iter_mut().flatten() {
+            self.intern_tree(child);
+        }
+    }
+
+    pub(super) fn get(&self, id: &BlobId) -> Option<&Blob> {
+        self.blobs.get(id)
+    }
+
+    fn add(&mut self, id: BlobId, blob: Blob) {
+        self.blobs.insert(id, blob);
+        if self.blobs.len() > self.collect_at {
+            self.collect();
+        }
+    }
+
+    /// Drops blobs with no users and returns how many were dropped.
+    pub(super) fn collect(&mut self) -> usize {
+        let before = self.blobs.len();
+        self.blobs.retain(|_, blob| blob.users() > 0);
+        self.collect_at = (self.blobs.len() * 2).max(MIN_COLLECT_THRESHOLD);
+        before - self.blobs.len()
+    }
+}
+
+/// How much the store saves: `file_bytes` is what the workspace's files
+/// would take stored separately, `stored_bytes` what their distinct
+/// contents take.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlobStats {
+    pub files: usize,
+    pub unique_blobs: usize,
+    pub file_bytes: u64,
+    pub stored_bytes: u64,
+}
+
+/// The content of every file at one point in time. Holds blob handles
+/// rather than copies, so taking one costs a map of paths to hashes, and the
+/// old content stays readable for diffs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub taken_at: u64,
+    files: BTreeMap<String, (BlobId, Blob)>,
+}
+
+impl Snapshot {
+    pub fn blob_id(&self, path: &str) -> Option<BlobId> {
+        let path = normalize_path(path).ok()?;
+        self.files.get(&path).map(|(id, _)| *id)
+    }
+
+    pub fn content(&self, path: &str) -> Option<&Blob> {
+        let path = normalize_path(path).ok()?;
+        self.files.get(&path).map(|(_, blob)| blob)
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotChange {
+    Added(String),
+    Modified(String),
+    Deleted(String),
+}
+
+impl VirtualFileSystem {
+    /// Content of the blob `id`, if any file, journal entry, trash entry or
+    /// snapshot still uses it.
+    pub fn blob(&self, id: &BlobId) -> Option<&Blob> {
+        self.blobs.get(id)
+    }
+
+    pub fn blob_stats(&self) -> BlobStats {
+        let mut stats = BlobStats::default();
+        let mut seen = HashSet::new();
+        for node in self.walk() {
+            if node.file_type != FileType::File {
+                continue;
+            }
+            let size = node_blob(node).map_or(0, |blob| blob.as_bytes().len() as u64);
+            stats.files += 1;
+            stats.file_bytes += size;
+            if node.metadata.hash.is_some_and(|id| seen.insert(id)) {
+                stats.unique_blobs += 1;
+                stats.stored_bytes += size;
+            }
+        }
+        stats
+    }
+
+    /// Drops blobs that nothing refers to any more. This also happens
+    /// automatically as the store grows.
+    pub fn collect_garbage(&mut self) -> usize {
+        self.blobs.collect()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let files = self
+            .walk()
+            .into_iter()
+            .filter_map(|node| {
+                let path = node.path.clone()?;
+                let id = node.metadata.hash?;
+                Some((path, (id, node_blob(node)?)))
+            })
+            .collect();
+        Snapshot {
+            taken_at: now_millis(),
+            files,
+        }
+    }
+
+    /// Whether the file at `path` differs from its state in `snapshot`,
+    /// including having been created or deleted since.
+    pub fn is_changed_since(&self, path: &str, snapshot: &Snapshot) -> bool {
+        let current = self.get(path).and_then(|node| node.metadata.hash);
+        current != snapshot.blob_id(path)
+    }
+
+    /// Every file added, modified or deleted since `snapshot`, by path.
+    pub fn changes_since(&self, snapshot: &Snapshot) -> Vec<SnapshotChange> {
+        let current: BTreeMap<String, BlobId> = self
+            .walk()
+            .into_iter()
+            .filter_map(|node| Some((node.path.clone()?, node.metadata.hash?)))
+            .collect();
+        let mut changes = Vec::new();
+        for (path, id) in &current {
+            match snapshot.files.get(path) {
+                None => changes.push(SnapshotChange::Added(path.clone())),
+                Some((old, _)) if old != id => changes.push(SnapshotChange::Modified(path.clone())),
+                Some(_) => {}
+            }
+        }
+        for path in snapshot.files.keys() {
+            if !current.contains_key(path) {
+                changes.push(SnapshotChange::Deleted(path.clone()));
+            }
+        }
+        changes
+    }
+}
+
+fn node_blob(node: &FileNode) -> Option<Blob> {
+    match (&node.data, &node.content) {
+        (Some(data), _) => Some(Blob::Binary(data.clone())),
+        (None, Some(content)) => Some(Blob::Text(content.clone())),
+        (None, None) => None,
+    }
+}
+
+/// Minimal SHA-1, needed only for git-style object ids.
+struct Sha1 {
+    state: [u32; 5],
+    buffer: Vec<u8>,
+    length: u64,
+}
+
+impl Sha1 {
+    fn new() -> Self {
+        Self {
+            state: [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0],
+            buffer: Vec::with_capacity(64),
+            length: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+        if !self.buffer.is_empty() {
+            let take = (64 - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.buffer.len() < 64 {
+                return;
+            }
+            let block: [u8; 64] = self.buffer[..].try_into().unwrap();
+            self.compress(&block);
+            self.buffer.clear();
+        }
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            self.compress(block.try_into().unwrap());
+        }
+        self.buffer.extend_from_slice(blocks.remainder());
+    }
+
+    fn finish(mut self) -> [u8; 20] {
+        let bit_length = self.length.wrapping_mul(8);
+        let used = (self.length % 64) as usize;
+        let zeros = if used < 56 { 55 - used } else { 119 - used };
+        let mut padding = vec![0x80];
+        padding.resize(1 + zeros, 0);
+        padding.extend_from_slice(&bit_length.to_be_bytes());
+        self.update(&padding);
+        let mut out = [0u8; 20];
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        out
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = self.state;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::borrow::Cow;
+
+use super::events::FsEvent;
+use super::{metadata, normalize_path, FsError, FsResult, VirtualFileSystem};
+use crate::types::{FileMetadata, FileNode, FileType, LineEnding, TextEncoding};
+
+/// How many leading bytes are inspected when guessing UTF-16 or binary
+/// content.
+const SNIFF_LEN: usize = 8 * 1024;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decoded {
+    /// Text with line endings normalized to `\n`.
+    Text {
+        content: String,
+        encoding: TextEncoding,
+        line_ending: LineEnding,
+    },
+    Binary,
+}
+
+/// Detects the encoding and line endings of raw file content. A BOM wins;
+/// otherwise UTF-16 is recognized by its zero bytes, then UTF-8 is tried,
+/// and anything else that does not look binary is read as Latin-1. UTF-16
+/// that decodes to control characters is binary too.
+pub fn decode(bytes: &[u8]) -> Decoded {
+    let Some((encoding, text)) = decode_text(bytes) else {
+        return Decoded::Binary;
+    };
+    Decoded::Text {
+        line_ending: detect_line_ending(&text),
+        content: normalize_line_endings(text),
+        encoding,
+    }
+}
+
+/// Encodes `\n`-separated text for writing out. Characters Latin-1 cannot
+/// represent become `?`.
+pub fn encode(content: &str, encoding: TextEncoding, line_ending: LineEnding) -> Vec<u8> {
+    let text = apply_line_ending(content, line_ending);
+    let bom = bom(encoding);
+    match encoding {
+        TextEncoding::Utf8 | TextEncoding::Utf8Bom => [bom, text.as_bytes()].concat(),
+        TextEncoding::Utf16Le | TextEncoding::Utf16LeBom => {
+            let mut out = bom.to_vec();
+            out.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            out
+        }
+        TextEncoding::Utf16Be | TextEncoding::Utf16BeBom => {
+            let mut out = bom.to_vec();
+            out.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+            out
+        }
+        TextEncoding::Latin1 => text
+            .chars()
+            .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+            .collect(),
+    }
+}
+
+/// Whether `content` survives encoding without loss.
+pub fn can_encode(content: &str, encoding: TextEncoding) -> bool {
+    match encoding {
+        TextEncoding::Latin1 => content.chars().all(|c| u32::from(c) <= 0xFF),
+        _ => true,
+    }
+}
+
+/// The size `content` takes once encoded, without encoding it.
+pub fn encoded_len(content: &str, encoding: TextEncoding, line_ending: LineEnding) -> u64 {
+    let extra_line_bytes = match line_ending {
+        LineEnding::Lf => 0,
+        LineEnding::CrLf => content.matches('\n').count(),
+    };
+    let len = match encoding {
+        TextEncoding::Utf8 | TextEncoding::Utf8Bom => content.len() + extra_line_bytes,
+        TextEncoding::Utf16Le | TextEncoding::Utf16Be | TextEncoding::Utf16LeBom | TextEncoding::Utf16BeBom => {
+            2 * (content.encode_utf16().count() + extra_line_bytes)
+        }
+        TextEncoding::Latin1 => content.chars().count() + extra_line_bytes,
+    };
+    (bom(encoding).len() + len) as u64
+}
+
+/// The byte order mark `encoding` is written with, if any.
+fn bom(encoding: TextEncoding) -> &'static [u8] {
+    match encoding {
+        TextEncoding::Utf8Bom => UTF8_BOM,
+        TextEncoding::Utf16LeBom => UTF16_LE_BOM,
+        TextEncoding::Utf16BeBom => UTF16_BE_BOM,
+        TextEncoding::Utf8 | TextEncoding::Utf16Le | TextEncoding::Utf16Be | TextEncoding::Latin1 => &[],
+    }
+}
+
+pub fn detect_line_ending(text: &str) -> LineEnding {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    if crlf > lf {
+        LineEnding::CrLf
+    } else {
+        LineEnding::Lf
+    }
+}
+
+pub fn normalize_line_endings(text: String) -> String {
+    if text.contains("\r\n") {
+        text.replace("\r\n", "\n")
+    } else {
+        text
+    }
+}
+
+fn apply_line_ending(content: &str, line_ending: LineEnding) -> Cow<'_, str> {
+    match line_ending {
+        LineEnding::Lf => Cow::Borrowed(content),
+        LineEnding::CrLf => Cow::Owned(content.replace('\n', "\r\n")),
+    }
+}
+
+fn decode_text(bytes: &[u8]) -> Option<(TextEncoding, String)> {
+    if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        return String::from_utf8(rest.to_vec()).ok().map(|text| (TextEncoding::Utf8Bom, text));
+    }
+    if let Some(rest) = bytes.strip_prefix(UTF16_LE_BOM) {
+        if let Some(text) = decode_utf16(rest, true) {
+            return Some((TextEncoding::Utf16LeBom, text));
+        }
+    }
+    if let Some(rest) = bytes.strip_prefix(UTF16_BE_BOM) {
+        if let Some(text) = decode_utf16(rest, false) {
+            return Some((TextEncoding::Utf16BeBom, text));
+        }
+    }
+    if let Some(little_endian) = sniff_utf16(bytes) {
+        let encoding = if little_endian { TextEncoding::Utf16Le } else { TextEncoding::Utf16Be };
+        if let Some(text) = decode_utf16(bytes, little_endian) {
+            return Some((encoding, text));
+        }
+    }
+    if looks_binary(bytes[..bytes.len().min(SNIFF_LEN)].iter().map(|&b| u32::from(b))) {
+        return None;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some((TextEncoding::Utf8, text.to_string())),
+        Err(_) => Some((TextEncoding::Latin1, bytes.iter().map(|&b| char::from(b)).collect())),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], little_endian: bool) -> Option<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| {
+            let pair = [pair[0], pair[1]];
+            if little_endian {
+                u16::from_le_bytes(pair)
+            } else {
+                u16::from_be_bytes(pair)
+            }
+        })
+        .collect();
+    let text = String::from_utf16(&units).ok()?;
+    (!looks_binary(text.chars().take(SNIFF_LEN).map(u32::from))).then_some(text)
+}
+
+/// BOM-less UTF-16 of mostly ASCII text has an ASCII character and a zero
+/// in most pairs of bytes, and few zeros elsewhere. Returns whether it
+/// looks little-endian, if it looks like UTF-16 at all.
+fn sniff_utf16(bytes: &[u8]) -> Option<bool> {
+    let sample = &bytes[..bytes.len().min(SNIFF_LEN) & !1];
+    let pairs = sample.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+    let ascii = |b: u8| matches!(b, 0x20..=0x7E | b'\t' | b'\n' | b'\r');
+    let units = |high: usize| sample.chunks_exact(2).filter(|pair| pair[high] == 0 && ascii(pair[1 - high])).count();
+    let zeros_at = |offset: usize| sample.iter().skip(offset).step_by(2).filter(|&&b| b == 0).count();
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+    if units(1) * 10 >= pairs * 4 && even * 20 < pairs {
+        Some(true)
+    } else if units(0) * 10 >= pairs * 4 && odd * 20 < pairs {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// NUL characters, or more than one control character in ten, mark a
+/// sample of bytes or decoded characters as binary.
+fn looks_binary(sample: impl Iterator<Item = u32>) -> bool {
+    let (mut len, mut control) = (0usize, 0usize);
+    for c in sample {
+        if c == 0 {
+            return true;
+        }
+        len += 1;
+        if (c < 0x20 && !matches!(c, 0x09 | 0x0A | 0x0D | 0x0C | 0x1B)) || c == 0x7F {
+            control += 1;
+        }
+    }
+    control * 10 > len
+}
+
+/// Encoded size of a text file's content with its current format.
+pub(super) fn text_size(content: &str, metadata: &FileMetadata) -> u64 {
+    encoded_len(content, metadata.encoding, metadata.line_ending)
+}
+
+/// A file's content as written out: binary data as is, text in its
+/// original encoding and line endings.
+pub(super) fn node_bytes(node: &FileNode) -> Cow<'_, [u8]> {
+    let metadata = &node.metadata;
+    match (&node.data, &node.content) {
+        (Some(data), _) => Cow::Borrowed(&data[..]),
+        (None, Some(content)) if metadata.encoding == TextEncoding::Utf8 && metadata.line_ending == LineEnding::Lf => {
+            Cow::Borrowed(content.as_bytes())
+        }
+        (None, Some(content)) => Cow::Owned(encode(content, metadata.encoding, metadata.line_ending)),
+        (None, None) => Cow::Borrowed(&[]),
+    }
+}
+
+impl VirtualFileSystem {
+    /// Changes the encoding and line endings a text file is written out
+    /// with. The content itself is unchanged. Like permission changes, this
+    /// is not recorded as an undo step.
+    pub fn set_text_format(&mut self, path: &str, encoding: TextEncoding, line_ending: LineEnding) -> FsResult<()> {
+        let normalized = normalize_path(path)?;
+        self.check_writable(&normalized)?;
+        let node = self.get_mut(&normalized)?;
+        if node.file_type != FileType::File {
+            return Err(FsError::NotAFile(normalized));
+        }
+        let Some(content) = node.content.as_deref().filter(|_| node.data.is_none()) else {
+            return Err(FsError::NotText(normalized));
+        };
+        if !can_encode(content, encoding) {
+            return Err(FsError::Unencodable(normalized));
+        }
+        if node.metadata.encoding == encoding && node.metadata.line_ending == line_ending {
+            return Ok(());
+        }
+        node.metadata.encoding = encoding;
+        node.metadata.line_ending = line_ending;
+        metadata::touch(node);
+        self.unsaved.insert(normalized.clone());
+        self.events.emit(FsEvent::Modified { path: normalized });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(content: &str, encoding: TextEncoding, line_ending: LineEnding) -> Decoded {
+        Decoded::Text {
+            content: content.to_string(),
+            encoding,
+            line_ending,
+        }
+    }
+
+    #[test]
+    fn every_encoding_round_trips() {
+        let content = "caf\u{e9} au lait\nna\u{ef}ve r\u{e9}sum\u{e9}\n";
+        for encoding in TextEncoding::ALL {
+            for line_ending in [LineEnding::Lf, LineEnding::CrLf] {
+                let bytes = encode(content, encoding, line_ending);
+                assert_eq!(bytes.len() as u64, encoded_len(content, encoding, line_ending), "{:?}", encoding);
+                assert_eq!(decode(&bytes), text(content, encoding, line_ending), "{:?}", encoding);
+            }
+        }
+    }
+
+    #[test]
+    fn bom_is_written_only_when_read() {
+        let bomless = [0x68, 0x00, 0x69, 0x00, 0x0A, 0x00];
+        assert_eq!(decode(&bomless), text("hi\n", TextEncoding::Utf16Le, LineEnding::Lf));
+        assert_eq!(encode("hi\n", TextEncoding::Utf16Le, LineEnding::Lf), bomless);
+        let with_bom = [0xFE, 0xFF, 0x00, 0x68, 0x00, 0x69];
+        assert_eq!(decode(&with_bom), text("hi", TextEncoding::Utf16BeBom, LineEnding::Lf));
+        assert_eq!(encode("hi", TextEncoding::Utf16BeBom, LineEnding::Lf), with_bom);
+    }
+
+    #[test]
+    fn binary_is_not_taken_for_text() {
+        let units: Vec<u8> = (1..=20).flat_map(|unit| [unit, 0]).collect();
+        assert_eq!(decode(&units), Decoded::Binary);
+        assert_eq!(decode(&[0, 255, 1, 2]), Decoded::Binary);
+        assert_eq!(decode(&[0xFF, 0xFE, 0x00, 0x00, 0x01, 0x00]), Decoded::Binary);
+        assert_eq!(decode(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0x0D]), Decoded::Binary);
+        assert_eq!(decode(b"plain\r\ntext\r\n"), text("plain\ntext\n", TextEncoding::Utf8, LineEnding::CrLf));
+    }
+
+    #[test]
+    fn latin1_replaces_what_it_cannot_hold() {
+        assert!(!can_encode("\u{20ac}", TextEncoding::Latin1));
+        assert_eq!(encode("\u{20ac}5", TextEncoding::Latin1, LineEnding::Lf), b"?5");
+    }
+}
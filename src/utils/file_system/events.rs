@@ -0,0 +1,233 @@
+use std::fmt;
+use std::rc::Rc;
+
+use super::VirtualFileSystem;
+use crate::types::FileType;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsEvent {
+    Created { path: String, file_type: FileType },
+    Modified { path: String },
+    Deleted { path: String },
+    Renamed { from: String, to: String },
+}
+
+impl FsEvent {
+    /// Whether the event concerns `prefix`, something below it, or one of
+    /// its ancestors (deleting `/src` affects a subscriber to `/src/lib`).
+    pub fn affects(&self, prefix: &str) -> bool {
+        match self {
+            FsEvent::Created { path, .. } | FsEvent::Modified { path } | FsEvent::Deleted { path } => {
+                is_related(path, prefix)
+            }
+            FsEvent::Renamed { from, to } => is_related(from, prefix) || is_related(to, prefix),
+        }
+    }
+}
+
+/// Rewrites `path` for a rename of `from` to `to`, if `path` is `from` or
+/// lies below it.
+pub fn renamed_path(path: &str, from: &str, to: &str) -> Option<String> {
+    if path == from {
+        Some(to.to_string())
+    } else {
+        path.strip_prefix(from)
+            .filter(|rest| rest.starts_with('/'))
+            .map(|rest| format!("{}{}", to, rest))
+    }
+}
+
+/// Whether `path` is `ancestor` or lies below it.
+pub fn is_within(path: &str, ancestor: &str) -> bool {
+    ancestor == "/"
+        || path == ancestor
+        || path.strip_prefix(ancestor).is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn is_related(path: &str, prefix: &str) -> bool {
+    is_within(path, prefix) || is_within(prefix, path)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionId(u64);
+
+type Listener = Rc<dyn Fn(&[FsEvent])>;
+
+struct Subscriber {
+    id: SubscriptionId,
+    prefix: Option<String>,
+    listener: Listener,
+}
+
+/// Subscriber registry. Events raised while a transaction (or undo/redo)
+/// is running are held back and delivered as one coalesced batch when it
+/// finishes.
+#[derive(Default)]
+pub(super) struct EventHub {
+    subscribers: Vec<Subscriber>,
+    next_id: u64,
+    held: usize,
+    pending: Vec<FsEvent>,
+}
+
+impl fmt::Debug for EventHub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventHub")
+            .field("subscribers", &self.subscribers.len())
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+/// Listeners belong to the instance they subscribed to; a cloned workspace
+/// starts without any.
+impl Clone for EventHub {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl EventHub {
+    pub(super) fn emit(&mut self, event: FsEvent) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        self.pending.push(event);
+        if self.held == 0 {
+            self.flush();
+        }
+    }
+
+    pub(super) fn hold(&mut self) {
+        self.held += 1;
+    }
+
+    pub(super) fn release(&mut self) {
+        self.held -= 1;
+        if self.held == 0 {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        let batch = coalesce(std::mem::take(&mut self.pending));
+        if batch.is_empty() {
+            return;
+        }
+        for subscriber in &self.subscribers {
+            match &subscriber.prefix {
+                None => (subscriber.listener)(&batch),
+                Some(prefix) => {
+                    let relevant: Vec<FsEvent> = batch.iter().filter(|e| e.affects(prefix)).cloned().collect();
+                    if !relevant.is_empty() {
+                        (subscriber.listener)(&relevant);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Collapses a batch to its net effect: repeated edits to one file become a
+/// single `Modified`, edits to a file created in the same batch disappear,
+/// entries created and deleted within the batch vanish, and anything below
+/// a folder created in the batch is implied by that folder's `Created`.
+fn coalesce(events: Vec<FsEvent>) -> Vec<FsEvent> {
+    let mut out: Vec<FsEvent> = Vec::with_capacity(events.len());
+    for event in events {
+        match &event {
+            FsEvent::Created { path, .. } => {
+                let implied = out.iter().any(|e| matches!(e, FsEvent::Created { path: p, .. } if p != path && is_within(path, p)));
+                if implied {
+                    continue;
+                }
+            }
+            FsEvent::Modified { path } => {
+                let redundant = out.iter().any(|e| match e {
+                    FsEvent::Created { path: p, .. } => is_within(path, p),
+                    FsEvent::Modified { path: p } => p == path,
+                    _ => false,
+                });
+                if redundant {
+                    continue;
+                }
+            }
+            FsEvent::Deleted { path } => {
+                let created_here = out
+                    .iter()
+                    .any(|e| matches!(e, FsEvent::Created { path: p, .. } if is_within(path, p)));
+                out.retain(|e| match e {
+                    FsEvent::Modified { path: p } | FsEvent::Created { path: p, .. } => !is_within(p, path),
+                    _ => true,
+                });
+                if created_here {
+                    continue;
+                }
+            }
+            FsEvent::Renamed { .. } => {}
+        }
+        out.push(event);
+    }
+    out
+}
+
+impl VirtualFileSystem {
+    /// Registers `listener` for change batches. With a `prefix`, only events
+    /// affecting that path (see [`FsEvent::affects`]) are delivered, and
+    /// batches with none are skipped.
+    ///
+    /// Listeners run while the workspace is still being mutated, so they
+    /// must not call back into it; the Leptos bridge in `hooks` defers
+    /// signal updates for that reason.
+    pub fn subscribe(&mut self, prefix: Option<&str>, listener: impl Fn(&[FsEvent]) + 'static) -> SubscriptionId {
+        let hub = &mut self.events;
+        let id = SubscriptionId(hub.next_id);
+        hub.next_id += 1;
+        hub.subscribers.push(Subscriber {
+            id,
+            prefix: prefix.map(|p| super::normalize_path(p).unwrap_or_else(|_| p.to_string())),
+            listener: Rc::new(listener),
+        });
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriptionId) {
+        self.events.subscribers.retain(|s| s.id != id);
+    }
+
+    /// Swaps in another workspace (e.g. one restored from storage) while
+    /// keeping this instance's subscribers and locked globs. Subscribers see the old top-level
+    /// entries deleted and the new ones created in a single batch.
+    pub fn replace_with(&mut self, other: VirtualFileSystem) {
+        let removed: Vec<String> = self.read_dir("/").unwrap_or_default().iter().filter_map(|n| n.path.clone()).collect();
+        let events = std::mem::take(&mut self.events);
+        let locked = std::mem::take(&mut self.locked);
+        *self = other;
+        self.events = events;
+        self.locked = locked;
+
+        let created: Vec<(String, FileType)> = self
+            .read_dir("/")
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|n| Some((n.path.clone()?, n.file_type)))
+            .collect();
+        self.events.hold();
+        for path in removed {
+            self.events.emit(FsEvent::Deleted { path });
+        }
+        for (path, file_type) in created {
+            self.events.emit(FsEvent::Created { path, file_type });
+        }
+        self.events.release();
+    }
+
+    /// Runs `f` with event delivery held, so everything it changes reaches
+    /// subscribers as one coalesced batch.
+    pub fn batch_events<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.events.hold();
+        let result = f(self);
+        self.events.release();
+        result
+    }
+}
@@ -0,0 +1,278 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use js_sys::{Array, Object, Reflect, Uint8Array};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{IdbDatabase, IdbKeyRange, IdbOpenDbRequest, IdbRequest, IdbTransaction, IdbTransactionMode};
+
+use super::storage::{LoadCallback, StorageBackend, StorageChange, StoredEntry, StoredWorkspace};
+use super::trash::TrashEntry;
+use super::{FsError, FsResult};
+use crate::types::{FileMetadata, FilePermissions, FileType, LineEnding, TextEncoding};
+
+const DB_VERSION: u32 = 2;
+const STORE: &str = "entries";
+/// Trash entries keyed by id, each stored as one JSON document.
+const TRASH_STORE: &str = "trash";
+
+/// Browser backend storing one IndexedDB record per path, so saves are
+/// incremental and not bound by the localStorage quota.
+pub struct IndexedDbStorage {
+    db: IdbDatabase,
+}
+
+impl IndexedDbStorage {
+    /// Opens (creating on first use) the database `name`.
+    pub fn open(name: &str, done: impl FnOnce(FsResult<Self>) + 'static) {
+        let request = match web_sys::window()
+            .and_then(|window| window.indexed_db().ok().flatten())
+            .ok_or_else(|| FsError::Storage("IndexedDB is not available".to_string()))
+            .and_then(|factory| factory.open_with_u32(name, DB_VERSION).map_err(js_error))
+        {
+            Ok(request) => request,
+            Err(e) => return done(Err(e)),
+        };
+
+        let on_upgrade = {
+            let request = request.clone();
+            Closure::<dyn FnMut(web_sys::Event)>::new(move |_| {
+                if let Some(db) = open_result(&request) {
+                    // Fails harmlessly for stores created by an earlier version.
+                    let _ = db.create_object_store(STORE);
+                    let _ = db.create_object_store(TRASH_STORE);
+                }
+            })
+        };
+        request.set_onupgradeneeded(Some(on_upgrade.as_ref().unchecked_ref()));
+        on_upgrade.forget();
+
+        let done = once(done);
+        on_request_done(&request, move |request| {
+            let result = match open_result(&request) {
+                Some(db) => Ok(Self { db }),
+                None => Err(request_error(&request)),
+            };
+            done(result);
+        });
+    }
+
+    fn transaction(&self, mode: IdbTransactionMode) -> Result<IdbTransaction, JsValue> {
+        let stores = Array::of2(&STORE.into(), &TRASH_STORE.into());
+        self.db.transaction_with_str_sequence_and_mode(&stores, mode)
+    }
+}
+
+impl StorageBackend for IndexedDbStorage {
+    fn apply(&self, changes: Vec<StorageChange>) {
+        let result = (|| -> Result<(), JsValue> {
+            let transaction = self.transaction(IdbTransactionMode::Readwrite)?;
+            let store = transaction.object_store(STORE)?;
+            let trash = transaction.object_store(TRASH_STORE)?;
+            for change in changes {
+                match change {
+                    StorageChange::Put(entry) => {
+                        store.put_with_key(&entry_to_js(&entry)?, &JsValue::from_str(&entry.path))?;
+                    }
+                    StorageChange::Remove(path) => {
+                        store.delete(&JsValue::from_str(&path))?;
+                        // Keys strictly between "path/" and "path0" are the
+                        // descendants, since '0' sorts right after '/'.
+                        let descendants = IdbKeyRange::bound_with_lower_open_and_upper_open(
+                            &JsValue::from_str(&format!("{}/", path)),
+                            &JsValue::from_str(&format!("{}0", path)),
+                            false,
+                            true,
+                        )?;
+                        store.delete(&descendants)?;
+                    }
+                    StorageChange::PutTrash(entry) => {
+                        trash.put_with_key(&trash_to_js(&entry)?, &JsValue::from_f64(entry.id as f64))?;
+                    }
+                    StorageChange::RemoveTrash(id) => {
+                        trash.delete(&JsValue::from_f64(id as f64))?;
+                    }
+                }
+            }
+            Ok(())
+        })();
+        if let Err(e) = result {
+            web_sys::console::warn_1(&format!("Error saving workspace: {}", js_error(e)).into());
+        }
+    }
+
+    fn load(&self, done: LoadCallback) {
+        let requests = self.transaction(IdbTransactionMode::Readonly).and_then(|transaction| {
+            let entries = transaction.object_store(STORE)?.get_all()?;
+            let trash = transaction.object_store(TRASH_STORE)?.get_all()?;
+            Ok((entries, trash))
+        });
+        let (entries_request, trash_request) = match requests {
+            Ok(requests) => requests,
+            Err(e) => return done(Err(js_error(e))),
+        };
+        let done = once(done);
+        // Requests in one transaction complete in order, so the entries are
+        // ready once the trash request is.
+        on_request_done(&trash_request, move |trash_request| {
+            let result = read_all(&entries_request, entry_from_js, "unreadable workspace entry").and_then(|entries| {
+                let trash = read_all(&trash_request, trash_from_js, "unreadable trash entry")?;
+                Ok(StoredWorkspace { entries, trash })
+            });
+            done(result);
+        });
+    }
+}
+
+fn entry_to_js(entry: &StoredEntry) -> Result<JsValue, JsValue> {
+    let object = Object::new();
+    let kind = match entry.file_type {
+        FileType::File => "file",
+        FileType::Folder => "folder",
+    };
+    Reflect::set(&object, &"path".into(), &entry.path.as_str().into())?;
+    Reflect::set(&object, &"type".into(), &kind.into())?;
+    if let Some(content) = &entry.content {
+        Reflect::set(&object, &"content".into(), &content.as_str().into())?;
+    }
+    if let Some(data) = &entry.data {
+        Reflect::set(&object, &"data".into(), &Uint8Array::from(data.as_slice()))?;
+    }
+    if let Some(metadata) = &entry.metadata {
+        Reflect::set(&object, &"size".into(), &(metadata.size as f64).into())?;
+        Reflect::set(&object, &"created".into(), &(metadata.created as f64).into())?;
+        Reflect::set(&object, &"modified".into(), &(metadata.modified as f64).into())?;
+        Reflect::set(&object, &"mimeType".into(), &metadata.mime_type.as_str().into())?;
+        Reflect::set(&object, &"read".into(), &metadata.permissions.read.into())?;
+        Reflect::set(&object, &"write".into(), &metadata.permissions.write.into())?;
+        Reflect::set(&object, &"execute".into(), &metadata.permissions.execute.into())?;
+        Reflect::set(&object, &"encoding".into(), &encoding_id(metadata.encoding).into())?;
+        Reflect::set(&object, &"lineEnding".into(), &line_ending_id(metadata.line_ending).into())?;
+    }
+    Ok(object.into())
+}
+
+fn entry_from_js(value: &JsValue) -> Option<StoredEntry> {
+    let field = |name: &str| Reflect::get(value, &name.into()).ok().filter(|v| !v.is_undefined());
+    let file_type = match field("type")?.as_string()?.as_str() {
+        "file" => FileType::File,
+        "folder" => FileType::Folder,
+        _ => return None,
+    };
+    let number = |name: &str| field(name).and_then(|v| v.as_f64()).unwrap_or_default() as u64;
+    let flag = |name: &str| field(name).and_then(|v| v.as_bool());
+    let metadata = field("modified").map(|_| FileMetadata {
+        size: number("size"),
+        created: number("created"),
+        modified: number("modified"),
+        mime_type: field("mimeType").and_then(|v| v.as_string()).unwrap_or_default(),
+        permissions: FilePermissions {
+            read: flag("read").unwrap_or(true),
+            write: flag("write").unwrap_or(true),
+            execute: flag("execute").unwrap_or(false),
+        },
+        encoding: field("encoding")
+            .and_then(|v| v.as_string())
+            .and_then(|id| TextEncoding::ALL.into_iter().find(|&e| encoding_id(e) == id))
+            .unwrap_or_default(),
+        line_ending: match field("lineEnding").and_then(|v| v.as_string()).as_deref() {
+            Some("crlf") => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        },
+        // Recomputed from the content on load.
+        hash: None,
+    });
+    Some(StoredEntry {
+        path: field("path")?.as_string()?,
+        file_type,
+        content: field("content").and_then(|v| v.as_string()),
+        data: field("data").map(|v| Uint8Array::new(&v).to_vec()),
+        metadata,
+    })
+}
+
+fn trash_to_js(entry: &TrashEntry) -> Result<JsValue, JsValue> {
+    let json = serde_json::to_string(entry).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let object = Object::new();
+    Reflect::set(&object, &"id".into(), &JsValue::from_f64(entry.id as f64))?;
+    Reflect::set(&object, &"json".into(), &json.into())?;
+    Ok(object.into())
+}
+
+fn trash_from_js(value: &JsValue) -> Option<TrashEntry> {
+    let json = Reflect::get(value, &"json".into()).ok()?.as_string()?;
+    serde_json::from_str(&json).ok()
+}
+
+fn read_all<T>(request: &IdbRequest, parse: fn(&JsValue) -> Option<T>, what: &str) -> FsResult<Vec<T>> {
+    let value = request.result().map_err(|_| request_error(request))?;
+    Array::from(&value)
+        .iter()
+        .map(|value| parse(&value))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| FsError::Storage(what.to_string()))
+}
+
+fn encoding_id(encoding: TextEncoding) -> &'static str {
+    match encoding {
+        TextEncoding::Utf8 => "utf-8",
+        TextEncoding::Utf8Bom => "utf-8-bom",
+        // Named like the BOM variants were when UTF-16 always had one.
+        TextEncoding::Utf16Le => "utf-16le-nobom",
+        TextEncoding::Utf16Be => "utf-16be-nobom",
+        TextEncoding::Utf16LeBom => "utf-16le",
+        TextEncoding::Utf16BeBom => "utf-16be",
+        TextEncoding::Latin1 => "latin1",
+    }
+}
+
+fn line_ending_id(line_ending: LineEnding) -> &'static str {
+    match line_ending {
+        LineEnding::Lf => "lf",
+        LineEnding::CrLf => "crlf",
+    }
+}
+
+fn open_result(request: &IdbOpenDbRequest) -> Option<IdbDatabase> {
+    request.result().ok()?.dyn_into().ok()
+}
+
+/// Runs `f` once the request succeeds or fails.
+fn on_request_done<R>(request: &R, f: impl FnOnce(R) + 'static)
+where
+    R: AsRef<IdbRequest> + Clone + 'static,
+{
+    let f = once(f);
+    let target = request.clone();
+    let handler = Closure::<dyn FnMut(web_sys::Event)>::new(move |_| f(target.clone()));
+    let idb_request: &IdbRequest = request.as_ref();
+    idb_request.set_onsuccess(Some(handler.as_ref().unchecked_ref()));
+    idb_request.set_onerror(Some(handler.as_ref().unchecked_ref()));
+    handler.forget();
+}
+
+/// Wraps a one-shot callback so it can be shared by several handlers of
+/// which only the first call has any effect.
+fn once<A: 'static>(f: impl FnOnce(A) + 'static) -> impl Fn(A) + Clone + 'static {
+    let slot = Rc::new(RefCell::new(Some(f)));
+    move |arg| {
+        if let Some(f) = slot.borrow_mut().take() {
+            f(arg);
+        }
+    }
+}
+
+fn request_error<R: AsRef<IdbRequest>>(request: &R) -> FsError {
+    let message = request
+        .as_ref()
+        .error()
+        .ok()
+        .flatten()
+        .map(|e| e.message())
+        .unwrap_or_else(|| "request failed".to_string());
+    FsError::Storage(message)
+}
+
+fn js_error(value: JsValue) -> FsError {
+    FsError::Storage(value.as_string().unwrap_or_else(|| format!("{:?}", value)))
+}
@@ -0,0 +1,397 @@
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use super::trash::TrashEntry;
+use super::{split_parent, FsResult, VirtualFileSystem};
+use crate::types::FileNode;
+
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// A single reversible change to the tree. Renames are recorded as moves
+/// within the same parent.
+#[derive(Debug, Clone)]
+pub enum FsOperation {
+    Create { path: String, node: FileNode },
+    Update { path: String, before: Rc<str>, after: Rc<str> },
+    Delete { path: String, node: FileNode },
+    Move { from: String, to: String },
+    Trash { entry: TrashEntry },
+    Restore { entry: TrashEntry },
+}
+
+impl FsOperation {
+    pub fn describe(&self) -> String {
+        match self {
+            FsOperation::Create { path, .. } => format!("Create {}", path),
+            FsOperation::Update { path, .. } => format!("Edit {}", path),
+            FsOperation::Delete { path, .. } => format!("Delete {}", path),
+            FsOperation::Move { from, to } => format!("Move {} to {}", from, to),
+            FsOperation::Trash { entry } => format!("Move {} to trash", entry.original_path),
+            FsOperation::Restore { entry } => format!("Restore {}", entry.original_path),
+        }
+    }
+}
+
+/// One undo step: every operation recorded between the outermost
+/// `transaction` call's start and end, in the order they were applied.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub label: String,
+    pub operations: Vec<FsOperation>,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Journal {
+    undo_stack: VecDeque<Transaction>,
+    redo_stack: Vec<Transaction>,
+    open: Option<Transaction>,
+    depth: usize,
+    limit: usize,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self {
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            open: None,
+            depth: 0,
+            limit: DEFAULT_HISTORY_LIMIT,
+        }
+    }
+}
+
+impl Journal {
+    pub(super) fn record(&mut self, operation: FsOperation) {
+        match self.open.as_mut() {
+            Some(transaction) => transaction.operations.push(operation),
+            None => self.push(Transaction {
+                label: operation.describe(),
+                operations: vec![operation],
+            }),
+        }
+    }
+
+    fn push(&mut self, transaction: Transaction) {
+        if transaction.operations.is_empty() {
+            return;
+        }
+        self.redo_stack.clear();
+        self.undo_stack.push_back(transaction);
+        while self.undo_stack.len() > self.limit {
+            self.undo_stack.pop_front();
+        }
+    }
+
+    fn open_len(&self) -> usize {
+        self.open.as_ref().map_or(0, |t| t.operations.len())
+    }
+}
+
+impl VirtualFileSystem {
+    /// Runs `f` as a single undo step. Nested calls join the outermost
+    /// transaction. If `f` fails, every operation it applied is rolled back
+    /// before the error is returned.
+    pub fn transaction<T>(
+        &mut self,
+        label: &str,
+        f: impl FnOnce(&mut Self) -> FsResult<T>,
+    ) -> FsResult<T> {
+        if self.journal.depth == 0 {
+            self.journal.open = Some(Transaction {
+                label: label.to_string(),
+                operations: Vec::new(),
+            });
+        }
+        self.journal.depth += 1;
+        self.events.hold();
+        let mark = self.journal.open_len();
+
+        let result = f(self);
+
+        if result.is_err() {
+            let rolled_back = self
+                .journal
+                .open
+                .as_mut()
+                .map(|t| t.operations.split_off(mark))
+                .unwrap_or_default();
+            for operation in rolled_back.iter().rev() {
+                // Best effort: the tree was consistent when these ran.
+                let _ = self.revert(operation);
+            }
+        }
+
+        self.journal.depth -= 1;
+        if self.journal.depth == 0 {
+            if let Some(transaction) = self.journal.open.take() {
+                self.journal.push(transaction);
+            }
+        }
+        self.events.release();
+        result
+    }
+
+    /// Reverts the most recent transaction. Returns `Ok(false)` when there
+    /// is nothing to undo. If reverting fails partway, the tree is left as
+    /// it was and the step stays on the undo stack.
+    pub fn undo(&mut self) -> FsResult<bool> {
+        let Some(transaction) = self.journal.undo_stack.pop_back() else {
+            return Ok(false);
+        };
+        if let Err(e) = self.replay(&transaction, true) {
+            self.journal.undo_stack.push_back(transaction);
+            return Err(e);
+        }
+        self.journal.redo_stack.push(transaction);
+        Ok(true)
+    }
+
+    /// Re-applies the most recently undone transaction. Returns `Ok(false)`
+    /// when there is nothing to redo. Fails like `undo`.
+    pub fn redo(&mut self) -> FsResult<bool> {
+        let Some(transaction) = self.journal.redo_stack.pop() else {
+            return Ok(false);
+        };
+        if let Err(e) = self.replay(&transaction, false) {
+            self.journal.redo_stack.push(transaction);
+            return Err(e);
+        }
+        self.journal.undo_stack.push_back(transaction);
+        Ok(true)
+    }
+
+    /// Reverts (`backwards`) or re-applies `transaction`. When an operation
+    /// fails, the ones already replayed are turned back before the error
+    /// is returned.
+    fn replay(&mut self, transaction: &Transaction, backwards: bool) -> FsResult<()> {
+        let operations: Vec<&FsOperation> = if backwards {
+            transaction.operations.iter().rev().collect()
+        } else {
+            transaction.operations.iter().collect()
+        };
+        self.batch_events(|fs| {
+            for (done, operation) in operations.iter().enumerate() {
+                let result = if backwards { fs.revert(operation) } else { fs.apply(operation) };
+                if let Err(e) = result {
+                    for operation in operations[..done].iter().rev() {
+                        // Best effort, as in `transaction`.
+                        let _ = if backwards { fs.apply(operation) } else { fs.revert(operation) };
+                    }
+                    return Err(e);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.journal.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.journal.redo_stack.is_empty()
+    }
+
+    /// Label of the step `undo` would revert, for menu items like
+    /// "Undo Delete /src".
+    pub fn undo_label(&self) -> Option<&str> {
+        self.journal.undo_stack.back().map(|t| t.label.as_str())
+    }
+
+    pub fn redo_label(&self) -> Option<&str> {
+        self.journal.redo_stack.last().map(|t| t.label.as_str())
+    }
+
+    /// Caps the number of undo steps kept; the oldest are dropped first.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.journal.limit = limit;
+        while self.journal.undo_stack.len() > limit {
+            self.journal.undo_stack.pop_front();
+        }
+    }
+
+    pub fn clear_history(&mut self) {
+        self.journal.undo_stack.clear();
+        self.journal.redo_stack.clear();
+    }
+
+    fn apply(&mut self, operation: &FsOperation) -> FsResult<()> {
+        match operation {
+            FsOperation::Create { path, node } => {
+                let (parent, _) = split_parent(path)?;
+                self.insert(&parent, node.clone())
+            }
+            FsOperation::Update { path, after, .. } => {
+                self.set_content(path, after.clone()).map(|_| ())
+            }
+            FsOperation::Delete { path, .. } => self.detach(path).map(|_| ()),
+            FsOperation::Move { from, to } => self.relocate(from, to),
+            FsOperation::Trash { entry } => self.put_in_trash(entry),
+            FsOperation::Restore { entry } => self.take_from_trash(entry),
+        }
+    }
+
+    fn revert(&mut self, operation: &FsOperation) -> FsResult<()> {
+        match operation {
+            FsOperation::Create { path, .. } => self.detach(path).map(|_| ()),
+            FsOperation::Update { path, before, .. } => {
+                self.set_content(path, before.clone()).map(|_| ())
+            }
+            FsOperation::Delete { path, node } => {
+                let (parent, _) = split_parent(path)?;
+                self.insert(&parent, node.clone())
+            }
+            FsOperation::Move { from, to } => self.relocate(to, from),
+            FsOperation::Trash { entry } => self.take_from_trash(entry),
+            FsOperation::Restore { entry } => self.put_in_trash(entry),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::file_system::FsError;
+
+    fn workspace() -> VirtualFileSystem {
+        let mut fs = VirtualFileSystem::new();
+        fs.create_dir("/src").unwrap();
+        fs.create_file("/src/main.rs", "fn main() {}").unwrap();
+        fs.clear_history();
+        fs
+    }
+
+    #[test]
+    fn undo_and_redo_each_step() {
+        let mut fs = workspace();
+        fs.update("/src/main.rs", "fn main() { run(); }").unwrap();
+        fs.rename("/src/main.rs", "app.rs").unwrap();
+        fs.create_file("/README.md", "# App").unwrap();
+        fs.delete("/src").unwrap();
+        assert_eq!(fs.file_paths(), ["/README.md"]);
+
+        assert_eq!(fs.undo_label(), Some("Delete /src"));
+        assert!(fs.undo().unwrap());
+        assert_eq!(fs.read("/src/app.rs").unwrap(), "fn main() { run(); }");
+        assert!(fs.undo().unwrap());
+        assert!(!fs.exists("/README.md"));
+        assert_eq!(fs.undo_label(), Some("Move /src/main.rs to /src/app.rs"));
+        assert!(fs.undo().unwrap());
+        assert!(fs.undo().unwrap());
+        assert_eq!(fs.read("/src/main.rs").unwrap(), "fn main() {}");
+        assert!(!fs.undo().unwrap());
+        assert!(!fs.can_undo());
+
+        assert_eq!(fs.redo_label(), Some("Edit /src/main.rs"));
+        while fs.redo().unwrap() {}
+        assert_eq!(fs.file_paths(), ["/README.md"]);
+        assert!(!fs.can_redo());
+
+        // A new step drops what was undone.
+        assert!(fs.undo().unwrap());
+        fs.create_file("/notes.txt", "").unwrap();
+        assert!(!fs.can_redo());
+        assert!(!fs.redo().unwrap());
+    }
+
+    #[test]
+    fn transactions_are_one_step() {
+        let mut fs = workspace();
+        fs.transaction("Scaffold", |fs| {
+            fs.create_dir("/tests")?;
+            fs.transaction("Inner", |fs| fs.create_file("/tests/a.rs", "#[test] fn a() {}"))?;
+            fs.update("/src/main.rs", "mod app;")
+        })
+        .unwrap();
+        assert_eq!(fs.undo_label(), Some("Scaffold"));
+        assert!(fs.undo().unwrap());
+        assert_eq!(fs.file_paths(), ["/src/main.rs"]);
+        assert_eq!(fs.read("/src/main.rs").unwrap(), "fn main() {}");
+        assert!(!fs.can_undo());
+        assert!(fs.redo().unwrap());
+        assert_eq!(fs.read("/tests/a.rs").unwrap(), "#[test] fn a() {}");
+        assert_eq!(fs.read("/src/main.rs").unwrap(), "mod app;");
+    }
+
+    #[test]
+    fn failed_transactions_roll_back() {
+        let mut fs = workspace();
+        let result = fs.transaction("Broken", |fs| {
+            fs.create_file("/a.txt", "a")?;
+            fs.update("/src/main.rs", "changed")?;
+            fs.create_file("/a.txt", "again")
+        });
+        assert_eq!(result, Err(FsError::AlreadyExists("/a.txt".to_string())));
+        assert!(!fs.exists("/a.txt"));
+        assert_eq!(fs.read("/src/main.rs").unwrap(), "fn main() {}");
+        assert!(!fs.can_undo());
+
+        // An inner failure the outer transaction gets past rolls back only
+        // its own operations.
+        fs.transaction("Outer", |fs| {
+            fs.create_file("/b.txt", "b")?;
+            let inner = fs.transaction("Inner", |fs| {
+                fs.create_file("/c.txt", "c")?;
+                fs.delete("/missing")
+            });
+            assert!(inner.is_err());
+            Ok(())
+        })
+        .unwrap();
+        assert!(fs.exists("/b.txt") && !fs.exists("/c.txt"));
+        assert!(fs.undo().unwrap());
+        assert!(!fs.exists("/b.txt"));
+    }
+
+    #[test]
+    fn failed_undo_and_redo_leave_the_tree_as_it_was() {
+        let mut fs = workspace();
+        fs.transaction("Edit", |fs| {
+            fs.update("/src/main.rs", "fn main() { edited(); }")?;
+            fs.create_file("/src/extra.rs", "")
+        })
+        .unwrap();
+        // Changed behind the journal's back, so reverting the edit fails
+        // after the created file was already removed.
+        let main = fs.detach("/src/main.rs").unwrap();
+        assert_eq!(fs.undo(), Err(FsError::NotFound("/src/main.rs".to_string())));
+        assert!(fs.exists("/src/extra.rs"));
+        assert_eq!(fs.undo_label(), Some("Edit"));
+
+        fs.insert("/src", main).unwrap();
+        assert!(fs.undo().unwrap());
+        assert_eq!(fs.read("/src/main.rs").unwrap(), "fn main() {}");
+        assert!(!fs.exists("/src/extra.rs"));
+
+        let mut fs = workspace();
+        fs.transaction("Edit", |fs| {
+            fs.create_file("/src/extra.rs", "")?;
+            fs.update("/src/main.rs", "fn main() { edited(); }")
+        })
+        .unwrap();
+        assert!(fs.undo().unwrap());
+        let main = fs.detach("/src/main.rs").unwrap();
+        assert!(fs.redo().is_err());
+        assert!(!fs.exists("/src/extra.rs"));
+        assert_eq!(fs.redo_label(), Some("Edit"));
+        fs.insert("/src", main).unwrap();
+        assert!(fs.redo().unwrap());
+        assert_eq!(fs.read("/src/main.rs").unwrap(), "fn main() { edited(); }");
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut fs = workspace();
+        for n in 0..5 {
+            fs.create_file(&format!("/{}.txt", n), "").unwrap();
+        }
+        fs.set_history_limit(3);
+        let mut undone = 0;
+        while fs.undo().unwrap() {
+            undone += 1;
+        }
+        assert_eq!(undone, 3);
+        assert_eq!(fs.file_paths(), ["/src/main.rs", "/0.txt", "/1.txt"]);
+    }
+}
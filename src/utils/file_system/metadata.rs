@@ -0,0 +1,202 @@
+use super::encoding;
+use super::events::FsEvent;
+use super::search::Glob;
+use super::{normalize_path, split_path, FsError, FsResult, VirtualFileSystem};
+use crate::types::{FileMetadata, FileNode, FilePermissions, FileType, LineEnding, TextEncoding};
+
+/// Folders that hold generated or vendored code, locked against edits by
+/// default in the editor.
+pub const GENERATED_FOLDERS: &[&str] = &["node_modules", "vendor", "target", "dist", "build", "generated"];
+
+/// Best-effort MIME type from the file extension. Unknown extensions fall
+/// back to `text/plain` or `application/octet-stream` depending on whether
+/// the content is text.
+pub fn mime_type(name: &str, binary: bool) -> &'static str {
+    let extension = name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase());
+    let known = match extension.as_deref() {
+        Some("rs") => "text/x-rust",
+        Some("ts" | "tsx" | "mts" | "cts") => "text/typescript",
+        Some("js" | "jsx" | "mjs" | "cjs") => "text/javascript",
+        Some("py") => "text/x-python",
+        Some("sh" | "bash" | "zsh") => "application/x-sh",
+        Some("json") => "application/json",
+        Some("toml") => "application/toml",
+        Some("yaml" | "yml") => "application/yaml",
+        Some("xml") => "application/xml",
+        Some("md" | "markdown") => "text/markdown",
+        Some("html" | "htm") => "text/html",
+        Some("css") => "text/css",
+        Some("csv") => "text/csv",
+        Some("txt" | "log" | "lock") => "text/plain",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("pdf") => "application/pdf",
+        Some("wasm") => "application/wasm",
+        Some("zip") => "application/zip",
+        Some("tar") => "application/x-tar",
+        Some("gz" | "tgz") => "application/gzip",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        _ => "",
+    };
+    match (known, binary) {
+        ("", true) => "application/octet-stream",
+        ("", false) => "text/plain",
+        (mime, _) => mime,
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(super) fn now_millis() -> u64 {
+    js_sys::Date::now() as u64
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(super) fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+pub(super) fn new_metadata(name: &str, file_type: FileType) -> FileMetadata {
+    let now = now_millis();
+    let (mime_type, permissions) = match file_type {
+        FileType::Folder => (
+            "inode/directory",
+            FilePermissions {
+                execute: true,
+                ..FilePermissions::default()
+            },
+        ),
+        FileType::File => (mime_type(name, false), FilePermissions::default()),
+    };
+    FileMetadata {
+        size: 0,
+        created: now,
+        modified: now,
+        mime_type: mime_type.to_string(),
+        permissions,
+        encoding: TextEncoding::default(),
+        line_ending: LineEnding::default(),
+        hash: None,
+    }
+}
+
+/// Refreshes size and modification time after the content of `node`
+/// changed.
+pub(super) fn touch(node: &mut FileNode) {
+    let binary = node.data.is_some();
+    node.metadata.size = match (&node.data, &node.content) {
+        (Some(data), _) => data.len() as u64,
+        (None, Some(content)) => encoding::text_size(content, &node.metadata),
+        (None, None) => 0,
+    };
+    node.metadata.modified = now_millis();
+    if node.file_type == FileType::File {
+        node.metadata.mime_type = mime_type(&node.name, binary).to_string();
+    }
+}
+
+pub(super) fn check_readable(path: &str, node: &FileNode) -> FsResult<()> {
+    if node.metadata.permissions.read {
+        Ok(())
+    } else {
+        Err(FsError::PermissionDenied(path.to_string()))
+    }
+}
+
+impl VirtualFileSystem {
+    pub fn metadata(&self, path: &str) -> FsResult<&FileMetadata> {
+        let normalized = normalize_path(path)?;
+        self.get(&normalized)
+            .map(|node| &node.metadata)
+            .ok_or(FsError::NotFound(normalized))
+    }
+
+    /// Changes the permissions of `path`. A folder without `write` protects
+    /// everything below it. Not recorded as an undo step, and allowed on
+    /// protected entries so that they can be unlocked again.
+    pub fn set_permissions(&mut self, path: &str, permissions: FilePermissions) -> FsResult<()> {
+        let normalized = normalize_path(path)?;
+        let node = self.get_mut(&normalized)?;
+        if node.metadata.permissions == permissions {
+            return Ok(());
+        }
+        node.metadata.permissions = permissions;
+        self.unsaved.insert(normalized.clone());
+        self.events.emit(FsEvent::Modified { path: normalized });
+        Ok(())
+    }
+
+    /// Locks every entry matching one of `globs` (and everything below it)
+    /// against edits, whatever its own permissions say. Replaces any
+    /// previously locked globs.
+    pub fn set_locked_globs(&mut self, globs: &[String]) -> FsResult<()> {
+        self.locked = globs.iter().map(|glob| Glob::new(glob)).collect::<FsResult<_>>()?;
+        Ok(())
+    }
+
+    pub fn is_writable(&self, path: &str) -> bool {
+        self.check_writable(path).is_ok()
+    }
+
+    /// Fails with [`FsError::PermissionDenied`] naming the entry that
+    /// protects `path`: the path itself, a read-only ancestor, or a locked
+    /// folder. Paths that do not exist yet are checked through their
+    /// ancestors.
+    pub fn check_writable(&self, path: &str) -> FsResult<()> {
+        if self.permission_checks_suspended {
+            return Ok(());
+        }
+        let segments = split_path(path)?;
+        let mut node = Some(&self.root);
+        let mut current = String::new();
+        for segment in &segments {
+            current.push('/');
+            current.push_str(segment);
+            node = node.and_then(|parent| parent.children.iter().flatten().find(|child| &child.name == segment));
+            let read_only = node.is_some_and(|node| !node.metadata.permissions.write);
+            if read_only || self.locked.iter().any(|glob| glob.matches(&current)) {
+                return Err(FsError::PermissionDenied(current));
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`check_writable`](Self::check_writable), but also fails if
+    /// anything below `path` is protected, as deleting or moving it would
+    /// take the protected entry along.
+    pub(super) fn check_tree_writable(&self, path: &str) -> FsResult<()> {
+        self.check_writable(path)?;
+        if self.permission_checks_suspended {
+            return Ok(());
+        }
+        fn visit(fs: &VirtualFileSystem, node: &FileNode) -> FsResult<()> {
+            for child in node.children.iter().flatten() {
+                let path = child.path.clone().unwrap_or_default();
+                if !child.metadata.permissions.write || fs.locked.iter().any(|glob| glob.matches(&path)) {
+                    return Err(FsError::PermissionDenied(path));
+                }
+                visit(fs, child)?;
+            }
+            Ok(())
+        }
+        match self.get(path) {
+            Some(node) => visit(self, node),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs `f` with permission checks disabled, for bulk operations that
+    /// validated their targets up front.
+    pub(super) fn without_permission_checks<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let previous = std::mem::replace(&mut self.permission_checks_suspended, true);
+        let result = f(self);
+        self.permission_checks_suspended = previous;
+        result
+    }
+}
@@ -0,0 +1,592 @@
+pub mod archive;
+pub mod blob;
+pub mod encoding;
+pub mod events;
+pub mod indexed_db;
+pub mod journal;
+pub mod metadata;
+pub mod search;
+pub mod storage;
+pub mod trash;
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::types::{FileNode, FileType};
+
+use blob::BlobStore;
+use encoding::Decoded;
+use events::{EventHub, FsEvent};
+use journal::{FsOperation, Journal};
+use search::Glob;
+use trash::Trash;
+
+/// Errors returned by [`VirtualFileSystem`] operations. Each variant carries
+/// the normalized path (or name) that caused the failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsError {
+    NotFound(String),
+    AlreadyExists(String),
+    NotADirectory(String),
+    NotAFile(String),
+    NotText(String),
+    InvalidName(String),
+    InvalidPath(String),
+    InvalidPattern(String),
+    InvalidArchive(String),
+    PermissionDenied(String),
+    Unencodable(String),
+    Storage(String),
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsError::NotFound(path) => write!(f, "No such file or directory: {}", path),
+            FsError::AlreadyExists(path) => write!(f, "File already exists: {}", path),
+            FsError::NotADirectory(path) => write!(f, "Not a directory: {}", path),
+            FsError::NotAFile(path) => write!(f, "Not a file: {}", path),
+            FsError::NotText(path) => write!(f, "Binary file cannot be opened as text: {}", path),
+            FsError::InvalidName(name) => write!(f, "Invalid file name: {:?}", name),
+            FsError::InvalidPath(path) => write!(f, "Invalid path: {}", path),
+            FsError::InvalidPattern(reason) => write!(f, "Invalid search pattern: {}", reason),
+            FsError::InvalidArchive(reason) => write!(f, "Invalid archive: {}", reason),
+            FsError::PermissionDenied(path) => write!(f, "Permission denied: {} is write-protected", path),
+            FsError::Unencodable(path) => write!(f, "Content of {} cannot be represented in that encoding", path),
+            FsError::Storage(reason) => write!(f, "Workspace storage error: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for FsError {}
+
+pub type FsResult<T> = Result<T, FsError>;
+
+/// In-memory workspace tree. Paths are `/`-separated and rooted at `/`;
+/// a leading slash is optional on input, and every node stores its
+/// normalized absolute path in `FileNode::path`.
+#[derive(Debug, Clone)]
+pub struct VirtualFileSystem {
+    root: FileNode,
+    journal: Journal,
+    /// Paths created or changed since the last storage flush.
+    unsaved: BTreeSet<String>,
+    /// Paths removed since the last storage flush.
+    removed: BTreeSet<String>,
+    events: EventHub,
+    blobs: BlobStore,
+    trash: Trash,
+    /// Globs of folders locked against edits, see
+    /// [`set_locked_globs`](Self::set_locked_globs).
+    locked: Vec<Glob>,
+    permission_checks_suspended: bool,
+}
+
+impl Default for VirtualFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualFileSystem {
+    pub fn new() -> Self {
+        Self {
+            root: FileNode {
+                name: "workspace".to_string(),
+                file_type: FileType::Folder,
+                path: Some("/".to_string()),
+                children: Some(Vec::new()),
+                modified: false,
+                content: None,
+                data: None,
+                metadata: metadata::new_metadata("workspace", FileType::Folder),
+            },
+            journal: Journal::default(),
+            unsaved: BTreeSet::new(),
+            removed: BTreeSet::new(),
+            events: EventHub::default(),
+            blobs: BlobStore::default(),
+            trash: Trash::default(),
+            locked: Vec::new(),
+            permission_checks_suspended: false,
+        }
+    }
+
+    pub fn root(&self) -> &FileNode {
+        &self.root
+    }
+
+    pub fn get(&self, path: &str) -> Option<&FileNode> {
+        let segments = split_path(path).ok()?;
+        find(&self.root, &segments)
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.get(path).is_some()
+    }
+
+    pub fn is_dir(&self, path: &str) -> bool {
+        matches!(self.get(path), Some(node) if node.file_type == FileType::Folder)
+    }
+
+    pub fn is_file(&self, path: &str) -> bool {
+        matches!(self.get(path), Some(node) if node.file_type == FileType::File)
+    }
+
+    pub fn create_file(&mut self, path: &str, content: impl Into<String>) -> FsResult<()> {
+        let (parent, name) = split_parent(path)?;
+        let node = new_node(&parent, &name, FileType::File, Some(content.into()));
+        self.insert_recorded(&parent, node)
+    }
+
+    pub fn create_dir(&mut self, path: &str) -> FsResult<()> {
+        let (parent, name) = split_parent(path)?;
+        let node = new_node(&parent, &name, FileType::Folder, None);
+        self.insert_recorded(&parent, node)
+    }
+
+    /// Creates `path` and any missing ancestors. Succeeds if the directory
+    /// already exists.
+    pub fn create_dir_all(&mut self, path: &str) -> FsResult<()> {
+        let segments = split_path(path)?;
+        let label = format!("Create /{}", segments.join("/"));
+        self.transaction(&label, |fs| {
+            let mut current = String::new();
+            for segment in &segments {
+                let parent = if current.is_empty() { "/".to_string() } else { current.clone() };
+                current = join(&parent, segment);
+                match fs.get(&current) {
+                    Some(node) if node.file_type == FileType::Folder => {}
+                    Some(_) => return Err(FsError::NotADirectory(current)),
+                    None => {
+                        let node = new_node(&parent, segment, FileType::Folder, None);
+                        fs.insert_recorded(&parent, node)?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Creates or overwrites the file at `path`, creating parent
+    /// directories as needed. Recorded as a single undo step.
+    pub fn write(&mut self, path: &str, content: impl Into<String>) -> FsResult<()> {
+        let (parent, name) = split_parent(path)?;
+        let path = join(&parent, &name);
+        let content = content.into();
+        self.transaction(&format!("Write {}", path), |fs| {
+            fs.create_dir_all(&parent)?;
+            match fs.get(&path) {
+                Some(node) if node.data.is_some() => {
+                    let previous = fs.delete(&path)?.metadata;
+                    let mut node = new_node(&parent, &name, FileType::File, Some(content));
+                    node.metadata.created = previous.created;
+                    node.metadata.permissions = previous.permissions;
+                    fs.insert_recorded(&parent, node)
+                }
+                Some(_) => fs.update(&path, content).map(|_| ()),
+                None => fs.create_file(&path, content),
+            }
+        })
+    }
+
+    /// Like [`write`](Self::write), but accepts raw file content. Text is
+    /// decoded and its encoding and line endings recorded so that
+    /// [`read_bytes`](Self::read_bytes) reproduces them; anything else is
+    /// stored as a binary file.
+    pub fn write_bytes(&mut self, path: &str, bytes: Vec<u8>) -> FsResult<()> {
+        let (parent, name) = split_parent(path)?;
+        let path = join(&parent, &name);
+        if let Decoded::Text {
+            content,
+            encoding,
+            line_ending,
+        } = encoding::decode(&bytes)
+        {
+            return self.transaction(&format!("Write {}", path), |fs| {
+                fs.write(&path, content)?;
+                let node = fs.get_mut(&path)?;
+                node.metadata.encoding = encoding;
+                node.metadata.line_ending = line_ending;
+                metadata::touch(node);
+                Ok(())
+            });
+        }
+        self.transaction(&format!("Write {}", path), |fs| {
+            fs.create_dir_all(&parent)?;
+            let previous = match fs.get(&path) {
+                Some(_) => Some(fs.delete(&path)?.metadata),
+                None => None,
+            };
+            let mut node = new_node(&parent, &name, FileType::File, None);
+            node.content = None;
+            node.data = Some(Rc::from(bytes));
+            metadata::touch(&mut node);
+            if let Some(previous) = previous {
+                node.metadata.created = previous.created;
+                node.metadata.permissions = previous.permissions;
+            }
+            fs.insert_recorded(&parent, node)
+        })
+    }
+
+    pub fn read(&self, path: &str) -> FsResult<&str> {
+        let normalized = normalize_path(path)?;
+        let node = self.get(&normalized).ok_or_else(|| FsError::NotFound(normalized.clone()))?;
+        if node.file_type != FileType::File {
+            return Err(FsError::NotAFile(normalized));
+        }
+        if node.data.is_some() {
+            return Err(FsError::NotText(normalized));
+        }
+        metadata::check_readable(&normalized, node)?;
+        Ok(node.content.as_deref().unwrap_or(""))
+    }
+
+    /// File content as bytes, for text and binary files alike. Text comes
+    /// back in the file's own encoding and line endings.
+    pub fn read_bytes(&self, path: &str) -> FsResult<Cow<'_, [u8]>> {
+        let normalized = normalize_path(path)?;
+        let node = self.get(&normalized).ok_or_else(|| FsError::NotFound(normalized.clone()))?;
+        if node.file_type != FileType::File {
+            return Err(FsError::NotAFile(normalized));
+        }
+        metadata::check_readable(&normalized, node)?;
+        Ok(encoding::node_bytes(node))
+    }
+
+    /// Replaces the content of an existing file and returns the previous
+    /// content.
+    pub fn update(&mut self, path: &str, content: impl Into<String>) -> FsResult<String> {
+        let normalized = normalize_path(path)?;
+        let after = encoding::normalize_line_endings(content.into());
+        self.check_writable(&normalized)?;
+        let (before, after) = self.set_content(&normalized, Rc::from(after))?;
+        let previous = before.to_string();
+        self.journal.record(FsOperation::Update {
+            path: normalized,
+            before,
+            after,
+        });
+        Ok(previous)
+    }
+
+    /// Removes a file or directory (recursively) and returns the detached
+    /// subtree.
+    pub fn delete(&mut self, path: &str) -> FsResult<FileNode> {
+        let normalized = normalize_path(path)?;
+        self.check_tree_writable(&normalized)?;
+        let node = self.detach(&normalized)?;
+        self.journal.record(FsOperation::Delete {
+            path: normalized,
+            node: node.clone(),
+        });
+        Ok(node)
+    }
+
+    /// Renames an entry in place and returns its new path.
+    pub fn rename(&mut self, path: &str, new_name: &str) -> FsResult<String> {
+        let (parent, _) = split_parent(path)?;
+        validate_name(new_name)?;
+        self.move_entry(path, &join(&parent, new_name))
+    }
+
+    /// Moves an entry to the full destination path `to` and returns it.
+    /// The destination's parent must already exist.
+    pub fn move_entry(&mut self, from: &str, to: &str) -> FsResult<String> {
+        let from = normalize_path(from)?;
+        let (to_parent, to_name) = split_parent(to)?;
+        let to = join(&to_parent, &to_name);
+        if from == "/" {
+            return Err(FsError::InvalidPath(from));
+        }
+        if to == from {
+            return Ok(to);
+        }
+        if to.starts_with(&format!("{}/", from)) {
+            return Err(FsError::InvalidPath(to));
+        }
+        if !self.exists(&from) {
+            return Err(FsError::NotFound(from));
+        }
+        if self.exists(&to) {
+            return Err(FsError::AlreadyExists(to));
+        }
+        self.children_mut(&to_parent)?;
+        self.check_tree_writable(&from)?;
+        self.check_writable(&to)?;
+
+        self.relocate(&from, &to)?;
+        self.journal.record(FsOperation::Move {
+            from,
+            to: to.clone(),
+        });
+        Ok(to)
+    }
+
+    pub fn read_dir(&self, path: &str) -> FsResult<&[FileNode]> {
+        let normalized = normalize_path(path)?;
+        let node = self.get(&normalized).ok_or_else(|| FsError::NotFound(normalized.clone()))?;
+        match (&node.file_type, &node.children) {
+            (FileType::Folder, Some(children)) => Ok(children),
+            (FileType::Folder, None) => Ok(&[]),
+            (FileType::File, _) => Err(FsError::NotADirectory(normalized)),
+        }
+    }
+
+    /// Depth-first list of every node below the root, in display order.
+    pub fn walk(&self) -> Vec<&FileNode> {
+        fn visit<'a>(node: &'a FileNode, out: &mut Vec<&'a FileNode>) {
+            for child in node.children.iter().flatten() {
+                out.push(child);
+                visit(child, out);
+            }
+        }
+        let mut out = Vec::new();
+        visit(&self.root, &mut out);
+        out
+    }
+
+    /// Paths of every file (not directory) in the workspace.
+    pub fn file_paths(&self) -> Vec<String> {
+        self.walk()
+            .into_iter()
+            .filter(|node| node.file_type == FileType::File)
+            .filter_map(|node| node.path.clone())
+            .collect()
+    }
+
+    fn get_mut(&mut self, path: &str) -> FsResult<&mut FileNode> {
+        let segments = split_path(path)?;
+        find_mut(&mut self.root, &segments).ok_or_else(|| FsError::NotFound(path.to_string()))
+    }
+
+    fn children_mut(&mut self, path: &str) -> FsResult<&mut Vec<FileNode>> {
+        let node = self.get_mut(path)?;
+        if node.file_type != FileType::Folder {
+            return Err(FsError::NotADirectory(path.to_string()));
+        }
+        Ok(node.children.get_or_insert_with(Vec::new))
+    }
+
+    fn insert_recorded(&mut self, parent: &str, mut node: FileNode) -> FsResult<()> {
+        let path = join(parent, &node.name);
+        self.check_writable(parent)?;
+        self.blobs.intern_tree(&mut node);
+        self.insert(parent, node.clone())?;
+        self.journal.record(FsOperation::Create { path, node });
+        Ok(())
+    }
+
+    // The helpers below mutate the tree without touching the journal; they
+    // are shared by the public operations and by undo/redo replay.
+
+    /// Returns the previous content and the (interned) new one.
+    fn set_content(&mut self, path: &str, content: Rc<str>) -> FsResult<(Rc<str>, Rc<str>)> {
+        let (hash, content) = self.blobs.intern_text(content);
+        let node = self.get_mut(path)?;
+        if node.file_type != FileType::File {
+            return Err(FsError::NotAFile(path.to_string()));
+        }
+        if node.data.is_some() {
+            return Err(FsError::NotText(path.to_string()));
+        }
+        node.modified = true;
+        let previous = node.content.replace(content.clone()).unwrap_or_else(|| Rc::from(""));
+        node.metadata.hash = Some(hash);
+        metadata::touch(node);
+        self.unsaved.insert(path.to_string());
+        self.events.emit(FsEvent::Modified {
+            path: path.to_string(),
+        });
+        Ok((previous, content))
+    }
+
+    fn detach(&mut self, path: &str) -> FsResult<FileNode> {
+        let node = self.detach_node(path)?;
+        self.events.emit(FsEvent::Deleted {
+            path: node.path.clone().unwrap_or_else(|| path.to_string()),
+        });
+        Ok(node)
+    }
+
+    fn insert(&mut self, parent: &str, node: FileNode) -> FsResult<()> {
+        let event = FsEvent::Created {
+            path: join(parent, &node.name),
+            file_type: node.file_type,
+        };
+        self.insert_node(parent, node)?;
+        self.events.emit(event);
+        Ok(())
+    }
+
+    fn relocate(&mut self, from: &str, to: &str) -> FsResult<()> {
+        let (to_parent, to_name) = split_parent(to)?;
+        let mut node = self.detach_node(from)?;
+        node.name = to_name;
+        if node.file_type == FileType::File {
+            node.metadata.mime_type = metadata::mime_type(&node.name, node.data.is_some()).to_string();
+        }
+        set_paths(&mut node, &to_parent);
+        self.insert_node(&to_parent, node)?;
+        self.events.emit(FsEvent::Renamed {
+            from: from.to_string(),
+            to: to.to_string(),
+        });
+        Ok(())
+    }
+
+    fn detach_node(&mut self, path: &str) -> FsResult<FileNode> {
+        let (parent, name) = split_parent(path)?;
+        let children = self.children_mut(&parent)?;
+        let index = children
+            .iter()
+            .position(|child| child.name == name)
+            .ok_or_else(|| FsError::NotFound(join(&parent, &name)))?;
+        let node = children.remove(index);
+        self.removed.insert(join(&parent, &name));
+        Ok(node)
+    }
+
+    fn insert_node(&mut self, parent: &str, node: FileNode) -> FsResult<()> {
+        let mut touched = BTreeSet::new();
+        mark_unsaved(&node, parent, &mut touched);
+        let children = self.children_mut(parent)?;
+        if children.iter().any(|child| child.name == node.name) {
+            return Err(FsError::AlreadyExists(join(parent, &node.name)));
+        }
+        let index = children
+            .binary_search_by(|child| display_order(child).cmp(&display_order(&node)))
+            .unwrap_or_else(|index| index);
+        children.insert(index, node);
+        self.unsaved.extend(touched);
+        Ok(())
+    }
+}
+
+fn mark_unsaved(node: &FileNode, parent: &str, unsaved: &mut BTreeSet<String>) {
+    let path = join(parent, &node.name);
+    for child in node.children.iter().flatten() {
+        mark_unsaved(child, &path, unsaved);
+    }
+    unsaved.insert(path);
+}
+
+/// Normalizes a path to the absolute `/a/b` form used as node keys.
+pub fn normalize_path(path: &str) -> FsResult<String> {
+    let segments = split_path(path)?;
+    Ok(format!("/{}", segments.join("/")))
+}
+
+/// Returns the normalized parent directory and final name of `path`.
+pub fn split_parent(path: &str) -> FsResult<(String, String)> {
+    let mut segments = split_path(path)?;
+    let name = segments.pop().ok_or_else(|| FsError::InvalidPath(path.to_string()))?;
+    Ok((format!("/{}", segments.join("/")), name))
+}
+
+pub fn join(parent: &str, name: &str) -> String {
+    if parent == "/" {
+        format!("/{}", name)
+    } else {
+        format!("{}/{}", parent.trim_end_matches('/'), name)
+    }
+}
+
+fn split_path(path: &str) -> FsResult<Vec<String>> {
+    let mut segments: Vec<String> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(FsError::InvalidPath(path.to_string()));
+                }
+            }
+            name => {
+                validate_name(name)?;
+                segments.push(name.to_string());
+            }
+        }
+    }
+    Ok(segments)
+}
+
+fn validate_name(name: &str) -> FsResult<()> {
+    let invalid = name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.len() > 255
+        || name.chars().any(|c| c == '/' || c == '\\' || c.is_control());
+    if invalid {
+        Err(FsError::InvalidName(name.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+fn new_node(parent: &str, name: &str, file_type: FileType, content: Option<String>) -> FileNode {
+    let mut metadata = metadata::new_metadata(name, file_type);
+    let content = match file_type {
+        FileType::Folder => None,
+        FileType::File => {
+            let content = content.unwrap_or_default();
+            metadata.line_ending = encoding::detect_line_ending(&content);
+            let content = encoding::normalize_line_endings(content);
+            metadata.size = encoding::text_size(&content, &metadata);
+            Some(Rc::from(content))
+        }
+    };
+    FileNode {
+        name: name.to_string(),
+        file_type,
+        path: Some(join(parent, name)),
+        children: match file_type {
+            FileType::Folder => Some(Vec::new()),
+            FileType::File => None,
+        },
+        modified: false,
+        content,
+        data: None,
+        metadata,
+    }
+}
+
+fn find<'a>(node: &'a FileNode, segments: &[String]) -> Option<&'a FileNode> {
+    match segments.split_first() {
+        None => Some(node),
+        Some((head, rest)) => {
+            let child = node.children.as_ref()?.iter().find(|child| &child.name == head)?;
+            find(child, rest)
+        }
+    }
+}
+
+fn find_mut<'a>(node: &'a mut FileNode, segments: &[String]) -> Option<&'a mut FileNode> {
+    match segments.split_first() {
+        None => Some(node),
+        Some((head, rest)) => {
+            let child = node.children.as_mut()?.iter_mut().find(|child| &child.name == head)?;
+            find_mut(child, rest)
+        }
+    }
+}
+
+fn set_paths(node: &mut FileNode, parent: &str) {
+    let path = join(parent, &node.name);
+    for child in node.children.iter_mut().flatten() {
+        set_paths(child, &path);
+    }
+    node.path = Some(path);
+}
+
+/// Folders before files, then case-insensitive by name.
+fn display_order(node: &FileNode) -> (u8, String, String) {
+    let rank = match node.file_type {
+        FileType::Folder => 0,
+        FileType::File => 1,
+    };
+    (rank, node.name.to_lowercase(), node.name.clone())
+}
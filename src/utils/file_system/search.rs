@@ -0,0 +1,634 @@
+use regex::{Regex, RegexBuilder};
+
+use super::{FsError, FsResult, VirtualFileSystem};
+use crate::types::FileType;
+
+const PREVIEW_CONTEXT: usize = 40;
+const PREVIEW_MAX: usize = 160;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentPattern {
+    Literal(String),
+    Regex(String),
+}
+
+/// Content search request. `globs` select which files are searched; an
+/// entry starting with `!` excludes matching paths (and everything below a
+/// matching directory). With no positive globs every file is a candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+    pub pattern: ContentPattern,
+    pub globs: Vec<String>,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub max_results: Option<usize>,
+}
+
+impl SearchQuery {
+    pub fn literal(text: &str) -> Self {
+        Self::new(ContentPattern::Literal(text.to_string()))
+    }
+
+    pub fn regex(pattern: &str) -> Self {
+        Self::new(ContentPattern::Regex(pattern.to_string()))
+    }
+
+    fn new(pattern: ContentPattern) -> Self {
+        Self {
+            pattern,
+            globs: Vec::new(),
+            case_sensitive: false,
+            whole_word: false,
+            max_results: None,
+        }
+    }
+
+    /// Compiles the content pattern with the query's case and word options.
+    pub fn compile(&self) -> FsResult<Regex> {
+        let mut source = match &self.pattern {
+            ContentPattern::Literal(text) => regex::escape(text),
+            ContentPattern::Regex(pattern) => pattern.clone(),
+        };
+        if self.whole_word {
+            source = format!(r"\b(?:{})\b", source);
+        }
+        RegexBuilder::new(&source)
+            .case_insensitive(!self.case_sensitive)
+            .multi_line(true)
+            .build()
+            .map_err(|e| FsError::InvalidPattern(e.to_string()))
+    }
+}
+
+/// `replace` with numbered groups followed by a word char braced, so that
+/// `$1x` means group 1 and then `x` rather than a group named `1x`.
+pub(crate) fn brace_group_numbers(replace: &str) -> String {
+    let mut braced = String::with_capacity(replace.len());
+    let mut rest = replace;
+    while let Some(dollar) = rest.find('$') {
+        braced.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+        if let Some(escaped) = after.strip_prefix('$') {
+            braced.push_str("$$");
+            rest = escaped;
+            continue;
+        }
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let word_follows = after[digits..].starts_with(|c: char| c.is_alphanumeric() || c == '_');
+        if digits > 0 && word_follows {
+            braced.push_str(&format!("${{{}}}", &after[..digits]));
+        } else {
+            braced.push('$');
+            braced.push_str(&after[..digits]);
+        }
+        rest = &after[digits..];
+    }
+    braced.push_str(rest);
+    braced
+}
+
+/// A content hit. `line` and `column` are 1-based, with the column counted
+/// in characters. `preview` is the surrounding line (clipped for very long
+/// lines) and `preview_range` is the byte range of the hit within it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+    pub preview: String,
+    pub preview_range: (usize, usize),
+}
+
+impl VirtualFileSystem {
+    /// Paths of every file matching `globs` (see [`SearchQuery::globs`]).
+    pub fn glob(&self, globs: &[String]) -> FsResult<Vec<String>> {
+        let filter = GlobFilter::new(globs)?;
+        Ok(self
+            .walk()
+            .into_iter()
+            .filter(|node| node.file_type == FileType::File)
+            .filter_map(|node| node.path.clone())
+            .filter(|path| filter.matches(path))
+            .collect())
+    }
+
+    pub fn search(&self, query: &SearchQuery) -> FsResult<Vec<SearchMatch>> {
+        let mut session = SearchSession::new(self, query)?;
+        Ok(session
+            .step(self, usize::MAX)
+            .into_iter()
+            .flat_map(|file| file.matches)
+            .collect())
+    }
+
+    /// What replacing every match of `query` in the files at `paths` with
+    /// `replacement` would change. Regex replacements may refer to groups
+    /// as `$1` or `${name}`. Files without matches are left out.
+    pub fn plan_replace(
+        &self,
+        query: &SearchQuery,
+        paths: &[String],
+        replacement: &str,
+    ) -> FsResult<Vec<FileReplacement>> {
+        let regex = query.compile()?;
+        let expand = matches!(query.pattern, ContentPattern::Regex(_));
+        let replacement = if expand { brace_group_numbers(replacement) } else { replacement.to_string() };
+        Ok(paths
+            .iter()
+            .filter_map(|path| {
+                let content = self.get(path)?.content.as_deref()?;
+                let replaced = replace_content(content, &regex, expand, &replacement);
+                (replaced.count > 0).then(|| FileReplacement {
+                    path: path.clone(),
+                    ..replaced
+                })
+            })
+            .collect())
+    }
+
+    /// Writes planned replacements as a single undo step. If any file
+    /// cannot be written, none is.
+    pub fn apply_replacements(&mut self, replacements: &[FileReplacement]) -> FsResult<()> {
+        self.transaction("Replace in files", |fs| {
+            for replacement in replacements {
+                fs.update(&replacement.path, replacement.content.clone())?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// The hits in one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMatches {
+    pub path: String,
+    pub matches: Vec<SearchMatch>,
+}
+
+/// A workspace search run a slice at a time, so that a caller on the UI
+/// thread can yield between slices and stop at any point. The files to
+/// search are fixed when it starts; ones deleted since are skipped.
+#[derive(Debug, Clone)]
+pub struct SearchSession {
+    regex: Regex,
+    paths: Vec<String>,
+    next: usize,
+    remaining: usize,
+}
+
+impl SearchSession {
+    pub fn new(fs: &VirtualFileSystem, query: &SearchQuery) -> FsResult<Self> {
+        Ok(Self {
+            regex: query.compile()?,
+            paths: fs.glob(&query.globs)?,
+            next: 0,
+            remaining: query.max_results.unwrap_or(usize::MAX),
+        })
+    }
+
+    /// Searches further files until about `budget` bytes of content have
+    /// been read, and returns the hits in them.
+    pub fn step(&mut self, fs: &VirtualFileSystem, budget: usize) -> Vec<FileMatches> {
+        let mut read = 0;
+        let mut found = Vec::new();
+        while read < budget && !self.is_done() {
+            let path = &self.paths[self.next];
+            self.next += 1;
+            let Some(content) = fs.get(path).and_then(|node| node.content.as_deref()) else {
+                continue;
+            };
+            read += content.len().max(1);
+            let mut matches = Vec::new();
+            search_content(path, content, &self.regex, self.remaining, &mut matches);
+            self.remaining -= matches.len();
+            if !matches.is_empty() {
+                found.push(FileMatches {
+                    path: path.clone(),
+                    matches,
+                });
+            }
+        }
+        found
+    }
+
+    /// Whether every file has been searched or the result limit reached.
+    pub fn is_done(&self) -> bool {
+        self.next >= self.paths.len() || self.remaining == 0
+    }
+
+    /// Files searched so far, and in all.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.next, self.paths.len())
+    }
+}
+
+/// One changed stretch of a file: the lines starting at `line` (1-based)
+/// that a replacement removes, and those that take their place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplaceHunk {
+    pub line: usize,
+    pub removed: Vec<String>,
+    pub added: Vec<String>,
+}
+
+/// The effect of replacing the matches in one file: how many there are,
+/// the changed lines for a preview, and the new content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReplacement {
+    pub path: String,
+    pub count: usize,
+    pub hunks: Vec<ReplaceHunk>,
+    pub content: String,
+}
+
+/// Replaces the non-empty matches of `regex` in `content`, with matches
+/// on the same lines sharing a hunk. `path` is left empty.
+fn replace_content(content: &str, regex: &Regex, expand: bool, replacement: &str) -> FileReplacement {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+    let line_end = |line: usize| line_starts.get(line + 1).map_or(content.len(), |&next| next - 1);
+    // Closes the hunk over lines `first..=last` whose replaced text, up to
+    // byte `at` of the original, is `text`.
+    let finish = |first: usize, last: usize, mut text: String, at: usize| {
+        text.push_str(&content[at..line_end(last).max(at)]);
+        ReplaceHunk {
+            line: first + 1,
+            removed: content[line_starts[first]..line_end(last)]
+                .split('\n')
+                .map(str::to_string)
+                .collect(),
+            added: text.split('\n').map(str::to_string).collect(),
+        }
+    };
+
+    let mut result = FileReplacement {
+        path: String::new(),
+        count: 0,
+        hunks: Vec::new(),
+        content: String::with_capacity(content.len()),
+    };
+    let mut at = 0;
+    // The hunk being built: its first and last line, and its text so far.
+    let mut open: Option<(usize, usize, String)> = None;
+    for captures in regex.captures_iter(content) {
+        let found = captures.get(0).expect("group 0 is the whole match");
+        if found.is_empty() {
+            continue;
+        }
+        let mut replaced = String::new();
+        if expand {
+            captures.expand(replacement, &mut replaced);
+        } else {
+            replaced.push_str(replacement);
+        }
+        let (first, last) = (line_of(found.start()), line_of(found.end()));
+        match &mut open {
+            Some((_, hunk_last, text)) if first <= *hunk_last => {
+                text.push_str(&content[at..found.start()]);
+                *hunk_last = (*hunk_last).max(last);
+            }
+            _ => {
+                if let Some((hunk_first, hunk_last, text)) = open.take() {
+                    result.hunks.push(finish(hunk_first, hunk_last, text, at));
+                }
+                open = Some((first, last, content[line_starts[first]..found.start()].to_string()));
+            }
+        }
+        if let Some((_, _, text)) = &mut open {
+            text.push_str(&replaced);
+        }
+        result.content.push_str(&content[at..found.start()]);
+        result.content.push_str(&replaced);
+        result.count += 1;
+        at = found.end();
+    }
+    if let Some((first, last, text)) = open {
+        result.hunks.push(finish(first, last, text, at));
+    }
+    result.content.push_str(&content[at..]);
+    result
+}
+
+/// Appends up to `limit` hits of `regex` in `content` to `out`.
+pub(super) fn search_content(
+    path: &str,
+    content: &str,
+    regex: &Regex,
+    limit: usize,
+    out: &mut Vec<SearchMatch>,
+) {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+
+    for found in regex.find_iter(content).take(limit) {
+        if found.is_empty() {
+            continue;
+        }
+        let line_index = line_starts.partition_point(|&start| start <= found.start()) - 1;
+        let line_start = line_starts[line_index];
+        let line_end = content[line_start..]
+            .find('\n')
+            .map_or(content.len(), |i| line_start + i);
+        let line_text = content[line_start..line_end].trim_end_matches('\r');
+        let start_in_line = found.start() - line_start;
+        let end_in_line = (found.end() - line_start).min(line_text.len());
+        let (preview, preview_range) = clip_preview(line_text, start_in_line, end_in_line);
+
+        out.push(SearchMatch {
+            path: path.to_string(),
+            line: line_index + 1,
+            column: line_text[..start_in_line].chars().count() + 1,
+            length: found.as_str().chars().count(),
+            preview,
+            preview_range,
+        });
+    }
+}
+
+fn clip_preview(line: &str, start: usize, end: usize) -> (String, (usize, usize)) {
+    let leading = line.len() - line.trim_start().len();
+    let mut from = leading.min(start);
+    if start - from > PREVIEW_CONTEXT {
+        from = floor_char_boundary(line, start - PREVIEW_CONTEXT);
+    }
+    let mut to = line.len();
+    if to - from > PREVIEW_MAX {
+        to = floor_char_boundary(line, (from + PREVIEW_MAX).max(end));
+    }
+    let preview = line[from..to].to_string();
+    let range = (start - from, end.min(to) - from);
+    (preview, range)
+}
+
+fn floor_char_boundary(s: &str, mut index: usize) -> usize {
+    while index > 0 && !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Compiled include/exclude glob list.
+#[derive(Debug, Clone, Default)]
+pub struct GlobFilter {
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+}
+
+impl GlobFilter {
+    pub fn new(globs: &[String]) -> FsResult<Self> {
+        let mut filter = Self::default();
+        for glob in globs {
+            let glob = glob.trim();
+            if glob.is_empty() {
+                continue;
+            }
+            match glob.strip_prefix('!') {
+                Some(negated) => filter.exclude.push(Glob::new(negated)?),
+                None => filter.include.push(Glob::new(glob)?),
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.trim_start_matches('/');
+        let included = self.include.is_empty() || self.include.iter().any(|g| g.matches(path));
+        included && !self.exclude.iter().any(|g| g.matches_self_or_ancestor(path))
+    }
+}
+
+/// A path glob supporting `*`, `?`, `**`, `[a-z]`, `[!a-z]` and `{a,b}`.
+/// Globs without a `/` match the file name at any depth, like
+/// `.gitignore` entries; a leading `/` anchors the glob at the root.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    alternatives: Vec<Vec<String>>,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> FsResult<Self> {
+        let anchored = pattern.starts_with('/') || pattern.trim_end_matches('/').contains('/');
+        let trimmed = pattern.trim_start_matches('/').trim_end_matches('/');
+        let mut alternatives = Vec::new();
+        for expanded in expand_braces(trimmed)? {
+            let mut segments: Vec<String> = expanded
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect();
+            if !anchored {
+                segments.insert(0, "**".to_string());
+            }
+            for segment in &segments {
+                validate_segment(segment, pattern)?;
+            }
+            alternatives.push(segments);
+        }
+        Ok(Self { alternatives })
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        self.alternatives.iter().any(|segments| match_segments(segments, &parts))
+    }
+
+    fn matches_self_or_ancestor(&self, path: &str) -> bool {
+        let parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        (1..=parts.len()).any(|len| {
+            self.alternatives
+                .iter()
+                .any(|segments| match_segments(segments, &parts[..len]))
+        })
+    }
+}
+
+fn expand_braces(pattern: &str) -> FsResult<Vec<String>> {
+    let Some(open) = pattern.find('{') else {
+        return Ok(vec![pattern.to_string()]);
+    };
+    let mut depth = 0;
+    let mut close = None;
+    let mut splits = Vec::new();
+    for (i, c) in pattern[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(open + i);
+                    break;
+                }
+            }
+            ',' if depth == 1 => splits.push(open + i),
+            _ => {}
+        }
+    }
+    let close = close.ok_or_else(|| FsError::InvalidPattern(format!("unclosed '{{' in {}", pattern)))?;
+
+    let mut bounds = vec![open];
+    bounds.extend(splits);
+    bounds.push(close);
+    let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+    let mut out = Vec::new();
+    for window in bounds.windows(2) {
+        let option = &pattern[window[0] + 1..window[1]];
+        for rest in expand_braces(&format!("{}{}{}", prefix, option, suffix))? {
+            out.push(rest);
+        }
+    }
+    Ok(out)
+}
+
+fn validate_segment(segment: &str, pattern: &str) -> FsResult<()> {
+    let mut chars = segment.chars();
+    while let Some(c) = chars.next() {
+        if c == '[' && !chars.by_ref().any(|c| c == ']') {
+            return Err(FsError::InvalidPattern(format!("unclosed '[' in {}", pattern)));
+        }
+    }
+    Ok(())
+}
+
+fn match_segments(segments: &[String], parts: &[&str]) -> bool {
+    match segments.split_first() {
+        None => parts.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=parts.len()).any(|skip| match_segments(rest, &parts[skip..]))
+        }
+        Some((first, rest)) => match parts.split_first() {
+            Some((part, remaining)) => {
+                match_wildcard(&first.chars().collect::<Vec<_>>(), &part.chars().collect::<Vec<_>>())
+                    && match_segments(rest, remaining)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_wildcard(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                '*' => {
+                    backtrack = Some((p, t));
+                    p += 1;
+                    continue;
+                }
+                '?' => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                '[' => {
+                    if let Some((matched, next)) = match_class(pattern, p, text[t]) {
+                        if matched {
+                            p = next;
+                            t += 1;
+                            continue;
+                        }
+                    }
+                }
+                c if c == text[t] => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        match backtrack {
+            Some((star, matched)) => {
+                p = star + 1;
+                t = matched + 1;
+                backtrack = Some((star, matched + 1));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches `c` against the class starting at `pattern[start] == '['`.
+/// Returns whether it matched and the index just past the closing `]`.
+fn match_class(pattern: &[char], start: usize, c: char) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() && (first || pattern[i] != ']') {
+        first = false;
+        if i + 2 < pattern.len() && pattern[i + 1] == '-' && pattern[i + 2] != ']' {
+            matched |= pattern[i] <= c && c <= pattern[i + 2];
+            i += 3;
+        } else {
+            matched |= pattern[i] == c;
+            i += 1;
+        }
+    }
+    (i < pattern.len()).then_some((matched != negated, i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace() -> VirtualFileSystem {
+        let mut fs = VirtualFileSystem::new();
+        fs.create_file("/a.rs", "let foo = 1;\nlet food = foo + 2;\n").unwrap();
+        fs.create_file("/b.rs", "nothing here\n").unwrap();
+        fs
+    }
+
+    #[test]
+    fn replaces_with_numbered_groups() {
+        let fs = workspace();
+        let paths = vec!["/a.rs".to_string(), "/b.rs".to_string()];
+        let mut query = SearchQuery::regex(r"(f)oo\b");
+        query.case_sensitive = true;
+        let planned = fs.plan_replace(&query, &paths, "$1x").unwrap();
+        assert_eq!(planned.len(), 1);
+        assert_eq!(planned[0].path, "/a.rs");
+        assert_eq!(planned[0].count, 2);
+        assert_eq!(planned[0].content, "let fx = 1;\nlet food = fx + 2;\n");
+        assert_eq!(
+            planned[0].hunks,
+            [
+                ReplaceHunk {
+                    line: 1,
+                    removed: vec!["let foo = 1;".to_string()],
+                    added: vec!["let fx = 1;".to_string()],
+                },
+                ReplaceHunk {
+                    line: 2,
+                    removed: vec!["let food = foo + 2;".to_string()],
+                    added: vec!["let food = fx + 2;".to_string()],
+                },
+            ]
+        );
+        let named = fs.plan_replace(&SearchQuery::regex(r"(?P<name>fo)o\b"), &paths, "${name}$$").unwrap();
+        assert_eq!(named[0].content, "let fo$ = 1;\nlet food = fo$ + 2;\n");
+    }
+
+    #[test]
+    fn literal_replacements_are_taken_as_written() {
+        let fs = workspace();
+        let mut query = SearchQuery::literal("foo");
+        query.whole_word = true;
+        let planned = fs.plan_replace(&query, &["/a.rs".to_string()], "$1").unwrap();
+        assert_eq!(planned[0].content, "let $1 = 1;\nlet food = $1 + 2;\n");
+    }
+
+    #[test]
+    fn braces_only_numbered_groups_before_word_chars() {
+        assert_eq!(brace_group_numbers("$1x $2 $12_ ${3}y $$1z $name"), "${1}x $2 ${12}_ ${3}y $$1z $name");
+    }
+}
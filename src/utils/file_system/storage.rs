@@ -0,0 +1,270 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use super::trash::TrashEntry;
+use super::{FsError, FsResult, VirtualFileSystem};
+use crate::types::{FileMetadata, FileNode, FileType};
+
+/// One persisted tree entry. Directories are stored too so that empty
+/// folders survive a reload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredEntry {
+    pub path: String,
+    pub file_type: FileType,
+    pub content: Option<String>,
+    pub data: Option<Vec<u8>>,
+    /// `None` for records written before metadata was stored; such entries
+    /// get fresh metadata on load.
+    pub metadata: Option<FileMetadata>,
+}
+
+impl StoredEntry {
+    fn from_node(path: &str, node: &FileNode) -> Self {
+        Self {
+            path: path.to_string(),
+            file_type: node.file_type,
+            content: node.content.as_deref().map(str::to_string),
+            data: node.data.as_deref().map(<[u8]>::to_vec),
+            metadata: Some(node.metadata.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageChange {
+    Put(StoredEntry),
+    /// Removes the entry at this path and everything below it.
+    Remove(String),
+    PutTrash(TrashEntry),
+    RemoveTrash(u64),
+}
+
+/// Everything a backend holds: the tree and the trash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoredWorkspace {
+    pub entries: Vec<StoredEntry>,
+    pub trash: Vec<TrashEntry>,
+}
+
+impl StoredWorkspace {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.trash.is_empty()
+    }
+}
+
+pub type LoadCallback = Box<dyn FnOnce(FsResult<StoredWorkspace>)>;
+
+/// Persistence for the workspace tree, keyed by path so that a save only
+/// writes the entries that changed. Browser storage is asynchronous, so
+/// `apply` is fire-and-forget and `load` reports through a callback.
+pub trait StorageBackend {
+    fn apply(&self, changes: Vec<StorageChange>);
+    fn load(&self, done: LoadCallback);
+}
+
+/// Backend that keeps entries in memory, for native tests and for running
+/// without IndexedDB (e.g. private browsing).
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    entries: RefCell<BTreeMap<String, StoredEntry>>,
+    trash: RefCell<BTreeMap<u64, TrashEntry>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+}
+
+impl StorageBackend for MemoryStorage {
+    fn apply(&self, changes: Vec<StorageChange>) {
+        let mut entries = self.entries.borrow_mut();
+        for change in changes {
+            match change {
+                StorageChange::Put(entry) => {
+                    entries.insert(entry.path.clone(), entry);
+                }
+                StorageChange::Remove(path) => {
+                    let prefix = format!("{}/", path);
+                    entries.retain(|key, _| key != &path && !key.starts_with(&prefix));
+                }
+                StorageChange::PutTrash(entry) => {
+                    self.trash.borrow_mut().insert(entry.id, entry);
+                }
+                StorageChange::RemoveTrash(id) => {
+                    self.trash.borrow_mut().remove(&id);
+                }
+            }
+        }
+    }
+
+    fn load(&self, done: LoadCallback) {
+        done(Ok(StoredWorkspace {
+            entries: self.entries.borrow().values().cloned().collect(),
+            trash: self.trash.borrow().values().cloned().collect(),
+        }));
+    }
+}
+
+impl VirtualFileSystem {
+    /// Rebuilds a workspace from persisted entries. The result starts with
+    /// an empty undo history and nothing pending to save.
+    pub fn from_entries(mut entries: Vec<StoredEntry>) -> FsResult<Self> {
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut fs = Self::new();
+        let mut restored = Vec::new();
+        for entry in entries {
+            if let Some(metadata) = entry.metadata {
+                restored.push((entry.path.clone(), metadata));
+            }
+            let result = match (entry.file_type, entry.data) {
+                (FileType::Folder, _) => fs.create_dir_all(&entry.path),
+                (FileType::File, Some(data)) => fs.write_bytes(&entry.path, data),
+                (FileType::File, None) => fs.write(&entry.path, entry.content.unwrap_or_default()),
+            };
+            result.map_err(|e| FsError::Storage(format!("corrupt entry {}: {}", entry.path, e)))?;
+        }
+        // Applied once the tree is complete, as read-only folders would
+        // otherwise refuse their own children.
+        for (path, metadata) in restored {
+            let node = fs.get_mut(&path)?;
+            node.metadata = FileMetadata {
+                hash: node.metadata.hash,
+                ..metadata
+            };
+        }
+        fs.clear_history();
+        fs.unsaved.clear();
+        fs.removed.clear();
+        for node in fs.root.children.iter_mut().flatten() {
+            clear_modified(node);
+        }
+        Ok(fs)
+    }
+
+    /// Like [`from_entries`](Self::from_entries), also restoring the trash.
+    pub fn from_stored(stored: StoredWorkspace) -> FsResult<Self> {
+        let mut fs = Self::from_entries(stored.entries)?;
+        let mut trash = stored.trash;
+        for entry in &mut trash {
+            fs.blobs.intern_tree(&mut entry.node);
+        }
+        fs.trash.load(trash);
+        fs.purge_trash();
+        Ok(fs)
+    }
+
+    pub fn has_unsaved_changes(&self) -> bool {
+        !self.unsaved.is_empty()
+            || !self.removed.is_empty()
+            || !self.trash.unsaved.is_empty()
+            || !self.trash.removed.is_empty()
+    }
+
+    /// Drains everything changed since the previous call as storage
+    /// operations. Removals come first so a path that was deleted and then
+    /// recreated ends up stored.
+    pub fn take_storage_changes(&mut self) -> Vec<StorageChange> {
+        let removed = std::mem::take(&mut self.removed);
+        let unsaved = std::mem::take(&mut self.unsaved);
+        let mut changes: Vec<StorageChange> = removed.into_iter().map(StorageChange::Remove).collect();
+        for path in unsaved {
+            // Paths that no longer exist are already in `removed`.
+            if let Some(node) = self.get(&path) {
+                changes.push(StorageChange::Put(StoredEntry::from_node(&path, node)));
+            }
+        }
+        let trash_removed = std::mem::take(&mut self.trash.removed);
+        let trash_unsaved = std::mem::take(&mut self.trash.unsaved);
+        changes.extend(trash_removed.into_iter().map(StorageChange::RemoveTrash));
+        for id in trash_unsaved {
+            if let Some(entry) = self.trash.get(id) {
+                changes.push(StorageChange::PutTrash(entry.clone()));
+            }
+        }
+        changes
+    }
+
+    /// Marks every entry (and trash entry) as unsaved, e.g. before writing to a fresh backend.
+    pub fn mark_all_unsaved(&mut self) {
+        let paths: Vec<String> = self.walk().into_iter().filter_map(|node| node.path.clone()).collect();
+        self.unsaved.extend(paths);
+        self.trash.mark_all_unsaved();
+    }
+
+    pub fn save_to(&mut self, backend: &dyn StorageBackend) {
+        let changes = self.take_storage_changes();
+        if !changes.is_empty() {
+            backend.apply(changes);
+        }
+    }
+}
+
+fn clear_modified(node: &mut FileNode) {
+    node.modified = false;
+    for child in node.children.iter_mut().flatten() {
+        clear_modified(child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    fn reload(storage: &MemoryStorage) -> VirtualFileSystem {
+        let loaded = Rc::new(RefCell::new(None));
+        let sink = loaded.clone();
+        storage.load(Box::new(move |stored| *sink.borrow_mut() = Some(stored)));
+        let stored = loaded.borrow_mut().take().unwrap().unwrap();
+        VirtualFileSystem::from_stored(stored).unwrap()
+    }
+
+    #[test]
+    fn round_trips_through_memory_storage() {
+        let storage = MemoryStorage::new();
+        let mut fs = VirtualFileSystem::new();
+        fs.create_dir_all("/src/empty").unwrap();
+        fs.create_file("/src/main.rs", "fn main() {}").unwrap();
+        fs.create_file("/src/lib.rs", "pub mod util;").unwrap();
+        fs.write_bytes("/logo.png", vec![0x89, b'P', b'N', b'G', 0]).unwrap();
+        fs.save_to(&storage);
+
+        let mut loaded = reload(&storage);
+        assert!(!loaded.has_unsaved_changes());
+        assert!(loaded.is_dir("/src/empty"));
+        assert_eq!(loaded.read("/src/main.rs").unwrap(), "fn main() {}");
+        assert_eq!(&*loaded.read_bytes("/logo.png").unwrap(), &[0x89, b'P', b'N', b'G', 0]);
+
+        // Only the changes since the last save are written.
+        loaded.delete("/src/lib.rs").unwrap();
+        assert_eq!(loaded.rename("/src", "app").unwrap(), "/app");
+        let changes = loaded.take_storage_changes();
+        assert!(changes.contains(&StorageChange::Remove("/src".to_string())));
+        let rewritten = |path: &str| {
+            changes
+                .iter()
+                .any(|change| matches!(change, StorageChange::Put(entry) if entry.path == path))
+        };
+        assert!(rewritten("/app/main.rs"));
+        assert!(!rewritten("/logo.png"));
+        storage.apply(changes);
+
+        let reloaded = reload(&storage);
+        assert!(!reloaded.exists("/src"));
+        assert!(!reloaded.exists("/app/lib.rs"));
+        assert!(reloaded.is_dir("/app/empty"));
+        assert_eq!(reloaded.read("/app/main.rs").unwrap(), "fn main() {}");
+        assert_eq!(&*reloaded.read_bytes("/logo.png").unwrap(), &[0x89, b'P', b'N', b'G', 0]);
+        assert_eq!(storage.len(), reloaded.walk().len());
+    }
+}
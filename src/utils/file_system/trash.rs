@@ -0,0 +1,222 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::journal::FsOperation;
+use super::metadata::now_millis;
+use super::{normalize_path, split_parent, FsError, FsResult, VirtualFileSystem};
+use crate::types::FileNode;
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// A deleted entry (with its whole subtree) waiting in the trash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: u64,
+    pub original_path: String,
+    /// Milliseconds since the Unix epoch.
+    pub deleted_at: u64,
+    pub node: FileNode,
+}
+
+impl TrashEntry {
+    /// Total size of the files in the entry.
+    pub fn size(&self) -> u64 {
+        fn total(node: &FileNode) -> u64 {
+            node.metadata.size + node.children.iter().flatten().map(total).sum::<u64>()
+        }
+        total(&self.node)
+    }
+}
+
+/// Limits enforced after every move to the trash; the oldest entries go
+/// first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrashPolicy {
+    pub max_age_ms: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for TrashPolicy {
+    fn default() -> Self {
+        Self {
+            max_age_ms: Some(30 * DAY_MS),
+            max_bytes: Some(64 * 1024 * 1024),
+        }
+    }
+}
+
+/// Trash contents, kept apart from the undo journal so that entries outlive
+/// history truncation. Tracks which entries still need saving, like the
+/// tree does.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Trash {
+    entries: Vec<TrashEntry>,
+    next_id: u64,
+    policy: TrashPolicy,
+    pub(super) unsaved: BTreeSet<u64>,
+    pub(super) removed: BTreeSet<u64>,
+}
+
+impl Trash {
+    pub(super) fn get(&self, id: u64) -> Option<&TrashEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    pub(super) fn insert(&mut self, entry: TrashEntry) {
+        self.next_id = self.next_id.max(entry.id + 1);
+        self.removed.remove(&entry.id);
+        self.unsaved.insert(entry.id);
+        let index = self.entries.partition_point(|e| (e.deleted_at, e.id) < (entry.deleted_at, entry.id));
+        self.entries.insert(index, entry);
+    }
+
+    pub(super) fn remove(&mut self, id: u64) -> Option<TrashEntry> {
+        let index = self.entries.iter().position(|entry| entry.id == id)?;
+        self.unsaved.remove(&id);
+        self.removed.insert(id);
+        Some(self.entries.remove(index))
+    }
+
+    /// Replaces the contents with entries loaded from storage, which are
+    /// already saved.
+    pub(super) fn load(&mut self, mut entries: Vec<TrashEntry>) {
+        entries.sort_by_key(|entry| (entry.deleted_at, entry.id));
+        self.next_id = entries.iter().map(|entry| entry.id + 1).max().unwrap_or(0);
+        self.entries = entries;
+        self.unsaved.clear();
+        self.removed.clear();
+    }
+
+    pub(super) fn mark_all_unsaved(&mut self) {
+        self.unsaved.extend(self.entries.iter().map(|entry| entry.id));
+    }
+
+    fn purge(&mut self, now: u64) -> usize {
+        let before = self.entries.len();
+        if let Some(max_age) = self.policy.max_age_ms {
+            let expired: Vec<u64> = self
+                .entries
+                .iter()
+                .filter(|entry| now.saturating_sub(entry.deleted_at) > max_age)
+                .map(|entry| entry.id)
+                .collect();
+            for id in expired {
+                self.remove(id);
+            }
+        }
+        if let Some(max_bytes) = self.policy.max_bytes {
+            let mut total: u64 = self.entries.iter().map(TrashEntry::size).sum();
+            while total > max_bytes {
+                let Some(oldest) = self.entries.first().map(|entry| entry.id) else {
+                    break;
+                };
+                total -= self.remove(oldest).map_or(0, |entry| entry.size());
+            }
+        }
+        before - self.entries.len()
+    }
+}
+
+impl VirtualFileSystem {
+    /// Moves `path` to the trash as one undo step and returns the trash
+    /// entry id. Undoing puts it back where it was.
+    pub fn move_to_trash(&mut self, path: &str) -> FsResult<u64> {
+        let normalized = normalize_path(path)?;
+        if normalized == "/" {
+            return Err(FsError::InvalidPath(normalized));
+        }
+        self.check_tree_writable(&normalized)?;
+        let node = self
+            .get(&normalized)
+            .cloned()
+            .ok_or_else(|| FsError::NotFound(normalized.clone()))?;
+        let entry = TrashEntry {
+            id: self.trash.next_id,
+            original_path: normalized,
+            deleted_at: now_millis(),
+            node,
+        };
+        let id = entry.id;
+        self.put_in_trash(&entry)?;
+        self.journal.record(FsOperation::Trash { entry });
+        self.purge_trash();
+        Ok(id)
+    }
+
+    /// Puts a trash entry back at its original path, recreating missing
+    /// parent folders, and returns that path.
+    pub fn restore_from_trash(&mut self, id: u64) -> FsResult<String> {
+        let entry = self
+            .trash
+            .get(id)
+            .cloned()
+            .ok_or_else(|| FsError::NotFound(format!("trash entry {}", id)))?;
+        let path = entry.original_path.clone();
+        if self.exists(&path) {
+            return Err(FsError::AlreadyExists(path));
+        }
+        self.check_writable(&path)?;
+        let (parent, _) = split_parent(&path)?;
+        self.transaction(&format!("Restore {}", path), |fs| {
+            fs.create_dir_all(&parent)?;
+            fs.take_from_trash(&entry)?;
+            fs.journal.record(FsOperation::Restore { entry });
+            Ok(path.clone())
+        })
+    }
+
+    /// Trash contents, oldest first.
+    pub fn trash_entries(&self) -> &[TrashEntry] {
+        &self.trash.entries
+    }
+
+    /// Permanently removes one entry from the trash.
+    pub fn delete_from_trash(&mut self, id: u64) -> FsResult<()> {
+        self.trash
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| FsError::NotFound(format!("trash entry {}", id)))
+    }
+
+    /// Permanently removes everything in the trash.
+    pub fn empty_trash(&mut self) {
+        let ids: Vec<u64> = self.trash.entries.iter().map(|entry| entry.id).collect();
+        for id in ids {
+            self.trash.remove(id);
+        }
+    }
+
+    pub fn trash_policy(&self) -> TrashPolicy {
+        self.trash.policy
+    }
+
+    pub fn set_trash_policy(&mut self, policy: TrashPolicy) {
+        self.trash.policy = policy;
+        self.purge_trash();
+    }
+
+    /// Drops entries beyond the trash policy's age and size limits and
+    /// returns how many were removed.
+    pub fn purge_trash(&mut self) -> usize {
+        self.trash.purge(now_millis())
+    }
+
+    /// Detaches the entry's node from the tree and files it in the trash.
+    /// Shared by trashing, undoing a restore and redo.
+    pub(super) fn put_in_trash(&mut self, entry: &TrashEntry) -> FsResult<()> {
+        self.detach(&entry.original_path)?;
+        self.trash.insert(entry.clone());
+        Ok(())
+    }
+
+    /// Puts the entry's node back in the tree and drops it from the trash.
+    /// Works from the journal's copy, so an entry purged in the meantime can
+    /// still be brought back by undo.
+    pub(super) fn take_from_trash(&mut self, entry: &TrashEntry) -> FsResult<()> {
+        let (parent, _) = split_parent(&entry.original_path)?;
+        self.insert(&parent, entry.node.clone())?;
+        self.trash.remove(entry.id);
+        Ok(())
+    }
+}
@@ -1,4 +1,7 @@
 pub mod code_analyzer;
+pub mod diagnostics;
+pub mod editor;
 pub mod file_system;
 pub mod git_utils;
 pub mod security;
+pub mod syntax;
@@ -0,0 +1,151 @@
+//! How each language is edited: its brackets, the pairs typed together,
+//! its comment tokens and when lines are indented. Supporting another
+//! language means adding an entry here.
+
+use super::Language;
+
+/// A pair whose closing half is typed along with the opening one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoClosingPair {
+    pub open: char,
+    pub close: char,
+    /// Not closed inside strings and comments.
+    pub code_only: bool,
+}
+
+const fn pair(open: char, close: char) -> AutoClosingPair {
+    AutoClosingPair {
+        open,
+        close,
+        code_only: false,
+    }
+}
+
+const fn quote(quote: char) -> AutoClosingPair {
+    AutoClosingPair {
+        open: quote,
+        close: quote,
+        code_only: true,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LanguageConfig {
+    pub brackets: &'static [(char, char)],
+    pub auto_closing_pairs: &'static [AutoClosingPair],
+    /// What may follow the cursor, besides whitespace, for a typed opening
+    /// to be closed.
+    pub close_before: &'static str,
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// One level of indentation.
+    pub indent_unit: &'static str,
+    /// Lines ending in one of these indent the next line one level deeper.
+    pub indent_after: &'static [&'static str],
+    /// Lines starting with one of these words indent the next line one
+    /// level shallower.
+    pub outdent_after: &'static [&'static str],
+}
+
+const BRACKETS: &[(char, char)] = &[('{', '}'), ('[', ']'), ('(', ')')];
+const CLOSE_BEFORE: &str = ";:.,=}])>";
+
+const PLAIN: LanguageConfig = LanguageConfig {
+    brackets: BRACKETS,
+    auto_closing_pairs: &[pair('{', '}'), pair('[', ']'), pair('(', ')')],
+    close_before: CLOSE_BEFORE,
+    line_comment: None,
+    block_comment: None,
+    indent_unit: "    ",
+    indent_after: &[],
+    outdent_after: &[],
+};
+
+const RUST: LanguageConfig = LanguageConfig {
+    // `'` starts lifetimes as often as chars, so it is not paired.
+    auto_closing_pairs: &[pair('{', '}'), pair('[', ']'), pair('(', ')'), quote('"')],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    indent_after: &["{", "[", "("],
+    ..PLAIN
+};
+
+const TYPESCRIPT: LanguageConfig = LanguageConfig {
+    auto_closing_pairs: &[pair('{', '}'), pair('[', ']'), pair('(', ')'), quote('"'), quote('\''), quote('`')],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    indent_unit: "  ",
+    indent_after: &["{", "[", "("],
+    ..PLAIN
+};
+
+const PYTHON: LanguageConfig = LanguageConfig {
+    auto_closing_pairs: &[pair('{', '}'), pair('[', ']'), pair('(', ')'), quote('"'), quote('\'')],
+    line_comment: Some("#"),
+    block_comment: Some(("\"\"\"", "\"\"\"")),
+    indent_after: &[":", "{", "[", "("],
+    outdent_after: &["return", "pass", "break", "continue", "raise"],
+    ..PLAIN
+};
+
+const JSON: LanguageConfig = LanguageConfig {
+    auto_closing_pairs: &[pair('{', '}'), pair('[', ']'), quote('"')],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    indent_unit: "  ",
+    indent_after: &["{", "["],
+    ..PLAIN
+};
+
+const TOML: LanguageConfig = LanguageConfig {
+    auto_closing_pairs: &[pair('{', '}'), pair('[', ']'), quote('"'), quote('\'')],
+    line_comment: Some("#"),
+    indent_after: &["[", "{"],
+    ..PLAIN
+};
+
+const MARKDOWN: LanguageConfig = LanguageConfig {
+    auto_closing_pairs: &[pair('{', '}'), pair('[', ']'), pair('(', ')'), quote('`')],
+    block_comment: Some(("<!--", "-->")),
+    indent_unit: "  ",
+    ..PLAIN
+};
+
+const SHELL: LanguageConfig = LanguageConfig {
+    auto_closing_pairs: &[pair('{', '}'), pair('[', ']'), pair('(', ')'), quote('"'), quote('\''), quote('`')],
+    line_comment: Some("#"),
+    indent_unit: "  ",
+    indent_after: &["{", "(", "then", "do", "else"],
+    ..PLAIN
+};
+
+impl Language {
+    pub fn config(self) -> &'static LanguageConfig {
+        match self {
+            Self::Rust => &RUST,
+            Self::TypeScript | Self::Tsx => &TYPESCRIPT,
+            Self::Python => &PYTHON,
+            Self::Json => &JSON,
+            Self::Toml => &TOML,
+            Self::Markdown => &MARKDOWN,
+            Self::Shell => &SHELL,
+            Self::PlainText => &PLAIN,
+        }
+    }
+}
+
+impl LanguageConfig {
+    /// The bracket `c` is half of, as (opening, closing), and whether it is
+    /// the opening half.
+    pub fn bracket(&self, c: char) -> Option<((char, char), bool)> {
+        self.brackets.iter().find_map(|&(open, close)| {
+            if c == open {
+                Some(((open, close), true))
+            } else if c == close {
+                Some(((open, close), false))
+            } else {
+                None
+            }
+        })
+    }
+}
@@ -0,0 +1,92 @@
+//! Tokens of a whole buffer, kept up to date incrementally.
+
+use std::ops::Range;
+
+use super::{tokenize_line, Language, LineState, Token};
+use crate::utils::editor::buffer::TextBuffer;
+
+#[derive(Debug, Clone)]
+struct Line {
+    start: LineState,
+    end: LineState,
+    tokens: Vec<Token>,
+}
+
+/// The tokens of each line of a buffer. [`update`](Self::update) finds the
+/// lines that changed since the last call and re-tokenizes from the first
+/// of them until a line ends in the same state as before the change; the
+/// rest keep their tokens.
+#[derive(Debug, Clone)]
+pub struct Highlighter {
+    language: Language,
+    text: TextBuffer,
+    lines: Vec<Line>,
+}
+
+impl Highlighter {
+    pub fn new(language: Language, text: &TextBuffer) -> Self {
+        let mut highlighter = Self {
+            language,
+            text: TextBuffer::new(),
+            lines: vec![Line {
+                start: LineState::Normal,
+                end: LineState::Normal,
+                tokens: Vec::new(),
+            }],
+        };
+        highlighter.update(text);
+        highlighter
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Catches up with `text` and returns the range of lines re-tokenized.
+    pub fn update(&mut self, text: &TextBuffer) -> Range<usize> {
+        let prefix = self.text.common_prefix_len(text);
+        if prefix == self.text.len() && prefix == text.len() {
+            self.text = text.clone();
+            return 0..0;
+        }
+        let suffix = self
+            .text
+            .common_suffix_len(text)
+            .min(self.text.len() - prefix)
+            .min(text.len() - prefix);
+        let first = text.offset_to_line(text.floor_char_boundary(prefix));
+        let old_last = self.text.offset_to_line(self.text.floor_char_boundary(self.text.len() - suffix));
+        let new_last = text.offset_to_line(text.floor_char_boundary(text.len() - suffix));
+        let placeholder = Line {
+            start: LineState::Normal,
+            end: LineState::Normal,
+            tokens: Vec::new(),
+        };
+        self.lines.splice(first..=old_last, vec![placeholder; new_last - first + 1]);
+        self.text = text.clone();
+
+        let mut state = first.checked_sub(1).map_or(LineState::Normal, |previous| self.lines[previous].end);
+        let mut line = first;
+        while line < self.lines.len() && (line <= new_last || self.lines[line].start != state) {
+            let (tokens, end) = tokenize_line(self.language, state, &text.line(line));
+            self.lines[line] = Line { start: state, end, tokens };
+            state = end;
+            line += 1;
+        }
+        first..line
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Tokens of `line`, as of the last update.
+    pub fn tokens(&self, line: usize) -> &[Token] {
+        self.lines.get(line).map_or(&[], |line| &line.tokens)
+    }
+
+    /// The state `line` starts in.
+    pub fn state_at(&self, line: usize) -> LineState {
+        self.lines.get(line).map_or(LineState::Normal, |line| line.start)
+    }
+}
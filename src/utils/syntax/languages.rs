@@ -0,0 +1,194 @@
+//! What the code lexer needs to know about each language.
+
+use super::lexer::{AttributeSyntax, Spec};
+use super::{Language, Quote};
+
+const fn quote(quote: u8, repeat: u8, escapes: bool, multiline: bool) -> Quote {
+    Quote {
+        quote,
+        repeat,
+        hashes: 0,
+        escapes,
+        multiline,
+    }
+}
+
+pub(super) fn spec(language: Language) -> &'static Spec {
+    match language {
+        Language::Rust => &RUST,
+        Language::TypeScript => &TYPESCRIPT,
+        Language::Tsx => &TSX,
+        Language::Python => &PYTHON,
+        Language::Json => &JSON,
+        Language::Toml => &TOML,
+        Language::Shell => &SHELL,
+        Language::Markdown | Language::PlainText => &PLAIN,
+    }
+}
+
+const PLAIN: Spec = Spec {
+    line_comments: &[],
+    block_comment: None,
+    nested_comments: false,
+    quotes: &[],
+    string_prefixes: "",
+    raw_strings: false,
+    char_literals: false,
+    keywords: &[],
+    types: &[],
+    constants: &[],
+    builtins: &[],
+    capitalized_types: false,
+    macros: false,
+    attributes: AttributeSyntax::None,
+    dollar_variables: false,
+    dollar_identifiers: false,
+    dashed_identifiers: false,
+    key_separator: None,
+    tables: false,
+    tags: false,
+    number_extras: "",
+};
+
+const RUST: Spec = Spec {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested_comments: true,
+    quotes: &[quote(b'"', 1, true, true)],
+    string_prefixes: "b",
+    raw_strings: true,
+    char_literals: true,
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "fn", "for",
+        "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self",
+        "static", "struct", "super", "trait", "type", "union", "unsafe", "use", "where", "while", "yield",
+    ],
+    types: &[
+        "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "str", "u8", "u16", "u32", "u64",
+        "u128", "usize",
+    ],
+    constants: &["true", "false"],
+    builtins: &[],
+    capitalized_types: true,
+    macros: true,
+    attributes: AttributeSyntax::Hash,
+    dollar_variables: false,
+    dollar_identifiers: false,
+    dashed_identifiers: false,
+    key_separator: None,
+    tables: false,
+    tags: false,
+    number_extras: "",
+};
+
+const TYPESCRIPT: Spec = Spec {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested_comments: false,
+    quotes: &[quote(b'`', 1, true, true), quote(b'"', 1, true, false), quote(b'\'', 1, true, false)],
+    string_prefixes: "",
+    raw_strings: false,
+    char_literals: false,
+    keywords: &[
+        "abstract", "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "debugger",
+        "declare", "default", "delete", "do", "else", "enum", "export", "extends", "finally", "for", "from",
+        "function", "get", "if", "implements", "import", "in", "instanceof", "interface", "keyof", "let", "namespace",
+        "new", "of", "private", "protected", "public", "readonly", "return", "set", "static", "super", "switch",
+        "this", "throw", "try", "type", "typeof", "var", "void", "while", "with", "yield",
+    ],
+    types: &[
+        "any", "bigint", "boolean", "never", "number", "object", "string", "symbol", "undefined", "unknown",
+    ],
+    constants: &["true", "false", "null", "NaN", "Infinity"],
+    builtins: &[],
+    capitalized_types: true,
+    macros: false,
+    attributes: AttributeSyntax::At,
+    dollar_variables: false,
+    dollar_identifiers: true,
+    dashed_identifiers: false,
+    key_separator: None,
+    tables: false,
+    tags: false,
+    number_extras: "",
+};
+
+const TSX: Spec = Spec {
+    tags: true,
+    ..TYPESCRIPT
+};
+
+const PYTHON: Spec = Spec {
+    line_comments: &["#"],
+    block_comment: None,
+    nested_comments: false,
+    quotes: &[
+        quote(b'"', 3, true, true),
+        quote(b'\'', 3, true, true),
+        quote(b'"', 1, true, false),
+        quote(b'\'', 1, true, false),
+    ],
+    string_prefixes: "rRbBfFuU",
+    raw_strings: false,
+    char_literals: false,
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else", "except",
+        "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "match", "nonlocal", "not", "or",
+        "pass", "raise", "return", "self", "try", "while", "with", "yield",
+    ],
+    types: &["bool", "bytes", "dict", "float", "int", "list", "object", "set", "str", "tuple", "type"],
+    constants: &["True", "False", "None"],
+    builtins: &[],
+    capitalized_types: true,
+    macros: false,
+    attributes: AttributeSyntax::At,
+    dollar_variables: false,
+    dollar_identifiers: false,
+    dashed_identifiers: false,
+    key_separator: None,
+    tables: false,
+    tags: false,
+    number_extras: "",
+};
+
+const JSON: Spec = Spec {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &[quote(b'"', 1, true, false)],
+    constants: &["true", "false", "null"],
+    key_separator: Some(b':'),
+    ..PLAIN
+};
+
+const TOML: Spec = Spec {
+    line_comments: &["#"],
+    quotes: &[
+        quote(b'"', 3, true, true),
+        quote(b'\'', 3, false, true),
+        quote(b'"', 1, true, false),
+        quote(b'\'', 1, false, false),
+    ],
+    constants: &["true", "false", "inf", "nan"],
+    dashed_identifiers: true,
+    key_separator: Some(b'='),
+    tables: true,
+    // Dates and times: 1979-05-27T07:32:00Z
+    number_extras: "-:",
+    ..PLAIN
+};
+
+const SHELL: Spec = Spec {
+    line_comments: &["#"],
+    quotes: &[quote(b'"', 1, true, true), quote(b'\'', 1, false, true)],
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local",
+        "readonly", "return", "select", "then", "until", "while",
+    ],
+    builtins: &[
+        "alias", "cd", "echo", "eval", "exec", "exit", "printf", "pwd", "read", "set", "shift", "source", "test",
+        "trap", "unset",
+    ],
+    dollar_variables: true,
+    dashed_identifiers: true,
+    ..PLAIN
+};
@@ -0,0 +1,458 @@
+//! The lexer shared by the programming and configuration languages. Each
+//! language is described by a [`Spec`] in `languages.rs`.
+
+use super::{LineState, Quote, Token, TokenKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum AttributeSyntax {
+    None,
+    /// `#[derive(Debug)]`
+    Hash,
+    /// `@decorator`
+    At,
+}
+
+pub(super) struct Spec {
+    pub line_comments: &'static [&'static str],
+    pub block_comment: Option<(&'static str, &'static str)>,
+    pub nested_comments: bool,
+    /// Longest opening delimiter first.
+    pub quotes: &'static [Quote],
+    /// Letters that may come right before a quote (`b"..."`, `f'...'`).
+    pub string_prefixes: &'static str,
+    /// Rust raw strings, `r#"..."#`.
+    pub raw_strings: bool,
+    /// `'c'` is a char and `'a` a lifetime, rather than `'` opening a string.
+    pub char_literals: bool,
+    pub keywords: &'static [&'static str],
+    pub types: &'static [&'static str],
+    pub constants: &'static [&'static str],
+    /// Commands highlighted as functions.
+    pub builtins: &'static [&'static str],
+    /// Whether `CamelCase` identifiers are types.
+    pub capitalized_types: bool,
+    /// Rust macro calls, `name!`.
+    pub macros: bool,
+    pub attributes: AttributeSyntax,
+    /// Shell `$name`, `${name}` and `$1`.
+    pub dollar_variables: bool,
+    /// `$` may appear in identifiers.
+    pub dollar_identifiers: bool,
+    /// `-` may appear in identifiers.
+    pub dashed_identifiers: bool,
+    /// What follows an object or table key.
+    pub key_separator: Option<u8>,
+    /// TOML `[table]` headers.
+    pub tables: bool,
+    /// JSX tags.
+    pub tags: bool,
+    /// Characters allowed inside a number between digits.
+    pub number_extras: &'static str,
+}
+
+/// Operators of more than one character, longest first.
+const OPERATORS: &[&str] = &[
+    "===", "!==", "**=", "<<=", ">>=", "&&=", "||=", "??=", "...", "..=", "::", "->", "=>", "==", "!=", "<=", ">=",
+    "&&", "||", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<", ">>", "..", "??", "?.", "++", "--", "**",
+];
+
+const OPERATOR_CHARS: &[u8] = b"+-*/%=<>!&|^~?:";
+
+pub(super) fn tokenize_line(spec: &Spec, state: LineState, line: &str) -> (Vec<Token>, LineState) {
+    let mut lexer = Lexer {
+        spec,
+        line,
+        bytes: line.as_bytes(),
+        at: 0,
+        tokens: Vec::new(),
+    };
+    let mut state = match state {
+        LineState::Comment { depth } if spec.block_comment.is_some() => lexer.block_comment(0, depth),
+        LineState::String(quote) => lexer.string(0, quote),
+        _ => LineState::Normal,
+    };
+    while state == LineState::Normal && lexer.at < lexer.bytes.len() {
+        state = lexer.next_token();
+    }
+    (lexer.tokens, state)
+}
+
+struct Lexer<'a> {
+    spec: &'a Spec,
+    line: &'a str,
+    bytes: &'a [u8],
+    at: usize,
+    tokens: Vec<Token>,
+}
+
+impl Lexer<'_> {
+    fn push(&mut self, kind: TokenKind, start: usize, end: usize) {
+        if end > start {
+            self.tokens.push(Token { kind, start, end });
+        }
+    }
+
+    fn starts_with(&self, at: usize, text: &str) -> bool {
+        self.bytes.get(at..).is_some_and(|rest| rest.starts_with(text.as_bytes()))
+    }
+
+    fn char_len(&self, at: usize) -> usize {
+        self.line[at..].chars().next().map_or(1, char::len_utf8)
+    }
+
+    fn is_identifier_start(&self, at: usize) -> bool {
+        match self.bytes.get(at) {
+            Some(&b) if b.is_ascii() => {
+                b.is_ascii_alphabetic() || b == b'_' || (b == b'$' && self.spec.dollar_identifiers)
+            }
+            Some(_) => self.line[at..].chars().next().is_some_and(char::is_alphabetic),
+            None => false,
+        }
+    }
+
+    fn identifier_end(&self, at: usize) -> usize {
+        let mut end = at;
+        while let Some(&b) = self.bytes.get(end) {
+            let continues = if b.is_ascii() {
+                b.is_ascii_alphanumeric()
+                    || b == b'_'
+                    || (b == b'$' && self.spec.dollar_identifiers)
+                    || (b == b'-' && self.spec.dashed_identifiers)
+            } else {
+                self.line[end..].chars().next().is_some_and(char::is_alphanumeric)
+            };
+            if !continues {
+                break;
+            }
+            end += self.char_len(end);
+        }
+        end
+    }
+
+    /// The first character from `at` on that is not a space or tab.
+    fn next_significant(&self, at: usize) -> Option<u8> {
+        self.bytes[at..].iter().copied().find(|&b| b != b' ' && b != b'\t')
+    }
+
+    fn next_token(&mut self) -> LineState {
+        let start = self.at;
+        let b = self.bytes[start];
+        if b.is_ascii_whitespace() {
+            self.at += 1;
+            return LineState::Normal;
+        }
+        if self.spec.line_comments.iter().any(|prefix| self.starts_with(start, prefix)) {
+            self.push(TokenKind::Comment, start, self.bytes.len());
+            self.at = self.bytes.len();
+            return LineState::Normal;
+        }
+        if let Some((open, _)) = self.spec.block_comment.filter(|(open, _)| self.starts_with(start, open)) {
+            self.at = start + open.len();
+            return self.block_comment(start, 1);
+        }
+        if let Some(state) = self.prefixed_string(start) {
+            return state;
+        }
+        if let Some(state) = self.string_at(start, start) {
+            self.mark_key();
+            return state;
+        }
+        let next_is_digit = self.bytes.get(start + 1).is_some_and(u8::is_ascii_digit);
+        if b.is_ascii_digit() || (b == b'.' && next_is_digit && !self.follows_identifier(start)) {
+            self.number(start);
+        } else if self.is_identifier_start(start) {
+            self.identifier(start);
+        } else if b == b'@' && self.spec.attributes == AttributeSyntax::At && self.is_identifier_start(start + 1) {
+            let mut end = start + 1;
+            while self.is_identifier_start(end) {
+                end = self.identifier_end(end);
+                if self.bytes.get(end) == Some(&b'.') && self.is_identifier_start(end + 1) {
+                    end += 1;
+                }
+            }
+            self.push(TokenKind::Attribute, start, end);
+            self.at = end;
+        } else if b == b'#'
+            && self.spec.attributes == AttributeSyntax::Hash
+            && (self.starts_with(start, "#[") || self.starts_with(start, "#!["))
+        {
+            self.attribute(start);
+        } else if b == b'$' && self.spec.dollar_variables {
+            self.variable(start);
+        } else if b == b'[' && self.spec.tables && self.line[..start].trim().is_empty() {
+            // `[table]` or `[[array.of.tables]]`
+            let end = self.line[start..].find(']').map_or(self.bytes.len(), |index| {
+                let close = start + index;
+                close + self.bytes[close..].iter().take_while(|&&b| b == b']').count()
+            });
+            self.push(TokenKind::Type, start, end);
+            self.at = end;
+        } else if b == b'<' && self.spec.tags && self.opens_tag(start) {
+            let name = if self.bytes.get(start + 1) == Some(&b'/') { start + 2 } else { start + 1 };
+            let mut end = name;
+            while self.bytes.get(end).is_some_and(|&b| b.is_ascii_alphanumeric() || b"-_.".contains(&b)) {
+                end += 1;
+            }
+            self.push(TokenKind::Punctuation, start, name);
+            self.push(TokenKind::Tag, name, end);
+            self.at = end;
+        } else if let Some(operator) = OPERATORS.iter().find(|operator| self.starts_with(start, operator)) {
+            self.push(TokenKind::Operator, start, start + operator.len());
+            self.at = start + operator.len();
+        } else if OPERATOR_CHARS.contains(&b) {
+            self.push(TokenKind::Operator, start, start + 1);
+            self.at = start + 1;
+        } else if b.is_ascii() {
+            self.push(TokenKind::Punctuation, start, start + 1);
+            self.at = start + 1;
+        } else {
+            // Other symbols, e.g. emoji.
+            self.at = start + self.char_len(start);
+        }
+        LineState::Normal
+    }
+
+    fn follows_identifier(&self, at: usize) -> bool {
+        at > 0 && (self.bytes[at - 1].is_ascii_alphanumeric() || self.bytes[at - 1] == b'_')
+    }
+
+    /// A string with a prefix such as `b"..."` or `r#"..."#`.
+    fn prefixed_string(&mut self, start: usize) -> Option<LineState> {
+        if self.spec.raw_strings {
+            let r = if self.bytes[start] == b'b' { start + 1 } else { start };
+            if self.bytes.get(r) == Some(&b'r') {
+                let hashes = self.bytes[r + 1..].iter().take_while(|&&b| b == b'#').count();
+                if self.bytes.get(r + 1 + hashes) == Some(&b'"') {
+                    self.at = r + 2 + hashes;
+                    return Some(self.string(
+                        start,
+                        Quote {
+                            quote: b'"',
+                            repeat: 1,
+                            hashes: hashes.min(u8::MAX as usize) as u8,
+                            escapes: false,
+                            multiline: true,
+                        },
+                    ));
+                }
+            }
+        }
+        let prefixes = self.spec.string_prefixes.as_bytes();
+        let len = self.bytes[start..].iter().take(2).take_while(|b| prefixes.contains(b)).count();
+        (1..=len).rev().find_map(|len| self.string_at(start, start + len))
+    }
+
+    /// A string or char literal whose quote is at `at`, with the token
+    /// starting at `start`.
+    fn string_at(&mut self, start: usize, at: usize) -> Option<LineState> {
+        if self.spec.char_literals && self.bytes.get(at) == Some(&b'\'') {
+            self.char_or_lifetime(start, at);
+            return Some(LineState::Normal);
+        }
+        let quote = *self.spec.quotes.iter().find(|quote| {
+            (0..quote.repeat as usize).all(|index| self.bytes.get(at + index) == Some(&quote.quote))
+        })?;
+        self.at = at + quote.repeat as usize;
+        Some(self.string(start, quote))
+    }
+
+    /// The rest of a string whose token starts at `start`, from `self.at`.
+    fn string(&mut self, start: usize, quote: Quote) -> LineState {
+        while self.at < self.bytes.len() {
+            if quote.escapes && self.bytes[self.at] == b'\\' {
+                self.at += 1;
+                if self.at < self.bytes.len() {
+                    self.at += self.char_len(self.at);
+                }
+                continue;
+            }
+            let closing = (quote.repeat as usize, quote.hashes as usize);
+            let closes = (0..closing.0).all(|index| self.bytes.get(self.at + index) == Some(&quote.quote))
+                && (0..closing.1).all(|index| self.bytes.get(self.at + closing.0 + index) == Some(&b'#'));
+            if closes {
+                self.at += closing.0 + closing.1;
+                self.push(TokenKind::String, start, self.at);
+                return LineState::Normal;
+            }
+            self.at += self.char_len(self.at);
+        }
+        self.push(TokenKind::String, start, self.bytes.len());
+        if quote.multiline {
+            LineState::String(quote)
+        } else {
+            LineState::Normal
+        }
+    }
+
+    /// Turns the string just lexed into a key if a key separator follows.
+    fn mark_key(&mut self) {
+        let Some(separator) = self.spec.key_separator else {
+            return;
+        };
+        let next = self.next_significant(self.at);
+        if next == Some(separator) || (separator == b'=' && next == Some(b'.')) {
+            if let Some(token) = self.tokens.last_mut().filter(|token| token.kind == TokenKind::String) {
+                token.kind = TokenKind::Property;
+            }
+        }
+    }
+
+    /// Rust `'c'`, `'\n'` or `'a` (a lifetime or label).
+    fn char_or_lifetime(&mut self, start: usize, at: usize) {
+        let end = if self.bytes.get(at + 1) == Some(&b'\\') {
+            self.line.get(at + 3..).and_then(|rest| rest.find('\'')).map(|index| at + 4 + index)
+        } else if at + 1 < self.bytes.len() {
+            let after = at + 1 + self.char_len(at + 1);
+            (self.bytes.get(after) == Some(&b'\'')).then_some(after + 1)
+        } else {
+            None
+        };
+        if let Some(end) = end {
+            self.push(TokenKind::String, start, end);
+            self.at = end;
+        } else if self.is_identifier_start(at + 1) {
+            let end = self.identifier_end(at + 1);
+            self.push(TokenKind::Keyword, start, end);
+            self.at = end;
+        } else {
+            self.push(TokenKind::Punctuation, start, at + 1);
+            self.at = at + 1;
+        }
+    }
+
+    fn block_comment(&mut self, start: usize, mut depth: u8) -> LineState {
+        let Some((open, close)) = self.spec.block_comment else {
+            return LineState::Normal;
+        };
+        while self.at < self.bytes.len() {
+            if self.spec.nested_comments && self.starts_with(self.at, open) {
+                depth = depth.saturating_add(1);
+                self.at += open.len();
+            } else if self.starts_with(self.at, close) {
+                self.at += close.len();
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    self.push(TokenKind::Comment, start, self.at);
+                    return LineState::Normal;
+                }
+            } else {
+                self.at += self.char_len(self.at);
+            }
+        }
+        self.push(TokenKind::Comment, start, self.bytes.len());
+        LineState::Comment { depth }
+    }
+
+    fn number(&mut self, start: usize) {
+        let hex = self.starts_with(start, "0x") || self.starts_with(start, "0X");
+        let extras = self.spec.number_extras.as_bytes();
+        let mut end = start + 1;
+        while let Some(&b) = self.bytes.get(end) {
+            let next_is_digit = self.bytes.get(end + 1).is_some_and(u8::is_ascii_digit);
+            let exponent = !hex && (b == b'+' || b == b'-') && matches!(self.bytes[end - 1], b'e' | b'E');
+            if b.is_ascii_alphanumeric() || b == b'_' || exponent || ((b == b'.' || extras.contains(&b)) && next_is_digit)
+            {
+                end += 1;
+            } else {
+                break;
+            }
+        }
+        self.push(TokenKind::Number, start, end);
+        self.at = end;
+    }
+
+    fn identifier(&mut self, start: usize) {
+        let mut end = self.identifier_end(start);
+        let word = &self.line[start..end];
+        let next = self.next_significant(end);
+        let spec = self.spec;
+        let kind = if spec.keywords.contains(&word) {
+            TokenKind::Keyword
+        } else if spec.constants.contains(&word) {
+            TokenKind::Constant
+        } else if spec.types.contains(&word) {
+            TokenKind::Type
+        } else if spec.builtins.contains(&word) {
+            TokenKind::Function
+        } else if spec.macros && self.bytes.get(end) == Some(&b'!') && self.bytes.get(end + 1) != Some(&b'=') {
+            end += 1;
+            TokenKind::Macro
+        } else if spec
+            .key_separator
+            .is_some_and(|separator| next == Some(separator) || (separator == b'=' && next == Some(b'.')))
+        {
+            TokenKind::Property
+        } else if next == Some(b'(') {
+            TokenKind::Function
+        } else if spec.capitalized_types
+            && word.starts_with(|c: char| c.is_uppercase())
+            && word.contains(|c: char| c.is_lowercase())
+        {
+            TokenKind::Type
+        } else {
+            TokenKind::Identifier
+        };
+        self.push(kind, start, end);
+        self.at = end;
+    }
+
+    /// `#[...]` up to its matching bracket, or the end of the line.
+    fn attribute(&mut self, start: usize) {
+        let mut depth = 0;
+        let mut end = self.bytes.len();
+        for (index, &b) in self.bytes.iter().enumerate().skip(start) {
+            match b {
+                b'[' => depth += 1,
+                b']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = index + 1;
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.push(TokenKind::Attribute, start, end);
+        self.at = end;
+    }
+
+    /// `$name`, `${...}`, `$1`, `$?`; `$(` is an operator.
+    fn variable(&mut self, start: usize) {
+        let end = match self.bytes.get(start + 1) {
+            Some(b'{') => self.line[start..].find('}').map_or(self.bytes.len(), |index| start + index + 1),
+            Some(b'(') => {
+                self.push(TokenKind::Operator, start, start + 2);
+                self.at = start + 2;
+                return;
+            }
+            Some(b) if b.is_ascii_digit() || b"@*#?$!-".contains(b) => start + 2,
+            Some(_) if self.is_identifier_start(start + 1) => {
+                let mut end = start + 1;
+                while self.bytes.get(end).is_some_and(|&b| b.is_ascii_alphanumeric() || b == b'_') {
+                    end += 1;
+                }
+                end
+            }
+            _ => start + 1,
+        };
+        let kind = if end == start + 1 { TokenKind::Punctuation } else { TokenKind::Variable };
+        self.push(kind, start, end);
+        self.at = end;
+    }
+
+    /// Whether the `<` at `at` opens a JSX tag rather than comparing or
+    /// starting type arguments, judging by what comes before and after.
+    fn opens_tag(&self, at: usize) -> bool {
+        let name = if self.bytes.get(at + 1) == Some(&b'/') { at + 2 } else { at + 1 };
+        if !self.bytes.get(name).is_some_and(u8::is_ascii_alphabetic) {
+            return false;
+        }
+        !self.tokens.last().is_some_and(|token| {
+            matches!(
+                token.kind,
+                TokenKind::Identifier | TokenKind::Type | TokenKind::Number | TokenKind::Constant | TokenKind::String
+            ) || matches!(&self.line[token.start..token.end], ")" | "]")
+        })
+    }
+}
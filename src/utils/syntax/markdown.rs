@@ -0,0 +1,119 @@
+//! Markdown: headings, quotes, list markers, fenced code blocks, and inline
+//! code, emphasis and links.
+
+use super::{LineState, Token, TokenKind};
+
+fn token(kind: TokenKind, start: usize, end: usize) -> Token {
+    Token { kind, start, end }
+}
+
+pub(super) fn tokenize_line(state: LineState, line: &str) -> (Vec<Token>, LineState) {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let rest = &line[indent..];
+    let end = line.trim_end().len().max(indent);
+    if let LineState::Fence { marker, len } = state {
+        let run = rest.bytes().take_while(|&b| b == marker).count();
+        if run >= len as usize && rest[run..].trim().is_empty() {
+            return (vec![token(TokenKind::Punctuation, indent, indent + run)], LineState::Normal);
+        }
+        let tokens = if line.is_empty() { Vec::new() } else { vec![token(TokenKind::Code, 0, line.len())] };
+        return (tokens, state);
+    }
+    let mut tokens = Vec::new();
+    // Block syntax may be indented by up to three spaces.
+    if indent <= 3 {
+        if let Some(marker @ (b'`' | b'~')) = rest.bytes().next() {
+            let run = rest.bytes().take_while(|&b| b == marker).count();
+            if run >= 3 && (marker == b'~' || !rest[run..].contains('`')) {
+                tokens.push(token(TokenKind::Punctuation, indent, indent + run));
+                let info = indent + run + (rest[run..].len() - rest[run..].trim_start().len());
+                if end > info {
+                    tokens.push(token(TokenKind::Keyword, info, end));
+                }
+                let len = run.min(u8::MAX as usize) as u8;
+                return (tokens, LineState::Fence { marker, len });
+            }
+        }
+        let hashes = rest.bytes().take_while(|&b| b == b'#').count();
+        if (1..=6).contains(&hashes) && (rest.len() == hashes || rest[hashes..].starts_with(' ')) {
+            return (vec![token(TokenKind::Heading, indent, end)], LineState::Normal);
+        }
+        let compact: Vec<u8> = rest.bytes().filter(|&b| b != b' ').collect();
+        let thematic_break = compact.len() >= 3
+            && matches!(compact[0], b'-' | b'*' | b'_')
+            && compact.iter().all(|&b| b == compact[0]);
+        if thematic_break {
+            return (vec![token(TokenKind::Punctuation, indent, end)], LineState::Normal);
+        }
+        if rest.starts_with('>') {
+            return (vec![token(TokenKind::Quote, indent, end)], LineState::Normal);
+        }
+    }
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    let marker = if rest.starts_with("- ") || rest.starts_with("* ") || rest.starts_with("+ ") {
+        1
+    } else if (1..=9).contains(&digits) && (rest[digits..].starts_with(". ") || rest[digits..].starts_with(") ")) {
+        digits + 1
+    } else {
+        0
+    };
+    if marker > 0 {
+        tokens.push(token(TokenKind::Punctuation, indent, indent + marker));
+    }
+    inline(line, indent + marker, &mut tokens);
+    (tokens, LineState::Normal)
+}
+
+fn inline(line: &str, mut at: usize, tokens: &mut Vec<Token>) {
+    let bytes = line.as_bytes();
+    while at < bytes.len() {
+        match bytes[at] {
+            b'\\' => {
+                at += 1;
+                at += line[at..].chars().next().map_or(0, char::len_utf8);
+            }
+            b'`' => {
+                let run = bytes[at..].iter().take_while(|&&b| b == b'`').count();
+                let fence = &line[at..at + run];
+                match line[at + run..].find(fence) {
+                    Some(index) => {
+                        let end = at + run + index + run;
+                        tokens.push(token(TokenKind::Code, at, end));
+                        at = end;
+                    }
+                    None => at += run,
+                }
+            }
+            marker @ (b'*' | b'_') => {
+                let run = if bytes.get(at + 1) == Some(&marker) { 2 } else { 1 };
+                let delimiter = &line[at..at + run];
+                let inside_word = marker == b'_' && at > 0 && bytes[at - 1].is_ascii_alphanumeric();
+                let closing = line[at + run..]
+                    .find(delimiter)
+                    .filter(|&index| index > 0 && !inside_word && !line[at + run..].starts_with(' '));
+                match closing {
+                    Some(index) => {
+                        let end = at + run + index + run;
+                        let kind = if run == 2 { TokenKind::Strong } else { TokenKind::Emphasis };
+                        tokens.push(token(kind, at, end));
+                        at = end;
+                    }
+                    None => at += run,
+                }
+            }
+            b'[' => {
+                let link = line[at..]
+                    .find("](")
+                    .and_then(|close| line[at + close..].find(')').map(|paren| at + close + paren + 1));
+                match link {
+                    Some(end) => {
+                        tokens.push(token(TokenKind::Link, at, end));
+                        at = end;
+                    }
+                    None => at += 1,
+                }
+            }
+            _ => at += line[at..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Syntax highlighting.
+//!
+//! Text is tokenized one line at a time. Whatever a line leaves open at its
+//! end (a block comment, a multi-line string, a fenced code block) is
+//! carried to the next line as a [`LineState`], so after an edit only the
+//! lines from the change until that state matches its old value need to be
+//! tokenized again; see [`highlighter::Highlighter`].
+
+pub mod config;
+pub mod highlighter;
+mod languages;
+mod lexer;
+mod markdown;
+pub mod theme;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    Rust,
+    TypeScript,
+    /// TypeScript or JavaScript with JSX tags.
+    Tsx,
+    Python,
+    Json,
+    Toml,
+    Markdown,
+    Shell,
+    PlainText,
+}
+
+impl Language {
+    /// The language of files with extension `extension` (without the dot,
+    /// any case). JavaScript is highlighted as TypeScript.
+    pub fn from_extension(extension: &str) -> Self {
+        match extension.to_ascii_lowercase().as_str() {
+            "rs" => Self::Rust,
+            "ts" | "mts" | "cts" | "js" | "mjs" | "cjs" => Self::TypeScript,
+            "tsx" | "jsx" => Self::Tsx,
+            "py" | "pyi" => Self::Python,
+            "json" | "jsonc" => Self::Json,
+            "toml" => Self::Toml,
+            "md" | "markdown" => Self::Markdown,
+            "sh" | "bash" | "zsh" => Self::Shell,
+            _ => Self::PlainText,
+        }
+    }
+
+    /// The language of the file at `path`, from its extension.
+    pub fn from_path(path: &str) -> Self {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        name.rsplit_once('.')
+            .map_or(Self::PlainText, |(_, extension)| Self::from_extension(extension))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Keyword,
+    Type,
+    Function,
+    Macro,
+    Identifier,
+    /// An object key or table key.
+    Property,
+    /// A shell variable (`$HOME`).
+    Variable,
+    /// `true`, `null`, `None` and the like.
+    Constant,
+    Number,
+    String,
+    Comment,
+    /// A Rust attribute or a decorator.
+    Attribute,
+    /// A JSX tag name.
+    Tag,
+    Operator,
+    Punctuation,
+    Heading,
+    Emphasis,
+    Strong,
+    /// Inline code or a fenced code block in Markdown.
+    Code,
+    Link,
+    Quote,
+}
+
+impl TokenKind {
+    /// The theme scope the token is colored by. Scopes are dotted and
+    /// follow TextMate naming, so a theme rule for `keyword` also covers
+    /// `keyword.operator` unless that has its own rule.
+    pub fn scope(self) -> &'static str {
+        match self {
+            Self::Keyword => "keyword",
+            Self::Type => "entity.name.type",
+            Self::Function => "entity.name.function",
+            Self::Macro => "entity.name.function.macro",
+            Self::Identifier => "variable",
+            Self::Property => "variable.other.property",
+            Self::Variable => "variable.other.normal",
+            Self::Constant => "constant.language",
+            Self::Number => "constant.numeric",
+            Self::String => "string",
+            Self::Comment => "comment",
+            Self::Attribute => "meta.attribute",
+            Self::Tag => "entity.name.tag",
+            Self::Operator => "keyword.operator",
+            Self::Punctuation => "punctuation",
+            Self::Heading => "markup.heading",
+            Self::Emphasis => "markup.italic",
+            Self::Strong => "markup.bold",
+            Self::Code => "markup.inline.raw",
+            Self::Link => "markup.underline.link",
+            Self::Quote => "markup.quote",
+        }
+    }
+}
+
+/// A token of one line; `start` and `end` are byte offsets into the line.
+/// Whitespace between tokens is not tokenized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// The closing delimiter of a string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Quote {
+    quote: u8,
+    /// 3 for `"""`.
+    repeat: u8,
+    /// Trailing `#`s of a Rust raw string.
+    hashes: u8,
+    escapes: bool,
+    /// Whether the string may continue onto the next line.
+    multiline: bool,
+}
+
+/// What the end of a line leaves open for the next one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LineState {
+    #[default]
+    Normal,
+    /// In a block comment, nested `depth` deep.
+    Comment { depth: u8 },
+    String(Quote),
+    /// In a Markdown code block fenced by `len` `marker`s.
+    Fence { marker: u8, len: u8 },
+}
+
+/// Tokenizes `line` (without its line break) of `language` code, starting in
+/// `state`, the state the previous line ended in. Returns the tokens and
+/// the state this line ends in.
+pub fn tokenize_line(language: Language, state: LineState, line: &str) -> (Vec<Token>, LineState) {
+    match language {
+        Language::Markdown => markdown::tokenize_line(state, line),
+        Language::PlainText => (Vec::new(), LineState::Normal),
+        _ => lexer::tokenize_line(languages::spec(language), state, line),
+    }
+}
+
+/// Tokenizes all of `text`, one list of tokens per line.
+pub fn tokenize(language: Language, text: &str) -> Vec<Vec<Token>> {
+    let mut state = LineState::Normal;
+    text.split('\n')
+        .map(|line| {
+            let (tokens, end) = tokenize_line(language, state, line);
+            state = end;
+            tokens
+        })
+        .collect()
+}
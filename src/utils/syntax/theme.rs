@@ -0,0 +1,139 @@
+//! Colors for token scopes.
+
+use crate::utils::diagnostics::Severity;
+
+/// How tokens whose scope starts with `scope` are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThemeRule {
+    pub scope: &'static str,
+    pub color: Option<&'static str>,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+const fn rule(scope: &'static str, color: &'static str) -> ThemeRule {
+    ThemeRule {
+        scope,
+        color: Some(color),
+        bold: false,
+        italic: false,
+    }
+}
+
+const DARK_PLUS: &[ThemeRule] = &[
+    rule("keyword", "#569cd6"),
+    rule("keyword.operator", "#d4d4d4"),
+    rule("entity.name.type", "#4ec9b0"),
+    rule("entity.name.function", "#dcdcaa"),
+    rule("entity.name.tag", "#569cd6"),
+    rule("variable.other.property", "#9cdcfe"),
+    rule("variable.other.normal", "#9cdcfe"),
+    rule("constant.language", "#569cd6"),
+    rule("constant.numeric", "#b5cea8"),
+    rule("string", "#ce9178"),
+    rule("comment", "#6a9955"),
+    rule("meta.attribute", "#c586c0"),
+    ThemeRule {
+        bold: true,
+        ..rule("markup.heading", "#569cd6")
+    },
+    ThemeRule {
+        scope: "markup.italic",
+        color: None,
+        bold: false,
+        italic: true,
+    },
+    ThemeRule {
+        scope: "markup.bold",
+        color: None,
+        bold: true,
+        italic: false,
+    },
+    rule("markup.inline.raw", "#ce9178"),
+    rule("markup.underline.link", "#3794ff"),
+    rule("markup.quote", "#6a9955"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub name: &'static str,
+    pub foreground: &'static str,
+    pub background: &'static str,
+    pub cursor: &'static str,
+    pub selection: &'static str,
+    /// The current find match and the others.
+    pub find_match: &'static str,
+    pub find_match_highlight: &'static str,
+    /// The bracket at the cursor and its match.
+    pub bracket_match: &'static str,
+    pub bracket_match_border: &'static str,
+    /// Squiggles under errors, warnings, and infos and hints.
+    pub error: &'static str,
+    pub warning: &'static str,
+    pub info: &'static str,
+    rules: &'static [ThemeRule],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark_plus()
+    }
+}
+
+impl Theme {
+    pub fn dark_plus() -> Self {
+        Self {
+            name: "Dark+",
+            foreground: "#cccccc",
+            background: "#1e1e1e",
+            cursor: "#aeafad",
+            selection: "rgba(38, 79, 120, 0.8)",
+            find_match: "rgba(81, 92, 106, 0.8)",
+            find_match_highlight: "rgba(234, 92, 0, 0.33)",
+            bracket_match: "rgba(0, 100, 0, 0.1)",
+            bracket_match_border: "#888888",
+            error: "#f14c4c",
+            warning: "#cca700",
+            info: "#3794ff",
+            rules: DARK_PLUS,
+        }
+    }
+
+    /// The color marking problems of `severity`.
+    pub fn severity(&self, severity: Severity) -> &'static str {
+        match severity {
+            Severity::Error => self.error,
+            Severity::Warning => self.warning,
+            Severity::Info | Severity::Hint => self.info,
+        }
+    }
+
+    /// The most specific rule for `scope`: the one with the longest scope
+    /// that is `scope` itself or a dotted prefix of it.
+    pub fn rule(&self, scope: &str) -> Option<&ThemeRule> {
+        self.rules
+            .iter()
+            .filter(|rule| {
+                scope
+                    .strip_prefix(rule.scope)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+            .max_by_key(|rule| rule.scope.len())
+    }
+
+    /// Inline CSS for tokens of `scope`, if the theme styles them.
+    pub fn style(&self, scope: &str) -> Option<String> {
+        let rule = self.rule(scope)?;
+        let mut style = String::new();
+        if let Some(color) = rule.color {
+            style.push_str(&format!("color: {};", color));
+        }
+        if rule.bold {
+            style.push_str(" font-weight: bold;");
+        }
+        if rule.italic {
+            style.push_str(" font-style: italic;");
+        }
+        Some(style)
+    }
+}